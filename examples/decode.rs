@@ -0,0 +1,38 @@
+//! Decodes the sample transaction and scripts of the `fixtures` directory,
+//! run it with `cargo run --example decode`
+use btc_tx_hex::{BtcTx, Network, StandardScripts};
+use std::io::Cursor;
+
+fn main() {
+    // The sample transaction and scripts are stored in the `fixtures` directory
+    let raw_tx = include_str!("../fixtures/transactions/p2pkh_two_inputs.hex");
+    let tx_decode = BtcTx::from_hex_bytes(hex::decode(raw_tx.trim()).unwrap());
+    assert!(tx_decode.is_ok());
+    let tx_decode = tx_decode.unwrap();
+    dbg!(&tx_decode);
+
+    // Print the script type and address of each output
+    tx_decode.outputs().iter().for_each(|output| {
+        dbg!(output.script_type(), output.address(Network::Mainnet));
+    });
+
+    [
+        include_str!("../fixtures/scripts/p2pk.hex"),
+        include_str!("../fixtures/scripts/p2pkh.hex"),
+        include_str!("../fixtures/scripts/p2sh.hex"),
+        include_str!("../fixtures/scripts/op_return.hex"),
+        include_str!("../fixtures/scripts/p2wpkh.hex"),
+        include_str!("../fixtures/scripts/p2wsh.hex"),
+        include_str!("../fixtures/scripts/p2tr.hex"),
+        include_str!("../fixtures/scripts/p2ms_2_of_3.hex"),
+        include_str!("../fixtures/scripts/p2ms_1_of_2.hex"),
+    ]
+    .iter()
+    .for_each(|script_hex| {
+        let script_bytes = hex::decode(script_hex.trim()).unwrap();
+        let mut script = Cursor::new(script_bytes.as_ref());
+        let outcome = StandardScripts::parse(&mut script);
+        assert!(outcome.is_ok());
+        dbg!(&outcome.unwrap());
+    });
+}
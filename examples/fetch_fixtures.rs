@@ -0,0 +1,142 @@
+//! Downloads the transactions listed in `fixtures/transactions.txt` into
+//! `fixtures/transactions/<name>.hex`, run it with
+//! `cargo run --example fetch_fixtures --features esplora -- [--esplora <url>] [list]`.
+//!
+//! Every download is checked against the transaction ID it was requested by and
+//! must serialize back to the same bytes before it is written. The expected
+//! decoding `<name>.json` is only written when it is missing and should be
+//! reviewed before it is committed since it comes from the decoder under test.
+use btc_tx_hex::{BtcTx, EsploraClient, Network, BLOCKSTREAM_URL};
+use serde_json::json;
+use std::{
+    env, fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+fn main() -> io::Result<()> {
+    let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+    let mut base_url = BLOCKSTREAM_URL.to_owned();
+    let mut list = fixtures.join("transactions.txt");
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--esplora" => {
+                base_url = args.next().ok_or_else(|| {
+                    io::Error::new(ErrorKind::InvalidInput, "--esplora needs a URL")
+                })?
+            }
+            _ => list = PathBuf::from(arg),
+        }
+    }
+
+    let entries = parse_list(&fs::read_to_string(&list)?)?;
+    let client = EsploraClient::new(base_url);
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+
+    for (name, txid) in entries {
+        let raw_tx = runtime.block_on(client.raw_transaction(&txid))?;
+        let tx = verify(&txid, &raw_tx)?;
+        let status = write_fixture(&fixtures.join("transactions"), &name, &raw_tx, &tx)?;
+        println!("{} {} {}", status, name, txid);
+    }
+
+    Ok(())
+}
+
+// The `<name> <txid>` pairs of the list skipping empty lines and `#` comments
+fn parse_list(list: &str) -> io::Result<Vec<(String, String)>> {
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(
+            |line| match line.split_whitespace().collect::<Vec<&str>>()[..] {
+                [name, txid] => Ok((name.to_owned(), txid.to_lowercase())),
+                _ => Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Expected `<name> <txid>` but got `{}`", line),
+                )),
+            },
+        )
+        .collect()
+}
+
+// A source returning another transaction or bytes the parser would
+// serialize differently must not end up in the corpus
+fn verify(txid: &str, raw_tx: &[u8]) -> io::Result<BtcTx> {
+    let tx = BtcTx::from_hex_bytes(raw_tx)?;
+
+    if tx.txid().to_string() != txid {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("Requested {} but the source returned {}", txid, tx.txid()),
+        ));
+    }
+    if tx.to_bytes() != raw_tx {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "The transaction {} does not serialize back to the same bytes",
+                txid
+            ),
+        ));
+    }
+
+    Ok(tx)
+}
+
+fn write_fixture(
+    directory: &Path,
+    name: &str,
+    raw_tx: &[u8],
+    tx: &BtcTx,
+) -> io::Result<&'static str> {
+    let hex_path = directory.join(format!("{}.hex", name));
+    let json_path = directory.join(format!("{}.json", name));
+
+    // Whitespace is ignored by the loader so hex wrapped by hand is not rewritten
+    let existing = fs::read_to_string(&hex_path)
+        .ok()
+        .map(|hex_string| hex_string.split_whitespace().collect::<String>());
+    let status = match existing {
+        Some(hex_string) if hex_string == hex::encode(raw_tx) => "unchanged",
+        Some(_) => "updated",
+        None => "added",
+    };
+    if status != "unchanged" {
+        fs::write(&hex_path, format!("{}\n", hex::encode(raw_tx)))?;
+    }
+
+    if !json_path.exists() {
+        let expected = json!({
+            "version": u32::from_le_bytes(tx.version().to_bytes()),
+            "locktime": tx.locktime(),
+            "inputs": tx
+                .inputs()
+                .iter()
+                .map(|input| json!({
+                    "previous_output_index": input.previous_output_index(),
+                    "spend_type": format!("{:?}", input.inferred_spend_type()),
+                }))
+                .collect::<Vec<_>>(),
+            "outputs": tx
+                .outputs()
+                .iter()
+                .map(|output| json!({
+                    "amount": output.amount(),
+                    "script_type": output.script_type().to_string(),
+                    "address": output.address(Network::Mainnet),
+                }))
+                .collect::<Vec<_>>(),
+        });
+        fs::write(
+            &json_path,
+            format!("{}\n", serde_json::to_string_pretty(&expected)?),
+        )?;
+    }
+
+    Ok(status)
+}
@@ -0,0 +1,502 @@
+use crate::{Checksum, ScriptType};
+use std::io::{self, ErrorKind};
+
+/// The Bitcoin network an address is encoded for.
+/// Each network uses different version bytes for Base58Check
+/// addresses and a different human readable part (HRP) for
+/// Bech32/Bech32m addresses.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default)]
+pub enum Network {
+    /// The main Bitcoin network
+    #[default]
+    Mainnet,
+    /// The test network
+    Testnet,
+    /// The signet test network
+    Signet,
+    /// The local regression test network
+    Regtest,
+}
+
+impl Network {
+    /// The version byte prepended to a P2PKH hash before Base58Check encoding
+    pub const fn p2pkh_version(&self) -> u8 {
+        match self {
+            Self::Mainnet => 0x00,
+            Self::Testnet | Self::Signet | Self::Regtest => 0x6f,
+        }
+    }
+
+    /// The version byte prepended to a P2SH hash before Base58Check encoding
+    pub const fn p2sh_version(&self) -> u8 {
+        match self {
+            Self::Mainnet => 0x05,
+            Self::Testnet | Self::Signet | Self::Regtest => 0xc4,
+        }
+    }
+
+    /// The human readable part used for segwit addresses
+    pub const fn hrp(&self) -> &'static str {
+        match self {
+            Self::Mainnet => "bc",
+            Self::Testnet | Self::Signet => "tb",
+            Self::Regtest => "bcrt",
+        }
+    }
+}
+
+/// The alphabet used by Base58 which excludes `0`, `O`, `I` and `l`
+/// since they look alike
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// The alphabet used by Bech32 and Bech32m
+const BECH32_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// The constant XORed into the checksum of Bech32 (witness version 0) addresses
+const BECH32_CONST: u32 = 1;
+
+/// The constant XORed into the checksum of Bech32m (witness version 1+) addresses
+const BECH32M_CONST: u32 = 0x2bc830a3;
+
+/// Handles encoding of locking scripts into addresses
+#[derive(Debug, Clone, Copy)]
+pub struct Address;
+
+impl Address {
+    /// Get the address of a locking script for the given network.
+    /// Returns `None` for scripts that do not have an address
+    /// like P2PK, P2MS, OP_RETURN and non-standard scripts.
+    pub fn from_script(script: &[u8], network: Network) -> Option<String> {
+        match ScriptType::from_script(script) {
+            // OP_DUP OP_HASH160 OP_PUSHBYTES_20 <20 bytes> OP_EQUALVERIFY OP_CHECKSIG
+            ScriptType::P2PKH => Some(Self::p2pkh(&script[3..23], network)),
+            // OP_HASH160 OP_PUSHBYTES_20 <20 bytes> OP_EQUAL
+            ScriptType::P2SH => Some(Self::p2sh(&script[2..22], network)),
+            // OP_0 OP_PUSHBYTES_20 <20 bytes> | OP_0 OP_PUSHBYTES_32 <32 bytes>
+            ScriptType::P2WPKH | ScriptType::P2WSH => Some(Self::segwit(0, &script[2..], network)),
+            // OP_1 OP_PUSHBYTES_32 <32 bytes>
+            ScriptType::P2TR => Some(Self::segwit(1, &script[2..], network)),
+            // OP_1 OP_PUSHBYTES_2 4e73
+            ScriptType::P2A => Some(Self::segwit(1, &script[2..], network)),
+            // OP_1..16 OP_PUSHBYTES_2..40 <2 to 40 bytes>
+            ScriptType::WitnessUnknown(version) => {
+                Some(Self::segwit(version, &script[2..], network))
+            }
+            _ => None,
+        }
+    }
+
+    /// Decode an address of `network` into the locking script it pays to.
+    /// Base58Check addresses give P2PKH and P2SH scripts and segwit addresses
+    /// give witness programs, with the Bech32 checksum for witness version 0
+    /// and the Bech32m checksum for the later versions
+    pub fn to_script(address: &str, network: Network) -> io::Result<Vec<u8>> {
+        if address
+            .to_lowercase()
+            .starts_with(&format!("{}1", network.hrp()))
+        {
+            return Self::decode_segwit(address, network);
+        }
+
+        let payload = Self::decode_base58(address)?;
+        let payload = Checksum::strip(&payload).ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                "The checksum of the address is not valid",
+            )
+        })?;
+        match payload {
+            [version, hash160 @ ..] if hash160.len() == 20 => {
+                if *version == network.p2pkh_version() {
+                    // OP_DUP OP_HASH160 OP_PUSHBYTES_20 <20 bytes> OP_EQUALVERIFY OP_CHECKSIG
+                    Ok([&[0x76, 0xa9, 0x14], hash160, &[0x88, 0xac]].concat())
+                } else if *version == network.p2sh_version() {
+                    // OP_HASH160 OP_PUSHBYTES_20 <20 bytes> OP_EQUAL
+                    Ok([&[0xa9, 0x14], hash160, &[0x87]].concat())
+                } else {
+                    Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        "The address is not an address of the network",
+                    ))
+                }
+            }
+            _ => Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "A Base58Check address must have a version byte and a 20 byte hash",
+            )),
+        }
+    }
+
+    /// Encode a HASH160 of a public key as a P2PKH address
+    pub fn p2pkh(hash160: &[u8], network: Network) -> String {
+        Self::base58check(network.p2pkh_version(), hash160)
+    }
+
+    /// Encode a HASH160 of a redeem script as a P2SH address
+    pub fn p2sh(hash160: &[u8], network: Network) -> String {
+        Self::base58check(network.p2sh_version(), hash160)
+    }
+
+    /// Encode a witness program as a segwit address.
+    /// Witness version 0 uses Bech32 (BIP-173) while
+    /// witness versions 1 to 16 use Bech32m (BIP-350)
+    pub fn segwit(witness_version: u8, program: &[u8], network: Network) -> String {
+        let checksum_const = if witness_version == 0 {
+            BECH32_CONST
+        } else {
+            BECH32M_CONST
+        };
+
+        // The data part is the witness version followed by the
+        // witness program converted from 8 bit to 5 bit groups
+        let mut data = vec![witness_version];
+        data.extend_from_slice(&Self::convert_bits(program));
+
+        let hrp = network.hrp();
+        let checksum = Self::bech32_checksum(hrp, &data, checksum_const);
+
+        let mut address = String::from(hrp);
+        address.push('1');
+        data.iter()
+            .chain(checksum.iter())
+            .for_each(|value| address.push(BECH32_CHARSET[*value as usize] as char));
+
+        address
+    }
+
+    /// Prepend the version byte, append the first four bytes of the
+    /// double SHA256 of the payload as a checksum and then encode as Base58
+    fn base58check(version: u8, payload: &[u8]) -> String {
+        let mut bytes = vec![version];
+        bytes.extend_from_slice(payload);
+        Checksum::append(&mut bytes);
+
+        Self::base58(&bytes)
+    }
+
+    // Encode bytes as Base58 by repeatedly dividing the big-endian number by 58
+    fn base58(bytes: &[u8]) -> String {
+        // The Base58 digits stored with the least significant digit first
+        let mut digits = Vec::<u8>::new();
+
+        bytes.iter().for_each(|byte| {
+            let mut carry = *byte as u32;
+
+            digits.iter_mut().for_each(|digit| {
+                carry += (*digit as u32) << 8;
+                *digit = (carry % 58) as u8;
+                carry /= 58;
+            });
+
+            while carry > 0 {
+                digits.push((carry % 58) as u8);
+                carry /= 58;
+            }
+        });
+
+        // Every leading zero byte is encoded as a `1`
+        let leading_zeros = bytes.iter().take_while(|byte| **byte == 0).count();
+
+        (0..leading_zeros)
+            .map(|_| BASE58_ALPHABET[0] as char)
+            .chain(
+                digits
+                    .iter()
+                    .rev()
+                    .map(|digit| BASE58_ALPHABET[*digit as usize] as char),
+            )
+            .collect()
+    }
+
+    // Decode Base58 by multiplying the big-endian number by 58 for every digit
+    fn decode_base58(address: &str) -> io::Result<Vec<u8>> {
+        // The bytes stored with the least significant byte first
+        let mut bytes = Vec::<u8>::new();
+
+        for char in address.bytes() {
+            let mut carry = BASE58_ALPHABET
+                .iter()
+                .position(|digit| *digit == char)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        ErrorKind::InvalidData,
+                        "The address has a character which is not Base58",
+                    )
+                })? as u32;
+
+            bytes.iter_mut().for_each(|byte| {
+                carry += (*byte as u32) * 58;
+                *byte = carry as u8;
+                carry >>= 8;
+            });
+
+            while carry > 0 {
+                bytes.push(carry as u8);
+                carry >>= 8;
+            }
+        }
+
+        // Every leading `1` is a zero byte
+        let leading_zeros = address
+            .bytes()
+            .take_while(|char| *char == BASE58_ALPHABET[0])
+            .count();
+        bytes.extend(std::iter::repeat_n(0, leading_zeros));
+        bytes.reverse();
+
+        Ok(bytes)
+    }
+
+    // Decode a segwit address into its witness program. Like BIP-173 requires
+    // the address is either all lowercase or all uppercase
+    fn decode_segwit(address: &str, network: Network) -> io::Result<Vec<u8>> {
+        let invalid = |message: &str| io::Error::new(ErrorKind::InvalidData, message.to_owned());
+
+        let lowercase = address.to_lowercase();
+        if address != lowercase && address != address.to_uppercase() {
+            return Err(invalid("A segwit address cannot mix cases"));
+        }
+        if lowercase.len() > 90 {
+            return Err(invalid("A segwit address is at most 90 characters"));
+        }
+
+        let values = lowercase[network.hrp().len() + 1..]
+            .bytes()
+            .map(|char| {
+                BECH32_CHARSET
+                    .iter()
+                    .position(|value| *value == char)
+                    .map(|value| value as u8)
+            })
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(|| invalid("The address has a character which is not Bech32"))?;
+        let Some(data_len) = values.len().checked_sub(6).filter(|len| *len > 0) else {
+            return Err(invalid("The segwit address is too short"));
+        };
+
+        let (data, checksum) = values.split_at(data_len);
+        let witness_version = data[0];
+        let checksum_const = if witness_version == 0 {
+            BECH32_CONST
+        } else {
+            BECH32M_CONST
+        };
+        if Self::bech32_checksum(network.hrp(), data, checksum_const) != checksum {
+            return Err(invalid("The checksum of the address is not valid"));
+        }
+
+        let program = Self::regroup_bits(&data[1..])
+            .ok_or_else(|| invalid("The witness program is not padded with zeros"))?;
+        match (witness_version, program.len()) {
+            (0, 20 | 32) | (1..=16, 2..=40) => (),
+            _ => {
+                return Err(invalid(
+                    "The witness program has a length its version forbids",
+                ))
+            }
+        }
+
+        // OP_0 or OP_1..16 followed by the push of the program
+        let version_opcode = if witness_version == 0 {
+            0x00
+        } else {
+            0x50 + witness_version
+        };
+
+        Ok([&[version_opcode, program.len() as u8], program.as_slice()].concat())
+    }
+
+    // Regroup 5 bit values into 8 bit bytes. Returns `None` when the
+    // padding is longer than 4 bits or is not made of zeros
+    fn regroup_bits(values: &[u8]) -> Option<Vec<u8>> {
+        let mut accumulator = 0u32;
+        let mut bits = 0u32;
+        let mut outcome = Vec::<u8>::new();
+
+        values.iter().for_each(|value| {
+            accumulator = ((accumulator << 5) | *value as u32) & 0xfff;
+            bits += 5;
+
+            if bits >= 8 {
+                bits -= 8;
+                outcome.push((accumulator >> bits) as u8);
+            }
+        });
+
+        (bits < 5 && accumulator & ((1 << bits) - 1) == 0).then_some(outcome)
+    }
+
+    // Regroup 8 bit bytes into 5 bit values padding the last group with zeros
+    fn convert_bits(bytes: &[u8]) -> Vec<u8> {
+        let mut accumulator = 0u32;
+        let mut bits = 0u32;
+        let mut outcome = Vec::<u8>::new();
+
+        bytes.iter().for_each(|byte| {
+            accumulator = (accumulator << 8) | *byte as u32;
+            bits += 8;
+
+            while bits >= 5 {
+                bits -= 5;
+                outcome.push(((accumulator >> bits) & 31) as u8);
+            }
+        });
+
+        if bits > 0 {
+            outcome.push(((accumulator << (5 - bits)) & 31) as u8);
+        }
+
+        outcome
+    }
+
+    // The BCH checksum over the expanded HRP and the data part
+    fn bech32_checksum(hrp: &str, data: &[u8], checksum_const: u32) -> [u8; 6] {
+        // Expand the HRP into the high bits of each character,
+        // a zero separator and then the low bits of each character
+        let mut values = hrp.bytes().map(|char| char >> 5).collect::<Vec<u8>>();
+        values.push(0);
+        values.extend(hrp.bytes().map(|char| char & 31));
+        values.extend_from_slice(data);
+        values.extend_from_slice(&[0u8; 6]);
+
+        let polymod = Self::polymod(&values) ^ checksum_const;
+
+        let mut checksum = [0u8; 6];
+        checksum.iter_mut().enumerate().for_each(|(index, value)| {
+            *value = ((polymod >> (5 * (5 - index))) & 31) as u8;
+        });
+
+        checksum
+    }
+
+    fn polymod(values: &[u8]) -> u32 {
+        const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+
+        values.iter().fold(1u32, |checksum, value| {
+            let top = checksum >> 25;
+            let checksum = ((checksum & 0x1ffffff) << 5) ^ *value as u32;
+
+            GENERATOR
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| (top >> index) & 1 == 1)
+                .fold(checksum, |checksum, (_, generator)| checksum ^ generator)
+        })
+    }
+}
+
+#[cfg(test)]
+mod address_sanity_checks {
+    use crate::{Address, Network};
+    use hex_literal::hex;
+
+    #[test]
+    fn base58check_addresses() {
+        let p2pkh = hex!("76a9140ce17649c1306c291ca9e587f8793b5b06563cea88ac");
+        assert_eq!(
+            Some("12B7CgUyGLPVWKFFSCFVR7MHTM2ptxNnu4".to_string()),
+            Address::from_script(&p2pkh, Network::Mainnet)
+        );
+        assert_eq!(
+            Some("mgh4VjZx5MpkHRis9mDsF2ZcKLdXoP3oQ4".to_string()),
+            Address::from_script(&p2pkh, Network::Testnet)
+        );
+
+        let p2sh = hex!("a914748284390f9e263a4b766a75d0633c50426eb87587");
+        assert_eq!(
+            Some("3CK4fEwbMP7heJarmU4eqA3sMbVJyEnU3V".to_string()),
+            Address::from_script(&p2sh, Network::Mainnet)
+        );
+    }
+
+    #[test]
+    fn segwit_addresses() {
+        // Test vectors from BIP-173 and BIP-350
+        let p2wpkh = hex!("0014751e76e8199196d454941c45d1b3a323f1433bd6");
+        assert_eq!(
+            Some("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string()),
+            Address::from_script(&p2wpkh, Network::Mainnet)
+        );
+
+        let p2tr = hex!("512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798");
+        assert_eq!(
+            Some("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0".to_string()),
+            Address::from_script(&p2tr, Network::Mainnet)
+        );
+
+        let p2a = hex!("51024e73");
+        assert_eq!(
+            Some("bc1pfeessrawgf".to_string()),
+            Address::from_script(&p2a, Network::Mainnet)
+        );
+
+        let witness_v16 = hex!("6002751e");
+        assert_eq!(
+            Some("bc1sw50qgdz25j".to_string()),
+            Address::from_script(&witness_v16, Network::Mainnet)
+        );
+    }
+
+    #[test]
+    fn decode_addresses() {
+        let scripts = [
+            hex!("76a9140ce17649c1306c291ca9e587f8793b5b06563cea88ac").to_vec(),
+            hex!("a914748284390f9e263a4b766a75d0633c50426eb87587").to_vec(),
+            hex!("0014751e76e8199196d454941c45d1b3a323f1433bd6").to_vec(),
+            hex!("512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").to_vec(),
+            hex!("51024e73").to_vec(),
+            hex!("6002751e").to_vec(),
+        ];
+        for network in [Network::Mainnet, Network::Testnet, Network::Regtest] {
+            for script in &scripts {
+                let address = Address::from_script(script, network).unwrap();
+                assert_eq!(script, &Address::to_script(&address, network).unwrap());
+            }
+        }
+
+        // Uppercase like in QR codes
+        assert_eq!(
+            scripts[2],
+            Address::to_script(
+                "BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4",
+                Network::Mainnet
+            )
+            .unwrap()
+        );
+
+        for (address, network) in [
+            // Another network
+            ("12B7CgUyGLPVWKFFSCFVR7MHTM2ptxNnu4", Network::Testnet),
+            (
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+                Network::Testnet,
+            ),
+            // Wrong checksums
+            ("12B7CgUyGLPVWKFFSCFVR7MHTM2ptxNnu5", Network::Mainnet),
+            (
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5",
+                Network::Mainnet,
+            ),
+            // A version 1 program with the Bech32 checksum of BIP-350
+            (
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd",
+                Network::Mainnet,
+            ),
+            // Mixed case, not Base58 and a version 0 program of 16 bytes
+            (
+                "bc1qW508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+                Network::Mainnet,
+            ),
+            ("0OIl", Network::Mainnet),
+            ("bc1qr508d6qejxtdg4y5r3zarvaryvg6kdaj", Network::Mainnet),
+        ] {
+            assert!(Address::to_script(address, network).is_err(), "{}", address);
+        }
+    }
+
+    #[test]
+    fn scripts_without_address() {
+        let op_return = hex!("6a0b68656c6c6f20776f726c64");
+        assert_eq!(None, Address::from_script(&op_return, Network::Mainnet));
+    }
+}
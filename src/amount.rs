@@ -0,0 +1,336 @@
+use std::{
+    fmt,
+    io::{self, ErrorKind},
+};
+
+/// The most satoshis that will ever exist, 21 million bitcoin. Consensus
+/// rejects outputs above it
+pub const MAX_MONEY: u64 = 21_000_000 * 100_000_000;
+
+/// The units an amount of satoshis can be shown in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Denomination {
+    /// The smallest unit
+    Satoshi,
+    /// 100 satoshis, a micro-bitcoin
+    Bit,
+    /// 100,000 satoshis
+    MilliBitcoin,
+    /// 100,000,000 satoshis
+    #[default]
+    Bitcoin,
+}
+
+impl Denomination {
+    /// All the denominations from the smallest
+    pub const ALL: [Self; 4] = [Self::Satoshi, Self::Bit, Self::MilliBitcoin, Self::Bitcoin];
+
+    /// The number of decimal places of a satoshi in this denomination
+    pub const fn decimals(&self) -> u32 {
+        match self {
+            Self::Satoshi => 0,
+            Self::Bit => 2,
+            Self::MilliBitcoin => 5,
+            Self::Bitcoin => 8,
+        }
+    }
+
+    /// The unit written after the amount
+    pub const fn unit(&self) -> &'static str {
+        match self {
+            Self::Satoshi => "sat",
+            Self::Bit => "bits",
+            Self::MilliBitcoin => "mBTC",
+            Self::Bitcoin => "BTC",
+        }
+    }
+
+    /// Find the denomination of a unit ignoring ASCII case. Both the singular
+    /// and the plural of `sat`, `satoshi` and `bit` are accepted
+    pub fn from_unit(unit: &str) -> Option<Self> {
+        match unit.to_ascii_lowercase().as_str() {
+            "sat" | "sats" | "satoshi" | "satoshis" => Some(Self::Satoshi),
+            "bit" | "bits" => Some(Self::Bit),
+            "mbtc" => Some(Self::MilliBitcoin),
+            "btc" => Some(Self::Bitcoin),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Denomination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.unit())
+    }
+}
+
+/// How amounts of satoshis are written and read. The separators are part of
+/// the options instead of the system locale so the output is the same on every
+/// machine and can be parsed back with the same options, as long as the
+/// thousands separator differs from the decimal separator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AmountFormat {
+    /// The unit of the amount
+    pub denomination: Denomination,
+    /// Whether zeros at the end of the fraction and a fraction of only zeros are left out
+    pub trim_trailing_zeros: bool,
+    /// The character grouping the whole part in thousands
+    pub thousands_separator: Option<char>,
+    /// The character between the whole part and the fraction
+    pub decimal_separator: char,
+    /// Whether the unit is written after the amount separated by a space
+    pub show_unit: bool,
+}
+
+impl Default for AmountFormat {
+    fn default() -> Self {
+        Self::new(Denomination::Bitcoin)
+    }
+}
+
+impl AmountFormat {
+    /// Write amounts in `denomination` with every decimal place, a `.` as the
+    /// decimal separator, no thousands separator and the unit
+    pub fn new(denomination: Denomination) -> Self {
+        Self {
+            denomination,
+            trim_trailing_zeros: false,
+            thousands_separator: None,
+            decimal_separator: '.',
+            show_unit: true,
+        }
+    }
+
+    /// Write amounts with every decimal place trimmed of trailing zeros
+    pub fn trimmed(mut self) -> Self {
+        self.trim_trailing_zeros = true;
+
+        self
+    }
+
+    /// Group the whole part of amounts in thousands with `separator`
+    pub fn with_thousands_separator(mut self, separator: char) -> Self {
+        self.thousands_separator = Some(separator);
+
+        self
+    }
+
+    /// Separate the whole part and the fraction with `separator`
+    pub fn with_decimal_separator(mut self, separator: char) -> Self {
+        self.decimal_separator = separator;
+
+        self
+    }
+
+    /// Write amounts without their unit
+    pub fn without_unit(mut self) -> Self {
+        self.show_unit = false;
+
+        self
+    }
+
+    /// Write an amount of satoshis
+    pub fn format(&self, satoshis: u64) -> String {
+        let scale = 10u64.pow(self.denomination.decimals());
+        let whole = (satoshis / scale).to_string();
+
+        let mut formatted = String::new();
+        whole.chars().enumerate().for_each(|(index, digit)| {
+            if let Some(separator) = self.thousands_separator {
+                if index > 0 && (whole.len() - index).is_multiple_of(3) {
+                    formatted.push(separator);
+                }
+            }
+            formatted.push(digit);
+        });
+
+        let decimals = self.denomination.decimals() as usize;
+        let mut fraction = match decimals {
+            0 => String::new(),
+            _ => format!("{:0decimals$}", satoshis % scale),
+        };
+        if self.trim_trailing_zeros {
+            fraction.truncate(fraction.trim_end_matches('0').len());
+        }
+        if !fraction.is_empty() {
+            formatted.push(self.decimal_separator);
+            formatted.push_str(&fraction);
+        }
+
+        if self.show_unit {
+            formatted.push(' ');
+            formatted.push_str(self.denomination.unit());
+        }
+
+        formatted
+    }
+
+    /// Read an amount written with these options and return it in satoshis.
+    /// A unit after the amount takes the place of the denomination of the
+    /// options, the amount may have fewer decimals and thousands separators
+    /// are optional. Options whose thousands separator is the decimal
+    /// separator are rejected
+    pub fn parse(&self, amount: &str) -> io::Result<u64> {
+        if self.thousands_separator == Some(self.decimal_separator) {
+            return Err(Self::invalid(format!(
+                "`{}` is both the thousands and the decimal separator",
+                self.decimal_separator
+            )));
+        }
+
+        let amount = amount.trim();
+        // With a whitespace thousands separator the last group is not a unit
+        let is_number = |part: &str| {
+            part.chars().all(|character| {
+                character.is_ascii_digit()
+                    || character == self.decimal_separator
+                    || Some(character) == self.thousands_separator
+            })
+        };
+        let (number, denomination) = match amount.rsplit_once(char::is_whitespace) {
+            Some((number, unit)) if !is_number(unit) => (
+                number.trim_end(),
+                Denomination::from_unit(unit)
+                    .ok_or_else(|| Self::invalid(format!("`{}` is not a unit of bitcoin", unit)))?,
+            ),
+            _ => (amount, self.denomination),
+        };
+
+        let number = number
+            .chars()
+            .filter(|character| Some(*character) != self.thousands_separator)
+            .collect::<String>();
+        let (whole, fraction) = number
+            .split_once(self.decimal_separator)
+            .unwrap_or((number.as_str(), ""));
+
+        let decimals = denomination.decimals() as usize;
+        if fraction.len() > decimals {
+            return Err(Self::invalid(format!(
+                "An amount in {} has at most {} decimals",
+                denomination, decimals
+            )));
+        }
+        if whole.is_empty() && fraction.is_empty() {
+            return Err(Self::invalid("The amount has no digits".to_string()));
+        }
+        if !whole
+            .chars()
+            .chain(fraction.chars())
+            .all(|character| character.is_ascii_digit())
+        {
+            return Err(Self::invalid(format!("`{}` is not a valid amount", amount)));
+        }
+
+        let whole = if whole.is_empty() {
+            0
+        } else {
+            whole.parse::<u64>().map_err(|_| Self::overflow())?
+        };
+        let fraction = format!("{:0<decimals$}", fraction);
+        let fraction = if fraction.is_empty() {
+            0
+        } else {
+            fraction.parse::<u64>().map_err(|_| Self::overflow())?
+        };
+
+        whole
+            .checked_mul(10u64.pow(denomination.decimals()))
+            .and_then(|satoshis| satoshis.checked_add(fraction))
+            .ok_or_else(Self::overflow)
+    }
+
+    fn invalid(message: String) -> io::Error {
+        io::Error::new(ErrorKind::InvalidInput, message)
+    }
+
+    fn overflow() -> io::Error {
+        io::Error::new(
+            ErrorKind::InvalidInput,
+            "The amount does not fit in 64 bits of satoshis",
+        )
+    }
+}
+
+#[cfg(test)]
+mod amount_sanity_checks {
+    use crate::{AmountFormat, Denomination, MAX_MONEY};
+
+    #[test]
+    fn format_and_parse() {
+        let btc = AmountFormat::default();
+        assert_eq!("0.00150000 BTC", btc.format(150_000));
+        assert_eq!("0.0015 BTC", btc.trimmed().format(150_000));
+        assert_eq!("21 BTC", btc.trimmed().format(2_100_000_000));
+        assert_eq!(
+            "1.500,0005 mBTC",
+            AmountFormat::new(Denomination::MilliBitcoin)
+                .trimmed()
+                .with_thousands_separator('.')
+                .with_decimal_separator(',')
+                .format(150_000_050)
+        );
+        assert_eq!(
+            "1 234 567",
+            AmountFormat::new(Denomination::Satoshi)
+                .with_thousands_separator(' ')
+                .without_unit()
+                .format(1_234_567)
+        );
+        assert_eq!(
+            "1,500.50 bits",
+            AmountFormat::new(Denomination::Bit)
+                .with_thousands_separator(',')
+                .format(150_050)
+        );
+
+        let european = AmountFormat::new(Denomination::Bitcoin)
+            .with_thousands_separator('.')
+            .with_decimal_separator(',');
+        assert_eq!(
+            Ok(123_456_789_000),
+            european
+                .parse(&european.format(123_456_789_000))
+                .map_err(|error| error.kind())
+        );
+
+        (0..Denomination::ALL.len() * 2 * 4 * 2 * 2).for_each(|options| {
+            let format = AmountFormat {
+                denomination: Denomination::ALL[options % 4],
+                trim_trailing_zeros: options / 4 % 2 == 1,
+                thousands_separator: [None, Some(','), Some('.'), Some(' ')][options / 8 % 4],
+                decimal_separator: ['.', ','][options / 32 % 2],
+                show_unit: options / 64 % 2 == 1,
+            };
+
+            [
+                0,
+                1,
+                999,
+                1_000,
+                150_000_050,
+                1_234_567_890,
+                MAX_MONEY,
+                u64::MAX,
+            ]
+            .into_iter()
+            .for_each(|satoshis| {
+                let parsed = format.parse(&format.format(satoshis));
+                if format.thousands_separator == Some(format.decimal_separator) {
+                    assert!(parsed.is_err());
+                } else {
+                    assert_eq!(satoshis, parsed.unwrap(), "{:?}", format);
+                }
+            });
+        });
+
+        assert_eq!(150_000, btc.parse("0.0015").unwrap());
+        assert_eq!(150_000, btc.parse("1.5 mBTC").unwrap());
+        assert_eq!(150_000, btc.parse("150000 sats").unwrap());
+        assert_eq!(150_000, btc.parse(".0015").unwrap());
+        assert!(btc.parse("0.000000001").is_err());
+        assert!(btc.parse("1.5 ETH").is_err());
+        assert!(btc.parse("-1").is_err());
+        assert!(btc.parse("999999999999 BTC").is_err());
+    }
+}
@@ -0,0 +1,23 @@
+mod signatures;
+pub use signatures::*;
+
+mod flow;
+pub use flow::*;
+
+mod clustering;
+pub use clustering::*;
+
+mod patterns;
+pub use patterns::*;
+
+mod coinjoin;
+pub use coinjoin::*;
+
+mod reuse;
+pub use reuse::*;
+
+mod privacy;
+pub use privacy::*;
+
+mod values;
+pub use values::*;
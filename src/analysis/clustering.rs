@@ -0,0 +1,415 @@
+use crate::{analysis::is_round_amount, BtcTx, Network, OutPoint, ScriptType, Txid};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Amounts that are a multiple of this many satoshis are considered round.
+/// Payments tend to be round while change is whatever is left over
+pub const ROUND_AMOUNT: u64 = 10_000;
+
+/// How likely the conclusion of a heuristic is to be correct
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Confidence {
+    /// A single weak signal, like one change heuristic picking an output
+    Low,
+    /// Several signals agree, like two change heuristics picking the same output
+    Medium,
+    /// Strong evidence, like addresses spent together or every change heuristic agreeing
+    High,
+}
+
+/// The heuristic that linked an address to a cluster
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ClusterHeuristic {
+    /// The addresses were spent as inputs of the same transaction
+    CommonInput,
+    /// The output is the only one with the same script type as all the inputs
+    ChangeScriptType,
+    /// The output is the only one that is not a round amount
+    ChangeRoundAmount,
+    /// The output is the only one paying an address not seen elsewhere in the set
+    ChangeAddressReuse,
+}
+
+/// Why an address was added to a cluster
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ClusterLink {
+    /// The transaction ID of the transaction the heuristics were applied to
+    pub txid: Txid,
+    /// The address that was linked
+    pub address: String,
+    /// The heuristics that linked the address
+    pub heuristics: Vec<ClusterHeuristic>,
+    /// How likely the link is to be correct
+    pub confidence: Confidence,
+}
+
+/// A group of addresses that are likely controlled by the same owner
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressCluster {
+    /// The addresses in the cluster
+    pub addresses: BTreeSet<String>,
+    /// How each address was linked to the cluster
+    pub links: Vec<ClusterLink>,
+    /// The lowest confidence of the links of the cluster
+    pub confidence: Confidence,
+}
+
+/// Group the addresses of `txs` into clusters using the common-input-ownership
+/// heuristic and change detection. Each transaction is paired with its transaction ID
+/// in the byte order explorers display it in. Inputs are only resolved to addresses
+/// when the transaction they spend is in the set. Addresses that are not linked
+/// to any other address are not returned.
+pub fn cluster_addresses(txs: &[(Txid, BtcTx)], network: Network) -> Vec<AddressCluster> {
+    let outputs = txs
+        .iter()
+        .flat_map(|(txid, tx)| {
+            tx.outputs().iter().enumerate().map(move |(vout, output)| {
+                (
+                    OutPoint::new(*txid, vout as u32),
+                    (output.address(network), output.script_type()),
+                )
+            })
+        })
+        .collect::<HashMap<OutPoint, (Option<String>, ScriptType)>>();
+
+    // The transactions each address is paid by, used to find reused addresses
+    let mut seen_in = HashMap::<String, BTreeSet<Txid>>::new();
+    txs.iter().for_each(|(txid, tx)| {
+        tx.outputs()
+            .iter()
+            .filter_map(|output| output.address(network))
+            .for_each(|address| {
+                seen_in.entry(address).or_default().insert(*txid);
+            });
+    });
+
+    let mut clusters = Clusters::default();
+
+    txs.iter().for_each(|(txid, tx)| {
+        let inputs = tx
+            .inputs()
+            .iter()
+            .filter_map(|input| outputs.get(&input.previous_output()))
+            .filter_map(|(address, script_type)| {
+                address.as_ref().map(|address| (address, *script_type))
+            })
+            .collect::<Vec<(&String, ScriptType)>>();
+
+        let Some((first, _)) = inputs.first() else {
+            return;
+        };
+
+        inputs.iter().for_each(|(address, _)| {
+            clusters.link(
+                first,
+                ClusterLink {
+                    txid: *txid,
+                    address: address.to_string(),
+                    heuristics: vec![ClusterHeuristic::CommonInput],
+                    confidence: Confidence::High,
+                },
+            )
+        });
+
+        if let Some(change) = detect_change(*txid, tx, &inputs, &seen_in, network) {
+            clusters.link(first, change);
+        }
+    });
+
+    clusters.into_clusters()
+}
+
+// Pick the output that most change heuristics agree on. Outputs paying back
+// to an input address are already in the cluster so they are left out
+fn detect_change(
+    txid: Txid,
+    tx: &BtcTx,
+    inputs: &[(&String, ScriptType)],
+    seen_in: &HashMap<String, BTreeSet<Txid>>,
+    network: Network,
+) -> Option<ClusterLink> {
+    if tx.outputs().len() < 2 {
+        return None;
+    }
+
+    let candidates = tx
+        .outputs()
+        .iter()
+        .map(|output| {
+            (
+                output.address(network),
+                output.script_type(),
+                output.amount(),
+            )
+        })
+        .collect::<Vec<(Option<String>, ScriptType, u64)>>();
+
+    if candidates.iter().any(|(address, _, _)| {
+        inputs
+            .iter()
+            .any(|(input, _)| Some(*input) == address.as_ref())
+    }) {
+        return None;
+    }
+
+    let mut votes = vec![Vec::<ClusterHeuristic>::new(); candidates.len()];
+
+    // Each heuristic only votes when exactly one output matches
+    let mut vote = |heuristic: ClusterHeuristic, matches: &dyn Fn(usize) -> bool| {
+        let matching = (0..candidates.len())
+            .filter(|index| matches(*index))
+            .collect::<Vec<usize>>();
+        if let [index] = matching[..] {
+            votes[index].push(heuristic);
+        }
+    };
+
+    vote(ClusterHeuristic::ChangeScriptType, &|index| {
+        inputs
+            .iter()
+            .all(|(_, script_type)| *script_type == candidates[index].1)
+    });
+    vote(ClusterHeuristic::ChangeRoundAmount, &|index| {
+        !is_round_amount(candidates[index].2)
+    });
+    vote(ClusterHeuristic::ChangeAddressReuse, &|index| {
+        candidates[index]
+            .0
+            .as_ref()
+            .and_then(|address| seen_in.get(address))
+            .is_some_and(|txids| txids.len() == 1)
+    });
+
+    let most_votes = votes.iter().map(|heuristics| heuristics.len()).max()?;
+    let mut best = votes
+        .iter()
+        .enumerate()
+        .filter(|(_, heuristics)| heuristics.len() == most_votes);
+
+    // Outputs without an address cannot be clustered and a tie says nothing
+    let (index, heuristics) = best.next()?;
+    if best.next().is_some() {
+        return None;
+    }
+
+    let confidence = match most_votes {
+        0 => return None,
+        1 => Confidence::Low,
+        2 => Confidence::Medium,
+        _ => Confidence::High,
+    };
+
+    Some(ClusterLink {
+        txid,
+        address: candidates[index].0.clone()?,
+        heuristics: heuristics.clone(),
+        confidence,
+    })
+}
+
+// A union-find over the addresses keeping the links that joined them
+#[derive(Debug, Default)]
+struct Clusters {
+    indexes: HashMap<String, usize>,
+    parents: Vec<usize>,
+    links: Vec<ClusterLink>,
+}
+
+impl Clusters {
+    fn index(&mut self, address: &str) -> usize {
+        if let Some(index) = self.indexes.get(address) {
+            return *index;
+        }
+
+        let index = self.parents.len();
+        self.parents.push(index);
+        self.indexes.insert(address.to_string(), index);
+
+        index
+    }
+
+    fn root(&mut self, mut index: usize) -> usize {
+        while self.parents[index] != index {
+            self.parents[index] = self.parents[self.parents[index]];
+            index = self.parents[index];
+        }
+
+        index
+    }
+
+    fn link(&mut self, address: &str, link: ClusterLink) {
+        let first = self.index(address);
+        let second = self.index(&link.address);
+        let (first, second) = (self.root(first), self.root(second));
+
+        if first != second {
+            self.parents[second] = first;
+            self.links.push(link);
+        }
+    }
+
+    fn into_clusters(mut self) -> Vec<AddressCluster> {
+        let mut clusters = BTreeMap::<usize, AddressCluster>::new();
+
+        let mut addresses = self.indexes.clone().into_iter().collect::<Vec<_>>();
+        addresses.sort();
+        addresses.into_iter().for_each(|(address, index)| {
+            let root = self.root(index);
+            clusters
+                .entry(root)
+                .or_insert_with(|| AddressCluster {
+                    addresses: BTreeSet::new(),
+                    links: Vec::new(),
+                    confidence: Confidence::High,
+                })
+                .addresses
+                .insert(address);
+        });
+
+        let links = core::mem::take(&mut self.links);
+        links.into_iter().for_each(|link| {
+            let index = self.indexes[&link.address];
+            let root = self.root(index);
+            if let Some(cluster) = clusters.get_mut(&root) {
+                cluster.confidence = cluster.confidence.min(link.confidence);
+                cluster.links.push(link);
+            }
+        });
+
+        clusters
+            .into_values()
+            .filter(|cluster| cluster.addresses.len() > 1)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod clustering_sanity_checks {
+    use super::{cluster_addresses, ClusterHeuristic, Confidence};
+    use crate::{
+        fixtures::{tx_to, txid},
+        Address, Network,
+    };
+
+    fn p2wpkh(byte: u8) -> Vec<u8> {
+        let mut script = vec![0x00, 0x14];
+        script.extend_from_slice(&[byte; 20]);
+        script
+    }
+
+    fn p2pkh(byte: u8) -> Vec<u8> {
+        let mut script = vec![0x76, 0xa9, 0x14];
+        script.extend_from_slice(&[byte; 20]);
+        script.extend_from_slice(&[0x88, 0xac]);
+        script
+    }
+
+    #[test]
+    fn common_input_and_change() {
+        let (funding, spend, reuse) = (txid(1), txid(2), txid(3));
+        let address = |script: Vec<u8>| Address::from_script(&script, Network::Mainnet).unwrap();
+
+        let txs = vec![
+            (
+                funding,
+                tx_to(
+                    &[(txid(9), 0)],
+                    vec![(60_000, p2wpkh(1)), (70_000, p2wpkh(2))],
+                ),
+            ),
+            // Pays a round amount to a reused P2PKH address with change
+            // back to a fresh P2WPKH address
+            (
+                spend,
+                tx_to(
+                    &[(funding, 0), (funding, 1)],
+                    vec![(100_000, p2pkh(4)), (28_765, p2wpkh(5))],
+                ),
+            ),
+            (reuse, tx_to(&[(txid(8), 0)], vec![(50_000, p2pkh(4))])),
+        ];
+
+        let clusters = cluster_addresses(&txs, Network::Mainnet);
+        assert_eq!(1, clusters.len());
+
+        let cluster = &clusters[0];
+        assert_eq!(
+            [address(p2wpkh(1)), address(p2wpkh(2)), address(p2wpkh(5))]
+                .into_iter()
+                .collect::<std::collections::BTreeSet<String>>(),
+            cluster.addresses
+        );
+        assert_eq!(Confidence::High, cluster.confidence);
+
+        let change = cluster
+            .links
+            .iter()
+            .find(|link| link.address == address(p2wpkh(5)))
+            .unwrap();
+        assert_eq!(
+            vec![
+                ClusterHeuristic::ChangeScriptType,
+                ClusterHeuristic::ChangeRoundAmount,
+                ClusterHeuristic::ChangeAddressReuse
+            ],
+            change.heuristics
+        );
+    }
+
+    #[test]
+    fn edge_cases() {
+        let funding = txid(1);
+        let funding_tx = tx_to(
+            &[(txid(9), 0)],
+            vec![(60_000, p2wpkh(1)), (70_000, p2wpkh(2))],
+        );
+
+        // A transaction without inputs links nothing
+        let txs = vec![(funding, funding_tx.clone())];
+        assert!(cluster_addresses(&txs, Network::Mainnet).is_empty());
+
+        // Spending the same output twice only links the address to itself
+        let txs = vec![
+            (funding, funding_tx.clone()),
+            (
+                txid(2),
+                tx_to(&[(funding, 0), (funding, 0)], vec![(50_000, p2pkh(4))]),
+            ),
+        ];
+        assert!(cluster_addresses(&txs, Network::Mainnet).is_empty());
+
+        // A zero value data output has no address so the change is still found
+        let txs = vec![
+            (funding, funding_tx.clone()),
+            (
+                txid(2),
+                tx_to(
+                    &[(funding, 0)],
+                    vec![
+                        (0, vec![0x6a, 0x01, 0xaa]),
+                        (50_000, p2pkh(4)),
+                        (9_765, p2wpkh(5)),
+                    ],
+                ),
+            ),
+        ];
+        let clusters = cluster_addresses(&txs, Network::Mainnet);
+        assert_eq!(1, clusters.len());
+        assert_eq!(
+            Address::from_script(&p2wpkh(5), Network::Mainnet).unwrap(),
+            clusters[0].links[0].address
+        );
+
+        // Two outputs which every heuristic picks are a tie so neither is change
+        let txs = vec![
+            (funding, funding_tx),
+            (
+                txid(2),
+                tx_to(
+                    &[(funding, 0)],
+                    vec![(30_123, p2wpkh(5)), (29_456, p2wpkh(6))],
+                ),
+            ),
+        ];
+        assert!(cluster_addresses(&txs, Network::Mainnet).is_empty());
+    }
+}
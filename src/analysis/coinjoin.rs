@@ -0,0 +1,202 @@
+use crate::{
+    analysis::{spent_outputs, Confidence, EqualOutputs, TxPattern},
+    BtcTx, Txid,
+};
+
+/// The denominations in satoshis of the Whirlpool pools
+pub const WHIRLPOOL_DENOMINATIONS: [u64; 4] = [100_000, 1_000_000, 5_000_000, 50_000_000];
+
+/// The number of inputs and outputs of a Whirlpool mix
+pub const WHIRLPOOL_PARTICIPANTS: usize = 5;
+
+/// The denomination in satoshis Wasabi 1.x rounds hover around
+pub const WASABI_DENOMINATION: u64 = 10_000_000;
+
+/// The least number of equal outputs of a Wasabi round
+pub const WASABI_MIN_PARTICIPANTS: usize = 10;
+
+/// The coordinator fee of a Wasabi round is at most this many
+/// parts per thousand of the total mixed amount
+pub const WASABI_MAX_COORDINATOR_FEE_PERMILLE: u64 = 5;
+
+/// The coinjoin implementation whose structure a transaction matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CoinJoinKind {
+    /// Five inputs and five outputs of a pool denomination and nothing else
+    Whirlpool,
+    /// Many equal outputs near 0.1 BTC with change and a coordinator fee output
+    Wasabi,
+    /// A few equal outputs of any amount where every participant
+    /// but at most one gets a change output, with no coordinator
+    JoinMarket,
+    /// Equal outputs that do not match a known implementation
+    Unknown,
+}
+
+/// The structure of a transaction that looks like a coinjoin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoinJoinAnalysis {
+    /// The implementation the structure matches
+    pub kind: CoinJoinKind,
+    /// The amount of the equal outputs
+    pub denomination: u64,
+    /// The number of equal outputs which is the number of participants
+    pub participants: usize,
+    /// The indexes of the equal outputs
+    pub equal_outputs: Vec<usize>,
+    /// The index of the output likely paying the coordinator
+    pub coordinator_fee_output: Option<usize>,
+    /// How likely the transaction is a coinjoin of `kind`
+    pub confidence: Confidence,
+}
+
+/// Recognise coinjoin structures on top of the `EqualOutputs` pattern.
+/// Returns `None` if the transaction does not have enough equal outputs
+/// or is invalid because it spends the same output twice
+pub fn analyze_coinjoin(txid: Txid, tx: &BtcTx) -> Option<CoinJoinAnalysis> {
+    if spent_outputs(tx) != tx.inputs().len() || !EqualOutputs::default().matches(txid, tx, &[]) {
+        return None;
+    }
+
+    // The denomination is the amount shared by the most outputs, the larger
+    // amount wins a tie since change outputs are smaller than the mixed amount
+    let (denomination, participants) = EqualOutputs::amount_counts(tx)
+        .into_iter()
+        .max_by_key(|(amount, count)| (*count, *amount))?;
+
+    let equal_outputs = tx
+        .outputs()
+        .iter()
+        .enumerate()
+        .filter(|(_, output)| output.amount() == denomination)
+        .map(|(index, _)| index)
+        .collect::<Vec<usize>>();
+
+    let inputs = tx.inputs().len();
+    let outputs = tx.outputs().len();
+
+    let mut analysis = CoinJoinAnalysis {
+        kind: CoinJoinKind::Unknown,
+        denomination,
+        participants,
+        equal_outputs,
+        coordinator_fee_output: None,
+        confidence: Confidence::Low,
+    };
+
+    if inputs == WHIRLPOOL_PARTICIPANTS
+        && outputs == WHIRLPOOL_PARTICIPANTS
+        && participants == WHIRLPOOL_PARTICIPANTS
+        && WHIRLPOOL_DENOMINATIONS.contains(&denomination)
+    {
+        analysis.kind = CoinJoinKind::Whirlpool;
+        analysis.confidence = Confidence::High;
+    } else if participants >= WASABI_MIN_PARTICIPANTS
+        && denomination.abs_diff(WASABI_DENOMINATION) <= WASABI_DENOMINATION / 10
+    {
+        analysis.kind = CoinJoinKind::Wasabi;
+        analysis.coordinator_fee_output = coordinator_fee_output(tx, denomination, participants);
+        analysis.confidence = if analysis.coordinator_fee_output.is_some() {
+            Confidence::High
+        } else {
+            Confidence::Medium
+        };
+    } else if participants <= inputs
+        && (outputs == participants * 2 || outputs + 1 == participants * 2)
+    {
+        analysis.kind = CoinJoinKind::JoinMarket;
+        analysis.confidence = Confidence::Medium;
+    }
+
+    Some(analysis)
+}
+
+// The largest output that is not of the denomination and small enough to be the fee.
+// Zero value outputs like data carriers cannot pay the coordinator
+fn coordinator_fee_output(tx: &BtcTx, denomination: u64, participants: usize) -> Option<usize> {
+    let max_fee = denomination * participants as u64 * WASABI_MAX_COORDINATOR_FEE_PERMILLE / 1000;
+
+    tx.outputs()
+        .iter()
+        .enumerate()
+        .filter(|(_, output)| {
+            output.amount() != denomination && output.amount() > 0 && output.amount() <= max_fee
+        })
+        .max_by_key(|(_, output)| output.amount())
+        .map(|(index, _)| index)
+}
+
+#[cfg(test)]
+mod coinjoin_sanity_checks {
+    use super::{analyze_coinjoin, CoinJoinKind};
+    use crate::{
+        analysis::Confidence,
+        fixtures::{outpoints, tx, txid},
+        Txid,
+    };
+
+    #[test]
+    fn coinjoin_kinds() {
+        let txid = Txid::default();
+
+        let whirlpool = analyze_coinjoin(txid, &tx(&outpoints(5), &[1_000_000; 5])).unwrap();
+        assert_eq!(CoinJoinKind::Whirlpool, whirlpool.kind);
+        assert_eq!(Confidence::High, whirlpool.confidence);
+
+        // Ten participants of 0.1 BTC, two change outputs and a 0.003 BTC coordinator fee
+        let mut amounts = vec![10_000_000; 10];
+        amounts.extend_from_slice(&[2_345_678, 300_000, 1_234_567]);
+        let wasabi = analyze_coinjoin(txid, &tx(&outpoints(14), &amounts)).unwrap();
+        assert_eq!(CoinJoinKind::Wasabi, wasabi.kind);
+        assert_eq!(10, wasabi.participants);
+        assert_eq!(Some(11), wasabi.coordinator_fee_output);
+
+        let joinmarket = analyze_coinjoin(
+            txid,
+            &tx(
+                &outpoints(4),
+                &[2_500_000, 2_500_000, 2_500_000, 10, 20, 30],
+            ),
+        )
+        .unwrap();
+        assert_eq!(CoinJoinKind::JoinMarket, joinmarket.kind);
+        assert_eq!(vec![0, 1, 2], joinmarket.equal_outputs);
+
+        assert_eq!(None, analyze_coinjoin(txid, &tx(&outpoints(1), &[5, 5, 7])));
+    }
+
+    #[test]
+    fn edge_cases() {
+        let analyze = |inputs: &[(Txid, u32)], amounts: &[u64]| {
+            analyze_coinjoin(Txid::default(), &tx(inputs, amounts))
+        };
+
+        // No inputs, or one output spent by every input
+        assert_eq!(None, analyze(&[], &[1_000_000; 5]));
+        assert_eq!(None, analyze(&[(txid(1), 0); 5], &[1_000_000; 5]));
+        let mut inputs = outpoints(5);
+        inputs.push((txid(1), 0));
+        assert_eq!(None, analyze(&inputs, &[1_000_000; 5]));
+
+        // Zero value outputs are neither participants nor the coordinator fee
+        assert_eq!(None, analyze(&outpoints(5), &[0; 5]));
+        let mut amounts = vec![10_000_000; 10];
+        amounts.extend_from_slice(&[2_345_678, 0]);
+        let wasabi = analyze(&outpoints(12), &amounts).unwrap();
+        assert_eq!(CoinJoinKind::Wasabi, wasabi.kind);
+        assert_eq!(None, wasabi.coordinator_fee_output);
+        assert_eq!(Confidence::Medium, wasabi.confidence);
+
+        // The larger amount of two shared by as many outputs is the denomination
+        let tie = analyze(&outpoints(6), &[100, 100, 100, 200, 200, 200]).unwrap();
+        assert_eq!(200, tie.denomination);
+        assert_eq!(vec![3, 4, 5], tie.equal_outputs);
+        assert_eq!(CoinJoinKind::JoinMarket, tie.kind);
+
+        // Every participant but one with a change output is still JoinMarket
+        let joinmarket = analyze(&outpoints(3), &[500, 500, 500, 10, 20]).unwrap();
+        assert_eq!(CoinJoinKind::JoinMarket, joinmarket.kind);
+        let unknown = analyze(&outpoints(3), &[500, 500, 500, 10]).unwrap();
+        assert_eq!(CoinJoinKind::Unknown, unknown.kind);
+    }
+}
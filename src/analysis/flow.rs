@@ -0,0 +1,319 @@
+use crate::{BtcTx, OutPoint, Txid};
+use std::collections::{BTreeSet, HashMap};
+
+/// Decides how the satoshis of one traced input are split
+/// between the outputs of the transaction spending it
+pub trait FlowHeuristic {
+    /// Split `amount` satoshis of the input at `input_index` of `tx` over its outputs.
+    /// `input_values` has the value of every input that is known from the
+    /// transaction set. Returns the output indexes and the amounts they receive.
+    fn distribute(
+        &self,
+        tx: &BtcTx,
+        input_index: usize,
+        amount: u64,
+        input_values: &[Option<u64>],
+    ) -> Vec<(usize, u64)>;
+}
+
+/// Every output receives a share of the traced satoshis in proportion to its
+/// amount and the share of the inputs the traced input makes up. When the value
+/// of an input is unknown the sum of the outputs is used as the total of the
+/// inputs which ignores the fee.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Haircut;
+
+impl FlowHeuristic for Haircut {
+    fn distribute(
+        &self,
+        tx: &BtcTx,
+        _input_index: usize,
+        amount: u64,
+        input_values: &[Option<u64>],
+    ) -> Vec<(usize, u64)> {
+        let outputs_total = tx
+            .outputs()
+            .iter()
+            .map(|output| output.amount())
+            .sum::<u64>();
+        let inputs_total = input_values
+            .iter()
+            .copied()
+            .sum::<Option<u64>>()
+            .unwrap_or(outputs_total);
+
+        if inputs_total == 0 {
+            return Vec::new();
+        }
+
+        tx.outputs()
+            .iter()
+            .enumerate()
+            .map(|(index, output)| {
+                let share = output.amount() as u128 * amount as u128 / inputs_total as u128;
+                (index, share as u64)
+            })
+            .filter(|(_, share)| *share > 0)
+            .collect()
+    }
+}
+
+/// The satoshis of the inputs fill the outputs in order so the traced input
+/// pays the outputs covering the same range of satoshis. The values of all the
+/// inputs before the traced input must be known otherwise nothing is traced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Fifo;
+
+impl FlowHeuristic for Fifo {
+    fn distribute(
+        &self,
+        tx: &BtcTx,
+        input_index: usize,
+        amount: u64,
+        input_values: &[Option<u64>],
+    ) -> Vec<(usize, u64)> {
+        let start = match input_values[..input_index]
+            .iter()
+            .copied()
+            .sum::<Option<u64>>()
+        {
+            Some(start) => start,
+            None => return Vec::new(),
+        };
+        let end = start + amount;
+
+        let mut output_start = 0u64;
+        tx.outputs()
+            .iter()
+            .enumerate()
+            .filter_map(|(index, output)| {
+                let output_end = output_start + output.amount();
+                let overlap = end.min(output_end).saturating_sub(start.max(output_start));
+                output_start = output_end;
+
+                (overlap > 0).then_some((index, overlap))
+            })
+            .collect()
+    }
+}
+
+/// An output holding traced satoshis and the outputs they went to next
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlowNode {
+    /// The output holding the traced satoshis
+    pub outpoint: OutPoint,
+    /// The number of traced satoshis in the output
+    pub amount: u64,
+    /// The outputs of the transaction spending this output that received
+    /// traced satoshis. Empty if the output is not spent in the transaction set
+    /// or the transaction spending it is invalid because it spends an output twice
+    pub children: Vec<FlowNode>,
+}
+
+impl FlowNode {
+    /// The outputs at the end of the flow which are not spent in the transaction set
+    pub fn leaves(&self) -> Vec<&FlowNode> {
+        if self.children.is_empty() {
+            return vec![self];
+        }
+
+        self.children
+            .iter()
+            .flat_map(|child| child.leaves())
+            .collect()
+    }
+}
+
+/// Follow the satoshis of `outpoint` forward through `txs` using the `Haircut`
+/// heuristic. Each transaction is paired with its transaction ID in the byte
+/// order explorers display it in. Returns `None` if the transaction creating
+/// `outpoint` is not in the set.
+pub fn trace_value(outpoint: OutPoint, txs: &[(Txid, BtcTx)]) -> Option<FlowNode> {
+    trace_value_with(outpoint, txs, &Haircut)
+}
+
+/// Same as `trace_value()` using the chosen heuristic
+pub fn trace_value_with(
+    outpoint: OutPoint,
+    txs: &[(Txid, BtcTx)],
+    heuristic: &impl FlowHeuristic,
+) -> Option<FlowNode> {
+    let tracer = Tracer::new(txs);
+    let amount = tracer.output_value(&outpoint)?;
+
+    Some(tracer.trace(outpoint, amount, heuristic, &mut BTreeSet::new()))
+}
+
+struct Tracer<'a> {
+    // The transactions of the set by transaction ID
+    txs: HashMap<Txid, &'a BtcTx>,
+    // The transaction ID and input index spending each output
+    spends: HashMap<OutPoint, (Txid, usize)>,
+}
+
+impl<'a> Tracer<'a> {
+    fn new(txs: &'a [(Txid, BtcTx)]) -> Self {
+        let spends = txs
+            .iter()
+            .flat_map(|(txid, tx)| {
+                tx.inputs()
+                    .iter()
+                    .enumerate()
+                    .map(move |(input_index, input)| {
+                        (input.previous_output(), (*txid, input_index))
+                    })
+            })
+            .collect();
+
+        Self {
+            txs: txs.iter().map(|(txid, tx)| (*txid, tx)).collect(),
+            spends,
+        }
+    }
+
+    fn output_value(&self, outpoint: &OutPoint) -> Option<u64> {
+        self.txs
+            .get(&outpoint.txid())?
+            .outputs()
+            .get(outpoint.vout() as usize)
+            .map(|output| output.amount())
+    }
+
+    fn trace(
+        &self,
+        outpoint: OutPoint,
+        amount: u64,
+        heuristic: &impl FlowHeuristic,
+        visited: &mut BTreeSet<OutPoint>,
+    ) -> FlowNode {
+        let mut node = FlowNode {
+            outpoint,
+            amount,
+            children: Vec::new(),
+        };
+
+        // A malformed set could spend the same output in a loop
+        if !visited.insert(outpoint) {
+            return node;
+        }
+
+        if let Some((txid, input_index)) = self.spends.get(&outpoint) {
+            let tx = self.txs[txid];
+            // A transaction spending an output twice is invalid and would
+            // count the value of the output twice
+            let mut spent = BTreeSet::<OutPoint>::new();
+            if !tx
+                .inputs()
+                .iter()
+                .all(|input| spent.insert(input.previous_output()))
+            {
+                return node;
+            }
+            let input_values = tx
+                .inputs()
+                .iter()
+                .map(|input| self.output_value(&input.previous_output()))
+                .collect::<Vec<Option<u64>>>();
+
+            node.children = heuristic
+                .distribute(tx, *input_index, amount, &input_values)
+                .into_iter()
+                .map(|(vout, share)| {
+                    self.trace(OutPoint::new(*txid, vout as u32), share, heuristic, visited)
+                })
+                .collect();
+        }
+
+        node
+    }
+}
+
+#[cfg(test)]
+mod flow_sanity_checks {
+    use super::{trace_value, trace_value_with, Fifo};
+    use crate::{
+        fixtures::{tx, txid},
+        OutPoint,
+    };
+
+    #[test]
+    fn trace() {
+        let (a, b, c) = (txid(1), txid(2), txid(3));
+        // `a` creates two outputs of 600 and 400, `b` spends both into 500 and 500
+        // and `c` spends the first output of `b`
+        let txs = vec![
+            (a, tx(&[(txid(9), 0)], &[600, 400])),
+            (b, tx(&[(a, 0), (a, 1)], &[500, 500])),
+            (c, tx(&[(b, 0)], &[500])),
+        ];
+
+        let haircut = trace_value(OutPoint::new(a, 0), &txs).unwrap();
+        assert_eq!(600, haircut.amount);
+        assert_eq!(
+            vec![300, 300],
+            haircut
+                .children
+                .iter()
+                .map(|child| child.amount)
+                .collect::<Vec<u64>>()
+        );
+        assert_eq!(OutPoint::new(c, 0), haircut.leaves()[0].outpoint);
+
+        // The first 600 satoshis fill the first output and 100 of the second
+        let fifo = trace_value_with(OutPoint::new(a, 0), &txs, &Fifo).unwrap();
+        assert_eq!(
+            vec![(0, 500), (1, 100)],
+            fifo.children
+                .iter()
+                .map(|child| (child.outpoint.vout(), child.amount))
+                .collect::<Vec<(u32, u64)>>()
+        );
+
+        assert_eq!(None, trace_value(OutPoint::new(txid(8), 0), &txs));
+    }
+
+    #[test]
+    fn edge_cases() {
+        let (a, b) = (txid(1), txid(2));
+
+        // A transaction without inputs and one spending into no outputs
+        let txs = vec![(a, tx(&[], &[600, 0])), (b, tx(&[(a, 0)], &[]))];
+        let node = trace_value(OutPoint::new(a, 0), &txs).unwrap();
+        assert!(node.children.is_empty());
+        assert_eq!(vec![&node], node.leaves());
+
+        // A zero value output holds nothing to trace
+        let zero = trace_value(OutPoint::new(a, 1), &txs).unwrap();
+        assert_eq!(0, zero.amount);
+        assert!(zero.children.is_empty());
+
+        // Spending the same output twice is invalid so the flow stops there
+        let txs = vec![
+            (a, tx(&[(txid(9), 0)], &[600])),
+            (b, tx(&[(a, 0), (a, 0)], &[1_200])),
+        ];
+        assert!(trace_value(OutPoint::new(a, 0), &txs)
+            .unwrap()
+            .children
+            .is_empty());
+
+        // Equal outputs share the satoshis equally and rounding loses the rest,
+        // while an input ending on the boundary of an output only pays that output
+        let txs = vec![
+            (a, tx(&[(txid(9), 0)], &[100, 200])),
+            (b, tx(&[(a, 0), (a, 1)], &[100, 100, 100])),
+        ];
+        let haircut = trace_value(OutPoint::new(a, 0), &txs).unwrap();
+        assert_eq!(
+            vec![33, 33, 33],
+            haircut
+                .children
+                .iter()
+                .map(|child| child.amount)
+                .collect::<Vec<u64>>()
+        );
+        let fifo = trace_value_with(OutPoint::new(a, 0), &txs, &Fifo).unwrap();
+        assert_eq!(1, fifo.children.len());
+        assert_eq!(OutPoint::new(b, 0), fifo.children[0].outpoint);
+    }
+}
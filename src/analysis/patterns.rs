@@ -0,0 +1,320 @@
+use crate::{BtcTx, OutPoint, Txid};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The label given to a transaction matching a `TxPattern`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TxLabel {
+    /// One input paying many outputs like an exchange processing withdrawals
+    BatchWithdrawal,
+    /// Many inputs merged into one output like a wallet consolidating its UTXOs
+    Consolidation,
+    /// Several outputs of the same amount like the mixing round of a coinjoin
+    CoinJoinLike,
+    /// A step of a chain where a large output is repeatedly split into a small
+    /// payment and a change output which is spent the same way
+    PeelingChain,
+    /// A label of a pattern defined outside this crate
+    Custom(String),
+}
+
+/// A rule recognising the structure of a transaction. Patterns get the
+/// transaction set the transaction belongs to so they can look at the
+/// transactions it spends from and the ones spending it.
+/// Each transaction is paired with its transaction ID.
+pub trait TxPattern {
+    /// The label of transactions matching the pattern
+    fn label(&self) -> TxLabel;
+
+    /// Whether the transaction `txid` of `txs` matches the pattern
+    fn matches(&self, txid: Txid, tx: &BtcTx, txs: &[(Txid, BtcTx)]) -> bool;
+}
+
+/// One input paying at least `min_outputs` outputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BatchWithdrawal {
+    /// The least number of outputs of a batch
+    pub min_outputs: usize,
+}
+
+impl Default for BatchWithdrawal {
+    fn default() -> Self {
+        Self { min_outputs: 5 }
+    }
+}
+
+impl TxPattern for BatchWithdrawal {
+    fn label(&self) -> TxLabel {
+        TxLabel::BatchWithdrawal
+    }
+
+    fn matches(&self, _txid: Txid, tx: &BtcTx, _txs: &[(Txid, BtcTx)]) -> bool {
+        tx.inputs().len() == 1 && tx.outputs().len() >= self.min_outputs
+    }
+}
+
+/// At least `min_inputs` inputs spending different outputs paying a single output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Consolidation {
+    /// The least number of inputs of a consolidation
+    pub min_inputs: usize,
+}
+
+impl Default for Consolidation {
+    fn default() -> Self {
+        Self { min_inputs: 3 }
+    }
+}
+
+impl TxPattern for Consolidation {
+    fn label(&self) -> TxLabel {
+        TxLabel::Consolidation
+    }
+
+    fn matches(&self, _txid: Txid, tx: &BtcTx, _txs: &[(Txid, BtcTx)]) -> bool {
+        spent_outputs(tx) >= self.min_inputs && tx.outputs().len() == 1
+    }
+}
+
+/// At least `min_equal_outputs` outputs of the same amount and at least
+/// as many inputs spending different outputs as there are equal outputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EqualOutputs {
+    /// The least number of outputs sharing an amount
+    pub min_equal_outputs: usize,
+}
+
+impl Default for EqualOutputs {
+    fn default() -> Self {
+        Self {
+            min_equal_outputs: 3,
+        }
+    }
+}
+
+impl EqualOutputs {
+    /// The amounts paid by more than one output with the number of outputs paying each.
+    /// Zero value outputs like data carriers do not pay anyone so they are left out
+    pub fn amount_counts(tx: &BtcTx) -> BTreeMap<u64, usize> {
+        let mut counts = BTreeMap::<u64, usize>::new();
+        tx.outputs()
+            .iter()
+            .filter(|output| output.amount() > 0)
+            .for_each(|output| {
+                *counts.entry(output.amount()).or_default() += 1;
+            });
+        counts.retain(|_, count| *count > 1);
+
+        counts
+    }
+}
+
+impl TxPattern for EqualOutputs {
+    fn label(&self) -> TxLabel {
+        TxLabel::CoinJoinLike
+    }
+
+    fn matches(&self, _txid: Txid, tx: &BtcTx, _txs: &[(Txid, BtcTx)]) -> bool {
+        Self::amount_counts(tx)
+            .values()
+            .max()
+            .is_some_and(|count| *count >= self.min_equal_outputs && spent_outputs(tx) >= *count)
+    }
+}
+
+// The number of different outputs the inputs of `tx` spend. A transaction spending
+// an output twice is invalid so its inputs are not counted as separate coins
+pub(crate) fn spent_outputs(tx: &BtcTx) -> usize {
+    tx.inputs()
+        .iter()
+        .map(|input| input.previous_output())
+        .collect::<BTreeSet<OutPoint>>()
+        .len()
+}
+
+/// One input paying two outputs where the transaction spends or is spent
+/// by another transaction of the set with the same shape
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct PeelingChain;
+
+impl PeelingChain {
+    fn is_peel(tx: &BtcTx) -> bool {
+        tx.inputs().len() == 1 && tx.outputs().len() == 2
+    }
+}
+
+impl TxPattern for PeelingChain {
+    fn label(&self) -> TxLabel {
+        TxLabel::PeelingChain
+    }
+
+    fn matches(&self, txid: Txid, tx: &BtcTx, txs: &[(Txid, BtcTx)]) -> bool {
+        if !Self::is_peel(tx) {
+            return false;
+        }
+
+        let parent = tx.inputs()[0].previous_tx_id();
+
+        txs.iter().any(|(other_txid, other)| {
+            Self::is_peel(other)
+                && (*other_txid == parent || other.inputs()[0].previous_tx_id() == txid)
+        })
+    }
+}
+
+/// Labels transactions with every pattern they match
+pub struct PatternMatcher {
+    patterns: Vec<Box<dyn TxPattern>>,
+}
+
+impl PatternMatcher {
+    /// A matcher with every built-in pattern using its default thresholds
+    pub fn new() -> Self {
+        Self::empty()
+            .with(BatchWithdrawal::default())
+            .with(Consolidation::default())
+            .with(EqualOutputs::default())
+            .with(PeelingChain)
+    }
+
+    /// A matcher without any pattern
+    pub fn empty() -> Self {
+        Self {
+            patterns: Vec::new(),
+        }
+    }
+
+    /// Add a pattern to the matcher
+    pub fn with(mut self, pattern: impl TxPattern + 'static) -> Self {
+        self.patterns.push(Box::new(pattern));
+
+        self
+    }
+
+    /// The labels of every pattern the transaction `txid` of `txs` matches
+    pub fn label(&self, txid: Txid, tx: &BtcTx, txs: &[(Txid, BtcTx)]) -> Vec<TxLabel> {
+        self.patterns
+            .iter()
+            .filter(|pattern| pattern.matches(txid, tx, txs))
+            .map(|pattern| pattern.label())
+            .collect()
+    }
+
+    /// The labels of every transaction of `txs` in the same order
+    pub fn label_all(&self, txs: &[(Txid, BtcTx)]) -> Vec<(Txid, Vec<TxLabel>)> {
+        txs.iter()
+            .map(|(txid, tx)| (*txid, self.label(*txid, tx, txs)))
+            .collect()
+    }
+}
+
+impl Default for PatternMatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod patterns_sanity_checks {
+    use super::{PatternMatcher, TxLabel, TxPattern};
+    use crate::{
+        fixtures::{tx, txid},
+        BtcTx, Txid,
+    };
+
+    struct OpReturnOnly;
+
+    impl TxPattern for OpReturnOnly {
+        fn label(&self) -> TxLabel {
+            TxLabel::Custom("zero value".into())
+        }
+
+        fn matches(&self, _txid: Txid, tx: &BtcTx, _txs: &[(Txid, BtcTx)]) -> bool {
+            tx.outputs().iter().all(|output| output.amount() == 0)
+        }
+    }
+
+    #[test]
+    fn built_in_patterns() {
+        let txs = vec![
+            (txid(1), tx(&[(txid(9), 0)], &[1, 2, 3, 4, 5, 6])),
+            (
+                txid(2),
+                tx(&[(txid(9), 1), (txid(9), 2), (txid(9), 3)], &[10]),
+            ),
+            (
+                txid(3),
+                tx(
+                    &[(txid(9), 4), (txid(9), 5), (txid(9), 6)],
+                    &[100, 100, 100, 7],
+                ),
+            ),
+            // Two peels in a row
+            (txid(4), tx(&[(txid(9), 7)], &[50, 950])),
+            (txid(5), tx(&[(txid(4), 1)], &[50, 900])),
+            (txid(6), tx(&[(txid(9), 8)], &[0])),
+        ];
+
+        let labels = PatternMatcher::new()
+            .with(OpReturnOnly)
+            .label_all(&txs)
+            .into_iter()
+            .map(|(_, labels)| labels)
+            .collect::<Vec<Vec<TxLabel>>>();
+
+        assert_eq!(
+            vec![
+                vec![TxLabel::BatchWithdrawal],
+                vec![TxLabel::Consolidation],
+                vec![TxLabel::CoinJoinLike],
+                vec![TxLabel::PeelingChain],
+                vec![TxLabel::PeelingChain],
+                vec![TxLabel::Custom("zero value".into())],
+            ],
+            labels
+        );
+    }
+
+    #[test]
+    fn edge_cases() {
+        let labels =
+            |tx: BtcTx| PatternMatcher::new().label(txid(1), &tx, &[(txid(1), tx.clone())]);
+
+        // No inputs
+        assert!(labels(tx(&[], &[])).is_empty());
+        assert!(labels(tx(&[], &[1, 2, 3, 4, 5, 6])).is_empty());
+        assert!(labels(tx(&[], &[100, 100, 100])).is_empty());
+
+        // The same output spent three times is not a consolidation of three coins
+        assert!(labels(tx(&[(txid(9), 0); 3], &[10])).is_empty());
+        assert!(labels(tx(&[(txid(9), 0); 3], &[100, 100, 100])).is_empty());
+
+        // Zero value outputs are not equal payments
+        assert!(labels(tx(&[(txid(9), 0), (txid(9), 1), (txid(9), 2)], &[0, 0, 0])).is_empty());
+
+        // Ties with the thresholds match
+        assert_eq!(
+            vec![TxLabel::BatchWithdrawal],
+            labels(tx(&[(txid(9), 0)], &[1, 2, 3, 4, 5]))
+        );
+        assert!(labels(tx(&[(txid(9), 0)], &[1, 2, 3, 4])).is_empty());
+        assert_eq!(
+            vec![TxLabel::Consolidation],
+            labels(tx(&[(txid(9), 0), (txid(9), 1), (txid(9), 2)], &[10]))
+        );
+        assert!(labels(tx(&[(txid(9), 0), (txid(9), 1)], &[10])).is_empty());
+
+        // Two amounts shared by as many outputs give a single label
+        let inputs = (0..3)
+            .map(|vout| (txid(9), vout))
+            .collect::<Vec<(Txid, u32)>>();
+        assert_eq!(
+            vec![TxLabel::CoinJoinLike],
+            labels(tx(&inputs, &[100, 100, 100, 200, 200, 200]))
+        );
+        // but not when there are fewer inputs than equal outputs
+        assert!(labels(tx(&inputs[..2], &[100, 100, 100])).is_empty());
+
+        // A lone peel has no chain
+        assert!(labels(tx(&[(txid(9), 0)], &[50, 950])).is_empty());
+    }
+}
@@ -0,0 +1,311 @@
+use crate::{
+    analysis::{analyze_values, spent_outputs, ChangeGuess},
+    BtcTx, TxOutput, MAX_MONEY,
+};
+use std::io::{self, ErrorKind};
+
+/// The most inputs and outputs together that `privacy_score()` analyzes. The
+/// work grows with three to the power of this number
+pub const MAX_PRIVACY_INPUTS_AND_OUTPUTS: usize = 14;
+
+/// The Boltzmann analysis of a transaction. An interpretation splits the
+/// transaction into sub-transactions, each with at least one input and one
+/// output whose inputs pay for its outputs
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrivacyScore {
+    /// The number of interpretations, one when the transaction is a single payment
+    pub interpretations: u64,
+    /// The base 2 logarithm of the number of interpretations
+    pub entropy: f64,
+    /// The share of interpretations in which each input, by the index of its
+    /// row, funds each output, by the index of its column
+    pub link_probabilities: Vec<Vec<f64>>,
+    /// The input and output pairs linked in every interpretation
+    pub deterministic_links: Vec<(usize, usize)>,
+    /// From 0 to 1, one minus the average over outputs of the probability of
+    /// their most likely input. 0 means every output is traced to an input
+    pub score: f64,
+    /// The output the amounts point to as the change, linking it to the
+    /// inputs even when the interpretations do not
+    pub likely_change: Option<ChangeGuess>,
+}
+
+/// Count the interpretations of `tx` spending `prevouts`, the outputs spent
+/// by every input in order, and how strongly they link inputs to outputs.
+/// Transactions with more than `MAX_PRIVACY_INPUTS_AND_OUTPUTS` inputs and
+/// outputs are not supported, nor amounts above `MAX_MONEY` or transactions
+/// spending the same output twice
+pub fn privacy_score(tx: &BtcTx, prevouts: &[TxOutput]) -> io::Result<PrivacyScore> {
+    let inputs = tx.inputs().len();
+    let outputs = tx.outputs().len();
+    if prevouts.len() != inputs {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "The transaction has {} inputs but {} spent outputs were given",
+                inputs,
+                prevouts.len()
+            ),
+        ));
+    }
+    // Every input would count the value of the output it spends
+    if spent_outputs(tx) != inputs {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "The transaction spends the same output twice",
+        ));
+    }
+    if inputs == 0 || outputs == 0 || inputs + outputs > MAX_PRIVACY_INPUTS_AND_OUTPUTS {
+        return Err(io::Error::new(
+            ErrorKind::Unsupported,
+            format!(
+                "Analyzing {} inputs and {} outputs is not supported",
+                inputs, outputs
+            ),
+        ));
+    }
+
+    if let Some(amount) = prevouts
+        .iter()
+        .chain(tx.outputs())
+        .map(|output| output.amount())
+        .find(|amount| *amount > MAX_MONEY)
+    {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("The amount of {} satoshis is above MAX_MONEY", amount),
+        ));
+    }
+
+    let interpretations = Interpretations::new(
+        &prevouts
+            .iter()
+            .map(|prevout| prevout.amount())
+            .collect::<Vec<u64>>(),
+        &tx.outputs()
+            .iter()
+            .map(|output| output.amount())
+            .collect::<Vec<u64>>(),
+    );
+    let (count, links) = interpretations.count_with_links();
+
+    let link_probabilities = links
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|linked| *linked as f64 / count.max(1) as f64)
+                .collect::<Vec<f64>>()
+        })
+        .collect::<Vec<Vec<f64>>>();
+    let deterministic_links = links
+        .iter()
+        .enumerate()
+        .flat_map(|(input_index, row)| {
+            row.iter()
+                .enumerate()
+                .filter(|(_, linked)| count > 0 && **linked == count)
+                .map(move |(output_index, _)| (input_index, output_index))
+        })
+        .collect();
+    let traced = (0..outputs)
+        .map(|output_index| {
+            link_probabilities
+                .iter()
+                .map(|row| row[output_index])
+                .fold(0f64, f64::max)
+        })
+        .sum::<f64>()
+        / outputs as f64;
+
+    Ok(PrivacyScore {
+        interpretations: count,
+        entropy: (count.max(1) as f64).log2(),
+        link_probabilities,
+        deterministic_links,
+        score: 1.0 - traced,
+        likely_change: analyze_values(tx, None).change,
+    })
+}
+
+// The sub-transactions are found by taking the lowest input left with any of
+// the other inputs and outputs left, so every interpretation is found once.
+// A state is the inputs left in the low bits and the outputs left above them
+struct Interpretations {
+    inputs: usize,
+    outputs: usize,
+    input_sums: Vec<u64>,
+    output_sums: Vec<u64>,
+}
+
+impl Interpretations {
+    fn new(input_amounts: &[u64], output_amounts: &[u64]) -> Self {
+        Self {
+            inputs: input_amounts.len(),
+            outputs: output_amounts.len(),
+            input_sums: Self::subset_sums(input_amounts),
+            output_sums: Self::subset_sums(output_amounts),
+        }
+    }
+
+    // The number of interpretations and how many of them link every input to
+    // every output
+    fn count_with_links(&self) -> (u64, Vec<Vec<u64>>) {
+        let states = 1usize << (self.inputs + self.outputs);
+        let full = states - 1;
+
+        // The interpretations of the inputs and outputs left in each state,
+        // computed from the smaller states up
+        let mut remaining = vec![0u64; states];
+        remaining[0] = 1;
+        for state in 1..states {
+            remaining[state] = self.blocks(state).map(|(rest, _, _)| remaining[rest]).sum();
+        }
+
+        // The number of ways to reach each state from the full transaction
+        let mut reached = vec![0u64; states];
+        reached[full] = 1;
+        let mut links = vec![vec![0u64; self.outputs]; self.inputs];
+        for state in (1..states).rev() {
+            if reached[state] == 0 {
+                continue;
+            }
+
+            for (rest, block_inputs, block_outputs) in self.blocks(state) {
+                if remaining[rest] == 0 {
+                    continue;
+                }
+                reached[rest] += reached[state];
+
+                let through = reached[state] * remaining[rest];
+                (0..self.inputs)
+                    .filter(|input| block_inputs & (1 << input) != 0)
+                    .for_each(|input| {
+                        (0..self.outputs)
+                            .filter(|output| block_outputs & (1 << output) != 0)
+                            .for_each(|output| links[input][output] += through)
+                    });
+            }
+        }
+
+        (remaining[full], links)
+    }
+
+    // The sub-transactions that can be taken from a state with the state left
+    // after them, as the state left, the inputs and the outputs
+    fn blocks(&self, state: usize) -> impl Iterator<Item = (usize, usize, usize)> + '_ {
+        let input_mask = (1usize << self.inputs) - 1;
+        let inputs_left = state & input_mask;
+        let outputs_left = state >> self.inputs;
+
+        let lowest = if inputs_left == 0 {
+            0
+        } else {
+            1 << inputs_left.trailing_zeros()
+        };
+        let other_inputs = inputs_left & !lowest;
+
+        Self::subsets(other_inputs)
+            .filter(move |_| lowest != 0)
+            .flat_map(move |others| {
+                let block_inputs = others | lowest;
+                Self::subsets(outputs_left)
+                    .filter(move |block_outputs| {
+                        *block_outputs != 0
+                            && self.input_sums[block_inputs] >= self.output_sums[*block_outputs]
+                    })
+                    .map(move |block_outputs| {
+                        (
+                            state & !(block_inputs | block_outputs << self.inputs),
+                            block_inputs,
+                            block_outputs,
+                        )
+                    })
+            })
+    }
+
+    // Every subset of the bits of `mask` including the empty one
+    fn subsets(mask: usize) -> impl Iterator<Item = usize> {
+        let mut next = Some(mask);
+
+        std::iter::from_fn(move || {
+            let subset = next?;
+            next = (subset != 0).then(|| (subset - 1) & mask);
+
+            Some(subset)
+        })
+    }
+
+    fn subset_sums(amounts: &[u64]) -> Vec<u64> {
+        (0..1usize << amounts.len())
+            .map(|subset| {
+                amounts
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| subset & (1 << index) != 0)
+                    .map(|(_, amount)| amount)
+                    .sum()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod privacy_sanity_checks {
+    use crate::{
+        analysis::privacy_score,
+        fixtures::{outpoints, tx, txid},
+        TxOutput,
+    };
+    use std::io::ErrorKind;
+
+    #[test]
+    fn boltzmann_scores() {
+        // A payment with change has one interpretation
+        let payment = privacy_score(
+            &tx(&outpoints(1), &[60_000, 39_000]),
+            &[TxOutput::new(100_000, vec![0x51])],
+        )
+        .unwrap();
+        assert_eq!(1, payment.interpretations);
+        assert_eq!(vec![(0, 0), (0, 1)], payment.deterministic_links);
+        assert_eq!(0.0, payment.score);
+
+        // Two equal inputs and outputs are one transaction or either pairing
+        let prevouts = vec![TxOutput::new(50_000, vec![0x51]); 2];
+        let mix = privacy_score(&tx(&outpoints(2), &[49_000, 49_000]), &prevouts).unwrap();
+        assert_eq!(3, mix.interpretations);
+        assert!(mix.deterministic_links.is_empty());
+        assert!((mix.link_probabilities[0][1] - 2.0 / 3.0).abs() < 1e-9);
+        assert!((mix.score - 1.0 / 3.0).abs() < 1e-9);
+
+        assert!(privacy_score(&tx(&outpoints(2), &[1_000]), &prevouts[..1]).is_err());
+
+        // Amounts which parse but could overflow the sums are rejected
+        let error =
+            privacy_score(&tx(&outpoints(2), &[u64::MAX, u64::MAX]), &prevouts).unwrap_err();
+        assert_eq!(ErrorKind::InvalidData, error.kind());
+    }
+
+    #[test]
+    fn edge_cases() {
+        let prevouts = vec![TxOutput::new(50_000, vec![0x51]); 2];
+
+        let error = privacy_score(&tx(&[], &[1_000]), &[]).unwrap_err();
+        assert_eq!(ErrorKind::Unsupported, error.kind());
+        let error = privacy_score(&tx(&[(txid(1), 0); 2], &[49_000, 49_000]), &prevouts);
+        assert_eq!(ErrorKind::InvalidData, error.unwrap_err().kind());
+
+        // A zero value output can go with either sub-transaction but is never the change
+        let payment = privacy_score(&tx(&outpoints(1), &[40_000, 0]), &prevouts[..1]).unwrap();
+        assert_eq!(1, payment.interpretations);
+        assert_eq!(None, payment.likely_change);
+        let mix = privacy_score(&tx(&outpoints(2), &[49_000, 49_000, 0]), &prevouts).unwrap();
+        assert_eq!(5, mix.interpretations);
+        assert!(mix.deterministic_links.is_empty());
+
+        // The equal outputs tie so neither is the change
+        let mix = privacy_score(&tx(&outpoints(2), &[49_000, 49_000]), &prevouts).unwrap();
+        assert_eq!(None, mix.likely_change);
+        assert_eq!(mix.link_probabilities[0], mix.link_probabilities[1]);
+    }
+}
@@ -0,0 +1,174 @@
+use crate::{Address, BtcTx, Network, OutPoint, Txid};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// A locking script paid by more than one transaction
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ScriptReuse {
+    /// The reused locking script
+    pub locking_script: Vec<u8>,
+    /// The address of the locking script if it has one
+    pub address: Option<String>,
+    /// The number of outputs paying the script
+    pub outputs: usize,
+    /// The transactions paying the script
+    pub txids: BTreeSet<Txid>,
+}
+
+/// An output paying back to the locking script of an output the transaction
+/// spends, the change address reuse that links the payment to the sender
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ChangeReuse {
+    /// The transaction paying back to its input
+    pub txid: Txid,
+    /// The index of the output paying back
+    pub output_index: usize,
+    /// The output spent by the transaction with the same locking script
+    pub spent: OutPoint,
+}
+
+/// The address reuse found in a set of transactions
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ReuseReport {
+    /// The locking scripts paid by more than one transaction, most reused first
+    pub reused_scripts: Vec<ScriptReuse>,
+    /// The outputs paying back to a spent locking script
+    pub change_reuse: Vec<ChangeReuse>,
+}
+
+impl ReuseReport {
+    /// Whether no address was reused
+    pub fn is_empty(&self) -> bool {
+        self.reused_scripts.is_empty() && self.change_reuse.is_empty()
+    }
+}
+
+/// Find the locking scripts of `txs` paid by more than one transaction and the
+/// transactions sending change back to the script they spend from. Each
+/// transaction is paired with its transaction ID in the byte order explorers
+/// display it in. Inputs are only resolved when the transaction they spend is
+/// in the set
+pub fn detect_reuse(txs: &[(Txid, BtcTx)], network: Network) -> ReuseReport {
+    let mut paid = BTreeMap::<&[u8], (usize, BTreeSet<Txid>)>::new();
+    txs.iter().for_each(|(txid, tx)| {
+        tx.outputs().iter().for_each(|output| {
+            let (outputs, txids) = paid.entry(output.locking_script()).or_default();
+            *outputs += 1;
+            txids.insert(*txid);
+        });
+    });
+
+    let mut reused_scripts = paid
+        .into_iter()
+        .filter(|(_, (_, txids))| txids.len() > 1)
+        .map(|(locking_script, (outputs, txids))| ScriptReuse {
+            locking_script: locking_script.to_vec(),
+            address: Address::from_script(locking_script, network),
+            outputs,
+            txids,
+        })
+        .collect::<Vec<ScriptReuse>>();
+    reused_scripts.sort_by_key(|reuse| std::cmp::Reverse(reuse.outputs));
+
+    let scripts = txs
+        .iter()
+        .flat_map(|(txid, tx)| {
+            tx.outputs().iter().enumerate().map(move |(vout, output)| {
+                (OutPoint::new(*txid, vout as u32), output.locking_script())
+            })
+        })
+        .collect::<HashMap<OutPoint, &[u8]>>();
+
+    let change_reuse = txs
+        .iter()
+        .flat_map(|(txid, tx)| {
+            let spent = tx
+                .inputs()
+                .iter()
+                .filter_map(|input| {
+                    scripts
+                        .get(&input.previous_output())
+                        .map(|script| (input.previous_output(), *script))
+                })
+                .collect::<Vec<(OutPoint, &[u8])>>();
+
+            tx.outputs()
+                .iter()
+                .enumerate()
+                .filter_map(move |(output_index, output)| {
+                    spent
+                        .iter()
+                        .find(|(_, script)| *script == output.locking_script())
+                        .map(|(spent, _)| ChangeReuse {
+                            txid: *txid,
+                            output_index,
+                            spent: *spent,
+                        })
+                })
+        })
+        .collect();
+
+    ReuseReport {
+        reused_scripts,
+        change_reuse,
+    }
+}
+
+#[cfg(test)]
+mod reuse_sanity_checks {
+    use crate::{
+        analysis::{detect_reuse, ChangeReuse},
+        BtcTx, Hash256, Network, OutPoint, TxInput, TxOutput, TxVersion, Txid,
+    };
+
+    #[test]
+    fn reused_scripts() {
+        let p2wpkh = |byte: u8| [&[0x00, 0x14][..], &[byte; 20]].concat();
+        let tx = |previous: Txid, outputs: Vec<TxOutput>| {
+            BtcTx::new(
+                TxVersion::Two,
+                vec![TxInput::new(previous, 0, Vec::new(), 0)],
+                outputs,
+                0,
+            )
+        };
+        let funding = Txid::new(Hash256::new([1u8; 32]));
+        let payment = Txid::new(Hash256::new([2u8; 32]));
+
+        let txs = vec![
+            (
+                funding,
+                tx(
+                    Txid::new(Hash256::new([9u8; 32])),
+                    vec![TxOutput::new(50_000, p2wpkh(0xaa))],
+                ),
+            ),
+            // Pays 0xbb and sends the change back to 0xaa
+            (
+                payment,
+                tx(
+                    funding,
+                    vec![
+                        TxOutput::new(20_000, p2wpkh(0xbb)),
+                        TxOutput::new(29_000, p2wpkh(0xaa)),
+                    ],
+                ),
+            ),
+        ];
+
+        let report = detect_reuse(&txs, Network::Mainnet);
+        assert_eq!(1, report.reused_scripts.len());
+        assert_eq!(p2wpkh(0xaa), report.reused_scripts[0].locking_script);
+        assert_eq!(2, report.reused_scripts[0].outputs);
+        assert!(report.reused_scripts[0].address.is_some());
+        assert_eq!(
+            vec![ChangeReuse {
+                txid: payment,
+                output_index: 1,
+                spent: OutPoint::new(funding, 0),
+            }],
+            report.change_reuse
+        );
+
+        assert!(detect_reuse(&txs[..1], Network::Mainnet).is_empty());
+    }
+}
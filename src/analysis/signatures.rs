@@ -0,0 +1,303 @@
+use crate::{BtcTx, SigHashType, StandardScripts};
+use std::collections::BTreeMap;
+
+/// Half of the order of the secp256k1 curve. Signatures with an `s` value
+/// above this are malleable since `n - s` is also a valid `s` value
+pub const SECP256K1_HALF_ORDER: [u8; 32] = [
+    0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0x5d, 0x57, 0x6e, 0x73, 0x57, 0xa4, 0x50, 0x1d, 0xdf, 0xe9, 0x2f, 0x46, 0x68, 0x1b, 0x20, 0xa0,
+];
+
+/// A signature found while scanning the inputs of a transaction
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub struct SignatureInfo {
+    // The index of the input the signature was pushed in
+    input_index: usize,
+    // The sighash flag which is the last byte of the signature
+    sighash: SigHashType,
+    // Whether the signature is strictly DER encoded
+    strict_der: bool,
+    // Whether the `s` value is at most half the curve order
+    low_s: bool,
+}
+
+impl SignatureInfo {
+    /// The index of the input the signature was pushed in
+    pub fn input_index(&self) -> usize {
+        self.input_index
+    }
+
+    /// The sighash flag of the signature
+    pub fn sighash(&self) -> SigHashType {
+        self.sighash
+    }
+
+    /// Whether the signature is strictly DER encoded as required by BIP66
+    pub fn strict_der(&self) -> bool {
+        self.strict_der
+    }
+
+    /// Whether the `s` value is at most half the curve order as required by
+    /// the standardness rules of Bitcoin Core
+    pub fn low_s(&self) -> bool {
+        self.low_s
+    }
+}
+
+/// Unusual or malleable patterns found in the signatures of a transaction
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum SignatureAnomaly {
+    /// The signature is not strictly DER encoded
+    NonStrictDer { input_index: usize },
+    /// The `s` value is above half the curve order making the signature malleable
+    HighS { input_index: usize },
+    /// The sighash byte is not one of the six standard flags
+    NonStandardSigHash { input_index: usize, byte: u8 },
+    /// `SIGHASH_SINGLE` is used on an input without an output of the same index.
+    /// Consensus signs the value `1` instead of a hash so the signature
+    /// can be reused to spend any output locked to the same key
+    SingleWithoutOutput { input_index: usize },
+    /// The signature only commits to its own input so anyone can add inputs
+    AnyoneCanPay { input_index: usize },
+}
+
+/// The sighash flags used by the signatures of a transaction
+/// together with any anomalies found
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct SignatureReport {
+    signatures: Vec<SignatureInfo>,
+    anomalies: Vec<SignatureAnomaly>,
+}
+
+impl SignatureReport {
+    /// Scan the pushes in the signature script and the witness elements of
+    /// every input of `tx` for signatures. Signature scripts which cannot
+    /// be split into pushes are skipped.
+    pub fn from_tx(tx: &BtcTx) -> Self {
+        let mut report = Self::default();
+
+        tx.inputs()
+            .iter()
+            .enumerate()
+            .for_each(|(input_index, input)| {
+                let mut pushes =
+                    StandardScripts::read_pushes(input.signature_script()).unwrap_or_default();
+                // Taproot signatures are 64 or 65 random looking bytes so witness elements
+                // also need the DER length to match before they are taken as ECDSA signatures
+                pushes.extend(
+                    input
+                        .witness()
+                        .iter()
+                        .filter(|element| {
+                            element.len() > 2 && element[1] as usize + 3 == element.len()
+                        })
+                        .map(|element| element.to_vec()),
+                );
+
+                pushes
+                    .iter()
+                    .filter(|push| Self::looks_like_signature(push))
+                    .for_each(|push| {
+                        let signature = Self::inspect(input_index, push);
+                        report.add_anomalies(&signature, tx.outputs().len());
+                        report.signatures.push(signature);
+                    });
+            });
+
+        report
+    }
+
+    /// Every signature found in the order of the inputs
+    pub fn signatures(&self) -> &[SignatureInfo] {
+        &self.signatures
+    }
+
+    /// Every anomaly found in the order of the inputs
+    pub fn anomalies(&self) -> &[SignatureAnomaly] {
+        &self.anomalies
+    }
+
+    /// The number of signatures using each sighash flag
+    pub fn sighash_counts(&self) -> BTreeMap<SigHashType, usize> {
+        self.signatures
+            .iter()
+            .fold(BTreeMap::new(), |mut counts, signature| {
+                *counts.entry(signature.sighash).or_default() += 1;
+
+                counts
+            })
+    }
+
+    /// Whether the transaction only uses low-S, strictly DER
+    /// encoded `SIGHASH_ALL` signatures
+    pub fn is_unremarkable(&self) -> bool {
+        self.anomalies.is_empty()
+            && self
+                .signatures
+                .iter()
+                .all(|signature| signature.sighash == SigHashType::All)
+    }
+
+    fn add_anomalies(&mut self, signature: &SignatureInfo, outputs_len: usize) {
+        let input_index = signature.input_index;
+
+        if !signature.strict_der {
+            self.anomalies
+                .push(SignatureAnomaly::NonStrictDer { input_index });
+        }
+
+        if !signature.low_s {
+            self.anomalies.push(SignatureAnomaly::HighS { input_index });
+        }
+
+        if let SigHashType::NonStandard(byte) = signature.sighash {
+            self.anomalies
+                .push(SignatureAnomaly::NonStandardSigHash { input_index, byte });
+        }
+
+        if signature.sighash.is_single() && input_index >= outputs_len {
+            self.anomalies
+                .push(SignatureAnomaly::SingleWithoutOutput { input_index });
+        }
+
+        if signature.sighash.is_anyone_can_pay() {
+            self.anomalies
+                .push(SignatureAnomaly::AnyoneCanPay { input_index });
+        }
+    }
+
+    // A DER signature starts with the compound tag `0x30` and together with
+    // the sighash byte is between 9 and 73 bytes
+    fn looks_like_signature(push: &[u8]) -> bool {
+        (9..=73).contains(&push.len()) && push[0] == 0x30
+    }
+
+    fn inspect(input_index: usize, push: &[u8]) -> SignatureInfo {
+        // The last byte is the sighash flag and the rest is the DER encoding
+        let (der, sighash) = push.split_at(push.len() - 1);
+        let sighash = SigHashType::from_byte(sighash[0]);
+
+        let (strict_der, low_s) = match Self::der_s_value(der) {
+            Some(s) => (true, Self::is_low_s(s)),
+            None => (false, true),
+        };
+
+        SignatureInfo {
+            input_index,
+            sighash,
+            strict_der,
+            low_s,
+        }
+    }
+
+    // Follows the BIP66 encoding rules
+    // `0x30 [total-length] 0x02 [R-length] [R] 0x02 [S-length] [S]`
+    // and returns the bytes of `S`
+    fn der_s_value(der: &[u8]) -> Option<&[u8]> {
+        if der.len() < 8 || der[0] != 0x30 || der[1] as usize != der.len() - 2 {
+            return None;
+        }
+
+        let r_len = der[3] as usize;
+        let s_position = 4 + r_len;
+        if der[2] != 0x02 || r_len == 0 || s_position + 2 > der.len() {
+            return None;
+        }
+
+        let s_len = der[s_position + 1] as usize;
+        if der[s_position] != 0x02 || s_len == 0 || s_position + 2 + s_len != der.len() {
+            return None;
+        }
+
+        let r = &der[4..s_position];
+        let s = &der[s_position + 2..];
+
+        (Self::is_minimal_integer(r) && Self::is_minimal_integer(s)).then_some(s)
+    }
+
+    // Integers must be positive and without unnecessary leading zero bytes
+    fn is_minimal_integer(integer: &[u8]) -> bool {
+        integer[0] & 0x80 == 0 && !(integer.len() > 1 && integer[0] == 0 && integer[1] & 0x80 == 0)
+    }
+
+    fn is_low_s(s: &[u8]) -> bool {
+        let first_non_zero = s.iter().position(|byte| *byte != 0).unwrap_or(s.len());
+        let s = &s[first_non_zero..];
+
+        match s.len() {
+            0..=31 => true,
+            32 => s <= SECP256K1_HALF_ORDER.as_slice(),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod signatures_sanity_checks {
+    use super::{SignatureAnomaly, SignatureReport};
+    use crate::{BtcTx, Hash256, SigHashType, TxInput, TxOutput, TxVersion, Txid};
+
+    fn push(bytes: &[u8]) -> Vec<u8> {
+        let mut script = vec![bytes.len() as u8];
+        script.extend_from_slice(bytes);
+
+        script
+    }
+
+    #[test]
+    fn sample_transaction() {
+        let raw_tx = include_str!("../../fixtures/transactions/p2pkh_two_inputs.hex");
+        let tx = BtcTx::from_hex_bytes(hex::decode(raw_tx.trim()).unwrap()).unwrap();
+        let report = SignatureReport::from_tx(&tx);
+
+        assert_eq!(2, report.signatures().len());
+        assert_eq!(Some(&2), report.sighash_counts().get(&SigHashType::All));
+        assert!(report
+            .signatures()
+            .iter()
+            .all(|signature| signature.low_s()));
+        assert!(report.is_unremarkable());
+    }
+
+    #[test]
+    fn anomalies() {
+        // r = 1, s = 1 signed with SIGHASH_SINGLE | SIGHASH_ANYONECANPAY
+        let single = [0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x01, 0x83];
+
+        // s = 0xff..ff which is above half the curve order
+        let mut high_s = vec![0x30, 0x26, 0x02, 0x01, 0x01, 0x02, 0x21, 0x00];
+        high_s.extend_from_slice(&[0xff; 32]);
+        high_s.push(0x01);
+
+        // r has an unnecessary leading zero byte
+        let non_der = [0x30, 0x07, 0x02, 0x02, 0x00, 0x01, 0x02, 0x01, 0x01, 0x01];
+
+        let input = |signature: &[u8]| {
+            TxInput::new(
+                Txid::new(Hash256::new([1u8; 32])),
+                0,
+                push(signature),
+                u32::MAX,
+            )
+        };
+        let tx = BtcTx::new(
+            TxVersion::Two,
+            vec![input(&high_s), input(&single), input(&non_der)],
+            vec![TxOutput::new(1000, vec![0x51])],
+            0,
+        );
+        let report = SignatureReport::from_tx(&tx);
+
+        assert_eq!(3, report.signatures().len());
+        assert_eq!(
+            &[
+                SignatureAnomaly::HighS { input_index: 0 },
+                SignatureAnomaly::SingleWithoutOutput { input_index: 1 },
+                SignatureAnomaly::AnyoneCanPay { input_index: 1 },
+                SignatureAnomaly::NonStrictDer { input_index: 2 },
+            ],
+            report.anomalies()
+        );
+        assert!(!report.is_unremarkable());
+    }
+}
@@ -0,0 +1,295 @@
+use crate::{
+    analysis::{Confidence, ROUND_AMOUNT},
+    BtcTx,
+};
+
+/// The number of satoshis in one bitcoin
+const SATS_PER_BTC: u128 = 100_000_000;
+
+/// The amount of an output and how round it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OutputValue {
+    /// The index of the output
+    pub output_index: usize,
+    /// The amount in satoshis
+    pub amount: u64,
+    /// The number of trailing zero digits of the amount in satoshis,
+    /// 5 for 0.001 BTC
+    pub round_digits: u32,
+    /// The value in cents of the fiat currency if a price was given
+    pub fiat_cents: Option<u64>,
+    /// Whether the fiat value is a whole number of the currency, allowing
+    /// for the rounding of the conversion to satoshis
+    pub round_fiat: bool,
+}
+
+impl OutputValue {
+    /// Whether the amount is a multiple of `ROUND_AMOUNT` satoshis
+    pub fn is_round_btc(&self) -> bool {
+        is_round_amount(self.amount)
+    }
+
+    /// Whether the amount is round in bitcoin or in the fiat currency
+    pub fn is_round(&self) -> bool {
+        self.is_round_btc() || self.round_fiat
+    }
+}
+
+/// The amounts of the outputs of a transaction
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ValueDistribution {
+    /// Every output in order
+    pub outputs: Vec<OutputValue>,
+    /// The sum of the amounts
+    pub total: u64,
+    /// The smallest amount
+    pub smallest: u64,
+    /// The largest amount
+    pub largest: u64,
+    /// The middle amount, the lower one of the two in the middle for an
+    /// even number of outputs
+    pub median: u64,
+}
+
+/// A sign that an output is the change of the transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChangeSignal {
+    /// The only output which is not a round amount of bitcoin
+    OnlyNonRoundBtc,
+    /// The only output which is not a round amount of the fiat currency
+    OnlyNonRoundFiat,
+    /// The only output with the fewest trailing zero digits
+    MostPrecise,
+}
+
+/// The output most likely paying the change back to the sender
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ChangeGuess {
+    /// The index of the output
+    pub output_index: usize,
+    /// The signals pointing at the output
+    pub signals: Vec<ChangeSignal>,
+    /// How likely the output is the change
+    pub confidence: Confidence,
+}
+
+/// The value distribution of the outputs of a transaction and its likely change
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ValueAnalysis {
+    /// The amounts of the outputs
+    pub distribution: ValueDistribution,
+    /// The likely change output, `None` if no output stands out
+    pub change: Option<ChangeGuess>,
+}
+
+/// Whether `amount` satoshis is a multiple of `ROUND_AMOUNT`, which payments
+/// tend to be while change is whatever is left over
+pub fn is_round_amount(amount: u64) -> bool {
+    amount.is_multiple_of(ROUND_AMOUNT)
+}
+
+/// Measure the amounts of the outputs of `tx` and guess the change from how
+/// round they are. The fiat values are computed with `fiat_price`, the price
+/// of one bitcoin in cents, when it is known
+pub fn analyze_values(tx: &BtcTx, fiat_price: Option<u64>) -> ValueAnalysis {
+    let outputs = tx
+        .outputs()
+        .iter()
+        .enumerate()
+        .map(|(output_index, output)| {
+            let amount = output.amount();
+            let fiat_cents = fiat_price.map(|price| fiat_value(amount, price));
+
+            OutputValue {
+                output_index,
+                amount,
+                round_digits: round_digits(amount),
+                fiat_cents,
+                round_fiat: fiat_price.is_some_and(|price| is_round_fiat(amount, price)),
+            }
+        })
+        .collect::<Vec<OutputValue>>();
+
+    let mut amounts = outputs
+        .iter()
+        .map(|output| output.amount)
+        .collect::<Vec<u64>>();
+    amounts.sort_unstable();
+    let distribution = ValueDistribution {
+        total: amounts.iter().sum(),
+        smallest: amounts.first().copied().unwrap_or_default(),
+        largest: amounts.last().copied().unwrap_or_default(),
+        median: amounts
+            .get(amounts.len().saturating_sub(1) / 2)
+            .copied()
+            .unwrap_or_default(),
+        outputs,
+    };
+
+    ValueAnalysis {
+        change: guess_change(&distribution.outputs, fiat_price.is_some()),
+        distribution,
+    }
+}
+
+// Each signal only counts when exactly one output matches it, like the
+// change heuristics of the clustering. Zero value outputs like data carriers
+// pay nobody so they are never the change
+fn guess_change(outputs: &[OutputValue], with_fiat: bool) -> Option<ChangeGuess> {
+    let candidates = outputs
+        .iter()
+        .filter(|output| output.amount > 0)
+        .collect::<Vec<&OutputValue>>();
+    if candidates.len() < 2 {
+        return None;
+    }
+
+    let mut signals = vec![Vec::<ChangeSignal>::new(); outputs.len()];
+    let mut signal = |change_signal: ChangeSignal, matches: &dyn Fn(&OutputValue) -> bool| {
+        let matching = candidates
+            .iter()
+            .copied()
+            .filter(|output| matches(output))
+            .collect::<Vec<&OutputValue>>();
+        if let [output] = matching[..] {
+            signals[output.output_index].push(change_signal);
+        }
+    };
+
+    signal(ChangeSignal::OnlyNonRoundBtc, &|output| {
+        !output.is_round_btc()
+    });
+    if with_fiat {
+        signal(ChangeSignal::OnlyNonRoundFiat, &|output| !output.round_fiat);
+    }
+    let fewest_digits = candidates.iter().map(|output| output.round_digits).min()?;
+    signal(ChangeSignal::MostPrecise, &|output| {
+        output.round_digits == fewest_digits
+    });
+
+    let most_signals = signals.iter().map(|signals| signals.len()).max()?;
+    let mut best = signals
+        .into_iter()
+        .enumerate()
+        .filter(|(_, signals)| signals.len() == most_signals);
+    let (output_index, signals) = best.next()?;
+    if best.next().is_some() {
+        return None;
+    }
+
+    let confidence = match most_signals {
+        0 => return None,
+        1 => Confidence::Low,
+        2 => Confidence::Medium,
+        _ => Confidence::High,
+    };
+
+    Some(ChangeGuess {
+        output_index,
+        signals,
+        confidence,
+    })
+}
+
+fn round_digits(amount: u64) -> u32 {
+    if amount == 0 {
+        return 0;
+    }
+
+    (0..)
+        .take_while(|digits| amount.is_multiple_of(10u64.pow(*digits + 1)))
+        .count() as u32
+}
+
+fn fiat_value(amount: u64, price: u64) -> u64 {
+    ((amount as u128 * price as u128 + SATS_PER_BTC / 2) / SATS_PER_BTC) as u64
+}
+
+// A wallet paying a whole amount of fiat rounds it to the nearest satoshi,
+// so the value is allowed to be off by one cent
+fn is_round_fiat(amount: u64, price: u64) -> bool {
+    let cents = fiat_value(amount, price);
+    let off_by = (cents % 100).min(100 - cents % 100);
+
+    cents >= 100 && off_by <= 1
+}
+
+#[cfg(test)]
+mod values_sanity_checks {
+    use crate::{
+        analysis::{analyze_values, ChangeSignal, Confidence},
+        BtcTx, Hash256, TxInput, TxOutput, TxVersion, Txid,
+    };
+
+    #[test]
+    fn round_amounts() {
+        let tx = BtcTx::new(
+            TxVersion::Two,
+            vec![TxInput::new(
+                Txid::new(Hash256::new([1u8; 32])),
+                0,
+                Vec::new(),
+                0,
+            )],
+            vec![
+                // $50 at $60,000 and 0.01 BTC
+                TxOutput::new(83_333, vec![0x51]),
+                TxOutput::new(1_000_000, vec![0x51]),
+                TxOutput::new(1_234_567, vec![0x51]),
+            ],
+            0,
+        );
+
+        let analysis = analyze_values(&tx, Some(6_000_000));
+        let outputs = &analysis.distribution.outputs;
+        assert_eq!(6, outputs[1].round_digits);
+        assert_eq!(Some(5_000), outputs[0].fiat_cents);
+        assert!(outputs[0].round_fiat && !outputs[0].is_round_btc());
+        assert!(outputs[1].is_round() && !outputs[2].is_round());
+        assert_eq!(83_333, analysis.distribution.smallest);
+        assert_eq!(1_000_000, analysis.distribution.median);
+
+        let change = analysis.change.unwrap();
+        assert_eq!(2, change.output_index);
+        assert_eq!(vec![ChangeSignal::OnlyNonRoundFiat], change.signals);
+        assert_eq!(Confidence::Low, change.confidence);
+
+        // Without a price both outputs which are not round in bitcoin tie
+        assert_eq!(None, analyze_values(&tx, None).change);
+    }
+
+    #[test]
+    fn zero_value_outputs() {
+        let tx = BtcTx::new(
+            TxVersion::Two,
+            vec![TxInput::new(
+                Txid::new(Hash256::new([1u8; 32])),
+                0,
+                Vec::new(),
+                0,
+            )],
+            vec![
+                TxOutput::new(1_000_000, vec![0x51]),
+                TxOutput::new(0, vec![0x6a]),
+                TxOutput::new(1_234_567, vec![0x51]),
+            ],
+            0,
+        );
+
+        let change = analyze_values(&tx, None).change.unwrap();
+        assert_eq!(2, change.output_index);
+        assert_eq!(
+            vec![ChangeSignal::OnlyNonRoundBtc, ChangeSignal::MostPrecise],
+            change.signals
+        );
+
+        // A payment with a data carrier output has no other output to tell it from
+        let tx = BtcTx::new(
+            *tx.version(),
+            tx.inputs().to_vec(),
+            tx.outputs()[..2].to_vec(),
+            0,
+        );
+        assert_eq!(None, analyze_values(&tx, None).change);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::{Address, Descriptor, Network, Script};
+use bitcoin::{
+    bip32::{ChildNumber, Xpub},
+    key::{Secp256k1, TapTweak},
+    ScriptBuf,
+};
+use std::{
+    io::{self, ErrorKind},
+    str::FromStr,
+};
+
+/// An output of a BIP86 single key taproot wallet
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Bip86Output {
+    /// The x-only public key derived from the account key
+    pub internal_key: [u8; 32],
+    /// The internal key tweaked without a script tree
+    pub output_key: [u8; 32],
+    /// The P2TR locking script paying to the output key
+    pub locking_script: Script,
+    /// The address of the locking script
+    pub address: String,
+    /// The `tr()` descriptor of the output with its checksum
+    pub descriptor: String,
+}
+
+/// Derivation of the outputs of BIP86 single key taproot wallets
+#[derive(Debug, Clone, Copy)]
+pub struct Bip86;
+
+impl Bip86 {
+    /// Derive the receive or change output at `index` from the account
+    /// extended public key at `m/86'/coin'/account'`
+    pub fn derive(
+        account_xpub: &str,
+        change: bool,
+        index: u32,
+        network: Network,
+    ) -> io::Result<Bip86Output> {
+        let invalid = |error: String| io::Error::new(ErrorKind::InvalidInput, error);
+
+        let xpub = Xpub::from_str(account_xpub).map_err(|error| invalid(error.to_string()))?;
+        let path = [
+            ChildNumber::from_normal_idx(change as u32)
+                .map_err(|error| invalid(error.to_string()))?,
+            ChildNumber::from_normal_idx(index).map_err(|error| invalid(error.to_string()))?,
+        ];
+
+        let secp = Secp256k1::verification_only();
+        let internal_key = xpub
+            .derive_pub(&secp, &path)
+            .map_err(|error| invalid(error.to_string()))?
+            .to_x_only_pub();
+        let (output_key, _) = internal_key.tap_tweak(&secp, None);
+
+        let locking_script = Script::new(ScriptBuf::new_p2tr_tweaked(output_key).into_bytes());
+        let address = Address::from_script(locking_script.as_bytes(), network)
+            .ok_or_else(|| invalid("The P2TR script has no address".to_string()))?;
+        let descriptor =
+            Descriptor::with_checksum(&format!("tr({}/{}/{})", account_xpub, change as u32, index))
+                .ok_or_else(|| invalid("The descriptor has invalid characters".to_string()))?;
+
+        Ok(Bip86Output {
+            internal_key: internal_key.serialize(),
+            output_key: output_key.to_x_only_public_key().serialize(),
+            locking_script,
+            address,
+            descriptor,
+        })
+    }
+}
+
+#[cfg(test)]
+mod bip86_sanity_checks {
+    use crate::{Bip86, Network};
+    use hex_literal::hex;
+
+    #[test]
+    fn bip86_vectors() {
+        // The first receive address of the test vectors of BIP86
+        let output = Bip86::derive(
+            "xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ",
+            false,
+            0,
+            Network::Mainnet,
+        )
+        .unwrap();
+
+        assert_eq!(
+            hex!("cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115"),
+            output.internal_key
+        );
+        assert_eq!(
+            hex!("a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c"),
+            output.output_key
+        );
+        assert_eq!(
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr",
+            output.address
+        );
+        assert!(output.descriptor.starts_with("tr(xpub6BgBgsesp"));
+        assert!(output.descriptor.contains("/0/0)#"));
+
+        assert!(Bip86::derive("xpub", false, 0, Network::Mainnet).is_err());
+        assert!(Bip86::derive(
+            "xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ",
+            false,
+            1 << 31,
+            Network::Mainnet
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,437 @@
+use crate::{BtcTx, Checksum, Hash256, Txid, VarInt};
+use std::io::{self, Cursor, ErrorKind, Read};
+
+/// The length of a serialized block header
+pub const BLOCK_HEADER_LEN: usize = 80;
+
+/// The 80 byte header of a block which commits to the previous block
+/// and to the transactions of the block through the merkle root
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BlockHeader {
+    // The block version used to signal soft-forks
+    version: i32,
+    // The hash of the previous block in the byte order explorers display it in
+    previous_block_hash: Hash256,
+    // The merkle root of the transaction IDs in the byte order explorers display it in
+    merkle_root: Hash256,
+    // The UNIX timestamp set by the miner
+    time: u32,
+    // The compact encoding of the proof of work target
+    bits: u32,
+    // The nonce miners change to find a valid proof of work
+    nonce: u32,
+}
+
+impl BlockHeader {
+    /// Instantiate a block header from its fields. The hashes are
+    /// in the byte order explorers display them in
+    pub fn new(
+        version: i32,
+        previous_block_hash: Hash256,
+        merkle_root: Hash256,
+        time: u32,
+        bits: u32,
+        nonce: u32,
+    ) -> Self {
+        Self {
+            version,
+            previous_block_hash,
+            merkle_root,
+            time,
+            bits,
+            nonce,
+        }
+    }
+
+    /// Parse a serialized block header of exactly 80 bytes
+    pub fn from_bytes(bytes: impl AsRef<[u8]>) -> io::Result<Self> {
+        let bytes = bytes.as_ref();
+        if bytes.len() != BLOCK_HEADER_LEN {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "A block header must be exactly 80 bytes",
+            ));
+        }
+
+        let mut bytes = Cursor::new(bytes);
+        let mut buffer = [0u8; 4];
+        let mut hash = [0u8; 32];
+
+        bytes.read_exact(&mut buffer)?;
+        let version = i32::from_le_bytes(buffer);
+        // The hashes are in network byte order so they are reversed
+        bytes.read_exact(&mut hash)?;
+        let previous_block_hash = Hash256::new(hash).reversed();
+        bytes.read_exact(&mut hash)?;
+        let merkle_root = Hash256::new(hash).reversed();
+        bytes.read_exact(&mut buffer)?;
+        let time = u32::from_le_bytes(buffer);
+        bytes.read_exact(&mut buffer)?;
+        let bits = u32::from_le_bytes(buffer);
+        bytes.read_exact(&mut buffer)?;
+        let nonce = u32::from_le_bytes(buffer);
+
+        Ok(Self::new(
+            version,
+            previous_block_hash,
+            merkle_root,
+            time,
+            bits,
+            nonce,
+        ))
+    }
+
+    /// Serialize the block header into its 80 bytes
+    pub fn to_bytes(&self) -> [u8; BLOCK_HEADER_LEN] {
+        let mut bytes = [0u8; BLOCK_HEADER_LEN];
+        bytes[..4].copy_from_slice(&self.version.to_le_bytes());
+        bytes[4..36].copy_from_slice(self.previous_block_hash.reversed().as_ref());
+        bytes[36..68].copy_from_slice(self.merkle_root.reversed().as_ref());
+        bytes[68..72].copy_from_slice(&self.time.to_le_bytes());
+        bytes[72..76].copy_from_slice(&self.bits.to_le_bytes());
+        bytes[76..].copy_from_slice(&self.nonce.to_le_bytes());
+
+        bytes
+    }
+
+    /// The block hash, the SHA256d of the header in the byte order explorers display it in
+    pub fn block_hash(&self) -> Hash256 {
+        Hash256::new(Checksum::sha256d(&self.to_bytes())).reversed()
+    }
+
+    /// The block version
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    /// The hash of the previous block in the byte order explorers display it in
+    pub fn previous_block_hash(&self) -> Hash256 {
+        self.previous_block_hash
+    }
+
+    /// The merkle root of the transaction IDs in the byte order explorers display it in
+    pub fn merkle_root(&self) -> Hash256 {
+        self.merkle_root
+    }
+
+    /// The UNIX timestamp of the block
+    pub fn time(&self) -> u32 {
+        self.time
+    }
+
+    /// The compact encoding of the proof of work target
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    /// The nonce of the block
+    pub fn nonce(&self) -> u32 {
+        self.nonce
+    }
+
+    /// Decode the compact `bits` into the 256 bit target as big-endian bytes.
+    /// The upper byte is the length of the target in bytes and the lower three
+    /// bytes are its most significant bytes. Negative and overflowing targets
+    /// are rejected like Bitcoin Core does
+    pub fn target(&self) -> io::Result<[u8; 32]> {
+        let exponent = (self.bits >> 24) as usize;
+        let mantissa = self.bits & 0x007fffff;
+
+        if self.bits & 0x00800000 != 0 && mantissa != 0 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "The proof of work target is negative",
+            ));
+        }
+
+        let mut target = [0u8; 32];
+        for (index, byte) in mantissa.to_be_bytes()[1..].iter().enumerate() {
+            // The byte is `exponent - 1 - index` bytes from the least significant byte
+            match (exponent as isize - 1 - index as isize).try_into() {
+                Ok(position @ 0..=31) => target[31 - position as usize] = *byte,
+                Ok(_) if *byte != 0 => {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        "The proof of work target overflows 256 bits",
+                    ))
+                }
+                _ => (),
+            }
+        }
+
+        Ok(target)
+    }
+
+    /// Whether the block hash is at or below the target encoded in `bits`
+    pub fn has_valid_proof_of_work(&self) -> bool {
+        // Big-endian bytes compare the same way as the numbers they encode
+        self.target()
+            .is_ok_and(|target| self.block_hash().to_byte_array() <= target)
+    }
+}
+
+/// A block, its header followed by its transactions starting with the coinbase
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Block {
+    // The header committing to the transactions
+    header: BlockHeader,
+    // The transactions in the order they are serialized in
+    txs: Vec<BtcTx>,
+}
+
+impl Block {
+    /// Instantiate a block from its header and transactions
+    pub fn new(header: BlockHeader, txs: Vec<BtcTx>) -> Self {
+        Self { header, txs }
+    }
+
+    /// Parse a serialized block like the ones returned by `getblock <hash> 0`
+    /// or published on the `rawblock` ZMQ topic. Trailing bytes are rejected
+    pub fn from_bytes(bytes: impl AsRef<[u8]>) -> io::Result<Self> {
+        let bytes = bytes.as_ref();
+        if bytes.len() < BLOCK_HEADER_LEN {
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "A block must start with an 80 byte header",
+            ));
+        }
+        let header = BlockHeader::from_bytes(&bytes[..BLOCK_HEADER_LEN])?;
+
+        let mut bytes = Cursor::new(&bytes[BLOCK_HEADER_LEN..]);
+        let mut varint_len = [0u8];
+        bytes.read_exact(&mut varint_len)?;
+        let tx_count = VarInt::integer(VarInt::parse(varint_len[0]), &mut bytes)?;
+
+        // The count is not trusted to preallocate since every transaction is parsed anyway
+        let mut txs = Vec::new();
+        for _ in 0..tx_count {
+            txs.push(BtcTx::read_from(&mut bytes)?);
+        }
+
+        if bytes.position() != bytes.get_ref().len() as u64 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "Found bytes after the last transaction of the block",
+            ));
+        }
+
+        Ok(Self { header, txs })
+    }
+
+    /// Serialize the block with the witnesses of its transactions
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header.to_bytes().to_vec();
+        bytes.extend_from_slice(&VarInt::encode(self.txs.len() as u64));
+        self.txs
+            .iter()
+            .for_each(|tx| bytes.extend_from_slice(&tx.to_bytes()));
+
+        bytes
+    }
+
+    /// The header of the block
+    pub fn header(&self) -> &BlockHeader {
+        &self.header
+    }
+
+    /// The transactions of the block
+    pub fn txs(&self) -> &[BtcTx] {
+        &self.txs
+    }
+
+    /// The block hash, see `BlockHeader::block_hash()`
+    pub fn block_hash(&self) -> Hash256 {
+        self.header.block_hash()
+    }
+
+    /// The weight of the block in weight units, four units per byte of
+    /// the header and the transaction count plus the weight of every transaction
+    pub fn weight(&self) -> usize {
+        (BLOCK_HEADER_LEN + VarInt::encode(self.txs.len() as u64).len()) * 4
+            + self.txs.iter().map(BtcTx::weight).sum::<usize>()
+    }
+
+    /// Compute the merkle root of the transaction IDs in the byte order explorers display it in
+    pub fn compute_merkle_root(&self) -> Hash256 {
+        merkle_root(self.txs.iter().map(|tx| tx.txid().to_hash()).collect())
+    }
+
+    /// Whether the header commits to the transactions of the block
+    pub fn has_valid_merkle_root(&self) -> bool {
+        self.compute_merkle_root() == self.header.merkle_root
+    }
+}
+
+// The merkle root of hashes in the byte order explorers display them in.
+// Levels with an odd number of nodes hash the last node with itself
+// and a tree without leaves has a root of zeros
+pub(crate) fn merkle_root(leaves: Vec<Hash256>) -> Hash256 {
+    let mut level = leaves
+        .iter()
+        .map(|leaf| leaf.reversed())
+        .collect::<Vec<Hash256>>();
+    if level.is_empty() {
+        return Hash256::all_zeros();
+    }
+
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| {
+                let right = pair.get(1).unwrap_or(&pair[0]);
+                Hash256::new(Checksum::sha256d(
+                    &[pair[0].as_ref(), right.as_ref()].concat(),
+                ))
+            })
+            .collect();
+    }
+
+    level[0].reversed()
+}
+
+/// The sibling hashes needed to recompute the merkle root of a block
+/// from one transaction ID, in the format returned by Esplora
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MerkleProof {
+    // The position of the transaction in the block
+    position: u32,
+    // The sibling hashes from the leaves up in the byte order explorers display them in
+    siblings: Vec<Hash256>,
+}
+
+impl MerkleProof {
+    /// Instantiate a proof for the transaction at `position` in the block
+    pub fn new(position: u32, siblings: Vec<Hash256>) -> Self {
+        Self { position, siblings }
+    }
+
+    /// The position of the transaction in the block
+    pub fn position(&self) -> u32 {
+        self.position
+    }
+
+    /// The sibling hashes from the leaves up
+    pub fn siblings(&self) -> &[Hash256] {
+        &self.siblings
+    }
+
+    /// Recompute the merkle root from the transaction ID of the proven transaction
+    pub fn compute_root(&self, txid: Txid) -> Hash256 {
+        let mut position = self.position;
+
+        let root = self
+            .siblings
+            .iter()
+            .fold(txid.to_hash().reversed(), |node, sibling| {
+                // The node is on the left when its position is even
+                let (left, right) = if position.is_multiple_of(2) {
+                    (node, sibling.reversed())
+                } else {
+                    (sibling.reversed(), node)
+                };
+                position /= 2;
+
+                Hash256::new(Checksum::sha256d(&[left.as_ref(), right.as_ref()].concat()))
+            });
+
+        root.reversed()
+    }
+}
+
+#[cfg(test)]
+mod block_sanity_checks {
+    use crate::{Block, BlockHeader, Hash256, MerkleProof, Txid};
+    use hex_literal::hex;
+
+    const GENESIS_HEADER: [u8; 80] = hex!("0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c");
+    const GENESIS_COINBASE: [u8; 204] = hex!("01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000");
+
+    #[test]
+    fn genesis_header() {
+        let header = BlockHeader::from_bytes(GENESIS_HEADER).unwrap();
+        assert_eq!(
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+            header.block_hash().to_string()
+        );
+        assert_eq!(
+            "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b",
+            header.merkle_root().to_string()
+        );
+        assert_eq!(GENESIS_HEADER, header.to_bytes());
+        assert!(header.has_valid_proof_of_work());
+        assert_eq!(
+            hex!("00000000ffff0000000000000000000000000000000000000000000000000000"),
+            header.target().unwrap()
+        );
+        assert!(BlockHeader::from_bytes(&GENESIS_HEADER[..79]).is_err());
+    }
+
+    #[test]
+    fn merkle_proof() {
+        // A single transaction is its own merkle root
+        let txid = Txid::new(Hash256::new([1u8; 32]));
+        assert_eq!(
+            txid.to_hash(),
+            MerkleProof::new(0, Vec::new()).compute_root(txid)
+        );
+
+        // Swapping the position swaps the order the pair is hashed in
+        let sibling = Hash256::new([2u8; 32]);
+        let left = MerkleProof::new(0, vec![sibling]).compute_root(txid);
+        let right = MerkleProof::new(1, vec![sibling]).compute_root(txid);
+        assert_ne!(left, right);
+        assert_eq!(
+            left,
+            MerkleProof::new(1, vec![txid.to_hash()]).compute_root(Txid::new(sibling))
+        );
+    }
+
+    #[test]
+    fn genesis_block() {
+        let raw = [&GENESIS_HEADER[..], &[1], &GENESIS_COINBASE].concat();
+        let block = Block::from_bytes(&raw).unwrap();
+        assert_eq!(1, block.txs().len());
+        assert_eq!(
+            "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b",
+            block.txs()[0].txid().to_string()
+        );
+        assert!(block.has_valid_merkle_root());
+        assert_eq!(raw, block.to_bytes());
+        assert_eq!(raw.len() * 4, block.weight());
+
+        // Truncated and trailing bytes
+        assert!(Block::from_bytes(&raw[..raw.len() - 1]).is_err());
+        assert!(Block::from_bytes([&raw[..], &[0]].concat()).is_err());
+        assert!(Block::from_bytes(&raw[..79]).is_err());
+
+        // A transaction more breaks the commitment
+        let mut txs = block.txs().to_vec();
+        txs.push(txs[0].clone());
+        assert!(!Block::new(*block.header(), txs).has_valid_merkle_root());
+    }
+
+    #[test]
+    fn merkle_root() {
+        let leaves = (1..=3u8)
+            .map(|byte| Hash256::new([byte; 32]))
+            .collect::<Vec<Hash256>>();
+        assert_eq!(Hash256::all_zeros(), super::merkle_root(Vec::new()));
+        assert_eq!(leaves[0], super::merkle_root(leaves[..1].to_vec()));
+
+        // Agrees with the proofs of every leaf, the odd leaf is paired with itself
+        let root = super::merkle_root(leaves.clone());
+        let pair = super::merkle_root(leaves[..2].to_vec());
+        let last = super::merkle_root(vec![leaves[2], leaves[2]]);
+        for (position, siblings) in [
+            (0, vec![leaves[1], last]),
+            (1, vec![leaves[0], last]),
+            (2, vec![leaves[2], pair]),
+        ] {
+            assert_eq!(
+                root,
+                MerkleProof::new(position, siblings)
+                    .compute_root(Txid::new(leaves[position as usize]))
+            );
+        }
+    }
+}
@@ -0,0 +1,564 @@
+use crate::{
+    block::merkle_root, Block, BlockHeader, BtcTx, Checksum, Hash256, TxInput, TxOutput, TxVersion,
+    Txid, VarInt, Witness, BLOCK_HEADER_LEN, SEQUENCE_FINAL,
+};
+use std::{
+    cmp::Ordering,
+    collections::BTreeSet,
+    io::{self, ErrorKind},
+};
+
+/// The most weight of a block
+pub const MAX_BLOCK_WEIGHT: u64 = 4_000_000;
+
+/// The most signature operation cost of a block
+pub const MAX_BLOCK_SIGOPS_COST: u64 = 80_000;
+
+/// The block version signalling no soft-fork with BIP9 version bits
+pub const VERSIONBITS_TOP_BITS: i32 = 0x20000000;
+
+// The compact target of regtest which almost every hash meets
+const REGTEST_BITS: u32 = 0x207fffff;
+
+// The subsidy of the first blocks, 50 BTC
+const INITIAL_SUBSIDY: u64 = 50 * 100_000_000;
+
+// The blocks between halvings of the subsidy
+const HALVING_INTERVAL: u32 = 210_000;
+
+// The most bytes pushed by `OP_PUSHBYTES_75`, the last direct push opcode
+const MAX_COINBASE_DATA_LEN: usize = 75;
+
+// A transaction offered to the template
+#[derive(Debug, Clone)]
+struct Candidate {
+    tx: BtcTx,
+    txid: Txid,
+    fee: u64,
+    weight: u64,
+    sigops_cost: u64,
+}
+
+/// Assemble a block from a set of unconfirmed transactions like the
+/// `getblocktemplate` of Bitcoin Core, for regtest mining tools and research.
+///
+/// Packages of a transaction and its ancestors in the set are selected by
+/// fee rate until the weight or signature operation cost of the block runs out.
+/// Parents come before their children and the coinbase pays the subsidy and the
+/// fees to the payout script with the BIP141 witness commitment. The header has no
+/// valid proof of work, the nonce is left for the miner to grind
+#[derive(Debug, Clone)]
+pub struct BlockTemplateBuilder {
+    previous_block_hash: Hash256,
+    height: u32,
+    payout_script: Vec<u8>,
+    coinbase_data: Vec<u8>,
+    version: i32,
+    time: u32,
+    bits: u32,
+    max_weight: u64,
+    max_sigops_cost: u64,
+    candidates: Vec<Candidate>,
+}
+
+impl BlockTemplateBuilder {
+    /// A template for the block at `height` on top of `previous_block_hash`, in the byte
+    /// order explorers display it in, paying the coinbase to `payout_script`. The
+    /// header uses `VERSIONBITS_TOP_BITS`, the regtest target and a time of zero
+    pub fn new(previous_block_hash: Hash256, height: u32, payout_script: Vec<u8>) -> Self {
+        Self {
+            previous_block_hash,
+            height,
+            payout_script,
+            coinbase_data: Vec::new(),
+            version: VERSIONBITS_TOP_BITS,
+            time: 0,
+            bits: REGTEST_BITS,
+            max_weight: MAX_BLOCK_WEIGHT,
+            max_sigops_cost: MAX_BLOCK_SIGOPS_COST,
+            candidates: Vec::new(),
+        }
+    }
+
+    /// Set the block version
+    pub fn with_version(mut self, version: i32) -> Self {
+        self.version = version;
+
+        self
+    }
+
+    /// Set the UNIX timestamp of the header
+    pub fn with_time(mut self, time: u32) -> Self {
+        self.time = time;
+
+        self
+    }
+
+    /// Set the compact encoding of the proof of work target
+    pub fn with_bits(mut self, bits: u32) -> Self {
+        self.bits = bits;
+
+        self
+    }
+
+    /// Set the largest weight of the block, lower than `MAX_BLOCK_WEIGHT` to leave room
+    pub fn with_max_weight(mut self, max_weight: u64) -> Self {
+        self.max_weight = max_weight;
+
+        self
+    }
+
+    /// Set the largest signature operation cost of the block
+    pub fn with_max_sigops_cost(mut self, max_sigops_cost: u64) -> Self {
+        self.max_sigops_cost = max_sigops_cost;
+
+        self
+    }
+
+    /// Push `data` like a pool tag or an extra nonce after the height in the coinbase
+    /// scriptSig. It is pushed by a single opcode, so it is at most 75 bytes which
+    /// also keeps the scriptSig under the 100 bytes consensus allows
+    pub fn with_coinbase_data(mut self, data: &[u8]) -> io::Result<Self> {
+        if data.len() > MAX_COINBASE_DATA_LEN {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "The coinbase data is at most 75 bytes",
+            ));
+        }
+        self.coinbase_data = data.to_vec();
+
+        Ok(self)
+    }
+
+    /// Offer a transaction to the template with `prevouts`, the outputs spent by
+    /// its inputs in order, which give its fee and signature operation cost.
+    /// Outputs of the other transactions offered give the same outputs back.
+    /// Coinbases, transactions offered twice and transactions spending an output
+    /// another transaction spends are rejected
+    pub fn with_transaction(mut self, tx: BtcTx, prevouts: &[TxOutput]) -> io::Result<Self> {
+        if tx.coinbase_info().is_some() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "A coinbase cannot be added to a block template",
+            ));
+        }
+
+        let txid = tx.txid();
+        if self
+            .candidates
+            .iter()
+            .any(|candidate| candidate.txid == txid)
+        {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "The transaction is already in the block template",
+            ));
+        }
+        if self.candidates.iter().any(|candidate| {
+            candidate.tx.inputs().iter().any(|spent| {
+                tx.inputs()
+                    .iter()
+                    .any(|input| input.previous_output() == spent.previous_output())
+            })
+        }) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "The transaction spends an output another transaction of the template spends",
+            ));
+        }
+
+        let sigops_cost = tx.sigop_cost(prevouts)? as u64;
+        let spent = prevouts.iter().map(TxOutput::amount).sum::<u64>();
+        let paid = tx.outputs().iter().map(TxOutput::amount).sum::<u64>();
+        let fee = spent.checked_sub(paid).ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                "The outputs pay more than the spent outputs",
+            )
+        })?;
+
+        self.candidates.push(Candidate {
+            weight: tx.weight() as u64,
+            txid,
+            fee,
+            sigops_cost,
+            tx,
+        });
+
+        Ok(self)
+    }
+
+    /// The subsidy of the block, 50 BTC halved every 210,000 blocks
+    pub fn subsidy(&self) -> u64 {
+        INITIAL_SUBSIDY
+            .checked_shr(self.height / HALVING_INTERVAL)
+            .unwrap_or_default()
+    }
+
+    /// Select the transactions and assemble the block
+    pub fn build(&self) -> Block {
+        // The parents in the set of every candidate
+        let parents = self
+            .candidates
+            .iter()
+            .map(|candidate| {
+                candidate
+                    .tx
+                    .inputs()
+                    .iter()
+                    .filter_map(|input| {
+                        self.candidates
+                            .iter()
+                            .position(|parent| parent.txid == input.previous_tx_id())
+                    })
+                    .collect::<BTreeSet<usize>>()
+            })
+            .collect::<Vec<BTreeSet<usize>>>();
+        let ancestors = (0..self.candidates.len())
+            .map(|index| {
+                let mut found = BTreeSet::<usize>::new();
+                let mut next = parents[index].iter().copied().collect::<Vec<usize>>();
+                while let Some(parent) = next.pop() {
+                    if found.insert(parent) {
+                        next.extend(parents[parent].iter().copied());
+                    }
+                }
+
+                found
+            })
+            .collect::<Vec<BTreeSet<usize>>>();
+
+        // The coinbase weighs the same whatever amount it pays
+        let coinbase = self.coinbase(0, Hash256::all_zeros());
+        let header_weight =
+            (BLOCK_HEADER_LEN + VarInt::encode(self.candidates.len() as u64 + 1).len()) as u64 * 4;
+        let mut weight_left = self
+            .max_weight
+            .saturating_sub(header_weight + coinbase.weight() as u64);
+        let mut sigops_left = self.max_sigops_cost.saturating_sub(
+            coinbase
+                .sigop_cost(&[TxOutput::new(0, Vec::new())])
+                .unwrap_or_default() as u64,
+        );
+
+        let mut selected = Vec::<usize>::new();
+        // Packages which did not fit, their descendants do not fit either
+        let mut skipped = BTreeSet::<usize>::new();
+        loop {
+            let packages = (0..self.candidates.len())
+                .filter(|index| !selected.contains(index) && !skipped.contains(index))
+                .map(|index| {
+                    let mut package = ancestors[index]
+                        .iter()
+                        .copied()
+                        .filter(|ancestor| !selected.contains(ancestor))
+                        .collect::<Vec<usize>>();
+                    package.push(index);
+
+                    let (fee, weight, sigops_cost) =
+                        package.iter().fold((0u64, 0u64, 0u64), |totals, member| {
+                            let candidate = &self.candidates[*member];
+                            (
+                                totals.0 + candidate.fee,
+                                totals.1 + candidate.weight,
+                                totals.2 + candidate.sigops_cost,
+                            )
+                        });
+
+                    (package, fee, weight, sigops_cost)
+                })
+                .collect::<Vec<_>>();
+
+            // The highest fee rate, the first offered on ties
+            let Some((mut package, _, weight, sigops_cost)) =
+                packages.into_iter().reduce(|best, package| {
+                    match (package.1 as u128 * best.2 as u128)
+                        .cmp(&(best.1 as u128 * package.2 as u128))
+                    {
+                        Ordering::Greater => package,
+                        _ => best,
+                    }
+                })
+            else {
+                break;
+            };
+
+            // The transaction the package was built for is last
+            let index = package[package.len() - 1];
+            if weight > weight_left || sigops_cost > sigops_left {
+                skipped.insert(index);
+                (0..self.candidates.len())
+                    .filter(|descendant| ancestors[*descendant].contains(&index))
+                    .for_each(|descendant| {
+                        skipped.insert(descendant);
+                    });
+                continue;
+            }
+            weight_left -= weight;
+            sigops_left -= sigops_cost;
+
+            // An ancestor always has fewer ancestors than its descendants
+            package.sort_by_key(|member| ancestors[*member].len());
+            selected.extend(package);
+        }
+
+        let fees = selected
+            .iter()
+            .map(|index| self.candidates[*index].fee)
+            .sum::<u64>();
+        // The wtxid of the coinbase is replaced by zeros in the witness merkle tree
+        let witness_root = merkle_root(
+            std::iter::once(Hash256::all_zeros())
+                .chain(
+                    selected
+                        .iter()
+                        .map(|index| self.candidates[*index].tx.wtxid().to_hash()),
+                )
+                .collect(),
+        );
+
+        let mut txs = vec![self.coinbase(self.subsidy() + fees, witness_root)];
+        txs.extend(
+            selected
+                .iter()
+                .map(|index| self.candidates[*index].tx.clone()),
+        );
+        let merkle_root = merkle_root(txs.iter().map(|tx| tx.txid().to_hash()).collect());
+        let header = BlockHeader::new(
+            self.version,
+            self.previous_block_hash,
+            merkle_root,
+            self.time,
+            self.bits,
+            0,
+        );
+
+        Block::new(header, txs)
+    }
+
+    // The coinbase paying `amount` with the commitment to `witness_root`, in the
+    // byte order explorers display it in, and an all zero witness reserved value
+    fn coinbase(&self, amount: u64, witness_root: Hash256) -> BtcTx {
+        let mut signature_script = height_push(self.height);
+        if !self.coinbase_data.is_empty() {
+            signature_script.push(self.coinbase_data.len() as u8);
+            signature_script.extend_from_slice(&self.coinbase_data);
+        }
+        // A scriptSig of less than two bytes is invalid
+        if signature_script.len() < 2 {
+            signature_script.push(0x00);
+        }
+
+        let witness_reserved_value = [0u8; 32];
+        let commitment = Checksum::sha256d(
+            &[
+                witness_root.reversed().as_ref(),
+                witness_reserved_value.as_slice(),
+            ]
+            .concat(),
+        );
+        // OP_RETURN OP_PUSHBYTES_36 aa21a9ed <commitment>
+        let commitment_script =
+            [&[0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed], commitment.as_slice()].concat();
+
+        let input = TxInput::new(Txid::default(), u32::MAX, signature_script, SEQUENCE_FINAL)
+            .with_witness(Witness::from_slice(&[witness_reserved_value.as_slice()]));
+
+        BtcTx::new(
+            TxVersion::Two,
+            vec![input],
+            vec![
+                TxOutput::new(amount, self.payout_script.clone()),
+                TxOutput::new(0, commitment_script),
+            ],
+            0,
+        )
+    }
+}
+
+// The BIP34 push of the height starting the coinbase scriptSig, the minimal
+// script number encoding with `OP_0` and `OP_1..16` for the smallest heights
+fn height_push(height: u32) -> Vec<u8> {
+    match height {
+        0 => vec![0x00],
+        1..=16 => vec![0x50 + height as u8],
+        _ => {
+            let mut number = height.to_le_bytes().to_vec();
+            while number.last() == Some(&0) {
+                number.pop();
+            }
+            // A set sign bit would make the number negative
+            if number.last().is_some_and(|byte| byte & 0x80 != 0) {
+                number.push(0);
+            }
+
+            [&[number.len() as u8], number.as_slice()].concat()
+        }
+    }
+}
+
+#[cfg(test)]
+mod block_template_sanity_checks {
+    use super::height_push;
+    use crate::{
+        block::merkle_root,
+        fixtures::{tx, tx_to, txid},
+        BlockTemplateBuilder, BtcTx, Checksum, CoinbaseInfo, Hash256, TxOutput,
+    };
+    use hex_literal::hex;
+
+    const P2WPKH: [u8; 22] = hex!("0014751e76e8199196d454941c45d1b3a323f1433bd6");
+
+    fn builder() -> BlockTemplateBuilder {
+        BlockTemplateBuilder::new(Hash256::new([7u8; 32]), 840_000, P2WPKH.to_vec())
+    }
+
+    // The prevouts of `tx` paying `amount` each to `OP_1`
+    fn prevouts(tx: &BtcTx, amount: u64) -> Vec<TxOutput> {
+        vec![TxOutput::new(amount, vec![0x51]); tx.inputs().len()]
+    }
+
+    #[test]
+    fn coinbase() {
+        let block = builder()
+            .with_time(1_713_571_767)
+            .with_coinbase_data(b"/template/")
+            .unwrap()
+            .build();
+        assert_eq!(1, block.txs().len());
+        assert!(block.has_valid_merkle_root());
+        assert_eq!(
+            Hash256::new([7u8; 32]),
+            block.header().previous_block_hash()
+        );
+        assert_eq!(1_713_571_767, block.header().time());
+
+        let coinbase = &block.txs()[0];
+        let info = coinbase.coinbase_info().unwrap();
+        assert_eq!(Some(840_000), info.height);
+        assert_eq!(vec!["/template/".to_string()], info.tags);
+        assert_eq!(312_500_000, coinbase.outputs()[0].amount());
+        assert_eq!(P2WPKH, coinbase.outputs()[0].locking_script());
+
+        // The commitment to a tree of the zero coinbase wtxid alone
+        let commitment = Checksum::sha256d(&[0u8; 64]);
+        assert_eq!(
+            [&hex!("6a24aa21a9ed")[..], &commitment].concat(),
+            coinbase.outputs()[1].locking_script()
+        );
+        assert_eq!(
+            Some([0u8; 32].as_slice()),
+            coinbase.inputs()[0].witness().last()
+        );
+
+        assert!(builder().with_coinbase_data(&[0u8; 76]).is_err());
+        assert_eq!(
+            0,
+            BlockTemplateBuilder::new(Hash256::all_zeros(), 13_440_000, Vec::new()).subsidy()
+        );
+    }
+
+    #[test]
+    fn height_pushes() {
+        for height in [
+            1, 16, 17, 127, 128, 255, 256, 32_767, 32_768, 840_000, 8_388_608,
+        ] {
+            let signature_script = [height_push(height), b"tag!".to_vec()].concat();
+            assert_eq!(
+                Some(height),
+                CoinbaseInfo::from_signature_script(&signature_script).height
+            );
+        }
+        assert_eq!(vec![0x00], height_push(0));
+        assert_eq!(hex!("0340d10c").to_vec(), height_push(840_000));
+        assert_eq!(hex!("028000").to_vec(), height_push(128));
+    }
+
+    #[test]
+    fn package_selection() {
+        let parent = tx(&[(txid(9), 0)], &[9_000]);
+        let child = tx(&[(parent.txid(), 0)], &[3_000]);
+        let other = tx(&[(txid(9), 1)], &[7_000]);
+        // A bare multisig output costs 80 sigops
+        let multisig = tx_to(
+            &[(txid(9), 2)],
+            vec![(
+                9_000,
+                [&[0x51, 0x21][..], &[0x02; 33], &[0x51, 0xae]].concat(),
+            )],
+        );
+
+        let with_txs = |builder: BlockTemplateBuilder| {
+            builder
+                .with_transaction(parent.clone(), &prevouts(&parent, 10_000))
+                .unwrap()
+                .with_transaction(child.clone(), &parent.outputs()[..1])
+                .unwrap()
+                .with_transaction(other.clone(), &prevouts(&other, 10_000))
+                .unwrap()
+                .with_transaction(multisig.clone(), &prevouts(&multisig, 10_000))
+                .unwrap()
+        };
+
+        // The child pays for its parent, 7000 sats for both is more than 3000
+        // for the other transaction and 1000 for the multisig one
+        let block = with_txs(builder()).build();
+        assert_eq!(
+            vec![parent.txid(), child.txid(), other.txid(), multisig.txid()],
+            block.txs()[1..].iter().map(BtcTx::txid).collect::<Vec<_>>()
+        );
+        assert_eq!(312_500_000 + 11_000, block.txs()[0].outputs()[0].amount());
+        assert!(block.has_valid_merkle_root());
+
+        // Room for the package but not for the other transactions
+        let empty = builder().build().weight() as u64;
+        let block = with_txs(builder().with_max_weight(empty + 488 + 243)).build();
+        assert_eq!(3, block.txs().len());
+        assert!(block.weight() as u64 <= empty + 488 + 243);
+
+        // No room for the multisig output
+        let block = with_txs(builder().with_max_sigops_cost(79)).build();
+        assert_eq!(4, block.txs().len());
+        assert!(!block.txs().contains(&multisig));
+
+        // The commitment covers the wtxids of the transactions
+        let witness_root = merkle_root(
+            std::iter::once(Hash256::all_zeros())
+                .chain(block.txs()[1..].iter().map(|tx| tx.wtxid().to_hash()))
+                .collect(),
+        );
+        let commitment =
+            Checksum::sha256d(&[witness_root.reversed().as_ref(), &[0u8; 32]].concat());
+        assert_eq!(
+            &commitment,
+            &block.txs()[0].outputs()[1].locking_script()[6..]
+        );
+    }
+
+    #[test]
+    fn rejected_transactions() {
+        let first = tx(&[(txid(9), 0)], &[9_000]);
+        let builder = builder()
+            .with_transaction(first.clone(), &prevouts(&first, 10_000))
+            .unwrap();
+
+        assert!(builder
+            .clone()
+            .with_transaction(first.clone(), &prevouts(&first, 10_000))
+            .is_err());
+        let double_spend = tx(&[(txid(9), 0)], &[8_000]);
+        assert!(builder
+            .clone()
+            .with_transaction(double_spend.clone(), &prevouts(&double_spend, 10_000))
+            .is_err());
+        let overpaying = tx(&[(txid(9), 1)], &[11_000]);
+        assert!(builder
+            .clone()
+            .with_transaction(overpaying.clone(), &prevouts(&overpaying, 10_000))
+            .is_err());
+        let coinbase = builder.build().txs()[0].clone();
+        assert!(builder
+            .with_transaction(coinbase, &[TxOutput::new(0, Vec::new())])
+            .is_err());
+    }
+}
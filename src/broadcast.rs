@@ -0,0 +1,444 @@
+use crate::{instrument::traced_request, BtcTx, Checksum, EsploraClient, Network, VarInt};
+use std::{
+    future::Future,
+    io::{self, ErrorKind},
+    net::SocketAddr,
+    pin::Pin,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+/// The P2P protocol version announced to peers
+pub const PROTOCOL_VERSION: i32 = 70016;
+
+/// The largest P2P message accepted from a peer, the limit of Bitcoin Core
+pub const MAX_PROTOCOL_MESSAGE_LEN: usize = 4_000_000;
+
+/// The future returned by a `Broadcaster`, resolving to the transaction ID
+pub type BroadcastFuture<'a> = Pin<Box<dyn Future<Output = io::Result<String>> + Send + 'a>>;
+
+/// A backend which submits raw transactions to the Bitcoin network
+pub trait Broadcaster: Send + Sync {
+    /// The name of the backend in a `BroadcastReport`
+    fn name(&self) -> String;
+
+    /// Submit the raw transaction and return its transaction ID
+    fn broadcast<'a>(&'a self, raw_tx: &'a [u8]) -> BroadcastFuture<'a>;
+}
+
+impl Broadcaster for EsploraClient {
+    fn name(&self) -> String {
+        "esplora".into()
+    }
+
+    fn broadcast<'a>(&'a self, raw_tx: &'a [u8]) -> BroadcastFuture<'a> {
+        Box::pin(EsploraClient::broadcast(self, raw_tx))
+    }
+}
+
+/// Submits transactions with the `sendrawtransaction` call of the JSON-RPC
+/// interface of Bitcoin Core
+#[derive(Debug, Clone)]
+pub struct CoreRpcBroadcaster {
+    // The URL of the RPC server like `http://127.0.0.1:8332`
+    url: String,
+    // The user and password of `-rpcuser` and `-rpcpassword` or the cookie file
+    auth: Option<(String, String)>,
+    client: reqwest::Client,
+}
+
+impl CoreRpcBroadcaster {
+    /// Instantiate a new broadcaster for the RPC server at `url`
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            auth: None,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Authenticate with `user` and `password`
+    pub fn with_auth(mut self, user: impl Into<String>, password: impl Into<String>) -> Self {
+        self.auth = Some((user.into(), password.into()));
+
+        self
+    }
+
+    async fn send_raw_transaction(&self, raw_tx: &[u8]) -> io::Result<String> {
+        traced_request("core_rpc_broadcast", raw_tx.len(), async {
+            let call = serde_json::json!({
+                "jsonrpc": "1.0",
+                "id": "btc-tx-hex",
+                "method": "sendrawtransaction",
+                "params": [hex::encode(raw_tx)],
+            });
+            let mut request = self
+                .client
+                .post(&self.url)
+                .header("content-type", "application/json")
+                .body(call.to_string());
+            if let Some((user, password)) = &self.auth {
+                request = request.basic_auth(user, Some(password));
+            }
+
+            // Bitcoin Core answers rejected transactions with an error status
+            // and the reason in the body so the body is always decoded
+            let response = request.send().await.map_err(io::Error::other)?;
+            let status = response.status();
+            let body = response.text().await.map_err(io::Error::other)?;
+
+            Self::decode_response(&body).map_err(|error| {
+                io::Error::new(
+                    error.kind(),
+                    format!("The RPC request failed with {}: {}", status, error),
+                )
+            })
+        })
+        .await
+    }
+
+    // The transaction ID in the `result` of the response or the `error`
+    fn decode_response(body: &str) -> io::Result<String> {
+        let response = serde_json::from_str::<serde_json::Value>(body)?;
+
+        if let Some(error) = response.get("error").filter(|error| !error.is_null()) {
+            return Err(io::Error::other(format!(
+                "error {}: {}",
+                error["code"], error["message"]
+            )));
+        }
+
+        response["result"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "The result is not a txid"))
+    }
+}
+
+impl Broadcaster for CoreRpcBroadcaster {
+    fn name(&self) -> String {
+        format!("core-rpc {}", self.url)
+    }
+
+    fn broadcast<'a>(&'a self, raw_tx: &'a [u8]) -> BroadcastFuture<'a> {
+        Box::pin(self.send_raw_transaction(raw_tx))
+    }
+}
+
+/// Submits transactions to a peer in a `tx` message after the version
+/// handshake. Peers do not acknowledge transactions so a success only means
+/// the message was sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct P2pBroadcaster {
+    address: SocketAddr,
+    network: Network,
+}
+
+impl P2pBroadcaster {
+    /// Instantiate a new broadcaster for the peer at `address` on `network`
+    pub fn new(address: SocketAddr, network: Network) -> Self {
+        Self { address, network }
+    }
+
+    /// The start bytes of every message on the network
+    pub const fn magic(network: Network) -> [u8; 4] {
+        match network {
+            Network::Mainnet => [0xf9, 0xbe, 0xb4, 0xd9],
+            Network::Testnet => [0x0b, 0x11, 0x09, 0x07],
+            Network::Signet => [0x0a, 0x03, 0xcf, 0x40],
+            Network::Regtest => [0xfa, 0xbf, 0xb5, 0xda],
+        }
+    }
+
+    /// Frame `payload` as a P2P message with the network magic, the `command`
+    /// padded to 12 bytes, the payload length and its checksum
+    pub fn message(&self, command: &str, payload: &[u8]) -> Vec<u8> {
+        let mut command_bytes = [0u8; 12];
+        command_bytes[..command.len()].copy_from_slice(command.as_bytes());
+
+        let mut message = Self::magic(self.network).to_vec();
+        message.extend_from_slice(&command_bytes);
+        message.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        message.extend_from_slice(&Checksum::compute(payload));
+        message.extend_from_slice(payload);
+
+        message
+    }
+
+    async fn send(&self, raw_tx: &[u8]) -> io::Result<String> {
+        traced_request("p2p_broadcast", raw_tx.len(), async {
+            let txid = BtcTx::from_hex_bytes(raw_tx)?.txid();
+            let mut stream = TcpStream::connect(self.address).await?;
+            stream
+                .write_all(&self.message("version", &self.version_payload()))
+                .await?;
+
+            // The peer sends its own version and acknowledges ours, in any order
+            let (mut version, mut verack) = (false, false);
+            while !(version && verack) {
+                match self.read_message(&mut stream).await?.as_str() {
+                    "version" => {
+                        version = true;
+                        stream.write_all(&self.message("verack", &[])).await?;
+                    }
+                    "verack" => verack = true,
+                    _ => (),
+                }
+            }
+
+            stream.write_all(&self.message("tx", raw_tx)).await?;
+            stream.flush().await?;
+
+            Ok(txid.to_string())
+        })
+        .await
+    }
+
+    // Read the next message from the peer and return its command
+    async fn read_message(&self, stream: &mut TcpStream) -> io::Result<String> {
+        let mut header = [0u8; 24];
+        stream.read_exact(&mut header).await?;
+        if header[..4] != Self::magic(self.network) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "The peer is on another network",
+            ));
+        }
+
+        let len = u32::from_le_bytes([header[16], header[17], header[18], header[19]]) as usize;
+        if len > MAX_PROTOCOL_MESSAGE_LEN {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("A message of {} bytes is too large", len),
+            ));
+        }
+        let mut payload = vec![0u8; len];
+        stream.read_exact(&mut payload).await?;
+        if !Checksum::verify(&payload, &header[20..24]) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "The checksum of the message is invalid",
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&header[4..16])
+            .trim_end_matches('\0')
+            .into())
+    }
+
+    // A version message without services which does not ask for relayed
+    // transactions since it only sends one
+    fn version_payload(&self) -> Vec<u8> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let user_agent = format!("/btc-tx-hex:{}/", env!("CARGO_PKG_VERSION"));
+
+        let mut payload = PROTOCOL_VERSION.to_le_bytes().to_vec();
+        // The services
+        payload.extend_from_slice(&0u64.to_le_bytes());
+        payload.extend_from_slice(&(now.as_secs() as i64).to_le_bytes());
+        // The address of the peer, its services, IPv6 or IPv4 mapped address and port
+        payload.extend_from_slice(&0u64.to_le_bytes());
+        payload.extend_from_slice(&match self.address {
+            SocketAddr::V4(address) => address.ip().to_ipv6_mapped().octets(),
+            SocketAddr::V6(address) => address.ip().octets(),
+        });
+        payload.extend_from_slice(&self.address.port().to_be_bytes());
+        // Our address is left empty
+        payload.extend_from_slice(&[0u8; 26]);
+        // The nonce detecting connections to ourselves
+        payload.extend_from_slice(&(now.subsec_nanos() as u64).to_le_bytes());
+        payload.extend_from_slice(&VarInt::encode(user_agent.len() as u64));
+        payload.extend_from_slice(user_agent.as_bytes());
+        // The start height and the relay flag
+        payload.extend_from_slice(&0i32.to_le_bytes());
+        payload.push(0);
+
+        payload
+    }
+}
+
+impl Broadcaster for P2pBroadcaster {
+    fn name(&self) -> String {
+        format!("p2p {}", self.address)
+    }
+
+    fn broadcast<'a>(&'a self, raw_tx: &'a [u8]) -> BroadcastFuture<'a> {
+        Box::pin(self.send(raw_tx))
+    }
+}
+
+/// The outcome of submitting a transaction to one backend
+#[derive(Debug)]
+pub struct BroadcastAttempt {
+    /// The name of the backend
+    pub backend: String,
+    /// The transaction ID or why the backend failed
+    pub result: io::Result<String>,
+}
+
+/// The backends a `FallbackBroadcaster` tried in order, ending with the first
+/// which accepted the transaction if any did
+#[derive(Debug, Default)]
+pub struct BroadcastReport {
+    /// The attempts in the order the backends were tried
+    pub attempts: Vec<BroadcastAttempt>,
+}
+
+impl BroadcastReport {
+    /// The transaction ID returned by the backend which accepted the transaction
+    pub fn txid(&self) -> Option<&str> {
+        self.attempts
+            .iter()
+            .find_map(|attempt| attempt.result.as_deref().ok())
+    }
+}
+
+/// Tries its backends in order until one accepts the transaction
+#[derive(Default)]
+pub struct FallbackBroadcaster {
+    backends: Vec<Box<dyn Broadcaster>>,
+}
+
+impl FallbackBroadcaster {
+    /// A broadcaster without backends
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a backend tried after the backends added before it
+    pub fn with_backend(mut self, backend: impl Broadcaster + 'static) -> Self {
+        self.backends.push(Box::new(backend));
+
+        self
+    }
+
+    /// Submit the raw transaction to the backends until one accepts it and
+    /// report the result of every backend tried
+    pub async fn broadcast_with_report(&self, raw_tx: &[u8]) -> BroadcastReport {
+        let mut report = BroadcastReport::default();
+
+        for backend in &self.backends {
+            let result = backend.broadcast(raw_tx).await;
+            let accepted = result.is_ok();
+            report.attempts.push(BroadcastAttempt {
+                backend: backend.name(),
+                result,
+            });
+
+            if accepted {
+                break;
+            }
+        }
+
+        report
+    }
+}
+
+impl Broadcaster for FallbackBroadcaster {
+    fn name(&self) -> String {
+        self.backends
+            .iter()
+            .map(|backend| backend.name())
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    fn broadcast<'a>(&'a self, raw_tx: &'a [u8]) -> BroadcastFuture<'a> {
+        Box::pin(async move {
+            let report = self.broadcast_with_report(raw_tx).await;
+            if let Some(txid) = report.txid() {
+                return Ok(txid.to_string());
+            }
+
+            Err(io::Error::other(format!(
+                "Every backend failed: {}",
+                report
+                    .attempts
+                    .iter()
+                    .map(|attempt| match &attempt.result {
+                        Ok(_) => attempt.backend.clone(),
+                        Err(error) => format!("{} ({})", attempt.backend, error),
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )))
+        })
+    }
+}
+
+#[cfg(test)]
+mod broadcast_sanity_checks {
+    use super::{BroadcastFuture, Broadcaster, CoreRpcBroadcaster, FallbackBroadcaster};
+    use crate::{Checksum, Network, P2pBroadcaster};
+    use std::{
+        future::Future,
+        io,
+        task::{Context, Poll, Waker},
+    };
+
+    struct Mock(Option<&'static str>);
+
+    impl Broadcaster for Mock {
+        fn name(&self) -> String {
+            format!("mock {:?}", self.0)
+        }
+
+        fn broadcast<'a>(&'a self, _raw_tx: &'a [u8]) -> BroadcastFuture<'a> {
+            let result = self
+                .0
+                .map(String::from)
+                .ok_or_else(|| io::Error::other("rejected"));
+
+            Box::pin(async move { result })
+        }
+    }
+
+    fn ready<T>(future: impl Future<Output = T>) -> T {
+        match std::pin::pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("The mock backends are always ready"),
+        }
+    }
+
+    #[test]
+    fn fallback_backends() {
+        let broadcaster = FallbackBroadcaster::new()
+            .with_backend(Mock(None))
+            .with_backend(Mock(Some("txid")))
+            .with_backend(Mock(Some("never tried")));
+
+        let report = ready(broadcaster.broadcast_with_report(&[0u8; 10]));
+        assert_eq!(2, report.attempts.len());
+        assert!(report.attempts[0].result.is_err());
+        assert_eq!(Some("txid"), report.txid());
+        assert_eq!("txid", ready(broadcaster.broadcast(&[0u8; 10])).unwrap());
+
+        let failing = FallbackBroadcaster::new().with_backend(Mock(None));
+        assert!(ready(failing.broadcast(&[0u8; 10])).is_err());
+
+        assert_eq!(
+            "ab".repeat(32),
+            CoreRpcBroadcaster::decode_response(&format!(
+                r#"{{"result":"{}","error":null,"id":"btc-tx-hex"}}"#,
+                "ab".repeat(32)
+            ))
+            .unwrap()
+        );
+        assert!(CoreRpcBroadcaster::decode_response(
+            r#"{"result":null,"error":{"code":-26,"message":"min relay fee not met"}}"#
+        )
+        .is_err());
+
+        let peer = P2pBroadcaster::new("127.0.0.1:18444".parse().unwrap(), Network::Regtest);
+        let message = peer.message("verack", &[]);
+        assert_eq!(24, message.len());
+        assert_eq!([0xfa, 0xbf, 0xb5, 0xda], message[..4]);
+        assert_eq!(b"verack\0\0\0\0\0\0", &message[4..16]);
+        assert_eq!(Checksum::compute(&[]), message[20..24]);
+    }
+}
@@ -0,0 +1,79 @@
+use sha2::{Digest, Sha256};
+
+/// The length of the checksum used by Base58Check, the P2P message
+/// header and extended key serialization
+pub const CHECKSUM_LEN: usize = 4;
+
+/// Handles the checksum which is the first four bytes of the
+/// double SHA256 (SHA256d) of some bytes
+#[derive(Debug, Clone, Copy)]
+pub struct Checksum;
+
+impl Checksum {
+    /// Hash the bytes twice using SHA256
+    pub fn sha256d(bytes: &[u8]) -> [u8; 32] {
+        Sha256::digest(Sha256::digest(bytes)).into()
+    }
+
+    /// The first four bytes of the SHA256d of the bytes
+    pub fn compute(bytes: &[u8]) -> [u8; CHECKSUM_LEN] {
+        let mut checksum = [0u8; CHECKSUM_LEN];
+        checksum.copy_from_slice(&Self::sha256d(bytes)[..CHECKSUM_LEN]);
+
+        checksum
+    }
+
+    /// Append the checksum of the bytes to the end of the bytes
+    pub fn append(bytes: &mut Vec<u8>) {
+        let checksum = Self::compute(bytes);
+        bytes.extend_from_slice(&checksum);
+    }
+
+    /// Check that `checksum` is the checksum of `bytes`
+    pub fn verify(bytes: &[u8], checksum: &[u8]) -> bool {
+        Self::constant_time_eq(&Self::compute(bytes), checksum)
+    }
+
+    /// Split bytes ending with a checksum into the payload and verify the
+    /// checksum. Returns `None` if the bytes are too short or the checksum is wrong.
+    pub fn strip(bytes: &[u8]) -> Option<&[u8]> {
+        let payload_len = bytes.len().checked_sub(CHECKSUM_LEN)?;
+        let (payload, checksum) = bytes.split_at(payload_len);
+
+        Self::verify(payload, checksum).then_some(payload)
+    }
+
+    /// Compare two byte slices in time that only depends on their length
+    /// so that the position of the first mismatched byte is not leaked
+    pub fn constant_time_eq(first: &[u8], second: &[u8]) -> bool {
+        if first.len() != second.len() {
+            return false;
+        }
+
+        first
+            .iter()
+            .zip(second.iter())
+            .fold(0u8, |difference, (a, b)| difference | (a ^ b))
+            == 0
+    }
+}
+
+#[cfg(test)]
+mod checksum_sanity_checks {
+    use crate::Checksum;
+
+    #[test]
+    fn checksum() {
+        // The checksum of an empty payload used in P2P messages like `verack`
+        assert_eq!([0x5d, 0xf6, 0xe0, 0xe2], Checksum::compute(&[]));
+
+        let mut bytes = b"hello".to_vec();
+        Checksum::append(&mut bytes);
+        assert_eq!(9, bytes.len());
+        assert_eq!(Some(b"hello".as_slice()), Checksum::strip(&bytes));
+
+        bytes[0] ^= 1;
+        assert_eq!(None, Checksum::strip(&bytes));
+        assert_eq!(None, Checksum::strip(&[0u8; 3]));
+    }
+}
@@ -0,0 +1,156 @@
+use crate::{BtcTx, SpendType};
+use std::ops::Range;
+
+/// Tags found in coinbase scriptSigs and the mining pools that use them.
+/// A tag matches if it is part of an ASCII run of the scriptSig
+pub const KNOWN_POOL_TAGS: &[(&str, &str)] = &[
+    ("Foundry USA", "Foundry USA"),
+    ("AntPool", "AntPool"),
+    ("ViaBTC", "ViaBTC"),
+    ("F2Pool", "F2Pool"),
+    ("/slush/", "Braiins Pool"),
+    ("MARA Pool", "MARA Pool"),
+    ("/Binance/", "Binance Pool"),
+    ("SpiderPool", "SpiderPool"),
+    ("/Luxor/", "Luxor"),
+    ("poolin.com", "Poolin"),
+    ("/BTC.COM/", "BTC.com"),
+    ("OCEAN.XYZ", "OCEAN"),
+    ("SecPool", "SECPOOL"),
+    ("/EMCD/", "EMCD"),
+];
+
+// The shortest run of printable ASCII treated as a tag
+const MIN_TAG_LEN: usize = 4;
+
+/// What a mining pool put into the scriptSig of a coinbase input
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CoinbaseInfo {
+    /// The block height of BIP34 which starts the scriptSig since block
+    /// 227,931. Earlier coinbases may start with other data decoded as a height
+    pub height: Option<u32>,
+    /// The byte ranges after the height which are not tags, where the extra
+    /// nonce rolled by the miner is found
+    pub extra_nonce: Vec<Range<usize>>,
+    /// The runs of at least four printable ASCII characters after the height
+    pub tags: Vec<String>,
+    /// The pool of the first tag in `KNOWN_POOL_TAGS` found in the tags
+    pub pool: Option<&'static str>,
+}
+
+impl CoinbaseInfo {
+    /// Extract the height, extra nonce and tags of a coinbase scriptSig
+    pub fn from_signature_script(signature_script: &[u8]) -> Self {
+        let (height, height_len) = Self::bip34_height(signature_script);
+
+        let mut info = CoinbaseInfo {
+            height,
+            ..Default::default()
+        };
+
+        // Split the rest of the scriptSig into ASCII runs and the bytes between them
+        let mut position = height_len;
+        while position < signature_script.len() {
+            let start = position;
+            let is_ascii = Self::is_printable(signature_script[start]);
+            while position < signature_script.len()
+                && Self::is_printable(signature_script[position]) == is_ascii
+            {
+                position += 1;
+            }
+
+            if is_ascii && position - start >= MIN_TAG_LEN {
+                info.tags
+                    .push(String::from_utf8_lossy(&signature_script[start..position]).into_owned());
+            } else {
+                // Short ASCII runs are usually bytes of the nonce which happen
+                // to be printable, so they are merged with their neighbours
+                match info.extra_nonce.last_mut() {
+                    Some(previous) if previous.end == start => previous.end = position,
+                    _ => info.extra_nonce.push(start..position),
+                }
+            }
+        }
+
+        info.pool = KNOWN_POOL_TAGS
+            .iter()
+            .find(|(tag, _)| info.tags.iter().any(|found| found.contains(tag)))
+            .map(|(_, pool)| *pool);
+
+        info
+    }
+
+    // The height pushed as a script number by the first opcode and the number
+    // of bytes it uses
+    fn bip34_height(signature_script: &[u8]) -> (Option<u32>, usize) {
+        match signature_script.first() {
+            // OP_1 to OP_16
+            Some(opcode @ 0x51..=0x60) => (Some((opcode - 0x50) as u32), 1),
+            Some(len @ 0x01..=0x04) => {
+                let len = *len as usize;
+                match signature_script.get(1..1 + len) {
+                    // The sign bit is set so it is not a height
+                    Some(number) if number[len - 1] & 0x80 != 0 => (None, 0),
+                    Some(number) => (
+                        Some(
+                            number
+                                .iter()
+                                .rev()
+                                .fold(0u32, |height, byte| (height << 8) | *byte as u32),
+                        ),
+                        1 + len,
+                    ),
+                    None => (None, 0),
+                }
+            }
+            _ => (None, 0),
+        }
+    }
+
+    fn is_printable(byte: u8) -> bool {
+        (0x20..=0x7e).contains(&byte)
+    }
+}
+
+impl BtcTx {
+    /// The height, extra nonce and tags of the scriptSig if this is a coinbase
+    /// transaction
+    pub fn coinbase_info(&self) -> Option<CoinbaseInfo> {
+        match self.inputs() {
+            [input] if input.inferred_spend_type() == SpendType::Coinbase => Some(
+                CoinbaseInfo::from_signature_script(input.signature_script()),
+            ),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod coinbase_sanity_checks {
+    use crate::CoinbaseInfo;
+    use hex_literal::hex;
+
+    #[test]
+    fn coinbase_tags() {
+        // Height 840,000, eight bytes of extra nonce and the pool tag
+        let signature_script = [
+            &hex!("0340d10c")[..],
+            &hex!("e6d5a2c0ffee0102"),
+            b"/Foundry USA Pool #dropgold/",
+            &hex!("00010203"),
+        ]
+        .concat();
+
+        let info = CoinbaseInfo::from_signature_script(&signature_script);
+        assert_eq!(Some(840_000), info.height);
+        assert_eq!(vec!["/Foundry USA Pool #dropgold/".to_string()], info.tags);
+        assert_eq!(vec![4..12, 40..44], info.extra_nonce);
+        assert_eq!(Some("Foundry USA"), info.pool);
+
+        // Heights up to 16 are pushed with OP_1 to OP_16
+        let early = CoinbaseInfo::from_signature_script(&hex!("5a0101"));
+        assert_eq!(Some(10), early.height);
+        assert!(early.tags.is_empty());
+        assert_eq!(None, early.pool);
+    }
+}
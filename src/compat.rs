@@ -0,0 +1,120 @@
+use crate::{BtcTx, Hash256, TxInput, TxOutput, TxVersion};
+use bitcoin::{
+    absolute::LockTime, hashes::Hash, transaction::Version, Amount, OutPoint, ScriptBuf, Sequence,
+    Transaction, TxIn, TxOut, Txid, Witness,
+};
+use std::io;
+
+impl From<&TxVersion> for Version {
+    fn from(version: &TxVersion) -> Self {
+        // The version is signed in the `bitcoin` crate but
+        // both are the same four bytes little-endian
+        Version(u32::from_le_bytes(version.to_bytes()) as i32)
+    }
+}
+
+impl From<Version> for TxVersion {
+    fn from(version: Version) -> Self {
+        TxVersion::from_bytes(version.0.to_le_bytes())
+    }
+}
+
+impl From<&TxInput> for TxIn {
+    fn from(input: &TxInput) -> Self {
+        // Our previous transaction ID is reversed into the order explorers
+        // display it in while `Txid` stores the bytes in network byte order
+        let previous_tx_id = input.previous_tx_id().to_hash().reversed();
+
+        TxIn {
+            previous_output: OutPoint {
+                txid: Txid::from_byte_array(previous_tx_id.to_byte_array()),
+                vout: input.previous_output_index(),
+            },
+            script_sig: ScriptBuf::from_bytes(input.signature_script().to_vec()),
+            sequence: Sequence(input.sequence_number()),
+            witness: Witness::from_slice(&input.witness().iter().collect::<Vec<&[u8]>>()),
+        }
+    }
+}
+
+impl TryFrom<&TxIn> for TxInput {
+    type Error = io::Error;
+
+    fn try_from(input: &TxIn) -> Result<Self, Self::Error> {
+        let previous_tx_id = Hash256::new(input.previous_output.txid.to_byte_array()).reversed();
+
+        Ok(TxInput::new(
+            crate::Txid::new(previous_tx_id),
+            input.previous_output.vout,
+            input.script_sig.to_bytes(),
+            input.sequence.0,
+        )
+        .with_witness(crate::Witness::from_slice(
+            &input.witness.iter().collect::<Vec<&[u8]>>(),
+        )))
+    }
+}
+
+impl From<&TxOutput> for TxOut {
+    fn from(output: &TxOutput) -> Self {
+        TxOut {
+            value: Amount::from_sat(output.amount()),
+            script_pubkey: ScriptBuf::from_bytes(output.locking_script().to_vec()),
+        }
+    }
+}
+
+impl From<&TxOut> for TxOutput {
+    fn from(output: &TxOut) -> Self {
+        TxOutput::new(output.value.to_sat(), output.script_pubkey.to_bytes())
+    }
+}
+
+impl From<&BtcTx> for Transaction {
+    fn from(tx: &BtcTx) -> Self {
+        Transaction {
+            version: tx.version().into(),
+            lock_time: LockTime::from_consensus(tx.locktime()),
+            input: tx.inputs().iter().map(TxIn::from).collect(),
+            output: tx.outputs().iter().map(TxOut::from).collect(),
+        }
+    }
+}
+
+impl TryFrom<&Transaction> for BtcTx {
+    type Error = io::Error;
+
+    fn try_from(tx: &Transaction) -> Result<Self, Self::Error> {
+        let inputs = tx
+            .input
+            .iter()
+            .map(TxInput::try_from)
+            .collect::<io::Result<Vec<TxInput>>>()?;
+
+        Ok(BtcTx::new(
+            tx.version.into(),
+            inputs,
+            tx.output.iter().map(TxOutput::from).collect(),
+            tx.lock_time.to_consensus_u32(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod compat_sanity_checks {
+    use crate::{fixtures::Fixture, BtcTx};
+    use bitcoin::{consensus::deserialize, Transaction};
+
+    #[test]
+    fn round_trip_through_bitcoin_transaction() {
+        let raw_tx = Fixture::load("transactions", "p2pkh_two_inputs")
+            .unwrap()
+            .bytes;
+
+        let btc_tx = BtcTx::from_hex_bytes(&raw_tx).unwrap();
+        let transaction: Transaction = deserialize(&raw_tx).unwrap();
+
+        assert_eq!(transaction, Transaction::from(&btc_tx));
+        assert_eq!(btc_tx, BtcTx::try_from(&transaction).unwrap());
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{BtcTx, Hash256};
+
+/// A parsed transaction together with the block it was confirmed in
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ConfirmedTx {
+    // The parsed transaction
+    tx: BtcTx,
+    // The hash of the block in the byte order explorers display it in
+    block_hash: Hash256,
+    // The height of the block
+    height: u32,
+    // The timestamp in the block header
+    block_time: u32,
+    // The position of the transaction in the block, the coinbase is at index 0
+    index_in_block: usize,
+}
+
+impl ConfirmedTx {
+    /// Instantiate a new confirmed transaction
+    pub fn new(
+        tx: BtcTx,
+        block_hash: Hash256,
+        height: u32,
+        block_time: u32,
+        index_in_block: usize,
+    ) -> Self {
+        Self {
+            tx,
+            block_hash,
+            height,
+            block_time,
+            index_in_block,
+        }
+    }
+
+    /// The parsed transaction
+    pub fn tx(&self) -> &BtcTx {
+        &self.tx
+    }
+
+    /// Take the parsed transaction discarding the block context
+    pub fn into_tx(self) -> BtcTx {
+        self.tx
+    }
+
+    /// The hash of the block in the byte order explorers display it in
+    pub fn block_hash(&self) -> Hash256 {
+        self.block_hash
+    }
+
+    /// The height of the block
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The timestamp in the block header
+    pub fn block_time(&self) -> u32 {
+        self.block_time
+    }
+
+    /// The position of the transaction in the block
+    pub fn index_in_block(&self) -> usize {
+        self.index_in_block
+    }
+
+    /// Whether this is the coinbase transaction of the block
+    pub fn is_coinbase(&self) -> bool {
+        self.index_in_block == 0
+    }
+
+    /// The number of confirmations given the height of the current chain tip.
+    /// Returns zero if the tip is below the block, for example after a reorg
+    pub fn confirmations(&self, tip_height: u32) -> u32 {
+        if tip_height < self.height {
+            return 0;
+        }
+
+        tip_height - self.height + 1
+    }
+}
+
+#[cfg(test)]
+mod confirmed_sanity_checks {
+    use crate::{BtcTx, ConfirmedTx, Hash256};
+
+    #[test]
+    fn confirmations() {
+        let confirmed = ConfirmedTx::new(
+            BtcTx::default(),
+            Hash256::all_zeros(),
+            800_000,
+            1690168629,
+            3,
+        );
+
+        assert_eq!(1, confirmed.confirmations(800_000));
+        assert_eq!(6, confirmed.confirmations(800_005));
+        assert_eq!(0, confirmed.confirmations(799_999));
+        assert!(!confirmed.is_coinbase());
+    }
+}
@@ -0,0 +1,315 @@
+use crate::{
+    BtcTx, OutPoint, ScriptType, TxInput, TxOutput, TxVersion, VarInt, WeightedUtxo,
+    EMPTY_INPUT_WEIGHT,
+};
+
+/// The sequence number of consolidation inputs which signals BIP125
+/// replaceability so the fee can be bumped if the fees rise again
+pub const CONSOLIDATION_SEQUENCE: u32 = 0xfffffffd;
+
+/// The weight of an input spending an output of `script_type` including its
+/// witness, assuming compressed public keys and 72 byte ECDSA signatures.
+/// Returns `None` if the weight depends on a script that is not known
+pub const fn input_weight(script_type: ScriptType) -> Option<u64> {
+    // The outpoint, the VarInt length of the scriptSig and the sequence number
+    const BASE: u64 = 36 + 1 + 4;
+
+    match script_type {
+        // A signature push in the scriptSig
+        ScriptType::P2PK => Some((BASE + 73) * 4),
+        // A signature push and a public key push in the scriptSig
+        ScriptType::P2PKH => Some((BASE + 73 + 34) * 4),
+        // The number of witness elements, a signature and a public key
+        ScriptType::P2WPKH => Some(BASE * 4 + 1 + 73 + 34),
+        // The number of witness elements and a Schnorr signature
+        ScriptType::P2TR => Some(BASE * 4 + 1 + 65),
+        // An empty witness
+        ScriptType::P2A => Some(BASE * 4 + 1),
+        _ => None,
+    }
+}
+
+/// The weight of an input like `input_weight()` when its ECDSA signature is
+/// at most 71 bytes because the signer grinds for a low R like Bitcoin Core
+pub const fn low_r_input_weight(script_type: ScriptType) -> Option<u64> {
+    match (script_type, input_weight(script_type)) {
+        // One byte less in the scriptSig
+        (ScriptType::P2PK | ScriptType::P2PKH, Some(weight)) => Some(weight - 4),
+        // One byte less in the witness
+        (ScriptType::P2WPKH, Some(weight)) => Some(weight - 1),
+        (_, weight) => weight,
+    }
+}
+
+/// An unspent output of the wallet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct WalletUtxo {
+    /// The output
+    pub outpoint: OutPoint,
+    /// The amount in satoshis
+    pub amount: u64,
+    /// The type of the locking script of the output
+    pub script_type: ScriptType,
+}
+
+impl WalletUtxo {
+    /// The fee in satoshis of spending the output at `fee_rate` sat/vB.
+    /// Returns `None` if the weight of the input is not known or the fee
+    /// does not fit in 64 bits
+    pub fn spending_fee(&self, fee_rate: u64) -> Option<u64> {
+        self.to_weighted_utxo()?.spending_fee(fee_rate)
+    }
+
+    /// Whether spending the output at `fee_rate` sat/vB costs at least
+    /// as much as the output is worth
+    pub fn is_uneconomical(&self, fee_rate: u64) -> bool {
+        // A fee that does not fit in 64 bits is more than any amount
+        self.to_weighted_utxo().is_some_and(|utxo| {
+            utxo.spending_fee(fee_rate)
+                .is_none_or(|fee| fee >= self.amount)
+        })
+    }
+
+    // The fee only depends on the weight of the input so the locking
+    // script is left empty
+    fn to_weighted_utxo(self) -> Option<WeightedUtxo> {
+        let satisfaction_weight = input_weight(self.script_type)? - EMPTY_INPUT_WEIGHT;
+
+        Some(WeightedUtxo::new(
+            self.outpoint,
+            TxOutput::new(self.amount, Vec::new()),
+            satisfaction_weight,
+        ))
+    }
+}
+
+/// The fee rate expected from a point in time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FeeForecast {
+    /// The UNIX timestamp the forecast starts at
+    pub time: u32,
+    /// The fee rate in sat/vB
+    pub fee_rate: u64,
+}
+
+/// An unsigned transaction merging outputs into a single output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsolidationPlan {
+    /// The UNIX timestamp of the low fee window to broadcast in
+    pub time: u32,
+    /// The fee rate in sat/vB the transaction pays
+    pub fee_rate: u64,
+    /// The unsigned transaction with empty scriptSigs and witnesses
+    pub tx: BtcTx,
+    /// The outputs spent by the transaction in the order of its inputs
+    pub spent: Vec<WalletUtxo>,
+    /// The virtual size of the transaction once it is signed
+    pub vsize: u64,
+    /// The fee in satoshis
+    pub fee: u64,
+}
+
+/// Finds the outputs that are not worth spending at the fee rate the wallet
+/// usually pays and plans their consolidation when fees are low
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DustPlanner {
+    /// The fee rate in sat/vB the wallet usually pays
+    pub target_fee_rate: u64,
+    /// The most inputs of one consolidation transaction
+    pub max_inputs: usize,
+}
+
+impl DustPlanner {
+    /// A planner for a wallet usually paying `target_fee_rate` sat/vB
+    /// which consolidates at most 100 outputs per transaction
+    pub fn new(target_fee_rate: u64) -> Self {
+        Self {
+            target_fee_rate,
+            max_inputs: 100,
+        }
+    }
+
+    /// The outputs costing at least as much to spend at the target fee rate as
+    /// they are worth. Outputs of unknown script types are never included
+    pub fn uneconomical<'a>(&self, utxos: &'a [WalletUtxo]) -> Vec<&'a WalletUtxo> {
+        utxos
+            .iter()
+            .filter(|utxo| utxo.is_uneconomical(self.target_fee_rate))
+            .collect()
+    }
+
+    /// Plan the consolidation of the uneconomical outputs into `locking_script`
+    /// during the forecast with the lowest fee rate below the target fee rate.
+    /// Outputs which are not worth spending even then are left out, and so is
+    /// a transaction whose output would itself be uneconomical at the target
+    /// fee rate. Nothing is planned if fees are never below the target
+    pub fn plan(
+        &self,
+        utxos: &[WalletUtxo],
+        forecasts: &[FeeForecast],
+        locking_script: Vec<u8>,
+    ) -> Vec<ConsolidationPlan> {
+        let Some(window) = forecasts
+            .iter()
+            .filter(|forecast| forecast.fee_rate < self.target_fee_rate)
+            .min_by_key(|forecast| (forecast.fee_rate, forecast.time))
+        else {
+            return Vec::new();
+        };
+
+        let spendable = self
+            .uneconomical(utxos)
+            .into_iter()
+            .filter(|utxo| !utxo.is_uneconomical(window.fee_rate))
+            .copied()
+            .collect::<Vec<WalletUtxo>>();
+
+        spendable
+            .chunks(self.max_inputs.max(1))
+            .filter_map(|chunk| self.consolidate(chunk, window, &locking_script))
+            .collect()
+    }
+
+    fn consolidate(
+        &self,
+        utxos: &[WalletUtxo],
+        window: &FeeForecast,
+        locking_script: &[u8],
+    ) -> Option<ConsolidationPlan> {
+        let output_weight =
+            (8 + VarInt::encoded_len(locking_script.len() as u64) + locking_script.len()) as u64
+                * 4;
+        // The version, the locktime and the VarInt counts of inputs and outputs
+        let overhead_weight =
+            (4 + 4 + VarInt::encoded_len(utxos.len() as u64) + VarInt::encoded_len(1)) as u64 * 4;
+        // The segwit marker and flag
+        let segwit_weight = if utxos.iter().any(|utxo| {
+            matches!(
+                utxo.script_type,
+                ScriptType::P2WPKH | ScriptType::P2TR | ScriptType::P2A
+            )
+        }) {
+            2
+        } else {
+            0
+        };
+        let input_weight = utxos
+            .iter()
+            .map(|utxo| input_weight(utxo.script_type))
+            .sum::<Option<u64>>()?;
+
+        let vsize = (overhead_weight + segwit_weight + input_weight + output_weight).div_ceil(4);
+        let fee = vsize.checked_mul(window.fee_rate)?;
+        let amount = utxos
+            .iter()
+            .try_fold(0u64, |amount, utxo| amount.checked_add(utxo.amount))?
+            .checked_sub(fee)?;
+
+        let output = TxOutput::new(amount, locking_script.to_vec());
+        let consolidated = WalletUtxo {
+            outpoint: utxos[0].outpoint,
+            amount,
+            script_type: output.script_type(),
+        };
+        if consolidated.is_uneconomical(self.target_fee_rate) {
+            return None;
+        }
+
+        let tx = BtcTx::new(
+            TxVersion::Two,
+            utxos
+                .iter()
+                .map(|utxo| {
+                    TxInput::new(
+                        utxo.outpoint.txid(),
+                        utxo.outpoint.vout(),
+                        Vec::new(),
+                        CONSOLIDATION_SEQUENCE,
+                    )
+                })
+                .collect(),
+            vec![output],
+            0,
+        );
+
+        Some(ConsolidationPlan {
+            time: window.time,
+            fee_rate: window.fee_rate,
+            tx,
+            spent: utxos.to_vec(),
+            vsize,
+            fee,
+        })
+    }
+}
+
+#[cfg(test)]
+mod consolidation_sanity_checks {
+    use super::{input_weight, low_r_input_weight, DustPlanner, FeeForecast, WalletUtxo};
+    use crate::{Hash256, OutPoint, ScriptType, Txid};
+    use hex_literal::hex;
+
+    fn utxo(vout: u32, amount: u64, script_type: ScriptType) -> WalletUtxo {
+        WalletUtxo {
+            outpoint: OutPoint::new(Txid::new(Hash256::new([1u8; 32])), vout),
+            amount,
+            script_type,
+        }
+    }
+
+    #[test]
+    fn plan_consolidation() {
+        let utxos = vec![
+            // Spending costs 68 vB, 3,400 sats at 50 sat/vB and 136 sats at 2 sat/vB
+            utxo(0, 3_000, ScriptType::P2WPKH),
+            utxo(1, 2_000, ScriptType::P2WPKH),
+            // Not even worth spending at 2 sat/vB
+            utxo(2, 100, ScriptType::P2WPKH),
+            utxo(3, 1_000_000, ScriptType::P2WPKH),
+            // The weight of the input is unknown
+            utxo(4, 500, ScriptType::P2WSH),
+        ];
+
+        let planner = DustPlanner::new(50);
+        assert_eq!(3, planner.uneconomical(&utxos).len());
+
+        assert_eq!(None, utxos[0].spending_fee(u64::MAX));
+        assert!(utxos[0].is_uneconomical(u64::MAX));
+        assert!(!utxos[4].is_uneconomical(u64::MAX));
+
+        assert_eq!(Some(271), low_r_input_weight(ScriptType::P2WPKH));
+        assert_eq!(Some(588), low_r_input_weight(ScriptType::P2PKH));
+        assert_eq!(
+            input_weight(ScriptType::P2TR),
+            low_r_input_weight(ScriptType::P2TR)
+        );
+
+        let forecasts = [
+            FeeForecast {
+                time: 1_000,
+                fee_rate: 60,
+            },
+            FeeForecast {
+                time: 2_000,
+                fee_rate: 2,
+            },
+        ];
+        let locking_script = hex!("0014751e76e8199196d454941c45d1b3a323f1433bd6").to_vec();
+        let output_script = locking_script.clone();
+        let mut plans = planner.plan(&utxos, &forecasts, locking_script);
+        assert_eq!(1, plans.len());
+        let plan = plans.remove(0);
+
+        assert_eq!(2_000, plan.time);
+        assert_eq!(2, plan.tx.inputs().len());
+        // 10.5 vB of overhead with the segwit marker, two inputs and one output
+        assert_eq!(11 + 68 * 2 + 31, plan.vsize);
+        assert_eq!(5_000 - plan.fee, plan.tx.outputs()[0].amount());
+        assert_eq!(output_script, plan.tx.outputs()[0].locking_script());
+
+        // Fees never drop below the target
+        assert!(planner
+            .plan(&utxos, &forecasts[..1], output_script)
+            .is_empty());
+    }
+}
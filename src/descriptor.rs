@@ -0,0 +1,365 @@
+use crate::{Address, KeySource, Network, ScriptType, StandardScripts, Witness};
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+
+/// The characters allowed in a descriptor in the order used by the checksum
+const INPUT_CHARSET: &str =
+    "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+
+/// The characters of the 8 character checksum after the `#`
+const CHECKSUM_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// Handles output script descriptors as defined in BIP380 and the following BIPs
+#[derive(Debug, Clone, Copy)]
+pub struct Descriptor;
+
+impl Descriptor {
+    /// Infer the descriptor of a locking script the same way Bitcoin Core does
+    /// when it knows no keys or scripts. Public keys written in the script give
+    /// `pk(KEY)`, `multi(k,KEY,..)` and `rawtr(KEY)`, other scripts with an address
+    /// give `addr(ADDRESS)` and everything else gives `raw(HEX)`.
+    /// The taproot output key is not checked to be a point on the curve.
+    pub fn infer(script: &[u8], network: Network) -> String {
+        let descriptor = match ScriptType::from_script(script) {
+            // The script is `OP_PUSHBYTES_65 <public key> OP_CHECKSIG`
+            ScriptType::P2PK => format!("pk({})", hex::encode(&script[1..66])),
+            ScriptType::P2MS => Self::multi(script),
+            // The script is `OP_1 OP_PUSHBYTES_32 <output key>`
+            ScriptType::P2TR => format!("rawtr({})", hex::encode(&script[2..])),
+            _ => match Address::from_script(script, network) {
+                Some(address) => format!("addr({})", address),
+                None => format!("raw({})", hex::encode(script)),
+            },
+        };
+
+        Self::with_checksum(&descriptor).unwrap_or(descriptor)
+    }
+
+    /// Infer the descriptor of a locking script from the scriptSig and witness
+    /// of an input spending it. Spends reveal the public keys behind key hashes
+    /// and the redeem and witness scripts behind script hashes giving
+    /// `pkh(KEY)`, `wpkh(KEY)`, `sh(wpkh(KEY))`, `wsh(multi(k,KEY,..))` and
+    /// `sh(multi(k,KEY,..))`. Falls back to `Self::infer()` when the spend does
+    /// not match the locking script. Taproot spends do not reveal the internal key
+    /// of key path spends or the whole script tree so P2TR gives `rawtr(KEY)`.
+    pub fn from_spend(
+        locking_script: &[u8],
+        signature_script: &[u8],
+        witness: &Witness,
+        network: Network,
+    ) -> String {
+        Self::spend_descriptor(locking_script, signature_script, witness)
+            .and_then(|descriptor| Self::with_checksum(&descriptor))
+            .unwrap_or_else(|| Self::infer(locking_script, network))
+    }
+
+    fn spend_descriptor(
+        locking_script: &[u8],
+        signature_script: &[u8],
+        witness: &Witness,
+    ) -> Option<String> {
+        let pushes = StandardScripts::read_pushes(signature_script).ok()?;
+
+        match ScriptType::from_script(locking_script) {
+            // `OP_DUP OP_HASH160 OP_PUSHBYTES_20 <hash> ..` spent by `<signature> <public key>`
+            ScriptType::P2PKH => {
+                let public_key = pushes.last()?;
+                (hash160(public_key) == locking_script[3..23])
+                    .then(|| format!("pkh({})", hex::encode(public_key)))
+            }
+            // `OP_0 OP_PUSHBYTES_20 <hash>` spent by the witness `<signature> <public key>`
+            ScriptType::P2WPKH => {
+                let public_key = witness.last().filter(|_| witness.len() == 2)?;
+                (hash160(public_key) == locking_script[2..22])
+                    .then(|| format!("wpkh({})", hex::encode(public_key)))
+            }
+            // `OP_0 OP_PUSHBYTES_32 <hash>` spent by a witness ending with the witness script
+            ScriptType::P2WSH => {
+                let witness_script = witness.last()?;
+                if Sha256::digest(witness_script).as_slice() != &locking_script[2..34] {
+                    return None;
+                }
+
+                Self::revealed_script(witness_script)
+                    .map(|descriptor| format!("wsh({})", descriptor))
+            }
+            // `OP_HASH160 OP_PUSHBYTES_20 <hash> OP_EQUAL` spent by a scriptSig ending with the redeem script
+            ScriptType::P2SH => {
+                let redeem_script = pushes.last()?;
+                if hash160(redeem_script) != locking_script[2..22] {
+                    return None;
+                }
+
+                match ScriptType::from_script(redeem_script) {
+                    ScriptType::P2WPKH | ScriptType::P2WSH => {
+                        Self::spend_descriptor(redeem_script, &[], witness)
+                    }
+                    _ => Self::revealed_script(redeem_script),
+                }
+                .map(|descriptor| format!("sh({})", descriptor))
+            }
+            _ => None,
+        }
+    }
+
+    // The descriptor of a redeem script or witness script revealed by a spend
+    fn revealed_script(script: &[u8]) -> Option<String> {
+        match ScriptType::from_script(script) {
+            ScriptType::P2PK => Some(format!("pk({})", hex::encode(&script[1..66]))),
+            ScriptType::P2MS => Some(Self::multi(script)),
+            _ => None,
+        }
+    }
+
+    /// The key origins written in a descriptor like `[d34db33f/86'/0'/0']` in
+    /// the order they appear. Origins which do not parse are skipped
+    pub fn key_sources(descriptor: &str) -> Vec<KeySource> {
+        descriptor
+            .split('[')
+            .skip(1)
+            .filter_map(|origin| origin.split_once(']'))
+            .filter_map(|(origin, _)| KeySource::from_str(origin).ok())
+            .collect()
+    }
+
+    /// The key origins of a descriptor extended with the derivation steps written
+    /// after each key, `*` being the child `index`. The key `[d34db33f/84'/0'/0']xpub/1/*`
+    /// at index 5 gives `[d34db33f/84'/0'/0'/1/5]` which is the origin of the key
+    /// a wallet derives for that child. Keys whose steps do not parse, like the
+    /// multipath steps `<0;1>`, are skipped like in `Self::key_sources()`
+    pub fn key_derivations(descriptor: &str, index: u32) -> Vec<KeySource> {
+        descriptor
+            .split('[')
+            .skip(1)
+            .filter_map(|origin| origin.split_once(']'))
+            .filter_map(|(origin, key)| {
+                // The key and its steps end at the next argument or closing parenthesis
+                let mut path = origin.to_string();
+                key.split([',', ')'])
+                    .next()
+                    .unwrap_or_default()
+                    .split('/')
+                    .skip(1)
+                    .for_each(|step| {
+                        path.push('/');
+                        path.push_str(&step.replacen('*', &index.to_string(), 1));
+                    });
+
+                KeySource::from_str(&path).ok()
+            })
+            .collect()
+    }
+
+    /// Append the `#` and checksum to a descriptor without one.
+    /// Returns `None` if the descriptor has characters that are not allowed
+    pub fn with_checksum(descriptor: &str) -> Option<String> {
+        Self::checksum(descriptor).map(|checksum| format!("{}#{}", descriptor, checksum))
+    }
+
+    /// Check that a descriptor ending with `#<checksum>` has the correct checksum
+    pub fn verify_checksum(descriptor: &str) -> bool {
+        match descriptor.rsplit_once('#') {
+            Some((descriptor, checksum)) => {
+                Self::checksum(descriptor).is_some_and(|expected| expected == checksum)
+            }
+            None => false,
+        }
+    }
+
+    /// The 8 character checksum of a descriptor
+    pub fn checksum(descriptor: &str) -> Option<String> {
+        // Every character is split into its position in a group of 32 characters
+        // and the group. The groups of every 3 characters are combined into 1 symbol
+        let mut symbols = Vec::<u8>::new();
+        let mut groups = Vec::<u8>::new();
+
+        for character in descriptor.chars() {
+            let position = INPUT_CHARSET.find(character)? as u8;
+            symbols.push(position & 31);
+            groups.push(position >> 5);
+
+            if groups.len() == 3 {
+                symbols.push(groups[0] * 9 + groups[1] * 3 + groups[2]);
+                groups.clear();
+            }
+        }
+
+        match groups.as_slice() {
+            [first] => symbols.push(*first),
+            [first, second] => symbols.push(first * 3 + second),
+            _ => (),
+        }
+
+        symbols.extend_from_slice(&[0u8; 8]);
+        let checksum = Self::polymod(&symbols) ^ 1;
+
+        Some(
+            (0..8)
+                .map(|index| {
+                    CHECKSUM_CHARSET[((checksum >> (5 * (7 - index))) & 31) as usize] as char
+                })
+                .collect(),
+        )
+    }
+
+    // The BCH code of the checksum which is similar to the one used by Bech32
+    fn polymod(symbols: &[u8]) -> u64 {
+        const GENERATOR: [u64; 5] = [
+            0xf5dee51989,
+            0xa9fdca3312,
+            0x1bab10e32d,
+            0x3706b1677a,
+            0x644d626ffd,
+        ];
+
+        symbols.iter().fold(1u64, |checksum, symbol| {
+            let top = checksum >> 35;
+            let checksum = ((checksum & 0x7ffffffff) << 5) ^ *symbol as u64;
+
+            GENERATOR
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| (top >> index) & 1 == 1)
+                .fold(checksum, |checksum, (_, generator)| checksum ^ generator)
+        })
+    }
+
+    // The script is `OP_k <public keys> OP_n OP_CHECKMULTISIG` where
+    // `OP_1..16` is `0x51..0x60`
+    fn multi(script: &[u8]) -> String {
+        let threshold = script[0] - 0x50;
+        let mut keys = Vec::<String>::new();
+
+        let mut position = 1usize;
+        while let push_len @ 1..=75 = script[position] as usize {
+            keys.push(hex::encode(&script[position + 1..position + 1 + push_len]));
+            position += 1 + push_len;
+        }
+
+        format!("multi({},{})", threshold, keys.join(","))
+    }
+}
+
+// RIPEMD160 of the SHA256 of public keys and redeem scripts
+fn hash160(bytes: &[u8]) -> [u8; 20] {
+    Ripemd160::digest(Sha256::digest(bytes)).into()
+}
+
+#[cfg(test)]
+mod descriptor_sanity_checks {
+    use crate::{Descriptor, KeySource, Network, Script, Witness};
+    use hex_literal::hex;
+    use std::str::FromStr;
+
+    const PUBLIC_KEY: [u8; 33] =
+        hex!("039ac8bac8f6d916b8a85b458e087e0cd07e6a76a6bfdde9bb766b17086d9a5c8a");
+    const MULTISIG: [u8; 71] = hex!("51210200000000000000000000000000000000000000000000000000000000000000012103000000000000000000000000000000000000000000000000000000000000000252ae");
+
+    #[test]
+    fn from_spend() {
+        let key_hash = Script::new(PUBLIC_KEY).p2sh_hash();
+        let signature = [0x30u8; 72];
+
+        let mut p2pkh = hex!("76a914").to_vec();
+        p2pkh.extend_from_slice(key_hash.as_ref());
+        p2pkh.extend_from_slice(&hex!("88ac"));
+        let mut signature_script = vec![72u8];
+        signature_script.extend_from_slice(&signature);
+        signature_script.push(33);
+        signature_script.extend_from_slice(&PUBLIC_KEY);
+        let descriptor =
+            Descriptor::from_spend(&p2pkh, &signature_script, &Witness::new(), Network::Mainnet);
+        assert_eq!(
+            format!("pkh({})", hex::encode(PUBLIC_KEY)),
+            descriptor.split('#').next().unwrap()
+        );
+        assert!(Descriptor::verify_checksum(&descriptor));
+
+        // Nested P2WPKH reveals the redeem script in the scriptSig and the key in the witness
+        let p2wpkh = Script::new([&[0x00, 0x14], key_hash.as_ref()].concat());
+        let mut redeem_push = vec![22u8];
+        redeem_push.extend_from_slice(p2wpkh.as_bytes());
+        let witness = Witness::from_slice(&[&signature, &PUBLIC_KEY]);
+        assert!(Descriptor::from_spend(
+            p2wpkh.to_p2sh().as_bytes(),
+            &redeem_push,
+            &witness,
+            Network::Mainnet
+        )
+        .starts_with(&format!("sh(wpkh({}))#", hex::encode(PUBLIC_KEY))));
+
+        let witness_script = Script::new(MULTISIG);
+        let witness = Witness::from_slice(&[&[], &signature, &MULTISIG]);
+        assert!(Descriptor::from_spend(
+            witness_script.to_p2wsh().as_bytes(),
+            &[],
+            &witness,
+            Network::Mainnet
+        )
+        .starts_with("wsh(multi(1,0200"));
+
+        // A witness script that does not hash to the output falls back to the address
+        let witness = Witness::from_slice(&[&signature, &[0x51]]);
+        assert!(Descriptor::from_spend(
+            witness_script.to_p2wsh().as_bytes(),
+            &[],
+            &witness,
+            Network::Mainnet
+        )
+        .starts_with("addr(bc1q"));
+    }
+
+    #[test]
+    fn checksum() {
+        assert_eq!(
+            Some("raw(deadbeef)#89f8spxm".to_string()),
+            Descriptor::with_checksum("raw(deadbeef)")
+        );
+        assert!(Descriptor::verify_checksum(
+            "addr(mkmZxiEcEd8ZqjQWVZuC6so5dFMKEFpN2j)#02wpgw69"
+        ));
+        assert!(!Descriptor::verify_checksum(
+            "addr(mkmZxiEcEd8ZqjQWVZuC6so5dFMKEFpN2j)#02wpgw68"
+        ));
+        assert_eq!(None, Descriptor::checksum("raw(é)"));
+    }
+
+    #[test]
+    fn infer() {
+        assert!(Descriptor::infer(
+            &hex!("a914748284390f9e263a4b766a75d0633c50426eb87587"),
+            Network::Mainnet
+        )
+        .starts_with("addr(3CK4fEwbMP7heJarmU4eqA3sMbVJyEnU3V)#"));
+        assert!(
+            Descriptor::infer(&hex!("6a0b68656c6c6f20776f726c64"), Network::Mainnet)
+                .starts_with("raw(6a0b68656c6c6f20776f726c64)#")
+        );
+
+        let multi = Descriptor::infer(&MULTISIG, Network::Mainnet);
+        assert!(multi.starts_with("multi(1,0200"));
+        assert!(Descriptor::verify_checksum(&multi));
+    }
+
+    #[test]
+    fn key_derivations() {
+        let descriptor = "wsh(sortedmulti(1,[d34db33f/48'/0'/0'/2']xpub1/0/*,[deadbeef/48h/0h/0h/2h]xpub2/1/*'))";
+        assert_eq!(
+            vec![
+                KeySource::from_str("[d34db33f/48'/0'/0'/2'/0/3]").unwrap(),
+                KeySource::from_str("[deadbeef/48'/0'/0'/2'/1/3']").unwrap(),
+            ],
+            Descriptor::key_derivations(descriptor, 3)
+        );
+
+        // A key without steps keeps its origin and multipath steps are skipped
+        assert_eq!(
+            vec![KeySource::from_str("[0badf00d/86'/0'/0']").unwrap()],
+            Descriptor::key_derivations(
+                "tr([0badf00d/86'/0'/0']xpub3,pk([d34db33f]xpub4/<0;1>/*))",
+                0
+            )
+        );
+    }
+}
@@ -0,0 +1,214 @@
+use crate::{BtcTx, TxInput, TxOutput};
+use std::{fmt, io};
+
+/// A field which differs between two transactions. The side which lacks the
+/// field, like an input only the other transaction has, is `None`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FieldDiff {
+    /// The path of the field like `inputs[1].sequence_number`
+    pub field: String,
+    /// The value in the first transaction
+    pub left: Option<String>,
+    /// The value in the second transaction
+    pub right: Option<String>,
+}
+
+/// The field by field difference of two transactions
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TxDiff {
+    /// The fields whose decoded values differ, in serialization order
+    pub fields: Vec<FieldDiff>,
+    /// The offset of the first byte where the raw transactions differ, which
+    /// also finds encodings like non-minimal VarInts of equal decoded values
+    pub first_differing_byte: Option<usize>,
+}
+
+impl TxDiff {
+    /// Decode both raw transactions and compare them field by field
+    pub fn new(left: &[u8], right: &[u8]) -> io::Result<Self> {
+        let mut diff = BtcTx::from_hex_bytes(left)?.diff(&BtcTx::from_hex_bytes(right)?);
+        diff.first_differing_byte = left
+            .iter()
+            .zip(right)
+            .position(|(left, right)| left != right)
+            .or_else(|| (left.len() != right.len()).then(|| left.len().min(right.len())));
+
+        Ok(diff)
+    }
+
+    /// Whether the transactions are byte for byte the same
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.first_differing_byte.is_none()
+    }
+
+    /// The diff with the removed values in red and the added values in green
+    /// using ANSI escape codes, for printing to a terminal
+    pub fn to_colored_string(&self) -> String {
+        self.render(("\x1b[31m", "\x1b[32m", "\x1b[0m"))
+    }
+
+    fn render(&self, (red, green, reset): (&str, &str, &str)) -> String {
+        let mut rendered = String::new();
+
+        if let Some(offset) = self.first_differing_byte {
+            rendered.push_str(&format!("The raw bytes differ from offset {}\n", offset));
+        }
+        self.fields.iter().for_each(|field| {
+            rendered.push_str(&format!("{}\n", field.field));
+            if let Some(left) = &field.left {
+                rendered.push_str(&format!("{}- {}{}\n", red, left, reset));
+            }
+            if let Some(right) = &field.right {
+                rendered.push_str(&format!("{}+ {}{}\n", green, right, reset));
+            }
+        });
+
+        rendered
+    }
+}
+
+impl fmt::Display for TxDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render(("", "", "")))
+    }
+}
+
+// A named field and how to display its value
+type Field<T> = (&'static str, fn(&T) -> String);
+
+impl BtcTx {
+    /// Compare the decoded fields of two transactions. Inputs and outputs are
+    /// aligned by index
+    pub fn diff(&self, other: &BtcTx) -> TxDiff {
+        let mut fields = Vec::<FieldDiff>::new();
+        let mut compare = |field: String, left: Option<String>, right: Option<String>| {
+            if left != right {
+                fields.push(FieldDiff { field, left, right });
+            }
+        };
+
+        compare(
+            "version".to_string(),
+            Some(u32::from_le_bytes(self.version().to_bytes()).to_string()),
+            Some(u32::from_le_bytes(other.version().to_bytes()).to_string()),
+        );
+
+        (0..self.inputs().len().max(other.inputs().len())).for_each(|index| {
+            let left = self.inputs().get(index);
+            let right = other.inputs().get(index);
+            Self::input_fields().iter().for_each(|(name, value)| {
+                compare(
+                    format!("inputs[{}].{}", index, name),
+                    left.map(value),
+                    right.map(value),
+                )
+            });
+        });
+
+        (0..self.outputs().len().max(other.outputs().len())).for_each(|index| {
+            let left = self.outputs().get(index);
+            let right = other.outputs().get(index);
+            Self::output_fields().iter().for_each(|(name, value)| {
+                compare(
+                    format!("outputs[{}].{}", index, name),
+                    left.map(value),
+                    right.map(value),
+                )
+            });
+        });
+
+        compare(
+            "locktime".to_string(),
+            Some(self.locktime().to_string()),
+            Some(other.locktime().to_string()),
+        );
+
+        TxDiff {
+            fields,
+            first_differing_byte: None,
+        }
+    }
+
+    fn input_fields() -> [Field<TxInput>; 5] {
+        [
+            ("previous_tx_id", |input| input.previous_tx_id().to_string()),
+            ("previous_output_index", |input| {
+                input.previous_output_index().to_string()
+            }),
+            ("signature_script", |input| {
+                hex::encode(input.signature_script())
+            }),
+            ("sequence_number", |input| {
+                format!("0x{:08x}", input.sequence_number())
+            }),
+            ("witness", |input| {
+                input
+                    .witness()
+                    .iter()
+                    .map(hex::encode)
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            }),
+        ]
+    }
+
+    fn output_fields() -> [Field<TxOutput>; 2] {
+        [
+            ("amount", |output| output.amount().to_string()),
+            ("locking_script", |output| {
+                hex::encode(output.locking_script())
+            }),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod diff_sanity_checks {
+    use crate::{FieldDiff, TxDiff};
+    use hex_literal::hex;
+
+    #[test]
+    fn diff_transactions() {
+        let left = [
+            &hex!("0200000001")[..],
+            &[0x11; 32],
+            &hex!("0000000000ffffffff01e8030000000000000151"),
+            &[0u8; 4],
+        ]
+        .concat();
+        // A different sequence number and a second output
+        let right = [
+            &hex!("0200000001")[..],
+            &[0x11; 32],
+            &hex!("0000000000feffffff02e8030000000000000151d007000000000000015200000000"),
+        ]
+        .concat();
+
+        let diff = TxDiff::new(&left, &right).unwrap();
+        assert_eq!(
+            vec![
+                FieldDiff {
+                    field: "inputs[0].sequence_number".to_string(),
+                    left: Some("0xffffffff".to_string()),
+                    right: Some("0xfffffffe".to_string()),
+                },
+                FieldDiff {
+                    field: "outputs[1].amount".to_string(),
+                    left: None,
+                    right: Some("2000".to_string()),
+                },
+                FieldDiff {
+                    field: "outputs[1].locking_script".to_string(),
+                    left: None,
+                    right: Some("52".to_string()),
+                },
+            ],
+            diff.fields
+        );
+        assert_eq!(Some(42), diff.first_differing_byte);
+        assert!(diff.to_string().contains("+ 2000"));
+        assert!(diff.to_colored_string().contains("\x1b[32m+ 2000\x1b[0m"));
+
+        assert!(TxDiff::new(&left, &left).unwrap().is_empty());
+    }
+}
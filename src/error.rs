@@ -0,0 +1,167 @@
+use crate::{Opcode, ScriptType};
+use std::{
+    fmt,
+    io::{self, ErrorKind},
+};
+
+/// The error returned when a script does not match the standard
+/// script template that was being parsed. It records the template,
+/// the byte offset in the script where parsing failed and what was
+/// expected compared to what was found at that offset.
+///
+/// Parsing methods still return `io::Result` so this error is wrapped
+/// inside an `io::Error` and can be recovered using
+/// `io::Error::get_ref()` and `downcast_ref::<ScriptError>()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptError {
+    // The template being attempted, `None` if no template was chosen yet
+    template: Option<ScriptType>,
+    // The offset of the byte where parsing failed
+    offset: usize,
+    // What the template expected at the offset
+    expected: String,
+    // What was found at the offset, `None` if the script ended early
+    found: Option<String>,
+}
+
+impl ScriptError {
+    /// Instantiate a new error
+    pub fn new(
+        template: Option<ScriptType>,
+        offset: usize,
+        expected: impl Into<String>,
+        found: Option<String>,
+    ) -> Self {
+        Self {
+            template,
+            offset,
+            expected: expected.into(),
+            found,
+        }
+    }
+
+    /// The error for a script that ended before the `expected` item was read
+    pub fn end_of_script(
+        template: Option<ScriptType>,
+        offset: usize,
+        expected: impl Into<String>,
+    ) -> Self {
+        Self::new(template, offset, expected, None)
+    }
+
+    /// The error for an opcode `byte` that is not the `expected` opcode
+    pub fn unexpected_opcode(
+        template: Option<ScriptType>,
+        offset: usize,
+        expected: impl Into<String>,
+        byte: u8,
+    ) -> Self {
+        Self::new(template, offset, expected, Some(Self::opcode_name(byte)))
+    }
+
+    /// The template being attempted
+    pub fn template(&self) -> Option<ScriptType> {
+        self.template
+    }
+
+    /// The byte offset in the script where parsing failed
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// What the template expected at the offset
+    pub fn expected(&self) -> &str {
+        &self.expected
+    }
+
+    /// What was found at the offset, `None` if the script ended early
+    pub fn found(&self) -> Option<&str> {
+        self.found.as_deref()
+    }
+
+    /// The name of an opcode byte
+    pub fn opcode_name(byte: u8) -> String {
+        Opcode::from_byte(byte).to_string()
+    }
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.template {
+            Some(template) => write!(f, "{}: ", template)?,
+            None => write!(f, "Script: ")?,
+        }
+
+        write!(
+            f,
+            "expected {} at offset {}, found {}",
+            self.expected,
+            self.offset,
+            self.found.as_deref().unwrap_or("end of script")
+        )
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+impl From<ScriptError> for io::Error {
+    fn from(error: ScriptError) -> Self {
+        // A script that ended early is an `UnexpectedEof` like `Read::read_exact()`
+        let kind = if error.found.is_none() {
+            ErrorKind::UnexpectedEof
+        } else {
+            ErrorKind::InvalidData
+        };
+
+        io::Error::new(kind, error)
+    }
+}
+
+/// The largest number of keys `OP_CHECKMULTISIG` accepts by consensus
+pub const MAX_P2MS_KEYS: usize = 20;
+
+/// The largest number of keys of a bare multisig output relayed by default
+pub const MAX_STANDARD_P2MS_KEYS: usize = 3;
+
+/// Why a multisig script was rejected. Like `ScriptError` it is returned
+/// wrapped inside an `io::Error`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MultisigError {
+    /// A push at `offset` is not a 33 byte compressed key starting with 0x02
+    /// or 0x03 or a 65 byte uncompressed key starting with 0x04
+    InvalidPublicKey { offset: usize, len: usize },
+    /// The script has more keys than `OP_CHECKMULTISIG` accepts
+    TooManyKeys { keys: usize },
+    /// The script has more keys than a standard bare multisig output
+    NonStandardKeyCount { keys: usize },
+}
+
+impl fmt::Display for MultisigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidPublicKey { offset, len } => write!(
+                f,
+                "P2MS: the push of {} bytes at offset {} is not a public key",
+                len, offset
+            ),
+            Self::TooManyKeys { keys } => write!(
+                f,
+                "P2MS: {} public keys is more than the {} allowed",
+                keys, MAX_P2MS_KEYS
+            ),
+            Self::NonStandardKeyCount { keys } => write!(
+                f,
+                "P2MS: {} public keys is more than the {} of a standard output",
+                keys, MAX_STANDARD_P2MS_KEYS
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MultisigError {}
+
+impl From<MultisigError> for io::Error {
+    fn from(error: MultisigError) -> Self {
+        io::Error::new(ErrorKind::InvalidData, error)
+    }
+}
@@ -0,0 +1,220 @@
+use crate::{instrument::traced_request, BtcTx, ConfirmedTx, Hash256};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::io::{self, ErrorKind};
+
+/// The Esplora API of mempool.space for the main Bitcoin network
+pub const MEMPOOL_SPACE_URL: &str = "https://mempool.space/api";
+
+/// The Esplora API of blockstream.info for the main Bitcoin network
+pub const BLOCKSTREAM_URL: &str = "https://blockstream.info/api";
+
+/// The confirmation status of a transaction
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TxStatus {
+    /// Whether the transaction is in a block
+    pub confirmed: bool,
+    /// The height of the block, `None` if unconfirmed
+    pub block_height: Option<u32>,
+    /// The hash of the block in hex, `None` if unconfirmed
+    pub block_hash: Option<String>,
+    /// The timestamp of the block, `None` if unconfirmed
+    pub block_time: Option<u64>,
+}
+
+/// Whether an output of a transaction has been spent and by which input
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct OutSpend {
+    /// Whether the output is spent
+    pub spent: bool,
+    /// The transaction ID of the spending transaction
+    pub txid: Option<String>,
+    /// The input index in the spending transaction
+    pub vin: Option<u32>,
+    /// The confirmation status of the spending transaction
+    pub status: Option<TxStatus>,
+}
+
+/// An unspent output belonging to an address
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Utxo {
+    /// The transaction ID of the transaction with the output
+    pub txid: String,
+    /// The index of the output
+    pub vout: u32,
+    /// The amount in satoshis
+    pub value: u64,
+    /// The confirmation status of the transaction
+    pub status: TxStatus,
+}
+
+/// An async client for the Esplora REST API served by
+/// mempool.space, blockstream.info or a self-hosted instance
+#[derive(Debug, Clone)]
+pub struct EsploraClient {
+    // The base URL of the API without a trailing `/`
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl EsploraClient {
+    /// Instantiate a new client for the API at `base_url`
+    /// like `MEMPOOL_SPACE_URL` or `http://localhost:3002`
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').into(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Fetch and decode the transaction `txid`
+    pub async fn transaction(&self, txid: &str) -> io::Result<BtcTx> {
+        BtcTx::from_hex_bytes(self.raw_transaction(txid).await?)
+    }
+
+    /// Fetch the serialization of the transaction `txid` without decoding it
+    pub async fn raw_transaction(&self, txid: &str) -> io::Result<Vec<u8>> {
+        let tx_hex = self.get_text(&format!("/tx/{}/hex", txid)).await?;
+
+        hex::decode(tx_hex.trim()).map_err(|error| io::Error::new(ErrorKind::InvalidData, error))
+    }
+
+    /// Fetch the confirmation status of the transaction `txid`
+    pub async fn transaction_status(&self, txid: &str) -> io::Result<TxStatus> {
+        self.get_json(&format!("/tx/{}/status", txid)).await
+    }
+
+    /// Fetch the transaction `txid` together with the block it was confirmed in.
+    /// Returns `None` if the transaction is still unconfirmed
+    pub async fn confirmed_transaction(&self, txid: &str) -> io::Result<Option<ConfirmedTx>> {
+        let status = self.transaction_status(txid).await?;
+
+        let (block_hash, height, block_time) =
+            match (status.block_hash, status.block_height, status.block_time) {
+                (Some(block_hash), Some(height), Some(block_time)) if status.confirmed => {
+                    (block_hash, height, block_time)
+                }
+                _ => return Ok(None),
+            };
+
+        // The position in the block is found from the list of transaction IDs of the block
+        let txids: Vec<String> = self
+            .get_json(&format!("/block/{}/txids", block_hash))
+            .await?;
+        let index_in_block = txids.iter().position(|id| id == txid).ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::NotFound,
+                "The transaction ID is not in the block it is confirmed in",
+            )
+        })?;
+
+        let block_hash: Hash256 = block_hash.parse()?;
+
+        Ok(Some(ConfirmedTx::new(
+            self.transaction(txid).await?,
+            block_hash,
+            height,
+            block_time as u32,
+            index_in_block,
+        )))
+    }
+
+    /// Fetch the spending status of every output of the transaction `txid`
+    pub async fn outspends(&self, txid: &str) -> io::Result<Vec<OutSpend>> {
+        self.get_json(&format!("/tx/{}/outspends", txid)).await
+    }
+
+    /// Fetch the unspent outputs of `address`
+    pub async fn address_utxos(&self, address: &str) -> io::Result<Vec<Utxo>> {
+        self.get_json(&format!("/address/{}/utxo", address)).await
+    }
+
+    /// Broadcast a raw transaction and return its transaction ID
+    pub async fn broadcast(&self, raw_tx: &[u8]) -> io::Result<String> {
+        traced_request("esplora_broadcast", raw_tx.len(), async {
+            let response = self
+                .client
+                .post(format!("{}/tx", self.base_url))
+                .body(hex::encode(raw_tx))
+                .send()
+                .await
+                .map_err(io::Error::other)?;
+
+            Self::response_text(response).await
+        })
+        .await
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, path: &str) -> io::Result<T> {
+        let body = self.get_text(path).await?;
+
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    async fn get_text(&self, path: &str) -> io::Result<String> {
+        traced_request("esplora_get", 0, async {
+            let response = self
+                .client
+                .get(format!("{}{}", self.base_url, path))
+                .send()
+                .await
+                .map_err(io::Error::other)?;
+
+            Self::response_text(response).await
+        })
+        .await
+    }
+
+    // Esplora returns the reason for a failure like a rejected broadcast
+    // in the body so we keep it in the error instead of only the status code
+    async fn response_text(response: reqwest::Response) -> io::Result<String> {
+        let status = response.status();
+        let body = response.text().await.map_err(io::Error::other)?;
+
+        if !status.is_success() {
+            let kind = if status == reqwest::StatusCode::NOT_FOUND {
+                ErrorKind::NotFound
+            } else {
+                ErrorKind::Other
+            };
+
+            return Err(io::Error::new(
+                kind,
+                format!("Esplora request failed with {}: {}", status, body),
+            ));
+        }
+
+        Ok(body)
+    }
+}
+
+#[cfg(test)]
+mod esplora_sanity_checks {
+    use crate::{OutSpend, TxStatus, Utxo};
+
+    #[test]
+    fn decode_responses() {
+        let status: TxStatus = serde_json::from_str(
+            r#"{"confirmed":true,"block_height":800000,"block_hash":"00000000000000000002a7c4c1e48d76c5a37902165a270156b7a8d72728a054","block_time":1690168629}"#,
+        )
+        .unwrap();
+        assert!(status.confirmed);
+        assert_eq!(Some(800000), status.block_height);
+
+        let outspends: Vec<OutSpend> = serde_json::from_str(
+            r#"[{"spent":false},{"spent":true,"txid":"ab","vin":0,"status":{"confirmed":false}}]"#,
+        )
+        .unwrap();
+        assert!(!outspends[0].spent);
+        assert_eq!(Some(0), outspends[1].vin);
+        assert_eq!(
+            Some(false),
+            outspends[1].status.as_ref().map(|status| status.confirmed)
+        );
+
+        let utxos: Vec<Utxo> = serde_json::from_str(
+            r#"[{"txid":"ab","vout":1,"status":{"confirmed":false},"value":95000}]"#,
+        )
+        .unwrap();
+        assert_eq!(95000, utxos[0].value);
+    }
+}
@@ -0,0 +1,400 @@
+use crate::{
+    input_weight, BtcTx, OutPoint, TxInput, TxOutput, TxVersion, VarInt, WeightedUtxo,
+    EMPTY_INPUT_WEIGHT, SEQUENCE_MAX_NON_RBF,
+};
+use std::io::{self, ErrorKind};
+
+/// The fee rate in sat/vB a BIP125 replacement pays for its own relay on top of
+/// the fee of the transaction it replaces, like the `-incrementalrelayfee` option
+/// of Bitcoin Core before version 30
+pub const INCREMENTAL_RELAY_FEE_RATE: u64 = 1;
+
+impl BtcTx {
+    /// Build the unsigned BIP125 replacement of the transaction paying `new_fee_rate`
+    /// sat/vB. The fee is taken from the output at `change_output_index` and when the
+    /// change would become dust the outputs of `utxos` are added in order until it is
+    /// not. `prevouts` are the outputs spent by every input in order and `utxos` must
+    /// be confirmed since a replacement cannot spend new unconfirmed outputs.
+    ///
+    /// The replacement keeps the inputs, outputs and sequence numbers, so it still
+    /// signals replaceability, and pays at least the fee of the transaction plus
+    /// `INCREMENTAL_RELAY_FEE_RATE` for its own size. Its size is estimated from
+    /// the scriptSigs and witnesses of the transaction or like `input_weight()`
+    /// when that is larger, so the transaction does not need to be signed
+    pub fn create_rbf_replacement(
+        &self,
+        new_fee_rate: u64,
+        change_output_index: usize,
+        prevouts: &[TxOutput],
+        utxos: &[WeightedUtxo],
+    ) -> io::Result<BtcTx> {
+        self.check_prevouts(prevouts)?;
+        if self
+            .inputs()
+            .iter()
+            .all(|input| input.sequence_number() >= SEQUENCE_MAX_NON_RBF)
+        {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "The transaction does not signal BIP125 replaceability",
+            ));
+        }
+        let Some(change) = self.outputs().get(change_output_index) else {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "The transaction has no output at the change index",
+            ));
+        };
+
+        let spent = prevouts.iter().map(TxOutput::amount).sum::<u64>();
+        let paid = self.outputs().iter().map(TxOutput::amount).sum::<u64>();
+        let original_fee = spent.checked_sub(paid).ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                "The outputs pay more than the spent outputs",
+            )
+        })?;
+        // The transaction may not be signed yet so its size is estimated too
+        let mut inputs = self.unsigned_inputs();
+        let mut satisfaction_weight = self.satisfaction_weight(prevouts);
+        // The unsigned transaction weighs the same whatever the change amount is
+        let unsigned = self.with_change(inputs.clone(), change_output_index, 0);
+        let original_vsize = estimated_weight(&unsigned, satisfaction_weight).div_ceil(4);
+        if new_fee_rate.saturating_mul(original_vsize) <= original_fee {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "The new fee rate must be higher than the fee rate of the transaction",
+            ));
+        }
+
+        let mut available = change.amount() + spent - paid;
+        let mut utxos = utxos.iter().filter(|utxo| !self.inputs_spend(utxo));
+
+        loop {
+            let unsigned = self.with_change(inputs.clone(), change_output_index, 0);
+            let vsize = estimated_weight(&unsigned, satisfaction_weight).div_ceil(4);
+            let fee = new_fee_rate
+                .checked_mul(vsize)
+                .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "The fee overflows"))?
+                .max(original_fee + INCREMENTAL_RELAY_FEE_RATE * vsize);
+
+            if let Some(amount) = available.checked_sub(fee) {
+                let replacement = self.with_change(inputs.clone(), change_output_index, amount);
+                if !replacement.outputs()[change_output_index].is_dust() {
+                    return Ok(replacement);
+                }
+            }
+
+            let Some(utxo) = utxos.next() else {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "The change output and the UTXOs cannot pay the fee of the replacement",
+                ));
+            };
+            // New inputs signal replaceability without a relative locktime
+            inputs.push(utxo.to_input(SEQUENCE_MAX_NON_RBF - 1));
+            satisfaction_weight += utxo.satisfaction_weight;
+            available += utxo.txout.amount();
+        }
+    }
+
+    /// Build the unsigned child spending the output `vout` of the transaction to
+    /// `locking_script` so the package of both pays `target_fee_rate` sat/vB.
+    /// `prevouts` are the outputs spent by every input of the transaction, the
+    /// parent. The sizes of both transactions are estimated like for
+    /// `BtcTx::create_rbf_replacement()` and spending the output must have a
+    /// known `input_weight()`. The child of a TRUC transaction is a TRUC transaction
+    pub fn create_cpfp_child(
+        &self,
+        target_fee_rate: u64,
+        vout: u32,
+        prevouts: &[TxOutput],
+        locking_script: Vec<u8>,
+    ) -> io::Result<BtcTx> {
+        self.check_prevouts(prevouts)?;
+        let output = self.outputs().get(vout as usize).ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidInput,
+                "The transaction has no output at the index",
+            )
+        })?;
+        let utxo = WeightedUtxo::estimated(OutPoint::new(self.txid(), vout), output.clone())
+            .ok_or_else(|| {
+                io::Error::new(
+                    ErrorKind::Unsupported,
+                    "The weight of spending the output is not known",
+                )
+            })?;
+
+        let spent = prevouts.iter().map(TxOutput::amount).sum::<u64>();
+        let paid = self.outputs().iter().map(TxOutput::amount).sum::<u64>();
+        let parent_fee = spent.checked_sub(paid).ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                "The outputs pay more than the spent outputs",
+            )
+        })?;
+        let parent = BtcTx::new(
+            *self.version(),
+            self.unsigned_inputs(),
+            self.outputs().to_vec(),
+            self.locktime(),
+        );
+        let parent_vsize =
+            estimated_weight(&parent, self.satisfaction_weight(prevouts)).div_ceil(4);
+
+        let version = if self.is_truc() {
+            *self.version()
+        } else {
+            TxVersion::Two
+        };
+        // The child can be replaced if the fees rise again
+        let unsigned = |amount: u64| {
+            BtcTx::new(
+                version,
+                vec![utxo.to_input(SEQUENCE_MAX_NON_RBF - 1)],
+                vec![TxOutput::new(amount, locking_script.clone())],
+                0,
+            )
+        };
+        let child_vsize = estimated_weight(&unsigned(0), utxo.satisfaction_weight).div_ceil(4);
+
+        let overflow = || io::Error::new(ErrorKind::InvalidInput, "The fee overflows");
+        let package_fee = target_fee_rate
+            .checked_mul(parent_vsize + child_vsize)
+            .ok_or_else(overflow)?;
+        // A parent already paying for the package still needs a child paying for itself
+        let fee = package_fee
+            .saturating_sub(parent_fee)
+            .max(target_fee_rate * child_vsize);
+
+        let child = output
+            .amount()
+            .checked_sub(fee)
+            .map(unsigned)
+            .filter(|child| !child.outputs()[0].is_dust())
+            .ok_or_else(|| {
+                io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "The output cannot pay the fee of the child",
+                )
+            })?;
+
+        Ok(child)
+    }
+
+    // The inputs of the transaction without their scriptSigs and witnesses
+    fn unsigned_inputs(&self) -> Vec<TxInput> {
+        self.inputs()
+            .iter()
+            .map(|input| {
+                TxInput::new(
+                    input.previous_tx_id(),
+                    input.previous_output_index(),
+                    Vec::new(),
+                    input.sequence_number(),
+                )
+            })
+            .collect()
+    }
+
+    // The estimated weight the scriptSigs and witnesses add to the unsigned inputs
+    fn satisfaction_weight(&self, prevouts: &[TxOutput]) -> u64 {
+        self.inputs()
+            .iter()
+            .zip(prevouts)
+            .map(|(input, prevout)| satisfaction_weight(input, prevout))
+            .sum()
+    }
+
+    fn inputs_spend(&self, utxo: &WeightedUtxo) -> bool {
+        self.inputs()
+            .iter()
+            .any(|input| input.previous_output() == utxo.outpoint)
+    }
+
+    // The transaction spending `inputs` with the output at `change_output_index` paying `amount`
+    fn with_change(&self, inputs: Vec<TxInput>, change_output_index: usize, amount: u64) -> Self {
+        let mut outputs = self.outputs().to_vec();
+        let change = &outputs[change_output_index];
+        outputs[change_output_index] = TxOutput::new(amount, change.locking_script().to_vec());
+
+        BtcTx::new(*self.version(), inputs, outputs, self.locktime())
+    }
+}
+
+// The weight the scriptSig and witness of `input` add to an input with neither,
+// the larger of their current weight and the estimate for the type of `prevout`
+fn satisfaction_weight(input: &TxInput, prevout: &TxOutput) -> u64 {
+    let signature_script = input.signature_script().len();
+    let mut weight = VarInt::encode(signature_script as u64).len() + signature_script - 1;
+    weight *= 4;
+    if !input.witness().is_empty() {
+        weight += input.witness().serialized_size();
+    }
+
+    let estimate = input_weight(prevout.script_type())
+        .map(|input_weight| input_weight - EMPTY_INPUT_WEIGHT)
+        .unwrap_or_default();
+
+    (weight as u64).max(estimate)
+}
+
+// The weight of `unsigned` once its inputs add `satisfaction_weight`. The segwit
+// marker, flag and the empty witnesses of legacy inputs are always counted
+fn estimated_weight(unsigned: &BtcTx, satisfaction_weight: u64) -> u64 {
+    unsigned.weight() as u64 + satisfaction_weight + 2 + unsigned.inputs().len() as u64
+}
+
+#[cfg(test)]
+mod fee_bump_sanity_checks {
+    use crate::{
+        fixtures::txid, BtcTx, OutPoint, TxInput, TxOutput, TxVersion, WeightedUtxo,
+        SEQUENCE_MAX_NON_RBF,
+    };
+    use hex_literal::hex;
+
+    const P2WPKH: [u8; 22] = hex!("0014751e76e8199196d454941c45d1b3a323f1433bd6");
+
+    // A transaction spending one P2WPKH output of `spent` to a payment and a change output
+    fn original(spent: u64, payment: u64, change: u64, sequence: u32) -> (BtcTx, Vec<TxOutput>) {
+        let tx = BtcTx::new(
+            TxVersion::Two,
+            vec![TxInput::new(txid(1), 0, Vec::new(), sequence)],
+            vec![
+                TxOutput::new(payment, P2WPKH.to_vec()),
+                TxOutput::new(change, P2WPKH.to_vec()),
+            ],
+            0,
+        );
+
+        (tx, vec![TxOutput::new(spent, P2WPKH.to_vec())])
+    }
+
+    #[test]
+    fn reduce_change() {
+        let (tx, prevouts) = original(100_000, 50_000, 49_000, 0xfffffffd);
+
+        // 113 bytes unsigned, a signature and a public key in the witness and the
+        // marker, flag and witness count make 563 weight units so 141 vbytes
+        let replacement = tx.create_rbf_replacement(10, 1, &prevouts, &[]).unwrap();
+        assert_eq!(100_000 - 50_000 - 1_410, replacement.outputs()[1].amount());
+        assert_eq!(tx.outputs()[0], replacement.outputs()[0]);
+        assert_eq!(
+            tx.inputs()[0].previous_output(),
+            replacement.inputs()[0].previous_output()
+        );
+        assert_eq!(0xfffffffd, replacement.inputs()[0].sequence_number());
+
+        // Just above the fee rate of the transaction the incremental relay fee decides
+        let replacement = tx.create_rbf_replacement(8, 1, &prevouts, &[]).unwrap();
+        assert_eq!(
+            100_000 - 50_000 - 1_000 - 141,
+            replacement.outputs()[1].amount()
+        );
+
+        // The fee rate must go up
+        assert!(tx.create_rbf_replacement(7, 1, &prevouts, &[]).is_err());
+        assert!(tx.create_rbf_replacement(10, 2, &prevouts, &[]).is_err());
+        assert!(tx.create_rbf_replacement(10, 1, &[], &[]).is_err());
+
+        let (final_tx, prevouts) = original(100_000, 50_000, 49_000, SEQUENCE_MAX_NON_RBF);
+        assert!(final_tx
+            .create_rbf_replacement(10, 1, &prevouts, &[])
+            .is_err());
+    }
+
+    #[test]
+    fn add_inputs() {
+        let (tx, prevouts) = original(50_400, 49_000, 400, 0xfffffffd);
+        let utxo = |vout: u32, amount: u64| {
+            WeightedUtxo::estimated(
+                OutPoint::new(txid(2), vout),
+                TxOutput::new(amount, P2WPKH.to_vec()),
+            )
+            .unwrap()
+        };
+        let spent =
+            WeightedUtxo::estimated(OutPoint::new(txid(1), 0), prevouts[0].clone()).unwrap();
+
+        // The change cannot pay 2820 sats so 836 weight units with a second input
+        let replacement = tx
+            .create_rbf_replacement(20, 1, &prevouts, &[spent.clone(), utxo(0, 10_000)])
+            .unwrap();
+        assert_eq!(2, replacement.inputs().len());
+        assert_eq!(
+            OutPoint::new(txid(2), 0),
+            replacement.inputs()[1].previous_output()
+        );
+        assert_eq!(60_400 - 49_000 - 4_180, replacement.outputs()[1].amount());
+
+        // A change output left as dust takes another input, 1109 weight units
+        let replacement = tx
+            .create_rbf_replacement(20, 1, &prevouts, &[utxo(0, 2_500), utxo(1, 10_000)])
+            .unwrap();
+        assert_eq!(3, replacement.inputs().len());
+        assert_eq!(62_900 - 49_000 - 5_560, replacement.outputs()[1].amount());
+
+        assert!(tx
+            .create_rbf_replacement(20, 1, &prevouts, &[spent, utxo(0, 1_000)])
+            .is_err());
+    }
+
+    #[test]
+    fn cpfp_child() {
+        let (parent, prevouts) = original(50_200, 0, 50_000, 0xfffffffd);
+        let destination = hex!("0014000000000000000000000000000000000000000f").to_vec();
+
+        // The parent pays 200 sats for 141 vbytes and the child
+        // has 82 bytes and a witness making 439 weight units
+        let child = parent
+            .create_cpfp_child(10, 1, &prevouts, destination.clone())
+            .unwrap();
+        assert_eq!(
+            OutPoint::new(parent.txid(), 1),
+            child.inputs()[0].previous_output()
+        );
+        assert_eq!(50_000 - (2_510 - 200), child.outputs()[0].amount());
+        assert_eq!(destination, child.outputs()[0].locking_script());
+        assert_eq!(&TxVersion::Two, child.version());
+
+        // A parent paying enough on its own still gets a child paying for itself
+        let (rich_parent, rich_prevouts) = original(55_000, 0, 50_000, 0xfffffffd);
+        let child = rich_parent
+            .create_cpfp_child(10, 1, &rich_prevouts, destination.clone())
+            .unwrap();
+        assert_eq!(50_000 - 1_100, child.outputs()[0].amount());
+
+        let truc_parent = BtcTx::new(
+            TxVersion::Custom(3),
+            parent.inputs().to_vec(),
+            parent.outputs().to_vec(),
+            0,
+        );
+        let child = truc_parent
+            .create_cpfp_child(10, 1, &prevouts, destination.clone())
+            .unwrap();
+        assert!(child.is_truc());
+
+        // The output is too small, unknown or missing
+        let (small_parent, small_prevouts) = original(2_200, 0, 2_000, 0xfffffffd);
+        assert!(small_parent
+            .create_cpfp_child(10, 1, &small_prevouts, destination.clone())
+            .is_err());
+        assert!(parent
+            .create_cpfp_child(10, 0, &prevouts, destination.clone())
+            .is_err());
+        assert!(parent
+            .create_cpfp_child(10, 2, &prevouts, destination.clone())
+            .is_err());
+        let bare = BtcTx::new(
+            TxVersion::Two,
+            parent.inputs().to_vec(),
+            vec![TxOutput::new(50_000, vec![0x51])],
+            0,
+        );
+        assert!(bare
+            .create_cpfp_child(10, 0, &prevouts, destination)
+            .is_err());
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{BtcTx, Hash256, TxInput, TxOutput, TxVersion, Txid};
+use serde_json::Value;
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::PathBuf,
+};
+
+/// A test fixture made up of the raw bytes stored as hex in
+/// `fixtures/<kind>/<name>.hex` and the expected decoding stored
+/// as JSON in `fixtures/<kind>/<name>.json`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fixture {
+    // The file name without the extension
+    pub name: String,
+    // The decoded bytes of the hex file
+    pub bytes: Vec<u8>,
+    // The parsed JSON expectations
+    pub expected: Value,
+}
+
+impl Fixture {
+    /// Load the fixture `name` from the `kind` directory like `scripts` or `transactions`
+    pub fn load(kind: &str, name: &str) -> io::Result<Self> {
+        let directory = Self::directory(kind);
+
+        // Whitespace is ignored so that long hex strings can be wrapped
+        let hex_string = fs::read_to_string(directory.join(format!("{}.hex", name)))?
+            .split_whitespace()
+            .collect::<String>();
+        let bytes = hex::decode(hex_string)
+            .map_err(|error| io::Error::new(ErrorKind::InvalidData, error))?;
+
+        let json_string = fs::read_to_string(directory.join(format!("{}.json", name)))?;
+        let expected = serde_json::from_str(&json_string)?;
+
+        Ok(Self {
+            name: name.into(),
+            bytes,
+            expected,
+        })
+    }
+
+    /// Load every fixture in the `kind` directory sorted by name
+    pub fn load_all(kind: &str) -> io::Result<Vec<Self>> {
+        let mut names = fs::read_dir(Self::directory(kind))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<PathBuf>>>()?
+            .into_iter()
+            .filter(|path| path.extension().is_some_and(|extension| extension == "hex"))
+            .filter_map(|path| Some(path.file_stem()?.to_str()?.to_owned()))
+            .collect::<Vec<String>>();
+        names.sort();
+
+        names.iter().map(|name| Self::load(kind, name)).collect()
+    }
+
+    fn directory(kind: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(kind)
+    }
+}
+
+/// The transaction ID made of `byte` repeated 32 times
+pub fn txid(byte: u8) -> Txid {
+    Txid::new(Hash256::new([byte; 32]))
+}
+
+/// The first `count` outputs of the transaction `txid(1)`
+pub fn outpoints(count: u32) -> Vec<(Txid, u32)> {
+    (0..count).map(|vout| (txid(1), vout)).collect()
+}
+
+/// A version 2 transaction spending the `inputs` outpoints with final
+/// sequence numbers into one `OP_1` output per amount
+pub fn tx(inputs: &[(Txid, u32)], amounts: &[u64]) -> BtcTx {
+    tx_to(
+        inputs,
+        amounts.iter().map(|amount| (*amount, vec![0x51])).collect(),
+    )
+}
+
+/// Same as `tx()` paying every amount to its own locking script
+pub fn tx_to(inputs: &[(Txid, u32)], outputs: Vec<(u64, Vec<u8>)>) -> BtcTx {
+    BtcTx::new(
+        TxVersion::Two,
+        inputs
+            .iter()
+            .map(|(txid, vout)| TxInput::new(*txid, *vout, Vec::new(), u32::MAX))
+            .collect(),
+        outputs
+            .into_iter()
+            .map(|(amount, script)| TxOutput::new(amount, script))
+            .collect(),
+        0,
+    )
+}
+
+#[cfg(test)]
+mod fixtures_sanity_checks {
+    use super::Fixture;
+    use crate::{AsmFormat, BtcTx, Network, ScriptType, StandardScripts};
+    use std::io::Cursor;
+
+    #[test]
+    fn script_fixtures() {
+        let fixtures = Fixture::load_all("scripts").unwrap();
+        assert!(!fixtures.is_empty());
+
+        fixtures.iter().for_each(|fixture| {
+            let mut bytes = Cursor::new(fixture.bytes.as_slice());
+            let asm = StandardScripts::parse(&mut bytes).unwrap();
+
+            assert_eq!(fixture.expected["asm"], asm, "{}", fixture.name);
+
+            let mut bytes = Cursor::new(fixture.bytes.as_slice());
+            let core_asm = StandardScripts::parse_as(&mut bytes, AsmFormat::CoreAsm).unwrap();
+            assert_eq!(fixture.expected["core_asm"], core_asm, "{}", fixture.name);
+            assert_eq!(
+                fixture.expected["script_type"],
+                ScriptType::from_script(&fixture.bytes).to_string(),
+                "{}",
+                fixture.name
+            );
+        });
+    }
+
+    #[test]
+    fn transaction_fixtures() {
+        Fixture::load_all("transactions")
+            .unwrap()
+            .iter()
+            .for_each(|fixture| {
+                let tx = BtcTx::from_hex_bytes(&fixture.bytes).unwrap();
+                let expected = &fixture.expected;
+                assert_eq!(fixture.bytes, tx.to_bytes(), "{}", fixture.name);
+
+                assert_eq!(
+                    expected["version"],
+                    u32::from_le_bytes(tx.version().to_bytes())
+                );
+                assert_eq!(expected["locktime"], tx.locktime());
+
+                let expected_inputs = expected["inputs"].as_array().unwrap();
+                assert_eq!(expected_inputs.len(), tx.inputs().len());
+                tx.inputs()
+                    .iter()
+                    .zip(expected_inputs)
+                    .for_each(|(input, expected)| {
+                        assert_eq!(
+                            expected["previous_output_index"],
+                            input.previous_output_index()
+                        );
+                        assert_eq!(
+                            expected["spend_type"],
+                            format!("{:?}", input.inferred_spend_type())
+                        );
+                    });
+
+                let expected_outputs = expected["outputs"].as_array().unwrap();
+                assert_eq!(expected_outputs.len(), tx.outputs().len());
+                tx.outputs()
+                    .iter()
+                    .zip(expected_outputs)
+                    .for_each(|(output, expected)| {
+                        assert_eq!(expected["amount"], output.amount());
+                        assert_eq!(expected["script_type"], output.script_type().to_string());
+                        assert_eq!(
+                            expected["address"].as_str(),
+                            output.address(Network::Mainnet).as_deref()
+                        );
+                    });
+            });
+    }
+}
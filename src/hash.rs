@@ -0,0 +1,160 @@
+use std::{
+    fmt,
+    io::{self, ErrorKind},
+    str::FromStr,
+};
+
+/// A hash of `N` bytes formatted as lowercase hex. Transaction IDs and
+/// block hashes are stored in the byte order explorers display them in
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub struct Hash<const N: usize>([u8; N]);
+
+/// A RIPEMD160 of a SHA256 like a public key hash or a script hash
+pub type Hash160 = Hash<20>;
+
+/// A 32 byte hash like a SHA256, a SHA256d or a tagged hash
+pub type Hash256 = Hash<32>;
+
+impl<const N: usize> Hash<N> {
+    /// The length of the hash in bytes
+    pub const LEN: usize = N;
+
+    /// Instantiate a hash from its bytes
+    pub const fn new(bytes: [u8; N]) -> Self {
+        Self(bytes)
+    }
+
+    /// A hash with every byte set to zero like the previous
+    /// transaction ID of a coinbase input
+    pub const fn all_zeros() -> Self {
+        Self([0u8; N])
+    }
+
+    /// The bytes of the hash
+    pub const fn to_byte_array(&self) -> [u8; N] {
+        self.0
+    }
+
+    /// The bytes of the hash in reverse, converting between the byte order
+    /// of the wire format and the byte order explorers display
+    pub fn reversed(&self) -> Self {
+        let mut bytes = self.0;
+        bytes.reverse();
+
+        Self(bytes)
+    }
+
+    /// Parse a hash from a byte slice of exactly `N` bytes
+    pub fn from_slice(bytes: &[u8]) -> io::Result<Self> {
+        bytes.try_into().map(Self).map_err(|_| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Expected a hash of {} bytes but got {} bytes",
+                    N,
+                    bytes.len()
+                ),
+            )
+        })
+    }
+}
+
+impl<const N: usize> Default for Hash<N> {
+    fn default() -> Self {
+        Self::all_zeros()
+    }
+}
+
+impl<const N: usize> fmt::Display for Hash<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+impl<const N: usize> fmt::Debug for Hash<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Hash<{}>({})", N, self)
+    }
+}
+
+impl<const N: usize> FromStr for Hash<N> {
+    type Err = io::Error;
+
+    fn from_str(hex_str: &str) -> Result<Self, Self::Err> {
+        let bytes =
+            hex::decode(hex_str).map_err(|error| io::Error::new(ErrorKind::InvalidData, error))?;
+
+        Self::from_slice(&bytes)
+    }
+}
+
+impl<const N: usize> AsRef<[u8]> for Hash<N> {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<const N: usize> From<[u8; N]> for Hash<N> {
+    fn from(bytes: [u8; N]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl<const N: usize> From<Hash<N>> for [u8; N] {
+    fn from(hash: Hash<N>) -> Self {
+        hash.0
+    }
+}
+
+// Hashes are serialized as hex strings the same way they are displayed
+#[cfg(feature = "serde")]
+impl<const N: usize> serde::Serialize for Hash<N> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, const N: usize> serde::Deserialize<'de> for Hash<N> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex_str = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+
+        hex_str.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod hash_sanity_checks {
+    use crate::{Hash160, Hash256};
+    use hex_literal::hex;
+
+    #[test]
+    fn hex_formatting() {
+        let hash = Hash160::new(hex!("da1745e9b549bd0bfa1a569971c77eba30cd5a4b"));
+        assert_eq!("da1745e9b549bd0bfa1a569971c77eba30cd5a4b", hash.to_string());
+        assert_eq!(
+            hash,
+            "da1745e9b549bd0bfa1a569971c77eba30cd5a4b".parse().unwrap()
+        );
+        assert_eq!(
+            "Hash<20>(da1745e9b549bd0bfa1a569971c77eba30cd5a4b)",
+            format!("{:?}", hash)
+        );
+
+        assert!("da1745e9".parse::<Hash160>().is_err());
+        assert!("zz".parse::<Hash160>().is_err());
+
+        let mut bytes = [0u8; 32];
+        bytes[0] = 1;
+        assert_eq!(1, Hash256::new(bytes).reversed().to_byte_array()[31]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_hex() {
+        let hash = Hash160::new(hex!("da1745e9b549bd0bfa1a569971c77eba30cd5a4b"));
+        let json = serde_json::to_string(&hash).unwrap();
+        assert_eq!("\"da1745e9b549bd0bfa1a569971c77eba30cd5a4b\"", json);
+        assert_eq!(hash, serde_json::from_str::<Hash160>(&json).unwrap());
+    }
+}
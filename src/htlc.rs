@@ -0,0 +1,333 @@
+use crate::{Hash256, LockTime, Network, RelativeLockTime, Script, Witness, SEQUENCE_MAX_NON_RBF};
+use std::io::{self, ErrorKind};
+
+/// The length of the preimage enforced by the claim branch. Limiting the size
+/// stops the preimage from being so long that it is only valid on one chain
+pub const HTLC_PREIMAGE_LEN: u8 = 32;
+
+/// When the sender can take back the coins of a hash time-locked contract
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HtlcTimeout {
+    /// Refund after an absolute locktime using `OP_CHECKLOCKTIMEVERIFY`
+    Absolute(LockTime),
+    /// Refund after the funding output is old enough using `OP_CHECKSEQUENCEVERIFY`
+    Relative(RelativeLockTime),
+}
+
+/// An item of a witness stack that still has to be provided by a signer
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum WitnessTemplate {
+    /// A signature by the private key of this compressed public key
+    Signature([u8; 33]),
+    /// The preimage of the payment hash
+    Preimage,
+    /// Bytes that are already known like the witness script
+    Bytes(Vec<u8>),
+}
+
+/// What a spender has to provide to spend an output through one branch of a script
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Satisfaction {
+    /// The witness stack from the bottom element to the top element
+    pub witness: Vec<WitnessTemplate>,
+    /// The locktime the spending transaction must have, if any
+    pub locktime: Option<u32>,
+    /// The sequence number the spending input must have, if any
+    pub sequence: Option<u32>,
+    /// The minimum version of the spending transaction
+    pub min_version: u32,
+}
+
+impl Satisfaction {
+    /// Fill in the signature and preimage placeholders to get the witness
+    pub fn to_witness(&self, signature: &[u8], preimage: &[u8]) -> Witness {
+        let mut witness = Witness::new();
+
+        self.witness.iter().for_each(|template| {
+            match template {
+                WitnessTemplate::Signature(_) => witness.push(signature),
+                WitnessTemplate::Preimage => witness.push(preimage),
+                WitnessTemplate::Bytes(bytes) => witness.push(bytes),
+            };
+        });
+
+        witness
+    }
+}
+
+/// Constructors for hash-locked and hash time-locked contract scripts
+/// which are the building blocks of atomic swaps and payment channels
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Htlc {
+    payment_hash: Hash256,
+    receiver: [u8; 33],
+    sender: [u8; 33],
+    timeout: HtlcTimeout,
+}
+
+impl Htlc {
+    /// Instantiate a contract paying `receiver` if they reveal the preimage of
+    /// the SHA256 `payment_hash` or refunding `sender` after the `timeout`.
+    /// The public keys are compressed public keys.
+    pub fn new(
+        payment_hash: Hash256,
+        receiver: [u8; 33],
+        sender: [u8; 33],
+        timeout: HtlcTimeout,
+    ) -> io::Result<Self> {
+        match timeout {
+            HtlcTimeout::Absolute(LockTime::Unlocked)
+            | HtlcTimeout::Relative(RelativeLockTime::Disabled) => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "The refund branch of an HTLC needs a timeout",
+                ))
+            }
+            _ => (),
+        }
+
+        Ok(Self {
+            payment_hash,
+            receiver,
+            sender,
+            timeout,
+        })
+    }
+
+    /// A script that can be spent by anyone who knows the preimage
+    /// of the SHA256 `payment_hash`. `OP_SHA256 <hash> OP_EQUAL`
+    pub fn hash_lock(payment_hash: Hash256) -> Script {
+        let mut script = vec![OP_SHA256];
+        push_data(&mut script, payment_hash.as_ref());
+        script.push(OP_EQUAL);
+
+        Script::new(script)
+    }
+
+    /// A script that can be spent by `public_key` once the `timeout` has passed.
+    /// `<timeout> OP_CHECKLOCKTIMEVERIFY OP_DROP <key> OP_CHECKSIG` or the same with
+    /// `OP_CHECKSEQUENCEVERIFY` for relative timeouts
+    pub fn time_lock(public_key: [u8; 33], timeout: HtlcTimeout) -> Script {
+        let mut script = Vec::<u8>::new();
+        push_timeout(&mut script, timeout);
+        push_data(&mut script, &public_key);
+        script.push(OP_CHECKSIG);
+
+        Script::new(script)
+    }
+
+    /// The witness script
+    /// ```text
+    /// OP_IF
+    ///     OP_SIZE 32 OP_EQUALVERIFY OP_SHA256 <payment_hash> OP_EQUALVERIFY <receiver>
+    /// OP_ELSE
+    ///     <timeout> OP_CHECKLOCKTIMEVERIFY OP_DROP <sender>
+    /// OP_ENDIF
+    /// OP_CHECKSIG
+    /// ```
+    pub fn script(&self) -> Script {
+        let mut script = vec![OP_IF, OP_SIZE];
+        push_number(&mut script, HTLC_PREIMAGE_LEN as u32);
+        script.extend_from_slice(&[OP_EQUALVERIFY, OP_SHA256]);
+        push_data(&mut script, self.payment_hash.as_ref());
+        script.push(OP_EQUALVERIFY);
+        push_data(&mut script, &self.receiver);
+
+        script.push(OP_ELSE);
+        push_timeout(&mut script, self.timeout);
+        push_data(&mut script, &self.sender);
+
+        script.extend_from_slice(&[OP_ENDIF, OP_CHECKSIG]);
+
+        Script::new(script)
+    }
+
+    /// The P2WSH locking script of the contract
+    pub fn p2wsh(&self) -> Script {
+        self.script().to_p2wsh()
+    }
+
+    /// The P2WSH address of the contract
+    pub fn p2wsh_address(&self, network: Network) -> String {
+        self.script().p2wsh_address(network)
+    }
+
+    /// The P2SH-wrapped P2WSH address of the contract for wallets
+    /// that cannot pay to native segwit addresses
+    pub fn p2sh_p2wsh_address(&self, network: Network) -> String {
+        self.p2wsh().p2sh_address(network)
+    }
+
+    /// What the receiver must provide to claim the coins using the preimage
+    pub fn claim_satisfaction(&self) -> Satisfaction {
+        Satisfaction {
+            witness: vec![
+                WitnessTemplate::Signature(self.receiver),
+                WitnessTemplate::Preimage,
+                // Any non-empty top element takes the `OP_IF` branch
+                WitnessTemplate::Bytes(vec![0x01]),
+                WitnessTemplate::Bytes(self.script().as_bytes().to_vec()),
+            ],
+            locktime: None,
+            sequence: None,
+            min_version: 1,
+        }
+    }
+
+    /// What the sender must provide to take back the coins after the timeout
+    pub fn refund_satisfaction(&self) -> Satisfaction {
+        let (locktime, sequence, min_version) = match self.timeout {
+            // The locktime is ignored if the sequence number is final
+            HtlcTimeout::Absolute(locktime) => {
+                (Some(locktime.to_consensus()), Some(SEQUENCE_MAX_NON_RBF), 1)
+            }
+            // BIP68 relative locktimes only apply from version 2
+            HtlcTimeout::Relative(relative) => (None, relative.to_sequence(), 2),
+        };
+
+        Satisfaction {
+            witness: vec![
+                WitnessTemplate::Signature(self.sender),
+                // An empty top element takes the `OP_ELSE` branch
+                WitnessTemplate::Bytes(Vec::new()),
+                WitnessTemplate::Bytes(self.script().as_bytes().to_vec()),
+            ],
+            locktime,
+            sequence,
+            min_version,
+        }
+    }
+}
+
+const OP_0: u8 = 0x00;
+const OP_1: u8 = 0x51;
+const OP_IF: u8 = 0x63;
+const OP_ELSE: u8 = 0x67;
+const OP_ENDIF: u8 = 0x68;
+const OP_DROP: u8 = 0x75;
+const OP_SIZE: u8 = 0x82;
+const OP_EQUAL: u8 = 0x87;
+const OP_EQUALVERIFY: u8 = 0x88;
+const OP_SHA256: u8 = 0xa8;
+const OP_CHECKSIG: u8 = 0xac;
+const OP_CHECKLOCKTIMEVERIFY: u8 = 0xb1;
+const OP_CHECKSEQUENCEVERIFY: u8 = 0xb2;
+
+// Every data push in the contracts is at most 75 bytes
+// so the push opcode is the length of the data
+fn push_data(script: &mut Vec<u8>, data: &[u8]) {
+    script.push(data.len() as u8);
+    script.extend_from_slice(data);
+}
+
+// Numbers are pushed in the minimal encoding required by the `MINIMALDATA` policy.
+// Zero and 1 to 16 have their own opcodes, other numbers are little endian
+// with an extra zero byte when the highest bit is set since it is the sign bit
+fn push_number(script: &mut Vec<u8>, number: u32) {
+    match number {
+        0 => script.push(OP_0),
+        1..=16 => script.push(OP_1 + number as u8 - 1),
+        _ => {
+            let mut bytes = number.to_le_bytes().to_vec();
+            while bytes.last() == Some(&0) {
+                bytes.pop();
+            }
+            if bytes.last().is_some_and(|byte| byte & 0x80 != 0) {
+                bytes.push(0x00);
+            }
+
+            push_data(script, &bytes);
+        }
+    }
+}
+
+fn push_timeout(script: &mut Vec<u8>, timeout: HtlcTimeout) {
+    match timeout {
+        HtlcTimeout::Absolute(locktime) => {
+            push_number(script, locktime.to_consensus());
+            script.push(OP_CHECKLOCKTIMEVERIFY);
+        }
+        HtlcTimeout::Relative(relative) => {
+            push_number(script, relative.to_sequence().unwrap_or_default());
+            script.push(OP_CHECKSEQUENCEVERIFY);
+        }
+    }
+    script.push(OP_DROP);
+}
+
+#[cfg(test)]
+mod htlc_sanity_checks {
+    use crate::{
+        Hash256, Htlc, HtlcTimeout, LockTime, Network, RelativeLockTime, ScriptType,
+        WitnessTemplate, SEQUENCE_MAX_NON_RBF,
+    };
+    use hex_literal::hex;
+
+    const RECEIVER: [u8; 33] =
+        hex!("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798");
+    const SENDER: [u8; 33] =
+        hex!("02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5");
+
+    #[test]
+    fn htlc_scripts() {
+        let payment_hash = Hash256::new([7u8; 32]);
+
+        let hash_lock = Htlc::hash_lock(payment_hash);
+        assert_eq!(35, hash_lock.len());
+        assert_eq!(0xa8, hash_lock.as_bytes()[0]);
+
+        // 800000 is 0x0c3500 which is pushed as three little endian bytes
+        let time_lock = Htlc::time_lock(
+            SENDER,
+            HtlcTimeout::Absolute(LockTime::BlockHeight(800_000)),
+        );
+        assert_eq!(&hex!("0300350cb175"), &time_lock.as_bytes()[..6]);
+
+        let htlc = Htlc::new(
+            payment_hash,
+            RECEIVER,
+            SENDER,
+            HtlcTimeout::Relative(RelativeLockTime::Blocks(144)),
+        )
+        .unwrap();
+        let script = htlc.script();
+        // 144 is 0x90 which needs a zero byte since the sign bit is set
+        assert!(script
+            .as_bytes()
+            .windows(5)
+            .any(|window| window == hex!("029000b275")));
+        assert_eq!(ScriptType::P2WSH, htlc.p2wsh().script_type());
+        assert!(htlc.p2wsh_address(Network::Mainnet).starts_with("bc1q"));
+
+        assert!(Htlc::new(
+            payment_hash,
+            RECEIVER,
+            SENDER,
+            HtlcTimeout::Absolute(LockTime::Unlocked)
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn satisfactions() {
+        let htlc = Htlc::new(
+            Hash256::new([7u8; 32]),
+            RECEIVER,
+            SENDER,
+            HtlcTimeout::Absolute(LockTime::BlockHeight(800_000)),
+        )
+        .unwrap();
+
+        let claim = htlc.claim_satisfaction();
+        assert_eq!(WitnessTemplate::Signature(RECEIVER), claim.witness[0]);
+        let witness = claim.to_witness(&[0x30; 72], &[1u8; 32]);
+        assert_eq!(4, witness.len());
+        assert_eq!(Some([1u8; 32].as_slice()), witness.get(1));
+        assert_eq!(Some(htlc.script().as_bytes()), witness.last());
+
+        let refund = htlc.refund_satisfaction();
+        assert_eq!(Some(800_000), refund.locktime);
+        assert_eq!(Some(SEQUENCE_MAX_NON_RBF), refund.sequence);
+        assert_eq!(Some([].as_slice()), refund.to_witness(&[], &[]).get(1));
+    }
+}
@@ -0,0 +1,275 @@
+use crate::{Opcode, Script};
+use std::{
+    fmt,
+    io::{self, ErrorKind},
+};
+
+/// An opcode of a script with the data it pushes
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Instruction {
+    /// An opcode without data, including `OP_0` and `OP_1` to `OP_16`
+    Op {
+        /// The offset of the opcode in the script
+        offset: usize,
+        /// The opcode
+        opcode: Opcode,
+    },
+    /// An `OP_PUSHBYTES_*` or `OP_PUSHDATA1/2/4` opcode and its data
+    Push {
+        /// The offset of the opcode in the script
+        offset: usize,
+        /// The opcode encoding the push
+        opcode: Opcode,
+        /// The pushed data
+        data: Vec<u8>,
+    },
+}
+
+impl Instruction {
+    /// The offset of the opcode in the script
+    pub fn offset(&self) -> usize {
+        match self {
+            Self::Op { offset, .. } | Self::Push { offset, .. } => *offset,
+        }
+    }
+
+    /// The opcode of the instruction
+    pub fn opcode(&self) -> Opcode {
+        match self {
+            Self::Op { opcode, .. } | Self::Push { opcode, .. } => *opcode,
+        }
+    }
+
+    /// The pushed data, `None` for an opcode without data
+    pub fn push_data(&self) -> Option<&[u8]> {
+        match self {
+            Self::Op { .. } => None,
+            Self::Push { data, .. } => Some(data),
+        }
+    }
+
+    /// Serialize the instruction. Returns an error if the length of the data
+    /// does not fit the push opcode or an opcode which pushes data has none
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let opcode = self.opcode();
+        let byte = opcode.to_byte().ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} is not an opcode", opcode),
+            )
+        })?;
+
+        let data = match self {
+            Self::Op { .. } if Self::push_len_bytes(opcode).is_some() => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("{} must be followed by its data", opcode),
+                ))
+            }
+            Self::Op { .. } => return Ok(vec![byte]),
+            Self::Push { data, .. } => data,
+        };
+
+        let mut bytes = vec![byte];
+        match (opcode, Self::push_len_bytes(opcode)) {
+            (Opcode::PushBytes(byte_len), _) if byte_len as usize == data.len() => (),
+            (_, Some(len_bytes @ 1..)) if (data.len() as u64) < 1u64 << (8 * len_bytes) => {
+                bytes.extend_from_slice(&(data.len() as u32).to_le_bytes()[..len_bytes]);
+            }
+            (_, Some(_)) => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("{} cannot push {} bytes", opcode, data.len()),
+                ))
+            }
+            (_, None) => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("{} does not push data", opcode),
+                ))
+            }
+        }
+        bytes.extend_from_slice(data);
+
+        Ok(bytes)
+    }
+
+    // The number of bytes encoding the length of the data after a push
+    // opcode, 0 for `OP_PUSHBYTES_*`
+    fn push_len_bytes(opcode: Opcode) -> Option<usize> {
+        match opcode {
+            Opcode::PushBytes(_) => Some(0),
+            Opcode::OP_PUSHDATA1 => Some(1),
+            Opcode::OP_PUSHDATA2 => Some(2),
+            Opcode::OP_PUSHDATA4 => Some(4),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Op { opcode, .. } => write!(f, "{}", opcode),
+            Self::Push { opcode, data, .. } => write!(f, "{} {}", opcode, hex::encode(data)),
+        }
+    }
+}
+
+impl Script {
+    /// Split any script into its instructions without matching it against a
+    /// standard template, so non-standard scripts and custom redeem scripts
+    /// can be shown. Returns an `UnexpectedEof` error if a push goes past the
+    /// end of the script
+    pub fn disassemble(script: &[u8]) -> io::Result<Vec<Instruction>> {
+        let mut instructions = Vec::<Instruction>::new();
+        let mut position = 0usize;
+
+        while position < script.len() {
+            let offset = position;
+            let (byte, push_len) = Self::read_opcode(script, &mut position)?;
+            let opcode = Opcode::from_byte(byte);
+
+            instructions.push(match push_len {
+                Some(push_len) => Instruction::Push {
+                    offset,
+                    opcode,
+                    data: script[position - push_len..position].to_vec(),
+                },
+                None => Instruction::Op { offset, opcode },
+            });
+        }
+
+        Ok(instructions)
+    }
+
+    /// Serialize instructions into a script, checking that the data of every
+    /// push fits its opcode
+    pub fn assemble(instructions: &[Instruction]) -> io::Result<Script> {
+        let bytes = instructions
+            .iter()
+            .map(Instruction::to_bytes)
+            .collect::<io::Result<Vec<Vec<u8>>>>()?;
+
+        Ok(Script::new(bytes.concat()))
+    }
+
+    /// Parse asm in the `AsmFormat::Explicit` format written by
+    /// `Script::disassemble_asm()` and `ScriptBuilder::build()`, where every
+    /// push opcode is named and followed by its data in hex, back into a script
+    pub fn from_asm(asm: &str) -> io::Result<Script> {
+        let mut instructions = Vec::<Instruction>::new();
+        let mut offset = 0usize;
+        let mut tokens = asm.split_whitespace();
+
+        while let Some(token) = tokens.next() {
+            let opcode = token.parse::<Opcode>()?;
+
+            let instruction = match Instruction::push_len_bytes(opcode) {
+                Some(_) => {
+                    let data = tokens
+                        .next()
+                        .ok_or_else(|| {
+                            io::Error::new(
+                                ErrorKind::InvalidInput,
+                                format!("{} must be followed by its data", opcode),
+                            )
+                        })
+                        .and_then(|data| {
+                            hex::decode(data).map_err(|error| {
+                                io::Error::new(
+                                    ErrorKind::InvalidInput,
+                                    format!("The data of {} is not hex: {}", opcode, error),
+                                )
+                            })
+                        })?;
+
+                    Instruction::Push {
+                        offset,
+                        opcode,
+                        data,
+                    }
+                }
+                None => Instruction::Op { offset, opcode },
+            };
+
+            offset += instruction.to_bytes()?.len();
+            instructions.push(instruction);
+        }
+
+        Self::assemble(&instructions)
+    }
+
+    /// The instructions of the script written out like `AsmFormat::Explicit`
+    pub fn disassemble_asm(script: &[u8]) -> io::Result<String> {
+        Ok(Self::disassemble(script)?
+            .iter()
+            .map(Instruction::to_string)
+            .collect::<Vec<String>>()
+            .join(" "))
+    }
+}
+
+#[cfg(test)]
+mod instruction_sanity_checks {
+    use crate::{Instruction, Opcode, Script};
+    use hex_literal::hex;
+    use std::io::ErrorKind;
+
+    #[test]
+    fn disassemble_scripts() {
+        // OP_IF <2 bytes> OP_CHECKSEQUENCEVERIFY OP_DROP OP_ELSE OP_PUSHDATA1 <1 byte> OP_ENDIF
+        let script = hex!("6302a005b27567" "4c01aa" "68");
+        let instructions = Script::disassemble(&script).unwrap();
+        assert_eq!(7, instructions.len());
+        assert_eq!(
+            Instruction::Push {
+                offset: 1,
+                opcode: Opcode::PushBytes(2),
+                data: vec![0xa0, 0x05]
+            },
+            instructions[1]
+        );
+        assert_eq!(Opcode::OP_PUSHDATA1, instructions[5].opcode());
+        assert_eq!(Some(&[0xaa][..]), instructions[5].push_data());
+        assert_eq!(10, instructions[6].offset());
+        assert_eq!(
+            "OP_IF OP_PUSHBYTES_2 a005 OP_CHECKSEQUENCEVERIFY OP_DROP OP_ELSE OP_PUSHDATA1 aa OP_ENDIF",
+            Script::disassemble_asm(&script).unwrap()
+        );
+
+        let error = Script::disassemble(&hex!("4c05aa")).unwrap_err();
+        assert_eq!(ErrorKind::UnexpectedEof, error.kind());
+    }
+
+    #[test]
+    fn assemble_asm() {
+        let p2pkh = hex!("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac");
+        let asm = Script::disassemble_asm(&p2pkh).unwrap();
+        assert_eq!(p2pkh.as_slice(), Script::from_asm(&asm).unwrap().as_bytes());
+
+        // Non-minimal pushes keep their encoding
+        let script = hex!("4d0300aabbcc" "4e01000000dd" "b1");
+        let asm = Script::disassemble_asm(&script).unwrap();
+        assert_eq!(
+            "OP_PUSHDATA2 aabbcc OP_PUSHDATA4 dd OP_CHECKLOCKTIMEVERIFY",
+            asm
+        );
+        assert_eq!(
+            script.as_slice(),
+            Script::from_asm(&asm).unwrap().as_bytes()
+        );
+
+        assert!(Script::from_asm("OP_PUSHBYTES_2 aa").is_err());
+        assert!(Script::from_asm("OP_PUSHBYTES_1").is_err());
+        assert!(Script::from_asm("OP_PUSHBYTES_1 zz").is_err());
+        assert!(Script::from_asm(&format!("OP_PUSHDATA1 {}", "00".repeat(256))).is_err());
+        assert!(Script::from_asm("OP_DUP aa").is_err());
+        assert!(Script::assemble(&[Instruction::Push {
+            offset: 0,
+            opcode: Opcode::OP_DUP,
+            data: vec![0xaa]
+        }])
+        .is_err());
+    }
+}
@@ -0,0 +1,68 @@
+use std::io;
+
+// Run a phase of the crate inside a `tracing` span recording the number of
+// bytes processed, then emit an event with the time it took and the error if
+// it failed. Without the `tracing` feature the phase is only run
+pub(crate) fn traced<T>(
+    phase: &'static str,
+    bytes: usize,
+    run: impl FnOnce() -> io::Result<T>,
+) -> io::Result<T> {
+    #[cfg(feature = "tracing")]
+    {
+        let _span = tracing::debug_span!("btc_tx_hex", phase, bytes).entered();
+        let start = std::time::Instant::now();
+        let outcome = run();
+        record(phase, start, &outcome);
+
+        outcome
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = (phase, bytes);
+        run()
+    }
+}
+
+// The same as `traced()` for requests to remote services
+#[cfg(feature = "esplora")]
+pub(crate) async fn traced_request<T>(
+    phase: &'static str,
+    bytes: usize,
+    request: impl std::future::Future<Output = io::Result<T>>,
+) -> io::Result<T> {
+    #[cfg(feature = "tracing")]
+    {
+        use tracing::Instrument;
+
+        let span = tracing::debug_span!("btc_tx_hex", phase, bytes);
+        let start = std::time::Instant::now();
+        let outcome = request.instrument(span.clone()).await;
+        span.in_scope(|| record(phase, start, &outcome));
+
+        outcome
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = (phase, bytes);
+        request.await
+    }
+}
+
+#[cfg(feature = "tracing")]
+fn record<T>(phase: &'static str, start: std::time::Instant, outcome: &io::Result<T>) {
+    let elapsed_us = start.elapsed().as_micros() as u64;
+
+    match outcome {
+        Ok(_) => tracing::debug!(phase, elapsed_us, "finished"),
+        Err(error) => tracing::warn!(
+            phase,
+            elapsed_us,
+            error = %error,
+            kind = ?error.kind(),
+            "failed"
+        ),
+    }
+}
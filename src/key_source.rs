@@ -0,0 +1,158 @@
+use std::{
+    fmt,
+    io::{self, ErrorKind},
+    str::FromStr,
+};
+
+/// The offset added to the index of a hardened BIP32 derivation step
+pub const HARDENED_INDEX: u32 = 0x8000_0000;
+
+/// Where a key comes from, the fingerprint of the master key and the BIP32
+/// path deriving the key from it. It is written `[d34db33f/86'/0'/0']` in
+/// descriptors and stored with the keys of PSBT inputs and outputs
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct KeySource {
+    /// The first 4 bytes of the HASH160 of the master public key
+    pub fingerprint: [u8; 4],
+    /// The derivation steps, hardened steps include `HARDENED_INDEX`
+    pub derivation_path: Vec<u32>,
+}
+
+impl KeySource {
+    /// Parse the value of a PSBT key origin, the fingerprint followed by
+    /// every step of the path as a little endian u32
+    pub fn from_psbt_value(value: &[u8]) -> io::Result<Self> {
+        if value.len() < 4 || !(value.len() - 4).is_multiple_of(4) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("A key origin of {} bytes is invalid", value.len()),
+            ));
+        }
+
+        let mut fingerprint = [0u8; 4];
+        fingerprint.copy_from_slice(&value[..4]);
+
+        Ok(Self {
+            fingerprint,
+            derivation_path: value[4..]
+                .chunks(4)
+                .map(|step| u32::from_le_bytes([step[0], step[1], step[2], step[3]]))
+                .collect(),
+        })
+    }
+
+    /// The key origin as the value of a PSBT key
+    pub fn to_psbt_value(&self) -> Vec<u8> {
+        self.fingerprint
+            .iter()
+            .copied()
+            .chain(
+                self.derivation_path
+                    .iter()
+                    .flat_map(|step| step.to_le_bytes()),
+            )
+            .collect()
+    }
+
+    /// Whether this key is derived from `account`, having the same master key
+    /// and a path starting with the path of `account`
+    pub fn is_derived_from(&self, account: &KeySource) -> bool {
+        self.fingerprint == account.fingerprint
+            && self.derivation_path.starts_with(&account.derivation_path)
+    }
+}
+
+impl fmt::Display for KeySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}", hex::encode(self.fingerprint))?;
+        self.derivation_path.iter().try_for_each(|step| {
+            if step >= &HARDENED_INDEX {
+                write!(f, "/{}'", step - HARDENED_INDEX)
+            } else {
+                write!(f, "/{}", step)
+            }
+        })?;
+
+        write!(f, "]")
+    }
+}
+
+impl FromStr for KeySource {
+    type Err = io::Error;
+
+    /// Parse a key origin like `[d34db33f/86'/0'/0']`. Hardened steps may be
+    /// marked with `'` or `h` and the brackets are optional
+    fn from_str(origin: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("`{}` is not a key origin", origin),
+            )
+        };
+
+        let origin = origin
+            .strip_prefix('[')
+            .and_then(|origin| origin.strip_suffix(']'))
+            .unwrap_or(origin);
+        let mut steps = origin.split('/');
+
+        let mut fingerprint = [0u8; 4];
+        steps
+            .next()
+            .and_then(|hex_fingerprint| {
+                hex::decode_to_slice(hex_fingerprint, &mut fingerprint).ok()
+            })
+            .ok_or_else(invalid)?;
+
+        let derivation_path = steps
+            .map(|step| {
+                let (index, hardened) = match step.strip_suffix(['\'', 'h']) {
+                    Some(index) => (index, HARDENED_INDEX),
+                    None => (step, 0),
+                };
+
+                index
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|index| *index < HARDENED_INDEX)
+                    .map(|index| index + hardened)
+                    .ok_or_else(invalid)
+            })
+            .collect::<io::Result<Vec<u32>>>()?;
+
+        Ok(Self {
+            fingerprint,
+            derivation_path,
+        })
+    }
+}
+
+#[cfg(test)]
+mod key_source_sanity_checks {
+    use crate::{KeySource, HARDENED_INDEX};
+    use hex_literal::hex;
+    use std::str::FromStr;
+
+    #[test]
+    fn key_origins() {
+        let account = KeySource::from_str("[d34db33f/86'/0h/0']").unwrap();
+        assert_eq!(hex!("d34db33f"), account.fingerprint);
+        assert_eq!(
+            vec![86 + HARDENED_INDEX, HARDENED_INDEX, HARDENED_INDEX],
+            account.derivation_path
+        );
+        assert_eq!("[d34db33f/86'/0'/0']", account.to_string());
+
+        let key = KeySource::from_str("d34db33f/86'/0'/0'/1/7").unwrap();
+        assert!(key.is_derived_from(&account));
+        assert!(!account.is_derived_from(&key));
+        assert_eq!(
+            key,
+            KeySource::from_psbt_value(&key.to_psbt_value()).unwrap()
+        );
+
+        assert!(KeySource::from_str("[d34db3/0]").is_err());
+        assert!(KeySource::from_str("[d34db33f/2147483648]").is_err());
+        assert!(KeySource::from_psbt_value(&[0u8; 6]).is_err());
+    }
+}
@@ -0,0 +1,240 @@
+//! Parse, build, sign and analyze Bitcoin transactions and scripts from their
+//! raw bytes.
+//!
+//! The `tx`, `scripts`, `varint` and `version` modules hold the core of the
+//! parser. The public API is re-exported by name at the root of the crate and
+//! the most used types are gathered in the `prelude`:
+//!
+//! ```
+//! use btc_tx_hex::prelude::*;
+//!
+//! let raw_tx = include_str!("../fixtures/transactions/p2pkh_two_inputs.hex");
+//! let tx = BtcTx::from_hex_bytes(hex::decode(raw_tx.trim()).unwrap()).unwrap();
+//! assert_eq!(&TxVersion::One, tx.version());
+//! ```
+
+pub mod version;
+pub use version::TxVersion;
+
+pub mod varint;
+pub use varint::VarInt;
+
+pub mod tx;
+pub use tx::{BtcTx, OutPoint, PartialTx, TxInput, TxOutput};
+
+pub mod scripts;
+pub use scripts::{
+    AsmFormat, Opcode, ScriptBuilder, ScriptType, SpendType, StandardScripts, OP_TRUE_SCRIPT,
+    P2A_SCRIPT,
+};
+
+mod script;
+pub(crate) use script::tagged_hash;
+pub use script::{Script, TAPSCRIPT_LEAF_VERSION};
+
+mod instruction;
+pub use instruction::Instruction;
+
+mod script_metrics;
+pub use script_metrics::{OpcodeCategory, ScriptMetrics};
+
+mod opcode_policy;
+pub use opcode_policy::{OpcodeScan, ScannedOpcode};
+
+mod script_limits;
+pub use script_limits::{
+    ScriptErrorCode, MAX_OPS_PER_SCRIPT, MAX_SCRIPT_ELEMENT_SIZE, MAX_SCRIPT_SIZE, MAX_STACK_SIZE,
+};
+
+mod amount;
+pub use amount::{AmountFormat, Denomination, MAX_MONEY};
+
+mod address;
+pub use address::{Address, Network};
+
+mod hash;
+pub use hash::{Hash, Hash160, Hash256};
+
+mod txid;
+pub use txid::{Ntxid, Txid, Wtxid};
+
+mod checksum;
+pub use checksum::{Checksum, CHECKSUM_LEN};
+
+mod error;
+pub use error::{MultisigError, ScriptError, MAX_P2MS_KEYS, MAX_STANDARD_P2MS_KEYS};
+
+mod confirmed;
+pub use confirmed::ConfirmedTx;
+
+mod block;
+pub use block::{Block, BlockHeader, MerkleProof, BLOCK_HEADER_LEN};
+
+mod coinbase;
+pub use coinbase::{CoinbaseInfo, KNOWN_POOL_TAGS};
+
+mod payment_proof;
+pub use payment_proof::PaymentProof;
+
+mod sighash;
+pub use sighash::{SigHashCache, SigHashPreimage, SigHashType};
+
+mod locktime;
+pub use locktime::{
+    LockTime, RelativeLockTime, LOCKTIME_THRESHOLD, SEQUENCE_FINAL, SEQUENCE_MAX_NON_RBF,
+};
+
+mod scheduler;
+pub(crate) use scheduler::earliest_tip;
+pub use scheduler::{BlockTime, BroadcastScheduler, ReadyTx};
+
+mod presigned;
+pub use presigned::{PresignedStatus, PresignedWatcher, SpendConflict, StatusChange};
+
+mod witness;
+pub use witness::{Witness, WitnessIter};
+
+mod htlc;
+pub use htlc::{Htlc, HtlcTimeout, Satisfaction, WitnessTemplate, HTLC_PREIMAGE_LEN};
+
+mod satisfy;
+pub use satisfy::InputSatisfaction;
+
+mod signing;
+#[cfg(feature = "rust-bitcoin-compat")]
+pub use signing::EcdsaSigner;
+pub use signing::{InputSigners, SigningResults, SigningSession};
+
+mod preimage;
+pub use preimage::{PreimageHash, RevealedPreimage};
+
+mod lint;
+pub use lint::{KeyLocation, PublicKeyEncoding, TxLint};
+
+mod policy;
+pub use policy::{
+    check_truc, DatacarrierPolicy, DatacarrierViolation, InputViolation, TrucViolation,
+    DEFAULT_BYTES_PER_SIGOP, DUST_RELAY_FEE_RATE, MAX_P2SH_SIGOPS, TRUC_CHILD_MAX_VSIZE,
+    TRUC_MAX_VSIZE, TRUC_VERSION,
+};
+
+mod softfork;
+pub use softfork::{Anachronism, SoftFork};
+
+mod diff;
+pub use diff::{FieldDiff, TxDiff};
+
+mod descriptor;
+pub use descriptor::Descriptor;
+
+mod report;
+pub use report::{
+    core_script_type, decode_to_report, esplora_json, esplora_script_type, InputReport,
+    OutputReport, ScriptReport, SegwitReport, TxReport,
+};
+
+mod mempool;
+pub use mempool::{
+    FeeBucket, MempoolEntry, MempoolSnapshot, SnapshotDiff, FEE_HISTOGRAM_BUCKETS, MAX_BLOCK_VSIZE,
+};
+
+mod orphan;
+pub use orphan::{Orphan, OrphanPool, OrphanStatus, DEFAULT_MAX_ORPHANS, DEFAULT_ORPHAN_EXPIRY};
+
+mod tracker;
+pub use tracker::{AddressTracker, ChainEvent, TrackerEvent, DEFAULT_UNDO_DEPTH};
+
+mod consolidation;
+pub use consolidation::{
+    input_weight, low_r_input_weight, ConsolidationPlan, DustPlanner, FeeForecast, WalletUtxo,
+    CONSOLIDATION_SEQUENCE,
+};
+
+mod weighted_utxo;
+pub use weighted_utxo::{WeightedUtxo, EMPTY_INPUT_WEIGHT};
+
+mod block_template;
+pub use block_template::{
+    BlockTemplateBuilder, MAX_BLOCK_SIGOPS_COST, MAX_BLOCK_WEIGHT, VERSIONBITS_TOP_BITS,
+};
+
+mod fee_bump;
+pub use fee_bump::INCREMENTAL_RELAY_FEE_RATE;
+
+mod key_source;
+pub use key_source::{KeySource, HARDENED_INDEX};
+
+mod psbt;
+pub use psbt::{Psbt, PsbtRole, MAX_PSBT_SIZE, PSBT_MAGIC};
+
+mod storage;
+pub use storage::{MemoryTxStore, StoredTx, TxStore};
+
+// Passes over parsed transactions like the signature report
+pub mod analysis;
+
+// Conversions to and from the `bitcoin` crate types
+#[cfg(feature = "rust-bitcoin-compat")]
+mod compat;
+
+// Taproot outputs of BIP86 single key wallets
+#[cfg(feature = "rust-bitcoin-compat")]
+mod bip86;
+#[cfg(feature = "rust-bitcoin-compat")]
+pub use bip86::{Bip86, Bip86Output};
+
+// Taproot script trees and the witnesses of script path spends
+#[cfg(feature = "rust-bitcoin-compat")]
+mod taproot;
+#[cfg(feature = "rust-bitcoin-compat")]
+pub use taproot::{ScriptPathSpend, TapTree, TAPROOT_CONTROL_MAX_NODE_COUNT};
+
+// Spans and events of the `tracing` feature
+mod instrument;
+
+// Async client for the Esplora REST API
+#[cfg(feature = "esplora")]
+mod esplora;
+#[cfg(feature = "esplora")]
+pub use esplora::{EsploraClient, OutSpend, TxStatus, Utxo, BLOCKSTREAM_URL, MEMPOOL_SPACE_URL};
+
+// Broadcasting through Bitcoin Core, Esplora and P2P peers with fallback
+#[cfg(feature = "broadcast")]
+mod broadcast;
+#[cfg(feature = "broadcast")]
+pub use broadcast::{
+    BroadcastAttempt, BroadcastFuture, BroadcastReport, Broadcaster, CoreRpcBroadcaster,
+    FallbackBroadcaster, P2pBroadcaster, MAX_PROTOCOL_MESSAGE_LEN, PROTOCOL_VERSION,
+};
+
+// Blocking HTTP server exposing the decoders
+#[cfg(feature = "server")]
+mod server;
+#[cfg(feature = "server")]
+pub use server::DecodeServer;
+
+// Blocking subscriber to the raw transactions and blocks bitcoind publishes over ZMQ
+#[cfg(feature = "zmq")]
+mod zmq;
+#[cfg(feature = "zmq")]
+pub use zmq::{ZmqMessage, ZmqSubscriber, ZmqTopic};
+
+// Animated QR code parts of PSBTs and transactions for air-gapped signers
+#[cfg(feature = "ur")]
+mod ur;
+#[cfg(feature = "ur")]
+pub use ur::{UrDecoder, UrEncoder, UrPayload, MIN_UR_FRAGMENT_LEN};
+
+// Loads the raw bytes and expected decodings in the `fixtures` directory
+#[cfg(test)]
+mod fixtures;
+
+/// The types needed to parse and build transactions and scripts,
+/// `use btc_tx_hex::prelude::*` brings them into scope
+pub mod prelude {
+    pub use crate::{
+        Address, BtcTx, Hash160, Hash256, LockTime, Network, OutPoint, RelativeLockTime, Script,
+        ScriptBuilder, ScriptType, StandardScripts, TxInput, TxOutput, TxVersion, Txid, VarInt,
+        Wtxid,
+    };
+}
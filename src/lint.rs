@@ -0,0 +1,364 @@
+use crate::{
+    analysis::{SignatureAnomaly, SignatureReport},
+    BtcTx, ScriptType, SpendType, StandardScripts, VarInt,
+};
+use std::io::{self, Cursor, Read};
+
+/// A non-canonical or unusual part of a transaction which is valid by consensus
+/// but may not be relayed or may point to a bug in the software that built it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TxLint {
+    /// A VarInt at this byte offset is longer than needed for its value
+    NonMinimalVarInt { offset: u64 },
+    /// A witness element is an uncompressed public key which segwit policy rejects
+    UncompressedKeyInWitness { input_index: usize },
+    /// A signature has an `s` value above half the curve order
+    HighS { input_index: usize },
+    /// A signature has a sighash byte that is not one of the six standard flags
+    NonStandardSigHash { input_index: usize, byte: u8 },
+    /// An input whose scriptSig does not spend a segwit output has a witness
+    UnexpectedWitness { input_index: usize },
+    /// An output which can be spent carries no value. Pay-to-anchor outputs
+    /// are left out since ephemeral anchors are expected to be empty
+    ZeroValueOutput { output_index: usize },
+    /// A public key with the `0x06` or `0x07` prefix of the hybrid encoding,
+    /// which is nonstandard and usually written by broken signing software
+    HybridPublicKey { location: KeyLocation },
+    /// Data in the place of a public key which is neither a compressed,
+    /// uncompressed nor hybrid public key
+    MalformedPublicKey { location: KeyLocation },
+}
+
+/// Where a public key checked by `BtcTx::lint()` was found. Keys are looked
+/// for after the signature of scriptSigs and witnesses with a signature and a
+/// key, and in P2PK and bare multisig locking scripts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum KeyLocation {
+    /// The scriptSig of the input
+    SignatureScript { input_index: usize },
+    /// The witness of the input
+    Witness { input_index: usize },
+    /// The locking script of the output
+    LockingScript { output_index: usize },
+}
+
+/// How the bytes in the place of a public key are encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PublicKeyEncoding {
+    /// 33 bytes starting with `0x02` or `0x03`
+    Compressed,
+    /// 65 bytes starting with `0x04`
+    Uncompressed,
+    /// 65 bytes starting with `0x06` or `0x07`, valid by consensus in legacy
+    /// scripts but nonstandard
+    Hybrid,
+    /// Any other bytes
+    Malformed,
+}
+
+impl PublicKeyEncoding {
+    /// The encoding of the bytes of a public key
+    pub fn from_bytes(public_key: &[u8]) -> Self {
+        match (public_key.len(), public_key.first()) {
+            (33, Some(0x02 | 0x03)) => Self::Compressed,
+            (65, Some(0x04)) => Self::Uncompressed,
+            (65, Some(0x06 | 0x07)) => Self::Hybrid,
+            _ => Self::Malformed,
+        }
+    }
+
+    /// The lint of a key with this encoding found at `location`
+    pub fn lint(&self, location: KeyLocation) -> Option<TxLint> {
+        match self {
+            Self::Compressed | Self::Uncompressed => None,
+            Self::Hybrid => Some(TxLint::HybridPublicKey { location }),
+            Self::Malformed => Some(TxLint::MalformedPublicKey { location }),
+        }
+    }
+}
+
+impl BtcTx {
+    /// Check the transaction for non-canonical or unusual parts. The VarInts
+    /// are no longer known once a transaction is parsed, use `BtcTx::lint_bytes()`
+    /// to include them
+    pub fn lint(&self) -> Vec<TxLint> {
+        let mut lints = Vec::<TxLint>::new();
+
+        self.inputs()
+            .iter()
+            .enumerate()
+            .filter(|(_, input)| !input.witness().is_empty())
+            .for_each(|(input_index, input)| {
+                if input
+                    .witness()
+                    .iter()
+                    .any(|element| element.len() == 65 && element[0] == 0x04)
+                {
+                    lints.push(TxLint::UncompressedKeyInWitness { input_index });
+                }
+
+                let expects_witness = match input.inferred_spend_type() {
+                    // The witness reserved value of the witness commitment
+                    SpendType::Coinbase => {
+                        input.witness().len() == 1
+                            && input
+                                .witness()
+                                .get(0)
+                                .is_some_and(|value| value.len() == 32)
+                    }
+                    SpendType::P2PK | SpendType::P2PKH | SpendType::P2MS | SpendType::P2SH => false,
+                    _ => true,
+                };
+                if !expects_witness {
+                    lints.push(TxLint::UnexpectedWitness { input_index });
+                }
+            });
+
+        self.inputs()
+            .iter()
+            .enumerate()
+            .for_each(|(input_index, input)| {
+                let pushes =
+                    StandardScripts::read_pushes(input.signature_script()).unwrap_or_default();
+                let pushes = pushes.iter().map(Vec::as_slice).collect::<Vec<&[u8]>>();
+                lints.extend(signed_key(&pushes).and_then(|key| {
+                    PublicKeyEncoding::from_bytes(key)
+                        .lint(KeyLocation::SignatureScript { input_index })
+                }));
+
+                let elements = input.witness().iter().collect::<Vec<&[u8]>>();
+                lints.extend(signed_key(&elements).and_then(|key| {
+                    PublicKeyEncoding::from_bytes(key).lint(KeyLocation::Witness { input_index })
+                }));
+            });
+
+        self.outputs()
+            .iter()
+            .enumerate()
+            .for_each(|(output_index, output)| {
+                lints.extend(
+                    locking_script_keys(output.locking_script())
+                        .iter()
+                        .filter_map(|key| {
+                            PublicKeyEncoding::from_bytes(key)
+                                .lint(KeyLocation::LockingScript { output_index })
+                        }),
+                )
+            });
+
+        SignatureReport::from_tx(self)
+            .anomalies()
+            .iter()
+            .for_each(|anomaly| match anomaly {
+                SignatureAnomaly::HighS { input_index } => lints.push(TxLint::HighS {
+                    input_index: *input_index,
+                }),
+                SignatureAnomaly::NonStandardSigHash { input_index, byte } => {
+                    lints.push(TxLint::NonStandardSigHash {
+                        input_index: *input_index,
+                        byte: *byte,
+                    })
+                }
+                _ => (),
+            });
+
+        self.outputs()
+            .iter()
+            .enumerate()
+            .filter(|(_, output)| {
+                // Scripts starting with `OP_RETURN` can never be spent
+                output.amount() == 0
+                    && output.locking_script().first() != Some(&0x6a)
+                    && output.script_type() != ScriptType::P2A
+            })
+            .for_each(|(output_index, _)| lints.push(TxLint::ZeroValueOutput { output_index }));
+
+        lints
+    }
+
+    /// Parse the raw transaction and check it like `BtcTx::lint()`
+    /// including whether every VarInt is minimally encoded
+    pub fn lint_bytes(bytes: impl AsRef<[u8]>) -> io::Result<Vec<TxLint>> {
+        let tx = BtcTx::from_hex_bytes(bytes.as_ref())?;
+
+        let mut lints = non_minimal_varints(bytes.as_ref())?
+            .into_iter()
+            .map(|offset| TxLint::NonMinimalVarInt { offset })
+            .collect::<Vec<TxLint>>();
+        lints.extend(tx.lint());
+
+        Ok(lints)
+    }
+}
+
+// The key of a stack holding a DER signature followed by a key like the
+// scriptSig of P2PKH and the witness of P2WPKH
+fn signed_key<'a>(stack: &[&'a [u8]]) -> Option<&'a [u8]> {
+    match stack {
+        [signature, key] if signature.first() == Some(&0x30) => Some(key),
+        _ => None,
+    }
+}
+
+// The keys of a `<key> OP_CHECKSIG` or `OP_m <keys> OP_n OP_CHECKMULTISIG`
+// locking script whatever their encoding
+fn locking_script_keys(locking_script: &[u8]) -> Vec<Vec<u8>> {
+    match locking_script {
+        [pushes @ .., 0xac] => StandardScripts::read_pushes(pushes)
+            .ok()
+            .filter(|keys| keys.len() == 1)
+            .unwrap_or_default(),
+        [0x51..=0x60, pushes @ .., 0x51..=0x60, 0xae] => {
+            StandardScripts::read_pushes(pushes).unwrap_or_default()
+        }
+        _ => Vec::new(),
+    }
+}
+
+// Walk the counts and script lengths of a transaction without witnesses
+// and return the offsets of the VarInts that are not minimally encoded
+fn non_minimal_varints(raw_tx: &[u8]) -> io::Result<Vec<u64>> {
+    let mut bytes = Cursor::new(raw_tx);
+    let mut offsets = Vec::<u64>::new();
+
+    let mut varint = |bytes: &mut Cursor<&[u8]>| -> io::Result<usize> {
+        let offset = bytes.position();
+        let mut prefix = [0u8];
+        bytes.read_exact(&mut prefix)?;
+        let value = VarInt::integer(VarInt::parse(prefix[0]), bytes)?;
+
+        if VarInt::encoded_len(value as u64) as u64 != bytes.position() - offset {
+            offsets.push(offset);
+        }
+
+        Ok(value)
+    };
+    let skip =
+        |bytes: &mut Cursor<&[u8]>, len: usize| bytes.set_position(bytes.position() + len as u64);
+
+    // The version
+    skip(&mut bytes, 4);
+    for _ in 0..varint(&mut bytes)? {
+        // The outpoint, the scriptSig and the sequence number
+        skip(&mut bytes, 36);
+        let script_len = varint(&mut bytes)?;
+        skip(&mut bytes, script_len + 4);
+    }
+    for _ in 0..varint(&mut bytes)? {
+        // The amount and the locking script
+        skip(&mut bytes, 8);
+        let script_len = varint(&mut bytes)?;
+        skip(&mut bytes, script_len);
+    }
+
+    Ok(offsets)
+}
+
+#[cfg(test)]
+mod lint_sanity_checks {
+    use crate::{
+        BtcTx, Hash256, KeyLocation, PublicKeyEncoding, TxInput, TxLint, TxOutput, TxVersion, Txid,
+        Witness,
+    };
+    use hex_literal::hex;
+
+    #[test]
+    fn lints() {
+        let p2pkh_script_sig = hex!("4730440220199a6aa56306cebcdacd1eba26b55eaf6f92eb46eb90d1b7e7724bacbe1d19140220101c0d46e033361c60536b6989efdd6fa692265fcda164676e2f49885871038a0121039ac8bac8f6d916b8a85b458e087e0cd07e6a76a6bfdde9bb766b17086d9a5c8a");
+        let uncompressed_key = [&[0x04][..], &[0x11; 64]].concat();
+
+        let tx = BtcTx::new(
+            TxVersion::Two,
+            vec![
+                TxInput::new(
+                    Txid::new(Hash256::new([1u8; 32])),
+                    0,
+                    p2pkh_script_sig.to_vec(),
+                    0,
+                )
+                .with_witness(Witness::from_slice(&[&[0x01]])),
+                TxInput::new(Txid::new(Hash256::new([1u8; 32])), 1, Vec::new(), 0)
+                    .with_witness(Witness::from_slice(&[&[0x30; 72], &uncompressed_key])),
+            ],
+            vec![
+                TxOutput::new(0, vec![0x6a]),
+                TxOutput::new(0, vec![0x51]),
+                TxOutput::pay_to_anchor(0),
+            ],
+            0,
+        );
+        assert_eq!(
+            vec![
+                TxLint::UnexpectedWitness { input_index: 0 },
+                TxLint::UncompressedKeyInWitness { input_index: 1 },
+                TxLint::ZeroValueOutput { output_index: 1 },
+            ],
+            tx.lint()
+        );
+
+        // The input count is encoded with the 0xfd prefix
+        let raw_tx = [
+            &hex!("01000000fd0100")[..],
+            &[0x11; 36],
+            &hex!("00ffffffff01e803000000000000015100000000"),
+        ]
+        .concat();
+        assert_eq!(
+            vec![TxLint::NonMinimalVarInt { offset: 4 }],
+            BtcTx::lint_bytes(raw_tx).unwrap()
+        );
+    }
+
+    #[test]
+    fn public_key_encodings() {
+        let signature = [&[0x30; 70][..], &[0x01]].concat();
+        let hybrid_key = [&[0x06][..], &[0x11; 64]].concat();
+        let p2pk = [&[0x41][..], &[0x07], &[0x11; 64], &[0xac]].concat();
+        let multisig = [
+            &[0x51, 0x21, 0x02][..],
+            &[0x11; 32],
+            &[0x21, 0x05],
+            &[0x11; 32],
+            &[0x52, 0xae],
+        ]
+        .concat();
+
+        let tx = BtcTx::new(
+            TxVersion::Two,
+            vec![
+                TxInput::new(
+                    Txid::new(Hash256::new([1u8; 32])),
+                    0,
+                    [&[0x47][..], &signature, &[0x41], &hybrid_key].concat(),
+                    0,
+                ),
+                TxInput::new(Txid::new(Hash256::new([1u8; 32])), 1, Vec::new(), 0)
+                    .with_witness(Witness::from_slice(&[&signature, &[0x02; 20]])),
+            ],
+            vec![TxOutput::new(1_000, p2pk), TxOutput::new(1_000, multisig)],
+            0,
+        );
+
+        assert_eq!(
+            vec![
+                TxLint::HybridPublicKey {
+                    location: KeyLocation::SignatureScript { input_index: 0 }
+                },
+                TxLint::MalformedPublicKey {
+                    location: KeyLocation::Witness { input_index: 1 }
+                },
+                TxLint::HybridPublicKey {
+                    location: KeyLocation::LockingScript { output_index: 0 }
+                },
+                TxLint::MalformedPublicKey {
+                    location: KeyLocation::LockingScript { output_index: 1 }
+                },
+            ],
+            tx.lint()
+        );
+        assert_eq!(
+            PublicKeyEncoding::Compressed,
+            PublicKeyEncoding::from_bytes(&[&[0x03][..], &[0x11; 32]].concat())
+        );
+    }
+}
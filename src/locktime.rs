@@ -0,0 +1,156 @@
+use std::fmt;
+
+/// Locktime values below this are block heights and values at or
+/// above it are UNIX timestamps
+pub const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+
+/// The highest sequence number, it disables the locktime of the transaction
+pub const SEQUENCE_FINAL: u32 = 0xffffffff;
+
+/// BIP125 replacement is signaled by any input with a sequence number below this
+pub const SEQUENCE_MAX_NON_RBF: u32 = 0xfffffffe;
+
+/// The interpretation of the locktime field of a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LockTime {
+    /// A locktime of zero, the transaction can be mined in any block
+    Unlocked,
+    /// The transaction can only be mined in a block above this height
+    BlockHeight(u32),
+    /// The transaction can only be mined once the median time past
+    /// of the previous blocks is above this UNIX timestamp
+    Timestamp(u32),
+}
+
+impl LockTime {
+    /// Interpret the locktime field of a transaction
+    pub const fn from_consensus(locktime: u32) -> Self {
+        match locktime {
+            0 => Self::Unlocked,
+            height if height < LOCKTIME_THRESHOLD => Self::BlockHeight(height),
+            timestamp => Self::Timestamp(timestamp),
+        }
+    }
+
+    /// Convert back to the locktime field of a transaction
+    pub const fn to_consensus(&self) -> u32 {
+        match self {
+            Self::Unlocked => 0,
+            Self::BlockHeight(value) | Self::Timestamp(value) => *value,
+        }
+    }
+}
+
+impl fmt::Display for LockTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unlocked => write!(f, "Unlocked"),
+            Self::BlockHeight(height) => write!(f, "Block height {}", height),
+            Self::Timestamp(timestamp) => write!(f, "UNIX timestamp {}", timestamp),
+        }
+    }
+}
+
+/// The BIP68 relative locktime encoded in the sequence number of an input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RelativeLockTime {
+    /// The disable flag is set or the transaction version is below 2
+    Disabled,
+    /// The output being spent must have this many confirmations
+    Blocks(u16),
+    /// The output being spent must be this many seconds old.
+    /// This is always a multiple of 512 seconds
+    Seconds(u32),
+}
+
+impl RelativeLockTime {
+    /// Bit 31 disables the relative locktime
+    pub const DISABLE_FLAG: u32 = 1 << 31;
+    /// Bit 22 selects units of 512 seconds instead of blocks
+    pub const TYPE_FLAG: u32 = 1 << 22;
+    /// The value is in the lower 16 bits
+    pub const VALUE_MASK: u32 = 0x0000ffff;
+    /// Time based relative locktimes are in units of 512 seconds
+    pub const SECONDS_PER_UNIT: u32 = 512;
+
+    /// Interpret the sequence number of an input of a transaction with `version`
+    pub const fn from_sequence(sequence_number: u32, version: u32) -> Self {
+        if version < 2 || sequence_number & Self::DISABLE_FLAG != 0 {
+            return Self::Disabled;
+        }
+
+        let value = sequence_number & Self::VALUE_MASK;
+
+        if sequence_number & Self::TYPE_FLAG != 0 {
+            Self::Seconds(value * Self::SECONDS_PER_UNIT)
+        } else {
+            Self::Blocks(value as u16)
+        }
+    }
+
+    /// Encode as the sequence number of an input. Returns `None` if disabled.
+    /// Seconds are rounded down to a multiple of 512 seconds
+    pub const fn to_sequence(&self) -> Option<u32> {
+        match self {
+            Self::Disabled => None,
+            Self::Blocks(blocks) => Some(*blocks as u32),
+            Self::Seconds(seconds) => {
+                Some(Self::TYPE_FLAG | ((*seconds / Self::SECONDS_PER_UNIT) & Self::VALUE_MASK))
+            }
+        }
+    }
+}
+
+impl fmt::Display for RelativeLockTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Disabled => write!(f, "Disabled"),
+            Self::Blocks(blocks) => write!(f, "{} blocks", blocks),
+            Self::Seconds(seconds) => write!(f, "{} seconds", seconds),
+        }
+    }
+}
+
+#[cfg(test)]
+mod locktime_sanity_checks {
+    use crate::{LockTime, RelativeLockTime, LOCKTIME_THRESHOLD};
+
+    #[test]
+    fn locktime() {
+        assert_eq!(LockTime::Unlocked, LockTime::from_consensus(0));
+        assert_eq!(
+            LockTime::BlockHeight(800_000),
+            LockTime::from_consensus(800_000)
+        );
+        assert_eq!(
+            LockTime::Timestamp(LOCKTIME_THRESHOLD),
+            LockTime::from_consensus(LOCKTIME_THRESHOLD)
+        );
+        assert_eq!(1690168629, LockTime::Timestamp(1690168629).to_consensus());
+    }
+
+    #[test]
+    fn relative_locktime() {
+        assert_eq!(
+            RelativeLockTime::Disabled,
+            RelativeLockTime::from_sequence(0xffffffff, 2)
+        );
+        assert_eq!(
+            RelativeLockTime::Disabled,
+            RelativeLockTime::from_sequence(144, 1)
+        );
+        assert_eq!(
+            RelativeLockTime::Blocks(144),
+            RelativeLockTime::from_sequence(144, 2)
+        );
+        assert_eq!(
+            RelativeLockTime::Seconds(1024),
+            RelativeLockTime::from_sequence(RelativeLockTime::TYPE_FLAG | 2, 2)
+        );
+        assert_eq!(
+            Some(RelativeLockTime::TYPE_FLAG | 2),
+            RelativeLockTime::Seconds(1024).to_sequence()
+        );
+        assert_eq!(None, RelativeLockTime::Disabled.to_sequence());
+    }
+}
@@ -1,76 +1,136 @@
-mod version;
-use std::io::Cursor;
-
-use hex_literal::hex;
-pub use version::*;
-
-mod varint;
-pub use varint::*;
-
-mod tx;
-pub use tx::*;
-
-mod scripts;
-pub use scripts::*;
+#[cfg(feature = "server")]
+use btc_tx_hex::DecodeServer;
+use btc_tx_hex::{Address, BtcTx, Network, TxDiff, TxOutput};
 
 fn main() {
-    // TODO - After parsing standard script, also add this transaction
-    /*let raw_tx = hex!("010000000269adb42422fb021f38da0ebe12a8d2a14c0fe484bcb0b7cb365841871f2d5e24000000006a4730440220199a6aa56306cebcdacd1eba26b55eaf6f92eb46eb90d1b7e7724bacbe1d19140220101c0d46e033361c60536b6989efdd6fa692265fcda164676e2f49885871038a0121039ac8bac8f6d916b8a85b458e087e0cd07e6a76a6bfdde9bb766b17086d9a5c8affffffff69adb42422fb021f38da0ebe12a8d2a14c0fe484bcb0b7cb365841871f2d5e24010000006b48304502210084ec4323ed07da4af6462091b4676250c377527330191a3ff3f559a88beae2e2022077251392ec2f52327cb7296be89cc001516e4039badd2ad7bbc950c4c1b6d7cc012103b9b554e25022c2ae549b0c30c18df0a8e0495223f627ae38df0992efb4779475ffffffff0118730100000000001976a9140ce17649c1306c291ca9e587f8793b5b06563cea88ac00000000");
-    let tx_decode = BtcTx::from_hex_bytes(raw_tx);
+    // `cargo run -- diff <hex1> <hex2>` prints the fields where two transactions differ
+    let args = std::env::args().collect::<Vec<String>>();
+    if let [_, command, left, right] = args.as_slice() {
+        if command == "diff" {
+            let diff = hex::decode(left.trim())
+                .and_then(|left| Ok((left, hex::decode(right.trim())?)))
+                .map_err(|error| format!("The transaction is not valid hex: {}", error))
+                .and_then(|(left, right)| {
+                    TxDiff::new(&left, &right).map_err(|error| error.to_string())
+                });
+            match diff {
+                Ok(diff) => print!("{}", diff.to_colored_string()),
+                Err(error) => exit_with(&error),
+            }
+            return;
+        }
+    }
 
-    dbg!(tx_decode.unwrap());*/
+    // `cargo run -- edit <hex> [flags]` prints the transaction with the edits applied
+    if let [_, command, raw_tx, flags @ ..] = args.as_slice() {
+        if command == "edit" {
+            match edit(raw_tx, flags) {
+                Ok(tx) => println!("{}", tx.to_hex()),
+                Err(error) => exit_with(&error),
+            }
+            return;
+        }
+    }
 
-    let p2pk_bytes = hex!("410000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000ac");
-    let mut p2pk = Cursor::new(p2pk_bytes.as_ref());
-    let outcome = StandardScripts::parse(&mut p2pk);
-    assert!(outcome.is_ok());
-    dbg!(&outcome.unwrap());
+    // `cargo run --features server -- 127.0.0.1:8080` serves the decoders over HTTP
+    #[cfg(feature = "server")]
+    if let Some(address) = std::env::args().nth(1) {
+        let server = DecodeServer::bind(address, Network::Mainnet)
+            .unwrap_or_else(|error| exit_with(&error.to_string()));
+        match server.local_addr() {
+            Ok(address) => println!("Listening on {}", address),
+            Err(error) => exit_with(&error.to_string()),
+        }
+        if let Err(error) = server.serve() {
+            exit_with(&error.to_string());
+        }
+        return;
+    }
 
-    let p2pkh_bytes = hex!("76a914000000000000000000000000000000000000000088ac");
-    let mut p2pkh = Cursor::new(p2pkh_bytes.as_ref());
-    let outcome = StandardScripts::parse(&mut p2pkh);
-    assert!(outcome.is_ok());
-    dbg!(&outcome.unwrap());
+    eprintln!("Usage: btc-tx-hex diff <hex1> <hex2>");
+    eprintln!(
+        "       btc-tx-hex edit <hex> [--set-locktime <locktime>] [--set-sequence <input>:<hex>]"
+    );
+    eprintln!(
+        "           [--drop-output <output>] [--add-output <address>:<sats>] [--network <network>]"
+    );
+    #[cfg(feature = "server")]
+    eprintln!("       btc-tx-hex <address:port>");
+    std::process::exit(2);
+}
 
-    let p2sh_bytes = hex!("a914748284390f9e263a4b766a75d0633c50426eb87587");
-    let mut p2sh = Cursor::new(p2sh_bytes.as_ref());
-    let outcome = StandardScripts::parse(&mut p2sh);
-    assert!(outcome.is_ok());
-    dbg!(&outcome.unwrap());
+// Print the error instead of panicking on input from the command line
+fn exit_with(error: &str) -> ! {
+    eprintln!("Error: {}", error);
+    std::process::exit(1);
+}
 
-    let op_return_bytes = hex!("6a0b68656c6c6f20776f726c64");
-    let mut op_return = Cursor::new(op_return_bytes.as_ref());
-    let outcome = StandardScripts::parse(&mut op_return);
-    assert!(outcome.is_ok());
-    dbg!(&outcome.unwrap());
+// Apply the edits of the flags in the order locktime, sequence numbers, dropped
+// outputs and added outputs. Dropped outputs are indexes of the original
+// transaction and the addresses are decoded for `--network`, mainnet by default
+fn edit(raw_tx: &str, flags: &[String]) -> Result<BtcTx, String> {
+    let mut tx = hex::decode(raw_tx.trim())
+        .map_err(|error| format!("The transaction is not valid hex: {}", error))
+        .and_then(|bytes| BtcTx::from_hex_bytes(bytes).map_err(|error| error.to_string()))?;
 
-    let p2wpkh_bytes = hex!("00140000000000000000000000000000000000000000");
-    let mut p2wpkh = Cursor::new(p2wpkh_bytes.as_ref());
-    let outcome = StandardScripts::parse(&mut p2wpkh);
-    assert!(outcome.is_ok());
-    dbg!(&outcome.unwrap());
+    let mut locktime = Option::<u32>::None;
+    let mut sequences = Vec::<(usize, u32)>::new();
+    let mut dropped = Vec::<usize>::new();
+    let mut added = Vec::<(String, u64)>::new();
+    let mut network = Network::Mainnet;
 
-    let p2wsh_bytes = hex!("00200000000000000000000000000000000000000000000000000000000000000000");
-    let mut p2wsh = Cursor::new(p2wsh_bytes.as_ref());
-    let outcome = StandardScripts::parse(&mut p2wsh);
-    assert!(outcome.is_ok());
-    dbg!(&outcome.unwrap());
+    let mut flags = flags.iter();
+    while let Some(flag) = flags.next() {
+        let value = flags
+            .next()
+            .ok_or_else(|| format!("{} needs a value", flag))?;
+        let invalid = || format!("Invalid value `{}` for {}", value, flag);
 
-    let p2tr_bytes = hex!("51200000000000000000000000000000000000000000000000000000000000000000");
-    let mut p2tr = Cursor::new(p2tr_bytes.as_ref());
-    let outcome = StandardScripts::parse(&mut p2tr);
-    assert!(outcome.is_ok());
-    dbg!(&outcome.unwrap());
+        match flag.as_str() {
+            "--set-locktime" => locktime = Some(value.parse().map_err(|_| invalid())?),
+            "--set-sequence" => {
+                let (index, sequence) = value.split_once(':').ok_or_else(invalid)?;
+                sequences.push((
+                    index.parse().map_err(|_| invalid())?,
+                    u32::from_str_radix(sequence, 16).map_err(|_| invalid())?,
+                ));
+            }
+            "--drop-output" => dropped.push(value.parse().map_err(|_| invalid())?),
+            "--add-output" => {
+                let (address, amount) = value.rsplit_once(':').ok_or_else(invalid)?;
+                added.push((address.to_owned(), amount.parse().map_err(|_| invalid())?));
+            }
+            "--network" => {
+                network = match value.as_str() {
+                    "mainnet" => Network::Mainnet,
+                    "testnet" => Network::Testnet,
+                    "signet" => Network::Signet,
+                    "regtest" => Network::Regtest,
+                    _ => return Err(invalid()),
+                }
+            }
+            _ => return Err(format!("Unknown flag {}", flag)),
+        }
+    }
 
-    let p2ms_3_bytes = hex!("524104d81fd577272bbe73308c93009eec5dc9fc319fc1ee2e7066e17220a5d47a18314578be2faea34b9f1f8ca078f8621acd4bc22897b03daa422b9bf56646b342a24104ec3afff0b2b66e8152e9018fe3be3fc92b30bf886b3487a525997d00fd9da2d012dce5d5275854adc3106572a5d1e12d4211b228429f5a7b2f7ba92eb0475bb14104b49b496684b02855bc32f5daefa2e2e406db4418f3b86bca5195600951c7d918cdbe5e6d3736ec2abf2dd7610995c3086976b2c0c7b4e459d10b34a316d5a5e753ae");
-    let mut p2ms_3 = Cursor::new(p2ms_3_bytes.as_ref());
-    let outcome = StandardScripts::parse(&mut p2ms_3);
-    assert!(outcome.is_ok());
-    dbg!(&outcome.unwrap());
+    if let Some(locktime) = locktime {
+        tx.set_locktime(locktime);
+    }
+    for (index, sequence) in sequences {
+        tx.set_sequence(index, sequence)
+            .map_err(|error| error.to_string())?;
+    }
+    // Removing from the last index keeps the others pointing at the same outputs
+    dropped.sort_unstable();
+    dropped.dedup();
+    for index in dropped.into_iter().rev() {
+        tx.remove_output(index).map_err(|error| error.to_string())?;
+    }
+    for (address, amount) in added {
+        let locking_script = Address::to_script(&address, network)
+            .map_err(|error| format!("{}: {}", address, error))?;
+        tx.push_output(TxOutput::new(amount, locking_script));
+    }
 
-    let p2ms_2_bytes = hex!("51210000000000000000000000000000000000000000000000000000000000000000002100000000000000000000000000000000000000000000000000000000000000000052ae");
-    let mut p2ms_2 = Cursor::new(p2ms_2_bytes.as_ref());
-    let outcome = StandardScripts::parse(&mut p2ms_2);
-    assert!(outcome.is_ok());
-    dbg!(&outcome.unwrap());
+    Ok(tx)
 }
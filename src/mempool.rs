@@ -0,0 +1,458 @@
+use crate::{BtcTx, Hash256, Txid, VarInt};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, Cursor, ErrorKind, Read},
+};
+
+/// The virtual size available to the transactions of a block,
+/// the maximum block weight of 4,000,000 divided by 4
+pub const MAX_BLOCK_VSIZE: u64 = 1_000_000;
+
+/// The lower bounds in sat/vB of the buckets of a fee histogram
+pub const FEE_HISTOGRAM_BUCKETS: [u64; 20] = [
+    1, 2, 3, 4, 5, 6, 8, 10, 12, 15, 20, 30, 40, 50, 70, 100, 150, 200, 500, 1000,
+];
+
+/// A transaction in the mempool
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MempoolEntry {
+    txid: Txid,
+    vsize: u64,
+    fee: u64,
+    ancestors: BTreeSet<Txid>,
+}
+
+impl MempoolEntry {
+    /// Instantiate an entry from its transaction ID in the byte order explorers
+    /// display it in, its virtual size, its fee in satoshis and the transaction
+    /// IDs of its unconfirmed ancestors
+    pub fn new(txid: Txid, vsize: u64, fee: u64, ancestors: BTreeSet<Txid>) -> Self {
+        Self {
+            txid,
+            vsize,
+            fee,
+            ancestors,
+        }
+    }
+
+    /// The transaction ID in the byte order explorers display it in
+    pub fn txid(&self) -> Txid {
+        self.txid
+    }
+
+    /// The virtual size of the transaction
+    pub fn vsize(&self) -> u64 {
+        self.vsize
+    }
+
+    /// The fee of the transaction in satoshis
+    pub fn fee(&self) -> u64 {
+        self.fee
+    }
+
+    /// The transaction IDs of the unconfirmed transactions that must be mined first
+    pub fn ancestors(&self) -> &BTreeSet<Txid> {
+        &self.ancestors
+    }
+
+    /// The fee rate of the transaction on its own in sat/vB
+    pub fn fee_rate(&self) -> f64 {
+        self.fee as f64 / self.vsize.max(1) as f64
+    }
+}
+
+/// The virtual size of the transactions of the projected block
+/// paying a fee rate within a bucket of the fee histogram
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FeeBucket {
+    /// The lowest fee rate in sat/vB of the bucket
+    pub min_fee_rate: u64,
+    /// The number of transactions in the bucket
+    pub count: usize,
+    /// The sum of the virtual sizes of the transactions in the bucket
+    pub vsize: u64,
+}
+
+/// The transactions added and removed between two snapshots
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    /// The transaction IDs in the newer snapshot only
+    pub added: Vec<Txid>,
+    /// The transaction IDs in the older snapshot only
+    pub removed: Vec<Txid>,
+}
+
+/// The transactions of a mempool at one point in time
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MempoolSnapshot {
+    timestamp: u64,
+    entries: BTreeMap<Txid, MempoolEntry>,
+}
+
+impl MempoolSnapshot {
+    /// Instantiate an empty snapshot taken at the UNIX `timestamp`
+    pub fn new(timestamp: u64) -> Self {
+        Self {
+            timestamp,
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Build a snapshot from raw transactions paired with their fees. The ancestors
+    /// of each transaction are the transactions of the snapshot it spends from,
+    /// directly or through other transactions of the snapshot
+    pub fn from_raw_txs(timestamp: u64, txs: &[(&[u8], u64)]) -> io::Result<Self> {
+        let mut parents = BTreeMap::<Txid, BTreeSet<Txid>>::new();
+        let mut snapshot = Self::new(timestamp);
+
+        for (raw_tx, fee) in txs {
+            let tx = BtcTx::from_hex_bytes(raw_tx)?;
+
+            let txid = tx.txid();
+
+            parents.insert(
+                txid,
+                tx.inputs()
+                    .iter()
+                    .map(|input| input.previous_tx_id())
+                    .collect(),
+            );
+
+            snapshot.insert(MempoolEntry::new(
+                txid,
+                tx.weight().div_ceil(4) as u64,
+                *fee,
+                BTreeSet::new(),
+            ));
+        }
+
+        let txids = snapshot.entries.keys().copied().collect::<Vec<Txid>>();
+        for txid in txids {
+            let mut ancestors = BTreeSet::new();
+            let mut pending = parents[&txid].iter().copied().collect::<Vec<Txid>>();
+
+            while let Some(parent) = pending.pop() {
+                if parent != txid && parents.contains_key(&parent) && ancestors.insert(parent) {
+                    pending.extend(parents[&parent].iter().copied());
+                }
+            }
+
+            if let Some(entry) = snapshot.entries.get_mut(&txid) {
+                entry.ancestors = ancestors;
+            }
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Add an entry replacing any entry with the same transaction ID
+    pub fn insert(&mut self, entry: MempoolEntry) -> &mut Self {
+        self.entries.insert(entry.txid, entry);
+
+        self
+    }
+
+    /// The UNIX timestamp the snapshot was taken at
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Get the entry with the transaction ID
+    pub fn get(&self, txid: &Txid) -> Option<&MempoolEntry> {
+        self.entries.get(txid)
+    }
+
+    /// The entries ordered by transaction ID
+    pub fn entries(&self) -> impl Iterator<Item = &MempoolEntry> {
+        self.entries.values()
+    }
+
+    /// The number of entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the snapshot has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entries a miner would put in the next block in the order they are
+    /// selected, paired with the fee rate of the package they were selected in.
+    /// Like Bitcoin Core the package with the highest fee rate including its
+    /// unselected ancestors is picked until the block is full
+    pub fn projected_block(&self) -> Vec<(&MempoolEntry, f64)> {
+        // The fee and virtual size of each entry with its unselected ancestors,
+        // updated for the descendants of every package as it is selected
+        let mut packages = self
+            .entries
+            .values()
+            .map(|entry| {
+                let package = entry
+                    .ancestors
+                    .iter()
+                    .filter_map(|ancestor| self.entries.get(ancestor))
+                    .fold((entry.fee, entry.vsize), |(fee, vsize), ancestor| {
+                        (
+                            fee.saturating_add(ancestor.fee),
+                            vsize.saturating_add(ancestor.vsize),
+                        )
+                    });
+
+                (entry.txid, package)
+            })
+            .collect::<BTreeMap<Txid, (u64, u64)>>();
+        let descendants = self.descendants();
+        let mut selected = Vec::new();
+        let mut space = MAX_BLOCK_VSIZE;
+
+        while let Some((txid, fee_rate, vsize)) = packages
+            .iter()
+            .map(|(txid, (fee, vsize))| (*txid, *fee as f64 / (*vsize).max(1) as f64, *vsize))
+            .max_by(|first, second| first.1.total_cmp(&second.1))
+        {
+            if vsize > space {
+                // The package does not fit but a smaller one might. The packages
+                // of the descendants include this one so they do not fit either
+                packages.remove(&txid);
+                descendants
+                    .get(&txid)
+                    .into_iter()
+                    .flatten()
+                    .for_each(|descendant| {
+                        packages.remove(descendant);
+                    });
+
+                continue;
+            }
+            space -= vsize;
+
+            let entry = &self.entries[&txid];
+            let mut package = entry
+                .ancestors
+                .iter()
+                .filter(|ancestor| packages.contains_key(*ancestor))
+                .filter_map(|ancestor| self.entries.get(ancestor))
+                .collect::<Vec<&MempoolEntry>>();
+            // An ancestor always has fewer ancestors than its descendants
+            package.sort_by_key(|ancestor| ancestor.ancestors.len());
+            package.push(entry);
+
+            package.into_iter().for_each(|entry| {
+                packages.remove(&entry.txid);
+                descendants
+                    .get(&entry.txid)
+                    .into_iter()
+                    .flatten()
+                    .for_each(|descendant| {
+                        if let Some((fee, vsize)) = packages.get_mut(descendant) {
+                            *fee = fee.saturating_sub(entry.fee);
+                            *vsize = vsize.saturating_sub(entry.vsize);
+                        }
+                    });
+                selected.push((entry, fee_rate));
+            });
+        }
+
+        selected
+    }
+
+    /// The fee histogram of the projected block. Buckets without
+    /// transactions are left out
+    pub fn fee_histogram(&self) -> Vec<FeeBucket> {
+        let mut buckets = FEE_HISTOGRAM_BUCKETS
+            .iter()
+            .map(|min_fee_rate| FeeBucket {
+                min_fee_rate: *min_fee_rate,
+                count: 0,
+                vsize: 0,
+            })
+            .collect::<Vec<FeeBucket>>();
+
+        self.projected_block()
+            .into_iter()
+            .for_each(|(entry, fee_rate)| {
+                // Transactions paying below the lowest bucket are counted in it
+                let index = buckets
+                    .iter()
+                    .rposition(|bucket| bucket.min_fee_rate as f64 <= fee_rate)
+                    .unwrap_or_default();
+                buckets[index].count += 1;
+                buckets[index].vsize += entry.vsize;
+            });
+
+        buckets.retain(|bucket| bucket.count > 0);
+
+        buckets
+    }
+
+    /// The transactions added and removed since the `older` snapshot
+    pub fn diff(&self, older: &MempoolSnapshot) -> SnapshotDiff {
+        SnapshotDiff {
+            added: self
+                .entries
+                .keys()
+                .filter(|txid| !older.entries.contains_key(*txid))
+                .copied()
+                .collect(),
+            removed: older
+                .entries
+                .keys()
+                .filter(|txid| !self.entries.contains_key(*txid))
+                .copied()
+                .collect(),
+        }
+    }
+
+    /// Serialize the snapshot. The timestamp is 8 bytes in little-endian followed
+    /// by the VarInt number of entries. Each entry is the 32 byte transaction ID,
+    /// the VarInt virtual size and fee then the VarInt number of ancestors
+    /// followed by their transaction IDs
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.timestamp.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&VarInt::encode(self.entries.len() as u64));
+
+        self.entries.values().for_each(|entry| {
+            bytes.extend_from_slice(entry.txid.as_ref());
+            bytes.extend_from_slice(&VarInt::encode(entry.vsize));
+            bytes.extend_from_slice(&VarInt::encode(entry.fee));
+            bytes.extend_from_slice(&VarInt::encode(entry.ancestors.len() as u64));
+            entry
+                .ancestors
+                .iter()
+                .for_each(|ancestor| bytes.extend_from_slice(ancestor.as_ref()));
+        });
+
+        bytes
+    }
+
+    /// Deserialize a snapshot serialized by `MempoolSnapshot::to_bytes()`
+    pub fn from_bytes(bytes: impl AsRef<[u8]>) -> io::Result<Self> {
+        let mut bytes = Cursor::new(bytes.as_ref());
+
+        let mut timestamp = [0u8; 8];
+        bytes.read_exact(&mut timestamp)?;
+        let mut snapshot = Self::new(u64::from_le_bytes(timestamp));
+
+        for _ in 0..Self::read_varint(&mut bytes)? {
+            let txid = Self::read_txid(&mut bytes)?;
+            let vsize = Self::read_varint(&mut bytes)? as u64;
+            let fee = Self::read_varint(&mut bytes)? as u64;
+            let ancestors = (0..Self::read_varint(&mut bytes)?)
+                .map(|_| Self::read_txid(&mut bytes))
+                .collect::<io::Result<BTreeSet<Txid>>>()?;
+
+            snapshot.insert(MempoolEntry::new(txid, vsize, fee, ancestors));
+        }
+
+        if bytes.position() != bytes.get_ref().len() as u64 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "Trailing bytes after the mempool snapshot",
+            ));
+        }
+
+        Ok(snapshot)
+    }
+
+    // The transaction IDs of the entries spending from each entry
+    fn descendants(&self) -> BTreeMap<Txid, Vec<Txid>> {
+        let mut descendants = BTreeMap::<Txid, Vec<Txid>>::new();
+        self.entries.values().for_each(|entry| {
+            entry
+                .ancestors
+                .iter()
+                .for_each(|ancestor| descendants.entry(*ancestor).or_default().push(entry.txid))
+        });
+
+        descendants
+    }
+
+    fn read_varint(bytes: &mut Cursor<&[u8]>) -> io::Result<usize> {
+        let mut varint_len = [0u8];
+        bytes.read_exact(&mut varint_len)?;
+
+        VarInt::integer(VarInt::parse(varint_len[0]), bytes)
+    }
+
+    fn read_txid(bytes: &mut Cursor<&[u8]>) -> io::Result<Txid> {
+        let mut txid = [0u8; 32];
+        bytes.read_exact(&mut txid)?;
+
+        Ok(Txid::new(Hash256::new(txid)))
+    }
+}
+
+#[cfg(test)]
+mod mempool_sanity_checks {
+    use crate::{fixtures::txid, MempoolEntry, MempoolSnapshot};
+    use std::collections::BTreeSet;
+
+    fn snapshot() -> MempoolSnapshot {
+        let mut snapshot = MempoolSnapshot::new(1_700_000_000);
+        // A low fee parent with a high fee child is mined as a package at 6 sat/vB
+        snapshot
+            .insert(MempoolEntry::new(txid(1), 200, 200, BTreeSet::new()))
+            .insert(MempoolEntry::new(
+                txid(2),
+                200,
+                2200,
+                BTreeSet::from([txid(1)]),
+            ))
+            .insert(MempoolEntry::new(txid(3), 100, 2000, BTreeSet::new()));
+
+        snapshot
+    }
+
+    #[test]
+    fn fee_histogram() {
+        let snapshot = snapshot();
+
+        let block = snapshot
+            .projected_block()
+            .into_iter()
+            .map(|(entry, _)| entry.txid().to_byte_array()[0])
+            .collect::<Vec<u8>>();
+        assert_eq!(vec![3, 1, 2], block);
+
+        let histogram = snapshot
+            .fee_histogram()
+            .into_iter()
+            .map(|bucket| (bucket.min_fee_rate, bucket.count, bucket.vsize))
+            .collect::<Vec<(u64, usize, u64)>>();
+        assert_eq!(vec![(6, 2, 400), (20, 1, 100)], histogram);
+
+        // Packages summing past u64::MAX do not overflow and never fit a block
+        let mut snapshot = snapshot.clone();
+        snapshot
+            .insert(MempoolEntry::new(
+                txid(4),
+                u64::MAX,
+                u64::MAX,
+                BTreeSet::new(),
+            ))
+            .insert(MempoolEntry::new(
+                txid(5),
+                u64::MAX,
+                u64::MAX,
+                BTreeSet::from([txid(4)]),
+            ));
+        let decoded = MempoolSnapshot::from_bytes(snapshot.to_bytes()).unwrap();
+        assert_eq!(3, decoded.projected_block().len());
+    }
+
+    #[test]
+    fn serialize() {
+        let snapshot = snapshot();
+        let decoded = MempoolSnapshot::from_bytes(snapshot.to_bytes()).unwrap();
+        assert_eq!(snapshot, decoded);
+
+        let mut newer = MempoolSnapshot::new(1_700_000_600);
+        newer.insert(MempoolEntry::new(txid(4), 150, 300, BTreeSet::new()));
+        let diff = newer.diff(&snapshot);
+        assert_eq!(vec![txid(4)], diff.added);
+        assert_eq!(3, diff.removed.len());
+
+        assert!(MempoolSnapshot::from_bytes([0u8; 4]).is_err());
+    }
+}
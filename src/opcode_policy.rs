@@ -0,0 +1,149 @@
+use crate::{Opcode, Script};
+use std::io;
+
+/// An opcode found in a script at a byte offset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ScannedOpcode {
+    /// The offset of the opcode in the script
+    pub offset: usize,
+    /// The opcode byte
+    pub opcode: u8,
+}
+
+/// The opcodes of a script whose meaning depends on whether the script runs
+/// as a legacy or segwit v0 script or as a tapscript
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct OpcodeScan {
+    /// Opcodes disabled since 2010 like `OP_CAT` and `OP_MUL`, which fail a
+    /// legacy or segwit v0 script even in a branch that is not executed
+    pub disabled: Vec<ScannedOpcode>,
+    /// The `OP_SUCCESSx` bytes of BIP342, which make a tapscript succeed
+    /// before it runs. Most of them are the disabled opcodes
+    pub op_success: Vec<ScannedOpcode>,
+}
+
+impl OpcodeScan {
+    /// Whether a legacy or segwit v0 script uses a disabled opcode and can never succeed
+    pub fn fails_as_legacy(&self) -> bool {
+        !self.disabled.is_empty()
+    }
+
+    /// Whether a tapscript has an `OP_SUCCESSx` and succeeds for any witness
+    pub fn succeeds_as_tapscript(&self) -> bool {
+        !self.op_success.is_empty()
+    }
+}
+
+impl Script {
+    /// Report the disabled opcodes and the `OP_SUCCESSx` bytes of the script.
+    /// Data pushes are skipped, so their contents are never reported. Returns
+    /// an error if a push goes past the end of the script
+    pub fn disabled_opcode_scan(&self) -> io::Result<OpcodeScan> {
+        let script = self.as_bytes();
+        let mut scan = OpcodeScan::default();
+        let mut position = 0usize;
+
+        while position < script.len() {
+            let offset = position;
+            let (opcode, _) = Self::read_opcode(script, &mut position)?;
+            let scanned = ScannedOpcode { offset, opcode };
+
+            let opcode = Opcode::from_byte(opcode);
+            if opcode.is_disabled() {
+                scan.disabled.push(scanned);
+            }
+            if opcode.is_op_success() {
+                scan.op_success.push(scanned);
+            }
+        }
+
+        Ok(scan)
+    }
+}
+
+impl Opcode {
+    /// Whether the opcode is one of the opcodes disabled since 2010, `OP_CAT`,
+    /// `OP_SUBSTR`, `OP_LEFT`, `OP_RIGHT`, `OP_INVERT`, `OP_AND`, `OP_OR`,
+    /// `OP_XOR`, `OP_2MUL`, `OP_2DIV`, `OP_MUL`, `OP_DIV`, `OP_MOD`,
+    /// `OP_LSHIFT` and `OP_RSHIFT`
+    pub fn is_disabled(&self) -> bool {
+        matches!(
+            self,
+            Self::OP_CAT
+                | Self::OP_SUBSTR
+                | Self::OP_LEFT
+                | Self::OP_RIGHT
+                | Self::OP_INVERT
+                | Self::OP_AND
+                | Self::OP_OR
+                | Self::OP_XOR
+                | Self::OP_2MUL
+                | Self::OP_2DIV
+                | Self::OP_MUL
+                | Self::OP_DIV
+                | Self::OP_MOD
+                | Self::OP_LSHIFT
+                | Self::OP_RSHIFT
+        )
+    }
+
+    /// Whether the opcode is an `OP_SUCCESSx` of BIP342, the opcodes 80, 98,
+    /// 126-129, 131-134, 137-138, 141-142, 149-153 and 187-254
+    pub fn is_op_success(&self) -> bool {
+        self.is_disabled()
+            || matches!(
+                self,
+                Self::OP_RESERVED
+                    | Self::OP_VER
+                    | Self::OP_RESERVED1
+                    | Self::OP_RESERVED2
+                    | Self::Unknown(_)
+            )
+    }
+}
+
+#[cfg(test)]
+mod opcode_policy_sanity_checks {
+    use crate::{Opcode, ScannedOpcode, Script};
+    use hex_literal::hex;
+
+    #[test]
+    fn disabled_opcodes() {
+        // <2 bytes> <2 bytes> OP_CAT OP_RESERVED1 0xbb OP_CHECKSIG
+        let scan = Script::new(hex!("02aabb02ccdd7e89bbac"))
+            .disabled_opcode_scan()
+            .unwrap();
+        assert_eq!(
+            vec![ScannedOpcode {
+                offset: 6,
+                opcode: 0x7e
+            }],
+            scan.disabled
+        );
+        assert_eq!(
+            vec![6, 7, 8],
+            scan.op_success
+                .iter()
+                .map(|scanned| scanned.offset)
+                .collect::<Vec<usize>>()
+        );
+        assert!(scan.fails_as_legacy());
+        assert!(scan.succeeds_as_tapscript());
+
+        let count =
+            |filter: fn(&Opcode) -> bool| (0..=255u8).map(Opcode::from_byte).filter(filter).count();
+        assert_eq!(15, count(Opcode::is_disabled));
+        assert_eq!(1 + 1 + 4 + 4 + 2 + 2 + 5 + 68, count(Opcode::is_op_success));
+
+        // The disabled opcode bytes inside a push are data
+        let pushed = Script::new(hex!("037e9597ac"))
+            .disabled_opcode_scan()
+            .unwrap();
+        assert!(!pushed.fails_as_legacy());
+        assert!(!pushed.succeeds_as_tapscript());
+
+        assert!(Script::new(hex!("4c05aabb"))
+            .disabled_opcode_scan()
+            .is_err());
+    }
+}
@@ -0,0 +1,283 @@
+use crate::{BtcTx, OutPoint, Txid};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+/// The most orphans an `OrphanPool` holds by default, like Bitcoin Core
+pub const DEFAULT_MAX_ORPHANS: usize = 100;
+
+/// The number of seconds an orphan is held by default, like Bitcoin Core
+pub const DEFAULT_ORPHAN_EXPIRY: u64 = 20 * 60;
+
+/// A transaction waiting for the outputs it spends
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Orphan {
+    /// The transaction ID of the orphan
+    pub txid: Txid,
+    /// The orphan
+    pub tx: BtcTx,
+    /// The UNIX timestamp the orphan was received at
+    pub received_at: u64,
+    /// The spent outputs which are not known yet
+    pub missing: BTreeSet<OutPoint>,
+}
+
+/// What `OrphanPool::add()` did with a transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrphanStatus {
+    /// Every output the transaction spends is known so it was not held
+    Complete,
+    /// The transaction is held until these outputs are known. The orphans
+    /// evicted to make room for it are listed
+    Held {
+        missing: BTreeSet<OutPoint>,
+        evicted: Vec<Txid>,
+    },
+    /// The transaction is already held
+    Duplicate,
+}
+
+/// Holds transactions of a live stream which arrive before the transactions
+/// they spend from and releases them once their parents arrive. The oldest
+/// orphans are evicted when the pool is full and orphans expire after a while
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrphanPool {
+    orphans: BTreeMap<Txid, Orphan>,
+    // The orphans spending each missing output
+    by_missing: BTreeMap<OutPoint, BTreeSet<Txid>>,
+    max_orphans: usize,
+    expiry: u64,
+}
+
+impl OrphanPool {
+    /// A pool holding at most `DEFAULT_MAX_ORPHANS` orphans for
+    /// `DEFAULT_ORPHAN_EXPIRY` seconds
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hold at most `max_orphans` orphans
+    pub fn with_max_orphans(mut self, max_orphans: usize) -> Self {
+        self.max_orphans = max_orphans;
+
+        self
+    }
+
+    /// Hold orphans for `expiry` seconds
+    pub fn with_expiry(mut self, expiry: u64) -> Self {
+        self.expiry = expiry;
+
+        self
+    }
+
+    /// The number of orphans held
+    pub fn len(&self) -> usize {
+        self.orphans.len()
+    }
+
+    /// Whether no orphan is held
+    pub fn is_empty(&self) -> bool {
+        self.orphans.is_empty()
+    }
+
+    /// The orphan with the transaction ID `txid`
+    pub fn get(&self, txid: &Txid) -> Option<&Orphan> {
+        self.orphans.get(txid)
+    }
+
+    /// The outputs some orphan is waiting for
+    pub fn missing_outputs(&self) -> impl Iterator<Item = &OutPoint> {
+        self.by_missing.keys()
+    }
+
+    /// Hold `tx` received at `now` if it spends outputs for which
+    /// `is_known` is false, for example outputs missing from the UTXO set or
+    /// the transactions seen so far. Outputs of held orphans are not known
+    pub fn add(
+        &mut self,
+        txid: Txid,
+        tx: BtcTx,
+        now: u64,
+        is_known: impl Fn(&OutPoint) -> bool,
+    ) -> OrphanStatus {
+        if self.orphans.contains_key(&txid) {
+            return OrphanStatus::Duplicate;
+        }
+
+        let missing = tx
+            .inputs()
+            .iter()
+            .map(|input| input.previous_output())
+            .filter(|outpoint| !is_known(outpoint))
+            .collect::<BTreeSet<OutPoint>>();
+        if missing.is_empty() || self.max_orphans == 0 {
+            return OrphanStatus::Complete;
+        }
+
+        let mut evicted = Vec::<Txid>::new();
+        while self.orphans.len() >= self.max_orphans {
+            let Some(oldest) = self
+                .orphans
+                .values()
+                .min_by_key(|orphan| (orphan.received_at, orphan.txid))
+                .map(|orphan| orphan.txid)
+            else {
+                break;
+            };
+
+            self.remove(&oldest);
+            evicted.push(oldest);
+        }
+
+        missing.iter().for_each(|outpoint| {
+            self.by_missing.entry(*outpoint).or_default().insert(txid);
+        });
+        self.orphans.insert(
+            txid,
+            Orphan {
+                txid,
+                tx,
+                received_at: now,
+                missing: missing.clone(),
+            },
+        );
+
+        OrphanStatus::Held { missing, evicted }
+    }
+
+    /// Release the orphans which are complete once the transaction `txid`
+    /// with `outputs` outputs arrived. The released orphans complete their own
+    /// children so they are returned with parents before children
+    pub fn parent_arrived(&mut self, txid: Txid, outputs: usize) -> Vec<Orphan> {
+        let mut released = Vec::<Orphan>::new();
+        let mut arrived = VecDeque::from([(txid, outputs)]);
+
+        while let Some((parent, outputs)) = arrived.pop_front() {
+            for vout in 0..outputs as u32 {
+                let outpoint = OutPoint::new(parent, vout);
+                let Some(children) = self.by_missing.remove(&outpoint) else {
+                    continue;
+                };
+
+                for child in children {
+                    let complete = self.orphans.get_mut(&child).is_some_and(|orphan| {
+                        orphan.missing.remove(&outpoint);
+                        orphan.missing.is_empty()
+                    });
+
+                    if let Some(orphan) = complete.then(|| self.remove(&child)).flatten() {
+                        arrived.push_back((orphan.txid, orphan.tx.outputs().len()));
+                        released.push(orphan);
+                    }
+                }
+            }
+        }
+
+        released
+    }
+
+    /// Drop the orphans received more than the expiry before `now`
+    pub fn expire(&mut self, now: u64) -> Vec<Txid> {
+        let expired = self
+            .orphans
+            .values()
+            .filter(|orphan| now.saturating_sub(orphan.received_at) > self.expiry)
+            .map(|orphan| orphan.txid)
+            .collect::<Vec<Txid>>();
+        expired.iter().for_each(|txid| {
+            self.remove(txid);
+        });
+
+        expired
+    }
+
+    /// Drop the orphan `txid`, for example once a block confirmed a conflict
+    pub fn remove(&mut self, txid: &Txid) -> Option<Orphan> {
+        let orphan = self.orphans.remove(txid)?;
+        orphan.missing.iter().for_each(|outpoint| {
+            if let Some(children) = self.by_missing.get_mut(outpoint) {
+                children.remove(txid);
+                if children.is_empty() {
+                    self.by_missing.remove(outpoint);
+                }
+            }
+        });
+
+        Some(orphan)
+    }
+}
+
+impl Default for OrphanPool {
+    fn default() -> Self {
+        Self {
+            orphans: BTreeMap::new(),
+            by_missing: BTreeMap::new(),
+            max_orphans: DEFAULT_MAX_ORPHANS,
+            expiry: DEFAULT_ORPHAN_EXPIRY,
+        }
+    }
+}
+
+#[cfg(test)]
+mod orphan_sanity_checks {
+    use crate::{
+        fixtures::{self, txid},
+        BtcTx, OrphanPool, OrphanStatus, OutPoint, Txid,
+    };
+
+    fn tx(spends: &[OutPoint]) -> BtcTx {
+        fixtures::tx(
+            &spends
+                .iter()
+                .map(|outpoint| (outpoint.txid(), outpoint.vout()))
+                .collect::<Vec<(Txid, u32)>>(),
+            &[1_000],
+        )
+    }
+
+    #[test]
+    fn release_orphans() {
+        let confirmed = OutPoint::new(txid(9), 0);
+        let is_known = |outpoint: &OutPoint| *outpoint == confirmed;
+
+        let mut pool = OrphanPool::new().with_max_orphans(2).with_expiry(60);
+        assert_eq!(
+            OrphanStatus::Complete,
+            pool.add(txid(1), tx(&[confirmed]), 0, is_known)
+        );
+
+        // The grandchild arrives before the child which arrives before the parent
+        let parent = txid(1);
+        let child = txid(2);
+        let grandchild = txid(3);
+        assert!(matches!(
+            pool.add(grandchild, tx(&[OutPoint::new(child, 0), confirmed]), 10, is_known),
+            OrphanStatus::Held { ref missing, .. } if missing.len() == 1
+        ));
+        pool.add(child, tx(&[OutPoint::new(parent, 0)]), 20, is_known);
+        assert_eq!(
+            OrphanStatus::Duplicate,
+            pool.add(child, tx(&[]), 20, is_known)
+        );
+        assert_eq!(2, pool.len());
+
+        let released = pool.parent_arrived(parent, 1);
+        assert_eq!(
+            vec![child, grandchild],
+            released
+                .iter()
+                .map(|orphan| orphan.txid)
+                .collect::<Vec<Txid>>()
+        );
+        assert!(pool.is_empty());
+        assert_eq!(0, pool.missing_outputs().count());
+
+        // The oldest orphan is evicted when the pool is full and the rest expire
+        pool.add(txid(4), tx(&[OutPoint::new(txid(8), 0)]), 100, is_known);
+        pool.add(txid(5), tx(&[OutPoint::new(txid(8), 1)]), 110, is_known);
+        assert!(matches!(
+            pool.add(txid(6), tx(&[OutPoint::new(txid(8), 2)]), 120, is_known),
+            OrphanStatus::Held { ref evicted, .. } if evicted == &vec![txid(4)]
+        ));
+        assert_eq!(vec![txid(5)], pool.expire(175));
+        assert!(pool.get(&txid(6)).is_some());
+    }
+}
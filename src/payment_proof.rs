@@ -0,0 +1,231 @@
+use crate::{BlockHeader, BtcTx, Hash256, MerkleProof, TxOutput, Txid, VarInt, BLOCK_HEADER_LEN};
+use std::io::{self, Cursor, ErrorKind, Read};
+
+/// A receipt proving that a transaction paying an output was confirmed in a
+/// block. Anyone holding a chain of block headers can check it without trusting
+/// the party handing it out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentProof {
+    // The raw transaction, kept so the transaction ID can be recomputed
+    raw_tx: Vec<u8>,
+    // The parsed transaction
+    tx: BtcTx,
+    // The index of the output paying the merchant
+    vout: u32,
+    // The header of the block the transaction was confirmed in
+    header: BlockHeader,
+    // The proof that the transaction is in the block
+    merkle_proof: MerkleProof,
+}
+
+impl PaymentProof {
+    /// Bundle the raw transaction, the index of the paying output, the header of
+    /// the block it was confirmed in and the merkle proof of the transaction
+    pub fn new(
+        raw_tx: Vec<u8>,
+        vout: u32,
+        header: BlockHeader,
+        merkle_proof: MerkleProof,
+    ) -> io::Result<Self> {
+        let tx = BtcTx::from_hex_bytes(&raw_tx)?;
+
+        if vout as usize >= tx.outputs().len() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "The transaction has no output at the index being proven",
+            ));
+        }
+
+        Ok(Self {
+            raw_tx,
+            tx,
+            vout,
+            header,
+            merkle_proof,
+        })
+    }
+
+    /// The transaction ID of the transaction
+    pub fn txid(&self) -> Txid {
+        Txid::hash(&self.raw_tx)
+    }
+
+    /// The parsed transaction
+    pub fn tx(&self) -> &BtcTx {
+        &self.tx
+    }
+
+    /// The output paying the merchant
+    pub fn output(&self) -> &TxOutput {
+        &self.tx.outputs()[self.vout as usize]
+    }
+
+    /// The index of the output paying the merchant
+    pub fn vout(&self) -> u32 {
+        self.vout
+    }
+
+    /// The header of the block the transaction was confirmed in
+    pub fn header(&self) -> &BlockHeader {
+        &self.header
+    }
+
+    /// The proof that the transaction is in the block
+    pub fn merkle_proof(&self) -> &MerkleProof {
+        &self.merkle_proof
+    }
+
+    /// Check the proof against a chain of block headers ordered from the oldest.
+    /// Every header must link to the one before it and have a valid proof of work,
+    /// the block of the proof must be in the chain and the merkle proof must lead
+    /// to its merkle root. Returns the number of confirmations of the transaction
+    pub fn verify(&self, header_chain: &[BlockHeader]) -> io::Result<usize> {
+        if let Some(header) = header_chain
+            .iter()
+            .find(|header| !header.has_valid_proof_of_work())
+        {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Block {} has an invalid proof of work", header.block_hash()),
+            ));
+        }
+
+        if header_chain
+            .windows(2)
+            .any(|pair| pair[1].previous_block_hash() != pair[0].block_hash())
+        {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "The block headers do not form a chain",
+            ));
+        }
+
+        let block_hash = self.header.block_hash();
+        let height_in_chain = header_chain
+            .iter()
+            .position(|header| header.block_hash() == block_hash)
+            .ok_or_else(|| {
+                io::Error::new(
+                    ErrorKind::NotFound,
+                    format!("Block {} is not in the header chain", block_hash),
+                )
+            })?;
+
+        if self.merkle_proof.compute_root(self.txid()) != self.header.merkle_root() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "The merkle proof does not lead to the merkle root of the block",
+            ));
+        }
+
+        Ok(header_chain.len() - height_in_chain)
+    }
+
+    /// Serialize the proof. The VarInt length of the raw transaction and the raw
+    /// transaction are followed by the 4 byte output index, the 80 byte block
+    /// header, the 4 byte position in the block, the VarInt number of sibling
+    /// hashes and the sibling hashes in network byte order
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = VarInt::encode(self.raw_tx.len() as u64);
+        bytes.extend_from_slice(&self.raw_tx);
+        bytes.extend_from_slice(&self.vout.to_le_bytes());
+        bytes.extend_from_slice(&self.header.to_bytes());
+        bytes.extend_from_slice(&self.merkle_proof.position().to_le_bytes());
+        bytes.extend_from_slice(&VarInt::encode(self.merkle_proof.siblings().len() as u64));
+        self.merkle_proof
+            .siblings()
+            .iter()
+            .for_each(|sibling| bytes.extend_from_slice(sibling.reversed().as_ref()));
+
+        bytes
+    }
+
+    /// Deserialize a proof serialized by `PaymentProof::to_bytes()`
+    pub fn from_bytes(bytes: impl AsRef<[u8]>) -> io::Result<Self> {
+        let total_len = bytes.as_ref().len();
+        let mut bytes = Cursor::new(bytes.as_ref());
+
+        let raw_tx_len = Self::read_varint(&mut bytes)?;
+        if raw_tx_len > total_len {
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "The transaction length is greater than the remaining bytes",
+            ));
+        }
+        let mut raw_tx = vec![0u8; raw_tx_len];
+        bytes.read_exact(&mut raw_tx)?;
+
+        let mut buffer = [0u8; 4];
+        bytes.read_exact(&mut buffer)?;
+        let vout = u32::from_le_bytes(buffer);
+
+        let mut header = [0u8; BLOCK_HEADER_LEN];
+        bytes.read_exact(&mut header)?;
+        let header = BlockHeader::from_bytes(header)?;
+
+        bytes.read_exact(&mut buffer)?;
+        let position = u32::from_le_bytes(buffer);
+        let siblings = (0..Self::read_varint(&mut bytes)?)
+            .map(|_| {
+                let mut sibling = [0u8; 32];
+                bytes.read_exact(&mut sibling)?;
+
+                Ok(Hash256::new(sibling).reversed())
+            })
+            .collect::<io::Result<Vec<Hash256>>>()?;
+
+        Self::new(raw_tx, vout, header, MerkleProof::new(position, siblings))
+    }
+
+    fn read_varint(bytes: &mut Cursor<&[u8]>) -> io::Result<usize> {
+        let mut varint_len = [0u8];
+        bytes.read_exact(&mut varint_len)?;
+
+        VarInt::integer(VarInt::parse(varint_len[0]), bytes)
+    }
+}
+
+#[cfg(test)]
+mod payment_proof_sanity_checks {
+    use crate::{BlockHeader, Hash256, MerkleProof, PaymentProof};
+    use hex_literal::hex;
+
+    const GENESIS_HEADER: [u8; 80] = hex!("0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c");
+    const GENESIS_COINBASE: [u8; 204] = hex!("01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000");
+
+    #[test]
+    fn genesis_payment() {
+        let header = BlockHeader::from_bytes(GENESIS_HEADER).unwrap();
+        let proof = PaymentProof::new(
+            GENESIS_COINBASE.to_vec(),
+            0,
+            header,
+            MerkleProof::new(0, Vec::new()),
+        )
+        .unwrap();
+
+        assert_eq!(5_000_000_000, proof.output().amount());
+        assert_eq!(Ok(1), proof.verify(&[header]).map_err(|error| error.kind()));
+
+        let decoded = PaymentProof::from_bytes(proof.to_bytes()).unwrap();
+        assert_eq!(proof, decoded);
+
+        // A block that is not in the chain and a proof leading elsewhere are rejected
+        assert!(proof.verify(&[]).is_err());
+        let wrong_proof = PaymentProof::new(
+            GENESIS_COINBASE.to_vec(),
+            0,
+            header,
+            MerkleProof::new(0, vec![Hash256::new([1u8; 32])]),
+        )
+        .unwrap();
+        assert!(wrong_proof.verify(&[header]).is_err());
+        assert!(PaymentProof::new(
+            GENESIS_COINBASE.to_vec(),
+            1,
+            header,
+            MerkleProof::new(0, vec![])
+        )
+        .is_err());
+    }
+}
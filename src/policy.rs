@@ -0,0 +1,776 @@
+use crate::{BtcTx, Script, ScriptType, StandardScripts, TxOutput, TxVersion, Txid, VarInt};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, ErrorKind},
+    ops::Range,
+};
+
+/// The most signature operations of the redeem script of a standard P2SH input
+pub const MAX_P2SH_SIGOPS: usize = 15;
+
+/// The version of topologically restricted until confirmation (TRUC) transactions of BIP431
+pub const TRUC_VERSION: u32 = 3;
+
+/// The largest sigop adjusted virtual size of a TRUC transaction
+pub const TRUC_MAX_VSIZE: u64 = 10_000;
+
+/// The largest sigop adjusted virtual size of a TRUC transaction spending
+/// an unconfirmed TRUC transaction
+pub const TRUC_CHILD_MAX_VSIZE: u64 = 1_000;
+
+/// The fee rate in sat/vB below which spending an output costs more than a third
+/// of its amount, like the `-dustrelayfee` option of Bitcoin Core
+pub const DUST_RELAY_FEE_RATE: u64 = 3;
+
+/// The virtual bytes each signature operation counts as, like the
+/// `-bytespersigop` option of Bitcoin Core
+pub const DEFAULT_BYTES_PER_SIGOP: usize = 20;
+
+/// The datacarrier relay limits of a node, like the `-datacarrier` and
+/// `-datacarriersize` options of Bitcoin Core. The default is the one
+/// `OP_RETURN` output of at most 83 bytes relayed before Bitcoin Core 30
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DatacarrierPolicy {
+    // Whether `OP_RETURN` outputs are relayed at all
+    enabled: bool,
+    // The largest locking script of an `OP_RETURN` output including the opcode
+    max_bytes: usize,
+    // The most `OP_RETURN` outputs of a transaction
+    max_outputs: usize,
+}
+
+impl DatacarrierPolicy {
+    /// The default policy
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reject every `OP_RETURN` output like `-datacarrier=0`
+    pub fn disabled(mut self) -> Self {
+        self.enabled = false;
+
+        self
+    }
+
+    /// Set the largest `OP_RETURN` locking script like `-datacarriersize`
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+
+        self
+    }
+
+    /// Set how many `OP_RETURN` outputs a transaction may have
+    pub fn with_max_outputs(mut self, max_outputs: usize) -> Self {
+        self.max_outputs = max_outputs;
+
+        self
+    }
+
+    /// Whether `OP_RETURN` outputs are relayed
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// The largest `OP_RETURN` locking script in bytes
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    /// The most `OP_RETURN` outputs of a transaction
+    pub fn max_outputs(&self) -> usize {
+        self.max_outputs
+    }
+
+    /// Whether a transaction may have more than one `OP_RETURN` output
+    pub fn allows_multiple(&self) -> bool {
+        self.max_outputs > 1
+    }
+}
+
+impl Default for DatacarrierPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_bytes: 83,
+            max_outputs: 1,
+        }
+    }
+}
+
+/// An `OP_RETURN` output which a node with a `DatacarrierPolicy` does not relay
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DatacarrierViolation {
+    /// The policy does not relay `OP_RETURN` outputs
+    Disabled { output_index: usize },
+    /// The locking script is larger than the policy allows
+    TooLarge { output_index: usize, bytes: usize },
+    /// The transaction has more `OP_RETURN` outputs than the policy allows
+    TooManyOutputs { outputs: usize },
+}
+
+impl BtcTx {
+    /// Check the `OP_RETURN` outputs against the datacarrier limits of a node
+    pub fn check_datacarrier(&self, policy: &DatacarrierPolicy) -> Vec<DatacarrierViolation> {
+        let data_outputs = self
+            .outputs()
+            .iter()
+            .enumerate()
+            .filter(|(_, output)| Self::is_null_data(output.locking_script()))
+            .collect::<Vec<_>>();
+
+        let mut violations = data_outputs
+            .iter()
+            .filter_map(|(output_index, output)| {
+                let bytes = output.locking_script().len();
+                if !policy.enabled {
+                    Some(DatacarrierViolation::Disabled {
+                        output_index: *output_index,
+                    })
+                } else if bytes > policy.max_bytes {
+                    Some(DatacarrierViolation::TooLarge {
+                        output_index: *output_index,
+                        bytes,
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<DatacarrierViolation>>();
+
+        if policy.enabled && data_outputs.len() > policy.max_outputs {
+            violations.push(DatacarrierViolation::TooManyOutputs {
+                outputs: data_outputs.len(),
+            });
+        }
+
+        violations
+    }
+
+    // `OP_RETURN` followed only by data pushes, the null data outputs which
+    // the datacarrier options apply to. Like Bitcoin Core every opcode up to
+    // `OP_16` counts as a push
+    fn is_null_data(locking_script: &[u8]) -> bool {
+        locking_script.first() == Some(&0x6a)
+            && Script::disassemble(&locking_script[1..]).is_ok_and(|instructions| {
+                instructions
+                    .iter()
+                    .all(|instruction| instruction.opcode().is_push())
+            })
+    }
+}
+
+impl TxOutput {
+    /// The smallest amount of the output a node relays, the fee of the output and
+    /// of the input spending it at `DUST_RELAY_FEE_RATE`. Spending a witness
+    /// program is assumed to take 67 virtual bytes and any other output 148.
+    /// Unspendable `OP_RETURN` outputs have no dust threshold
+    pub fn dust_threshold(&self) -> u64 {
+        let locking_script = self.locking_script();
+        if locking_script.first() == Some(&0x6a) {
+            return 0;
+        }
+
+        let output_size =
+            8 + VarInt::encode(locking_script.len() as u64).len() + locking_script.len();
+        let input_size = match self.script_type() {
+            ScriptType::P2WPKH
+            | ScriptType::P2WSH
+            | ScriptType::P2TR
+            | ScriptType::P2A
+            | ScriptType::WitnessUnknown(_) => 32 + 4 + 1 + 107 / 4 + 4,
+            _ => 32 + 4 + 1 + 107 + 4,
+        };
+
+        (output_size + input_size) as u64 * DUST_RELAY_FEE_RATE
+    }
+
+    /// Whether the amount of the output is below its dust threshold
+    pub fn is_dust(&self) -> bool {
+        self.amount() < self.dust_threshold()
+    }
+}
+
+/// A scriptSig which a node does not relay. Byte ranges are offsets into the
+/// scriptSig of the input
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum InputViolation {
+    /// An opcode other than a data push or a small integer
+    NotPushOnly {
+        input_index: usize,
+        opcode: u8,
+        range: Range<usize>,
+    },
+    /// A data push going past the end of the scriptSig
+    Truncated {
+        input_index: usize,
+        range: Range<usize>,
+    },
+    /// The redeem script pushed last has more than `MAX_P2SH_SIGOPS`
+    /// signature operations
+    TooManySigops {
+        input_index: usize,
+        sigops: usize,
+        range: Range<usize>,
+    },
+}
+
+impl BtcTx {
+    /// Check that every scriptSig only pushes data and that the redeem script
+    /// of inputs spending P2SH outputs has at most `MAX_P2SH_SIGOPS` signature
+    /// operations. `prevouts` are the outputs spent by every input in order
+    pub fn check_input_scripts(&self, prevouts: &[TxOutput]) -> io::Result<Vec<InputViolation>> {
+        self.check_prevouts(prevouts)?;
+
+        let mut violations = Vec::<InputViolation>::new();
+        for (input_index, (input, prevout)) in self.inputs().iter().zip(prevouts).enumerate() {
+            let signature_script = input.signature_script();
+            let mut position = 0usize;
+            // The range of the data pushed last
+            let mut last_push = Option::<Range<usize>>::None;
+
+            while position < signature_script.len() {
+                let start = position;
+                let (opcode, push_len) = match Script::read_opcode(signature_script, &mut position)
+                {
+                    Ok(opcode) => opcode,
+                    Err(_) => {
+                        violations.push(InputViolation::Truncated {
+                            input_index,
+                            range: start..signature_script.len(),
+                        });
+                        last_push = None;
+                        break;
+                    }
+                };
+
+                last_push = push_len.map(|push_len| position - push_len..position);
+                // `OP_16` is the last opcode counted as a push
+                if opcode > 0x60 {
+                    violations.push(InputViolation::NotPushOnly {
+                        input_index,
+                        opcode,
+                        range: start..position,
+                    });
+                }
+            }
+
+            if prevout.script_type() != ScriptType::P2SH {
+                continue;
+            }
+            if let Some(range) = last_push {
+                let sigops = Script::new(&signature_script[range.clone()])
+                    .metrics()
+                    .map(|metrics| metrics.sigops)
+                    .unwrap_or_default();
+                if sigops > MAX_P2SH_SIGOPS {
+                    violations.push(InputViolation::TooManySigops {
+                        input_index,
+                        sigops,
+                        range,
+                    });
+                }
+            }
+        }
+
+        Ok(violations)
+    }
+}
+
+/// A TRUC rule of BIP431 broken by an unconfirmed transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TrucViolation {
+    /// A TRUC transaction larger than `TRUC_MAX_VSIZE`
+    TooLarge { txid: Txid, vsize: u64 },
+    /// A TRUC transaction spending an unconfirmed TRUC transaction
+    /// which is larger than `TRUC_CHILD_MAX_VSIZE`
+    ChildTooLarge { txid: Txid, vsize: u64 },
+    /// A TRUC transaction with more than one unconfirmed ancestor
+    TooManyAncestors { txid: Txid, ancestors: usize },
+    /// A TRUC transaction with more than one unconfirmed descendant
+    TooManyDescendants { txid: Txid, descendants: usize },
+    /// A TRUC transaction spending an unconfirmed transaction of another version
+    SpendsNonTruc { txid: Txid, parent: Txid },
+    /// A transaction of another version spending an unconfirmed TRUC transaction
+    NonTrucSpendsTruc { txid: Txid, parent: Txid },
+}
+
+impl BtcTx {
+    /// Whether the transaction is a version 3 TRUC transaction
+    pub fn is_truc(&self) -> bool {
+        *self.version() == TxVersion::Custom(TRUC_VERSION)
+    }
+
+    /// The signature operation cost of the transaction like Bitcoin Core counts
+    /// it. Signature operations of scriptSigs, locking scripts and redeem
+    /// scripts cost 4 and those of witness programs cost 1. `prevouts` are the
+    /// outputs spent by every input in order
+    pub fn sigop_cost(&self, prevouts: &[TxOutput]) -> io::Result<usize> {
+        self.check_prevouts(prevouts)?;
+
+        let legacy = self
+            .inputs()
+            .iter()
+            .map(|input| sigops(input.signature_script(), false))
+            .chain(
+                self.outputs()
+                    .iter()
+                    .map(|output| sigops(output.locking_script(), false)),
+            )
+            .sum::<usize>();
+
+        let spends = self
+            .inputs()
+            .iter()
+            .zip(prevouts)
+            .map(|(input, prevout)| {
+                let mut program = prevout.locking_script().to_vec();
+                let mut cost = 0usize;
+
+                // The redeem script is pushed last and may be a witness program
+                if prevout.script_type() == ScriptType::P2SH {
+                    if let Some(redeem_script) =
+                        StandardScripts::read_pushes(input.signature_script())
+                            .ok()
+                            .and_then(|mut pushes| pushes.pop())
+                    {
+                        cost += sigops(&redeem_script, true) * 4;
+                        program = redeem_script;
+                    }
+                }
+
+                cost + match ScriptType::from_script(&program) {
+                    ScriptType::P2WPKH => 1,
+                    ScriptType::P2WSH => input
+                        .witness()
+                        .last()
+                        .map(|witness_script| sigops(witness_script, true))
+                        .unwrap_or_default(),
+                    _ => 0,
+                }
+            })
+            .sum::<usize>();
+
+        Ok(legacy * 4 + spends)
+    }
+
+    /// The virtual size relay policy limits, the larger of the virtual size and
+    /// the signature operation cost times `DEFAULT_BYTES_PER_SIGOP` divided by 4
+    pub fn sigop_adjusted_vsize(&self, prevouts: &[TxOutput]) -> io::Result<u64> {
+        let sigop_weight = self.sigop_cost(prevouts)? * DEFAULT_BYTES_PER_SIGOP;
+
+        Ok(self.weight().max(sigop_weight).div_ceil(4) as u64)
+    }
+
+    pub(crate) fn check_prevouts(&self, prevouts: &[TxOutput]) -> io::Result<()> {
+        if prevouts.len() != self.inputs().len() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "The transaction has {} inputs but {} spent outputs were given",
+                    self.inputs().len(),
+                    prevouts.len()
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Check the TRUC rules of BIP431 on unconfirmed transactions, each paired with
+/// the outputs spent by its inputs in order. They are the transactions of a
+/// package with their unconfirmed ancestors and descendants in the mempool, any
+/// other spent output is confirmed. Like for the other policy checks of Bitcoin
+/// Core every rule broken is reported
+/// - a TRUC transaction has at most one unconfirmed ancestor and one unconfirmed descendant
+/// - TRUC transactions only spend unconfirmed TRUC transactions and are only
+///   spent by unconfirmed TRUC transactions
+/// - a TRUC transaction is at most `TRUC_MAX_VSIZE` and at most
+///   `TRUC_CHILD_MAX_VSIZE` when it spends an unconfirmed transaction
+pub fn check_truc(unconfirmed: &[(BtcTx, Vec<TxOutput>)]) -> io::Result<Vec<TrucViolation>> {
+    let txids = unconfirmed
+        .iter()
+        .enumerate()
+        .map(|(index, (tx, _))| (tx.txid(), index))
+        .collect::<BTreeMap<Txid, usize>>();
+    // The unconfirmed transactions each transaction spends
+    let parents = unconfirmed
+        .iter()
+        .map(|(tx, _)| {
+            tx.inputs()
+                .iter()
+                .filter_map(|input| txids.get(&input.previous_tx_id()).copied())
+                .collect::<BTreeSet<usize>>()
+        })
+        .collect::<Vec<BTreeSet<usize>>>();
+    // Walk the parents or children of `index` up to every relative
+    let relatives = |index: usize, is_parent: &dyn Fn(usize, usize) -> bool| {
+        let mut found = BTreeSet::<usize>::new();
+        let mut next = vec![index];
+        while let Some(current) = next.pop() {
+            (0..unconfirmed.len())
+                .filter(|other| *other != index && is_parent(*other, current))
+                .for_each(|other| {
+                    if found.insert(other) {
+                        next.push(other);
+                    }
+                });
+        }
+
+        found
+    };
+
+    let mut violations = Vec::<TrucViolation>::new();
+    for (index, (tx, prevouts)) in unconfirmed.iter().enumerate() {
+        let txid = tx.txid();
+
+        if !tx.is_truc() {
+            violations.extend(
+                parents[index]
+                    .iter()
+                    .filter(|parent| unconfirmed[**parent].0.is_truc())
+                    .map(|parent| TrucViolation::NonTrucSpendsTruc {
+                        txid,
+                        parent: unconfirmed[*parent].0.txid(),
+                    }),
+            );
+            continue;
+        }
+
+        let vsize = tx.sigop_adjusted_vsize(prevouts)?;
+        if vsize > TRUC_MAX_VSIZE {
+            violations.push(TrucViolation::TooLarge { txid, vsize });
+        } else if !parents[index].is_empty() && vsize > TRUC_CHILD_MAX_VSIZE {
+            violations.push(TrucViolation::ChildTooLarge { txid, vsize });
+        }
+
+        violations.extend(
+            parents[index]
+                .iter()
+                .filter(|parent| !unconfirmed[**parent].0.is_truc())
+                .map(|parent| TrucViolation::SpendsNonTruc {
+                    txid,
+                    parent: unconfirmed[*parent].0.txid(),
+                }),
+        );
+
+        let ancestors = relatives(index, &|other, current| parents[current].contains(&other)).len();
+        if ancestors > 1 {
+            violations.push(TrucViolation::TooManyAncestors { txid, ancestors });
+        }
+        let descendants =
+            relatives(index, &|other, current| parents[other].contains(&current)).len();
+        if descendants > 1 {
+            violations.push(TrucViolation::TooManyDescendants { txid, descendants });
+        }
+    }
+
+    Ok(violations)
+}
+
+// The signature operations of a script counted like Bitcoin Core. Multisig
+// counts as its number of keys when `accurate` and they are pushed just
+// before, otherwise as 20. Counting stops at a push going past the end
+fn sigops(script: &[u8], accurate: bool) -> usize {
+    let mut position = 0usize;
+    let mut sigops = 0usize;
+    let mut previous = Option::<u8>::None;
+
+    while position < script.len() {
+        let Ok((opcode, _)) = Script::read_opcode(script, &mut position) else {
+            break;
+        };
+        sigops += match (opcode, previous) {
+            // OP_CHECKSIG and OP_CHECKSIGVERIFY
+            (0xac | 0xad, _) => 1,
+            // OP_CHECKMULTISIG and OP_CHECKMULTISIGVERIFY after OP_1 to OP_16
+            (0xae | 0xaf, Some(keys @ 0x51..=0x60)) if accurate => (keys - 0x50) as usize,
+            (0xae | 0xaf, _) => 20,
+            _ => 0,
+        };
+        previous = Some(opcode);
+    }
+
+    sigops
+}
+
+#[cfg(test)]
+mod policy_sanity_checks {
+    use crate::{
+        check_truc,
+        fixtures::{tx, tx_to, txid},
+        BtcTx, DatacarrierPolicy, DatacarrierViolation, Hash256, InputViolation, Script,
+        TrucViolation, TxInput, TxOutput, TxVersion, Txid, Witness, TRUC_VERSION,
+    };
+
+    #[test]
+    fn datacarrier_limits() {
+        let op_return = |len: usize| {
+            let mut script = vec![0x6a, 0x4c, len as u8];
+            script.resize(len + 3, 0xaa);
+            TxOutput::new(0, script)
+        };
+        let tx = BtcTx::new(
+            TxVersion::Two,
+            vec![TxInput::new(
+                Txid::new(Hash256::new([1u8; 32])),
+                0,
+                Vec::new(),
+                0,
+            )],
+            vec![
+                TxOutput::new(1_000, vec![0x51]),
+                op_return(80),
+                op_return(100),
+            ],
+            0,
+        );
+
+        assert_eq!(
+            vec![
+                DatacarrierViolation::TooLarge {
+                    output_index: 2,
+                    bytes: 103
+                },
+                DatacarrierViolation::TooManyOutputs { outputs: 2 },
+            ],
+            tx.check_datacarrier(&DatacarrierPolicy::default())
+        );
+
+        let permissive = DatacarrierPolicy::new()
+            .with_max_bytes(100_000)
+            .with_max_outputs(usize::MAX);
+        assert!(permissive.allows_multiple());
+        assert!(tx.check_datacarrier(&permissive).is_empty());
+
+        assert_eq!(
+            2,
+            tx.check_datacarrier(&DatacarrierPolicy::new().disabled())
+                .len()
+        );
+    }
+
+    #[test]
+    fn dust_thresholds() {
+        let output = |amount: u64, locking_script: &str| {
+            TxOutput::new(amount, hex::decode(locking_script).unwrap())
+        };
+        let p2wpkh = "0014751e76e8199196d454941c45d1b3a323f1433bd6";
+
+        assert_eq!(
+            546,
+            output(0, "76a9140ce17649c1306c291ca9e587f8793b5b06563cea88ac").dust_threshold()
+        );
+        assert_eq!(294, output(0, p2wpkh).dust_threshold());
+        assert_eq!(
+            330,
+            output(
+                0,
+                "5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c"
+            )
+            .dust_threshold()
+        );
+        assert_eq!(240, TxOutput::pay_to_anchor(0).dust_threshold());
+        assert_eq!(0, output(0, "6a0401020304").dust_threshold());
+
+        assert!(output(293, p2wpkh).is_dust());
+        assert!(!output(294, p2wpkh).is_dust());
+    }
+
+    #[test]
+    fn null_data_pushes() {
+        let violations = |locking_script: Vec<u8>| {
+            let tx = BtcTx::new(
+                TxVersion::Two,
+                vec![TxInput::new(
+                    Txid::new(Hash256::new([1u8; 32])),
+                    0,
+                    Vec::new(),
+                    0,
+                )],
+                vec![TxOutput::new(0, locking_script)],
+                0,
+            );
+
+            tx.check_datacarrier(&DatacarrierPolicy::new().disabled())
+                .len()
+        };
+
+        // OP_1, OP_1NEGATE and OP_16 are pushes
+        assert_eq!(1, violations(vec![0x6a, 0x51]));
+        assert_eq!(1, violations(vec![0x6a, 0x4f, 0x60]));
+        assert_eq!(
+            1,
+            violations([&[0x6a, 0x4c, 0x50][..], &[0xaa; 80]].concat())
+        );
+        assert_eq!(1, violations(vec![0x6a]));
+        // Not null data so the datacarrier options do not apply
+        assert_eq!(0, violations(vec![0x6a, 0x76]));
+        assert_eq!(0, violations(vec![0x6a, 0x4c, 0x05, 0xaa]));
+    }
+
+    #[test]
+    fn input_scripts() {
+        let redeem_script = [0xac; 16];
+        let prevouts = [
+            TxOutput::new(1_000, [&[0xa9, 0x14][..], &[0x11; 20], &[0x87]].concat()),
+            TxOutput::new(1_000, vec![0x51]),
+        ];
+        let tx = BtcTx::new(
+            TxVersion::Two,
+            vec![
+                // OP_0 OP_DUP <16 OP_CHECKSIG>
+                TxInput::new(
+                    Txid::new(Hash256::new([1u8; 32])),
+                    0,
+                    [&[0x00, 0x76, 0x10][..], &redeem_script].concat(),
+                    0,
+                ),
+                // OP_PUSHDATA1 of 5 bytes with only one byte
+                TxInput::new(
+                    Txid::new(Hash256::new([1u8; 32])),
+                    1,
+                    vec![0x51, 0x4c, 0x05, 0x01],
+                    0,
+                ),
+            ],
+            vec![TxOutput::new(1_000, vec![0x51])],
+            0,
+        );
+
+        assert_eq!(
+            vec![
+                InputViolation::NotPushOnly {
+                    input_index: 0,
+                    opcode: 0x76,
+                    range: 1..2,
+                },
+                InputViolation::TooManySigops {
+                    input_index: 0,
+                    sigops: 16,
+                    range: 3..19,
+                },
+                InputViolation::Truncated {
+                    input_index: 1,
+                    range: 1..4,
+                },
+            ],
+            tx.check_input_scripts(&prevouts).unwrap()
+        );
+        assert!(tx.check_input_scripts(&prevouts[..1]).is_err());
+    }
+
+    #[test]
+    fn sigop_cost() {
+        let multisig = Script::new([&[0x52][..], &[0x21; 3 * 34], &[0x53, 0xae]].concat());
+        let prevouts = [
+            TxOutput::new(1_000, [&[0x00, 0x14][..], &[0x11; 20]].concat()),
+            TxOutput::new(1_000, multisig.to_p2wsh().to_p2sh().as_bytes().to_vec()),
+        ];
+        // Spending a P2WPKH output and a 2-of-3 multisig P2WSH output nested in P2SH
+        let wrapped = BtcTx::new(
+            TxVersion::Two,
+            vec![
+                TxInput::new(txid(1), 0, Vec::new(), u32::MAX),
+                TxInput::new(
+                    txid(1),
+                    1,
+                    [&[0x22][..], multisig.to_p2wsh().as_bytes()].concat(),
+                    u32::MAX,
+                )
+                .with_witness(Witness::from_slice(&[&[], multisig.as_bytes()])),
+            ],
+            // A bare multisig output counts as 20 signature operations
+            vec![TxOutput::new(1_000, multisig.as_bytes().to_vec())],
+            0,
+        );
+
+        assert_eq!(80 + 1 + 3, wrapped.sigop_cost(&prevouts).unwrap());
+        assert_eq!(
+            (wrapped.weight().max(84 * 20) as u64).div_ceil(4),
+            wrapped.sigop_adjusted_vsize(&prevouts).unwrap()
+        );
+        assert!(wrapped.sigop_cost(&prevouts[..1]).is_err());
+    }
+
+    #[test]
+    fn truc_rules() {
+        let truc = |tx: BtcTx| {
+            BtcTx::new(
+                TxVersion::Custom(TRUC_VERSION),
+                tx.inputs().to_vec(),
+                tx.outputs().to_vec(),
+                0,
+            )
+        };
+        let prevouts = |tx: &BtcTx| vec![TxOutput::new(10_000, vec![0x51]); tx.inputs().len()];
+        let check = |txs: &[&BtcTx]| {
+            check_truc(
+                &txs.iter()
+                    .map(|tx| ((*tx).clone(), prevouts(tx)))
+                    .collect::<Vec<(BtcTx, Vec<TxOutput>)>>(),
+            )
+            .unwrap()
+        };
+
+        let parent = truc(tx(&[(txid(9), 0)], &[4_000, 4_000]));
+        let child = truc(tx(&[(parent.txid(), 0)], &[3_000]));
+        assert!(parent.is_truc() && !tx(&[], &[]).is_truc());
+        assert!(check(&[&parent, &child]).is_empty());
+
+        let sibling = truc(tx(&[(parent.txid(), 1)], &[3_000]));
+        let grandchild = truc(tx(&[(child.txid(), 0)], &[2_000]));
+        assert_eq!(
+            vec![
+                TrucViolation::TooManyDescendants {
+                    txid: parent.txid(),
+                    descendants: 3
+                },
+                TrucViolation::TooManyAncestors {
+                    txid: grandchild.txid(),
+                    ancestors: 2
+                },
+            ],
+            check(&[&parent, &child, &sibling, &grandchild])
+        );
+
+        let non_truc_child = tx(&[(parent.txid(), 0)], &[3_000]);
+        let non_truc_parent = tx(&[(txid(9), 1)], &[4_000]);
+        let truc_child = truc(tx(&[(non_truc_parent.txid(), 0)], &[3_000]));
+        assert_eq!(
+            vec![
+                TrucViolation::NonTrucSpendsTruc {
+                    txid: non_truc_child.txid(),
+                    parent: parent.txid()
+                },
+                TrucViolation::SpendsNonTruc {
+                    txid: truc_child.txid(),
+                    parent: non_truc_parent.txid()
+                },
+            ],
+            check(&[&parent, &non_truc_child, &non_truc_parent, &truc_child])
+        );
+
+        // 60 signature operations make a small child count as 1,200 vB
+        let sigops_child = truc(tx_to(&[(parent.txid(), 0)], vec![(3_000, vec![0xac; 60])]));
+        assert_eq!(
+            vec![TrucViolation::ChildTooLarge {
+                txid: sigops_child.txid(),
+                vsize: 1_200
+            }],
+            check(&[&parent, &sigops_child])
+        );
+        assert!(check(&[&sigops_child]).is_empty());
+        let large = truc(tx_to(&[(txid(9), 2)], vec![(3_000, vec![0xac; 600])]));
+        assert_eq!(
+            vec![TrucViolation::TooLarge {
+                txid: large.txid(),
+                vsize: 12_000
+            }],
+            check(&[&large])
+        );
+
+        let error = check_truc(&[(parent.clone(), Vec::new())]).unwrap_err();
+        assert_eq!(std::io::ErrorKind::InvalidInput, error.kind());
+    }
+}
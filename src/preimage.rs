@@ -0,0 +1,148 @@
+use crate::{BtcTx, Checksum, Hash160, Hash256, StandardScripts, TxInput, Witness};
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
+
+/// A hash committed to by a script with one of the hashing opcodes, in the
+/// byte order the script pushes it like the payment hash of an `Htlc`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PreimageHash {
+    /// `OP_SHA256` as used by HTLCs and Lightning payment hashes
+    Sha256(Hash256),
+    /// `OP_HASH160` which is the RIPEMD160 of the SHA256
+    Hash160(Hash160),
+    /// `OP_RIPEMD160`
+    Ripemd160(Hash160),
+    /// `OP_HASH256` which is the SHA256 of the SHA256
+    Hash256(Hash256),
+}
+
+impl PreimageHash {
+    /// Whether `data` hashes to this hash
+    pub fn matches(&self, data: &[u8]) -> bool {
+        match self {
+            Self::Sha256(hash) => hash.as_ref() == Sha256::digest(data).as_slice(),
+            Self::Hash160(hash) => {
+                hash.as_ref() == Ripemd160::digest(Sha256::digest(data)).as_slice()
+            }
+            Self::Ripemd160(hash) => hash.as_ref() == Ripemd160::digest(data).as_slice(),
+            Self::Hash256(hash) => hash.as_ref() == Checksum::sha256d(data),
+        }
+    }
+}
+
+/// A preimage revealed by an input of a transaction
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RevealedPreimage {
+    /// The index of the input revealing the preimage
+    pub input_index: usize,
+    /// The preimage
+    pub preimage: Vec<u8>,
+}
+
+impl Witness {
+    /// The elements of the witness that hash to `hash`
+    pub fn extract_preimages(&self, hash: &PreimageHash) -> Vec<&[u8]> {
+        self.iter()
+            .filter(|element| hash.matches(element))
+            .collect()
+    }
+}
+
+impl TxInput {
+    /// The data pushed by the scriptSig or the elements of the witness that
+    /// hash to `hash`. A scriptSig which is not push only has no preimages
+    pub fn extract_preimages(&self, hash: &PreimageHash) -> Vec<Vec<u8>> {
+        let mut preimages = StandardScripts::read_pushes(self.signature_script())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|push| hash.matches(push))
+            .collect::<Vec<Vec<u8>>>();
+        preimages.extend(
+            self.witness()
+                .extract_preimages(hash)
+                .into_iter()
+                .map(|element| element.to_vec()),
+        );
+
+        preimages
+    }
+}
+
+impl BtcTx {
+    /// Every preimage of `hash` revealed by the inputs of the transaction, for
+    /// example the secret of an HTLC which was claimed
+    pub fn extract_preimages(&self, hash: &PreimageHash) -> Vec<RevealedPreimage> {
+        self.inputs()
+            .iter()
+            .enumerate()
+            .flat_map(|(input_index, input)| {
+                input
+                    .extract_preimages(hash)
+                    .into_iter()
+                    .map(move |preimage| RevealedPreimage {
+                        input_index,
+                        preimage,
+                    })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod preimage_sanity_checks {
+    use crate::{
+        BtcTx, Hash160, Hash256, PreimageHash, RevealedPreimage, TxInput, TxOutput, TxVersion,
+        Txid, Witness,
+    };
+    use hex_literal::hex;
+
+    #[test]
+    fn extract_preimages() {
+        // The SHA256 and HASH160 of 32 zero bytes
+        let preimage = [0u8; 32];
+        let sha256 = PreimageHash::Sha256(Hash256::new(hex!(
+            "66687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925"
+        )));
+        let hash160 = PreimageHash::Hash160(Hash160::new(hex!(
+            "b8bcb07f6344b42ab04250c86a6e8b75d3fdbbc6"
+        )));
+
+        // A claim revealing the preimage in the witness and a P2SH claim in the scriptSig
+        let tx = BtcTx::new(
+            TxVersion::Two,
+            vec![
+                TxInput::new(Txid::new(Hash256::new([1u8; 32])), 0, Vec::new(), 0)
+                    .with_witness(Witness::from_slice(&[&[0x30; 72], &preimage, &[0x63]])),
+                TxInput::new(
+                    Txid::new(Hash256::new([1u8; 32])),
+                    1,
+                    [&[0x20][..], &preimage].concat(),
+                    0,
+                ),
+            ],
+            vec![TxOutput::new(1_000, vec![0x51])],
+            0,
+        );
+
+        assert_eq!(
+            vec![preimage.as_slice()],
+            tx.inputs()[0].witness().extract_preimages(&sha256)
+        );
+        assert_eq!(
+            vec![
+                RevealedPreimage {
+                    input_index: 0,
+                    preimage: preimage.to_vec(),
+                },
+                RevealedPreimage {
+                    input_index: 1,
+                    preimage: preimage.to_vec(),
+                },
+            ],
+            tx.extract_preimages(&hash160)
+        );
+        assert!(tx
+            .extract_preimages(&PreimageHash::Sha256(Hash256::new([0u8; 32])))
+            .is_empty());
+    }
+}
@@ -0,0 +1,298 @@
+use crate::{earliest_tip, BlockTime, BtcTx, OutPoint, Txid};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Where a pre-signed transaction stands at the chain tip
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PresignedStatus {
+    /// The transaction is confirmed in this block
+    Confirmed(BlockTime),
+    /// The transaction can be mined in the next block
+    Valid,
+    /// The timelocks of the transaction are not met yet. They are met once
+    /// the chain tip reaches this height and median time past
+    Timelocked(BlockTime),
+    /// The transaction spends outputs of these transactions which are not
+    /// confirmed yet
+    WaitingForParents(BTreeSet<Txid>),
+    /// An output spent by the transaction was spent by this confirmed
+    /// transaction instead so it can never be mined
+    Conflicted(Txid),
+    /// This parent of the transaction can never be mined so neither can it
+    ParentConflicted(Txid),
+}
+
+/// A transaction whose status changed between two chain tips
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StatusChange {
+    /// The transaction ID
+    pub txid: Txid,
+    /// The status at the previous chain tip, `None` for a transaction added since
+    pub from: Option<PresignedStatus>,
+    /// The status at the new chain tip
+    pub to: PresignedStatus,
+}
+
+/// Pre-signed transactions spending the same output, of which at most one
+/// can be mined, like the branches of a vault or a channel
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SpendConflict {
+    /// The output spent by every transaction
+    pub outpoint: OutPoint,
+    /// The transactions spending the output
+    pub txids: Vec<Txid>,
+}
+
+/// Watches a tree of pre-signed transactions, like the commitment and HTLC
+/// transactions of an L2 channel or the unvault and recovery transactions of a
+/// vault, and reports which of them become valid or invalid as the chain grows
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PresignedWatcher {
+    txs: BTreeMap<Txid, BtcTx>,
+    // The confirmed transactions of the tree and the ones it spends from
+    confirmed: BTreeMap<Txid, BlockTime>,
+    // The confirmed transactions spending each output
+    spent: BTreeMap<OutPoint, Txid>,
+    tip: Option<BlockTime>,
+    // The statuses at the last chain tip
+    statuses: BTreeMap<Txid, PresignedStatus>,
+}
+
+impl PresignedWatcher {
+    /// A watcher without transactions which has not seen a chain tip
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Watch a pre-signed transaction
+    pub fn add(&mut self, tx: BtcTx) -> Txid {
+        let txid = tx.txid();
+        self.txs.insert(txid, tx);
+
+        txid
+    }
+
+    /// Stop watching a transaction
+    pub fn remove(&mut self, txid: &Txid) -> Option<BtcTx> {
+        self.statuses.remove(txid);
+        self.txs.remove(txid)
+    }
+
+    /// The watched transaction `txid`
+    pub fn get(&self, txid: &Txid) -> Option<&BtcTx> {
+        self.txs.get(txid)
+    }
+
+    /// Record that the transaction `txid`, watched or one the tree spends
+    /// from, was confirmed. `confirmation` has the height of the confirming
+    /// block and the median time past of the block before it as BIP68 requires
+    pub fn confirmed(&mut self, txid: Txid, confirmation: BlockTime) {
+        self.confirmed.insert(txid, confirmation);
+
+        if let Some(tx) = self.txs.get(&txid) {
+            tx.inputs().iter().for_each(|input| {
+                self.spent.insert(input.previous_output(), txid);
+            });
+        }
+    }
+
+    /// Record that a confirmed transaction `spender` which is not watched spent
+    /// `outpoint`, which invalidates the watched transactions spending it
+    pub fn spent(&mut self, outpoint: OutPoint, spender: Txid) {
+        self.spent.insert(outpoint, spender);
+    }
+
+    /// Forget the confirmation of `txid` after a reorganization
+    pub fn unconfirmed(&mut self, txid: &Txid) {
+        self.confirmed.remove(txid);
+        self.spent.retain(|_, spender| spender != txid);
+    }
+
+    /// The last chain tip passed to `PresignedWatcher::update_tip()`
+    pub fn tip(&self) -> Option<BlockTime> {
+        self.tip
+    }
+
+    /// The status of every watched transaction at the last chain tip,
+    /// including confirmations recorded since
+    pub fn statuses(&self) -> BTreeMap<Txid, PresignedStatus> {
+        let mut statuses = BTreeMap::<Txid, PresignedStatus>::new();
+        self.txs.keys().for_each(|txid| {
+            self.status(txid, &mut statuses);
+        });
+
+        statuses
+    }
+
+    /// Move to a new chain tip and return the transactions whose status
+    /// changed since the previous one
+    pub fn update_tip(&mut self, tip: BlockTime) -> Vec<StatusChange> {
+        self.tip = Some(tip);
+        let statuses = self.statuses();
+
+        let changes = statuses
+            .iter()
+            .filter(|(txid, status)| self.statuses.get(txid) != Some(status))
+            .map(|(txid, status)| StatusChange {
+                txid: *txid,
+                from: self.statuses.get(txid).cloned(),
+                to: status.clone(),
+            })
+            .collect();
+        self.statuses = statuses;
+
+        changes
+    }
+
+    /// The outputs spent by more than one watched transaction
+    pub fn conflicts(&self) -> Vec<SpendConflict> {
+        let mut spenders = BTreeMap::<OutPoint, Vec<Txid>>::new();
+        self.txs.iter().for_each(|(txid, tx)| {
+            tx.inputs().iter().for_each(|input| {
+                spenders
+                    .entry(input.previous_output())
+                    .or_default()
+                    .push(*txid)
+            })
+        });
+
+        spenders
+            .into_iter()
+            .filter(|(_, txids)| txids.len() > 1)
+            .map(|(outpoint, txids)| SpendConflict { outpoint, txids })
+            .collect()
+    }
+
+    // The parents of a transaction are watched transactions so their statuses
+    // are found first
+    fn status(
+        &self,
+        txid: &Txid,
+        statuses: &mut BTreeMap<Txid, PresignedStatus>,
+    ) -> Option<PresignedStatus> {
+        if let Some(status) = statuses.get(txid) {
+            return Some(status.clone());
+        }
+        let tx = self.txs.get(txid)?;
+
+        let status = if let Some(confirmation) = self.confirmed.get(txid) {
+            PresignedStatus::Confirmed(*confirmation)
+        } else if let Some(spender) = tx
+            .inputs()
+            .iter()
+            .filter_map(|input| self.spent.get(&input.previous_output()))
+            .find(|spender| *spender != txid)
+        {
+            PresignedStatus::Conflicted(*spender)
+        } else if let Some(parent) = tx
+            .inputs()
+            .iter()
+            .map(|input| input.previous_output().txid())
+            .find(|parent| {
+                matches!(
+                    self.status(parent, statuses),
+                    Some(PresignedStatus::Conflicted(_) | PresignedStatus::ParentConflicted(_))
+                )
+            })
+        {
+            PresignedStatus::ParentConflicted(parent)
+        } else {
+            let prevouts = tx
+                .inputs()
+                .iter()
+                .map(|input| self.confirmed.get(&input.previous_output().txid()).copied())
+                .collect::<Vec<Option<BlockTime>>>();
+            let unconfirmed = tx
+                .inputs()
+                .iter()
+                .zip(&prevouts)
+                .filter(|(_, prevout)| prevout.is_none())
+                .map(|(input, _)| input.previous_output().txid())
+                .collect::<BTreeSet<Txid>>();
+
+            let tip = self.tip.unwrap_or_default();
+            // Every spent output is confirmed once the parents are so the
+            // relative timelocks have started
+            let earliest = earliest_tip(tx, &prevouts).unwrap_or_default();
+            if !unconfirmed.is_empty() {
+                PresignedStatus::WaitingForParents(unconfirmed)
+            } else if tip.height >= earliest.height
+                && tip.median_time_past >= earliest.median_time_past
+            {
+                PresignedStatus::Valid
+            } else {
+                PresignedStatus::Timelocked(earliest)
+            }
+        };
+        statuses.insert(*txid, status.clone());
+
+        Some(status)
+    }
+}
+
+#[cfg(test)]
+mod presigned_sanity_checks {
+    use crate::{
+        fixtures::txid, BlockTime, BtcTx, PresignedStatus, PresignedWatcher, TxInput, TxOutput,
+        TxVersion, Txid,
+    };
+
+    fn tx(parent: Txid, sequence_number: u32, locktime: u32, amount: u64) -> BtcTx {
+        BtcTx::new(
+            TxVersion::Two,
+            vec![TxInput::new(parent, 0, Vec::new(), sequence_number)],
+            vec![TxOutput::new(amount, vec![0x51])],
+            locktime,
+        )
+    }
+
+    #[test]
+    fn vault_tree() {
+        let block = |height| BlockTime {
+            height,
+            median_time_past: 1_700_000_000 + height * 600,
+        };
+        let deposit = txid(1);
+
+        // The unvault can be recovered at once or spent after 144 blocks
+        let mut watcher = PresignedWatcher::new();
+        let unvault = watcher.add(tx(deposit, 0xfffffffd, 0, 90_000));
+        let spend = watcher.add(tx(unvault, 144, 0, 80_000));
+        let recovery = watcher.add(tx(unvault, 0xfffffffd, 0, 85_000));
+        let conflicts = watcher.conflicts();
+        assert_eq!(1, conflicts.len());
+        assert_eq!(2, conflicts[0].txids.len());
+
+        let changes = watcher.update_tip(block(800_000));
+        assert_eq!(3, changes.len());
+        assert_eq!(
+            PresignedStatus::WaitingForParents([deposit].into()),
+            watcher.statuses()[&unvault]
+        );
+
+        watcher.confirmed(deposit, block(800_000));
+        watcher.confirmed(unvault, block(800_001));
+        watcher.update_tip(block(800_001));
+        let statuses = watcher.statuses();
+        assert_eq!(
+            PresignedStatus::Timelocked(BlockTime {
+                height: 800_144,
+                median_time_past: 0
+            }),
+            statuses[&spend]
+        );
+        assert_eq!(PresignedStatus::Valid, statuses[&recovery]);
+
+        let changes = watcher.update_tip(block(800_144));
+        assert_eq!(spend, changes[0].txid);
+        assert_eq!(PresignedStatus::Valid, changes[0].to);
+
+        // Confirming the recovery invalidates the spend
+        watcher.confirmed(recovery, block(800_145));
+        let changes = watcher.update_tip(block(800_145));
+        assert_eq!(2, changes.len());
+        assert_eq!(
+            PresignedStatus::Conflicted(recovery),
+            watcher.statuses()[&spend]
+        );
+    }
+}
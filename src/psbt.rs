@@ -0,0 +1,1397 @@
+use crate::{
+    BtcTx, Descriptor, Hash256, KeySource, Script, ScriptType, SpendType, TxInput, TxOutput, VarInt,
+};
+use std::{
+    collections::BTreeSet,
+    io::{self, Cursor, ErrorKind, Read},
+};
+
+/// The magic bytes every PSBT starts with, `psbt` followed by 0xff
+pub const PSBT_MAGIC: [u8; 5] = *b"psbt\xff";
+
+/// The largest PSBT in bytes that is decoded. PSBTs carrying the full previous
+/// transactions of many inputs stay well below it
+pub const MAX_PSBT_SIZE: usize = 10_000_000;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// The key type of the unsigned transaction in the global map
+const PSBT_GLOBAL_UNSIGNED_TX: u8 = 0x00;
+
+// The key types of the previous outputs spent by an input
+const PSBT_IN_NON_WITNESS_UTXO: u8 = 0x00;
+const PSBT_IN_WITNESS_UTXO: u8 = 0x01;
+
+// The key types of the signatures of an input
+const PSBT_IN_PARTIAL_SIG: u8 = 0x02;
+const PSBT_IN_TAP_KEY_SIG: u8 = 0x13;
+const PSBT_IN_TAP_SCRIPT_SIG: u8 = 0x14;
+
+// The key types of the finalized scriptSig and witness of an input
+const PSBT_IN_FINAL_SCRIPTSIG: u8 = 0x07;
+const PSBT_IN_FINAL_SCRIPTWITNESS: u8 = 0x08;
+
+// The key types of the redeem script and witness script of an input
+const PSBT_IN_REDEEM_SCRIPT: u8 = 0x04;
+const PSBT_IN_WITNESS_SCRIPT: u8 = 0x05;
+
+// The key types of the BIP32 derivations of input keys and taproot keys
+const PSBT_IN_BIP32_DERIVATION: u8 = 0x06;
+const PSBT_IN_TAP_BIP32_DERIVATION: u8 = 0x16;
+
+// The key types of the taproot script leaves, internal key and merkle root of an input
+const PSBT_IN_TAP_LEAF_SCRIPT: u8 = 0x15;
+const PSBT_IN_TAP_INTERNAL_KEY: u8 = 0x17;
+const PSBT_IN_TAP_MERKLE_ROOT: u8 = 0x18;
+
+// The key types of the BIP32 derivations of output keys and taproot keys
+const PSBT_OUT_BIP32_DERIVATION: u8 = 0x02;
+const PSBT_OUT_TAP_BIP32_DERIVATION: u8 = 0x07;
+
+// The input fields the finalizer clears once the input is final: the sighash
+// type, scripts, derivations, preimages and the taproot fields
+const PSBT_IN_CLEARED_BY_FINALIZER: [u8; 15] = [
+    0x02, 0x03, 0x04, 0x05, 0x06, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x13, 0x14, 0x15, 0x16, 0x17,
+];
+
+// The key-value pairs of a map
+type PsbtMap = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// The roles of BIP174 which each process a PSBT on its way to being signed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PsbtRole {
+    /// Makes the PSBT from the unsigned transaction with empty input and output maps
+    Creator,
+    /// Adds the previous outputs, scripts and key derivations signers need
+    Updater,
+    /// Adds signatures to the inputs it can sign
+    Signer,
+    /// Turns the signatures of each input into its scriptSig and witness
+    Finalizer,
+}
+
+/// A partially signed transaction of BIP174 whose key-value maps have been
+/// checked against its unsigned transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Psbt {
+    bytes: Vec<u8>,
+    unsigned_tx: BtcTx,
+    global: PsbtMap,
+    inputs: Vec<PsbtMap>,
+    outputs: Vec<PsbtMap>,
+}
+
+impl Psbt {
+    /// Check the binary PSBT. It must start with the magic bytes, have one
+    /// global map with the unsigned transaction followed by one map for every
+    /// input and output of it, and repeat no key within a map
+    pub fn from_bytes(bytes: Vec<u8>) -> io::Result<Self> {
+        if bytes.len() > MAX_PSBT_SIZE {
+            return Err(Self::invalid(format!(
+                "The PSBT of {} bytes is larger than {} bytes",
+                bytes.len(),
+                MAX_PSBT_SIZE
+            )));
+        }
+        if !bytes.starts_with(&PSBT_MAGIC) {
+            return Err(Self::invalid(
+                "The PSBT magic bytes are missing".to_string(),
+            ));
+        }
+
+        let mut cursor = Cursor::new(&bytes[PSBT_MAGIC.len()..]);
+
+        let global = Self::read_map(&mut cursor)?;
+        let unsigned_tx = global
+            .iter()
+            .find(|(key, _)| key.as_slice() == [PSBT_GLOBAL_UNSIGNED_TX])
+            .map(|(_, value)| BtcTx::from_hex_bytes(value))
+            .ok_or_else(|| Self::invalid("The PSBT has no unsigned transaction".to_string()))??;
+        if unsigned_tx
+            .inputs()
+            .iter()
+            .any(|input| !input.signature_script().is_empty() || !input.witness().is_empty())
+        {
+            return Err(Self::invalid(
+                "The unsigned transaction of the PSBT has a scriptSig or witness".to_string(),
+            ));
+        }
+
+        let inputs = (0..unsigned_tx.inputs().len())
+            .map(|_| Self::read_map(&mut cursor))
+            .collect::<io::Result<Vec<PsbtMap>>>()?;
+        let outputs = (0..unsigned_tx.outputs().len())
+            .map(|_| Self::read_map(&mut cursor))
+            .collect::<io::Result<Vec<PsbtMap>>>()?;
+
+        if cursor.position() as usize != cursor.get_ref().len() {
+            return Err(Self::invalid(
+                "The PSBT has bytes after its last map".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            bytes,
+            unsigned_tx,
+            global,
+            inputs,
+            outputs,
+        })
+    }
+
+    /// Decode a PSBT from base64 as emitted by `walletprocesspsbt` of Bitcoin
+    /// Core and most wallets. Only padded standard base64 without whitespace
+    /// is accepted
+    pub fn from_base64(encoded: &str) -> io::Result<Self> {
+        if encoded.len() > MAX_PSBT_SIZE.div_ceil(3) * 4 {
+            return Err(Self::invalid(format!(
+                "The base64 PSBT is longer than the {} bytes it may decode to",
+                MAX_PSBT_SIZE
+            )));
+        }
+
+        Self::from_bytes(Self::base64_decode(encoded)?)
+    }
+
+    /// The PSBT in base64
+    pub fn to_base64(&self) -> String {
+        Self::base64_encode(&self.bytes)
+    }
+
+    /// The binary PSBT
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The transaction being signed
+    pub fn unsigned_tx(&self) -> &BtcTx {
+        &self.unsigned_tx
+    }
+
+    /// The public keys of the input at `input_index` and where they come from,
+    /// from the BIP32 derivations of the input and the taproot key derivations
+    /// of BIP371 which have x-only keys
+    pub fn input_key_sources(&self, input_index: usize) -> io::Result<Vec<(Vec<u8>, KeySource)>> {
+        let input = self.inputs.get(input_index).ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidInput,
+                "The PSBT has no input at the index",
+            )
+        })?;
+
+        input
+            .iter()
+            .filter_map(|(key, value)| match key.first() {
+                Some(&PSBT_IN_BIP32_DERIVATION) => Some(Ok((&key[1..], value.as_slice()))),
+                Some(&PSBT_IN_TAP_BIP32_DERIVATION) => {
+                    // The value starts with the hashes of the leaves using the key
+                    let mut cursor = Cursor::new(value.as_slice());
+                    let leaves = Self::read_varint(&mut cursor).ok()?;
+                    let Some(origin_start) = leaves
+                        .checked_mul(32)
+                        .and_then(|len| len.checked_add(cursor.position() as usize))
+                    else {
+                        return Some(Err(Self::invalid(format!(
+                            "A taproot key derivation has {} leaf hashes",
+                            leaves
+                        ))));
+                    };
+
+                    value
+                        .get(origin_start..)
+                        .map(|origin| Ok((&key[1..], origin)))
+                }
+                _ => None,
+            })
+            .map(|derivation| {
+                let (public_key, origin) = derivation?;
+                KeySource::from_psbt_value(origin).map(|source| (public_key.to_vec(), source))
+            })
+            .collect()
+    }
+
+    /// The accounts among `accounts` which derive a key of the input at
+    /// `input_index` and can sign it, so the PSBT can be sent to their signers
+    pub fn signers_of<'a>(
+        &self,
+        input_index: usize,
+        accounts: &'a [KeySource],
+    ) -> io::Result<Vec<&'a KeySource>> {
+        let key_sources = self.input_key_sources(input_index)?;
+
+        Ok(accounts
+            .iter()
+            .filter(|account| {
+                key_sources
+                    .iter()
+                    .any(|(_, source)| source.is_derived_from(account))
+            })
+            .collect())
+    }
+
+    /// Add the full previous transaction of the input at `input_index`. Legacy
+    /// inputs can only be signed with it and hardware signers want it for segwit
+    /// v0 inputs too, to check the amount they sign for. It must be the
+    /// transaction the input spends
+    pub fn with_non_witness_utxo(
+        self,
+        input_index: usize,
+        previous_tx: &BtcTx,
+    ) -> io::Result<Self> {
+        let input = self.unsigned_input(input_index)?;
+        if previous_tx.txid() != input.previous_tx_id()
+            || previous_tx.outputs().len() <= input.previous_output_index() as usize
+        {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "The previous transaction is not the one input {} spends",
+                    input_index
+                ),
+            ));
+        }
+
+        self.with_input_field(
+            input_index,
+            vec![PSBT_IN_NON_WITNESS_UTXO],
+            previous_tx.to_bytes(),
+        )
+    }
+
+    /// Add the output spent by the segwit input at `input_index`
+    pub fn with_witness_utxo(
+        self,
+        input_index: usize,
+        spent_output: &TxOutput,
+    ) -> io::Result<Self> {
+        let value = [
+            &spent_output.amount().to_le_bytes()[..],
+            &VarInt::encode(spent_output.locking_script().len() as u64),
+            spent_output.locking_script(),
+        ]
+        .concat();
+
+        self.with_input_field(input_index, vec![PSBT_IN_WITNESS_UTXO], value)
+    }
+
+    /// Add the redeem script of the P2SH input at `input_index`. When the spent
+    /// output has been added it must be the P2SH output of the redeem script
+    pub fn with_redeem_script(
+        self,
+        input_index: usize,
+        redeem_script: &Script,
+    ) -> io::Result<Self> {
+        if self.spent_output(input_index)?.is_some_and(|spent_output| {
+            spent_output.locking_script() != redeem_script.to_p2sh().as_bytes()
+        }) {
+            return Err(Self::not_spent_script("redeem script", input_index));
+        }
+
+        self.with_input_field(
+            input_index,
+            vec![PSBT_IN_REDEEM_SCRIPT],
+            redeem_script.as_bytes().to_vec(),
+        )
+    }
+
+    /// Add the witness script of the P2WSH input at `input_index`. When the spent
+    /// output has been added it must be the P2WSH output of the witness script,
+    /// or a P2SH output whose redeem script, which must be added first, is it
+    pub fn with_witness_script(
+        self,
+        input_index: usize,
+        witness_script: &Script,
+    ) -> io::Result<Self> {
+        let p2wsh = witness_script.to_p2wsh();
+        let redeem_script = self.input_field(input_index, &[PSBT_IN_REDEEM_SCRIPT])?;
+
+        if let Some(spent_output) = self.spent_output(input_index)? {
+            let locking_script = match ScriptType::from_script(spent_output.locking_script()) {
+                ScriptType::P2SH => redeem_script.unwrap_or_default(),
+                _ => spent_output.locking_script().to_vec(),
+            };
+            if locking_script != p2wsh.as_bytes() {
+                return Err(Self::not_spent_script("witness script", input_index));
+            }
+        }
+
+        self.with_input_field(
+            input_index,
+            vec![PSBT_IN_WITNESS_SCRIPT],
+            witness_script.as_bytes().to_vec(),
+        )
+    }
+
+    /// Add where the public key of the input at `input_index` comes from so a
+    /// signer recognises its key. A 32 byte x-only key is added as a taproot key
+    /// derivation used by no leaf, see `Self::with_tap_bip32_derivation()`
+    pub fn with_bip32_derivation(
+        self,
+        input_index: usize,
+        public_key: &[u8],
+        source: &KeySource,
+    ) -> io::Result<Self> {
+        match public_key.len() {
+            32 => self.with_tap_bip32_derivation(input_index, public_key, &[], source),
+            _ if SpendType::is_public_key(public_key) => self.with_input_field(
+                input_index,
+                [&[PSBT_IN_BIP32_DERIVATION], public_key].concat(),
+                source.to_psbt_value(),
+            ),
+            _ => Err(Self::not_public_key(public_key)),
+        }
+    }
+
+    /// Add where the x-only key of the taproot input at `input_index` comes from
+    /// with the hashes of the leaves using it, none for the internal key
+    pub fn with_tap_bip32_derivation(
+        self,
+        input_index: usize,
+        x_only_key: &[u8],
+        leaf_hashes: &[Hash256],
+        source: &KeySource,
+    ) -> io::Result<Self> {
+        if x_only_key.len() != 32 {
+            return Err(Self::not_public_key(x_only_key));
+        }
+
+        self.with_input_field(
+            input_index,
+            [&[PSBT_IN_TAP_BIP32_DERIVATION], x_only_key].concat(),
+            Self::tap_key_origin(leaf_hashes, source),
+        )
+    }
+
+    /// Add the derivations of the keys of `descriptor` at the child `index` to the
+    /// input at `input_index`, see `Descriptor::key_derivations()`. The crate does
+    /// not derive keys so `public_keys` are the keys the wallet derived, in the
+    /// order of the keys of the descriptor
+    pub fn with_descriptor_derivations(
+        self,
+        input_index: usize,
+        descriptor: &str,
+        index: u32,
+        public_keys: &[Vec<u8>],
+    ) -> io::Result<Self> {
+        let sources = Descriptor::key_derivations(descriptor, index);
+        if sources.len() != public_keys.len() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "The descriptor has {} key origins but {} public keys were given",
+                    sources.len(),
+                    public_keys.len()
+                ),
+            ));
+        }
+
+        public_keys
+            .iter()
+            .zip(&sources)
+            .try_fold(self, |psbt, (public_key, source)| {
+                psbt.with_bip32_derivation(input_index, public_key, source)
+            })
+    }
+
+    /// Add the x-only internal key of the taproot input at `input_index`
+    pub fn with_tap_internal_key(
+        self,
+        input_index: usize,
+        internal_key: [u8; 32],
+    ) -> io::Result<Self> {
+        self.with_input_field(
+            input_index,
+            vec![PSBT_IN_TAP_INTERNAL_KEY],
+            internal_key.to_vec(),
+        )
+    }
+
+    /// Add the merkle root of the script tree of the taproot input at `input_index`
+    pub fn with_tap_merkle_root(
+        self,
+        input_index: usize,
+        merkle_root: Hash256,
+    ) -> io::Result<Self> {
+        self.with_input_field(
+            input_index,
+            vec![PSBT_IN_TAP_MERKLE_ROOT],
+            merkle_root.as_ref().to_vec(),
+        )
+    }
+
+    /// Add a leaf script the taproot input at `input_index` can be spent with
+    /// and the control block proving it is in the script tree
+    pub fn with_tap_leaf_script(
+        self,
+        input_index: usize,
+        control_block: &[u8],
+        leaf_script: &Script,
+        leaf_version: u8,
+    ) -> io::Result<Self> {
+        if !SpendType::is_control_block(control_block) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "The control block of {} bytes is invalid",
+                    control_block.len()
+                ),
+            ));
+        }
+
+        self.with_input_field(
+            input_index,
+            [&[PSBT_IN_TAP_LEAF_SCRIPT], control_block].concat(),
+            [leaf_script.as_bytes(), &[leaf_version]].concat(),
+        )
+    }
+
+    /// Add where the public key of the output at `output_index` comes from so a
+    /// signer recognises its change. A 32 byte x-only key is added as a taproot
+    /// key derivation used by no leaf
+    pub fn with_output_bip32_derivation(
+        mut self,
+        output_index: usize,
+        public_key: &[u8],
+        source: &KeySource,
+    ) -> io::Result<Self> {
+        let (key, value) = match public_key.len() {
+            32 => (
+                [&[PSBT_OUT_TAP_BIP32_DERIVATION], public_key].concat(),
+                Self::tap_key_origin(&[], source),
+            ),
+            _ if SpendType::is_public_key(public_key) => (
+                [&[PSBT_OUT_BIP32_DERIVATION], public_key].concat(),
+                source.to_psbt_value(),
+            ),
+            _ => return Err(Self::not_public_key(public_key)),
+        };
+
+        let output = self.outputs.get_mut(output_index).ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidInput,
+                "The PSBT has no output at the index",
+            )
+        })?;
+        Self::set_field(output, key, value);
+
+        Ok(Self::from_maps(
+            self.global,
+            self.inputs,
+            self.outputs,
+            self.unsigned_tx,
+        ))
+    }
+
+    /// Merge the key-value pairs of `other` into this PSBT like the combiner of
+    /// BIP174, for example to gather the partial signatures of every signer.
+    /// Both PSBTs must be of the same unsigned transaction and a key found in
+    /// both maps must have the same value, otherwise one of the signers or
+    /// updaters changed a field it should have left alone
+    pub fn combine(&self, other: &Psbt) -> io::Result<Psbt> {
+        if self.unsigned_tx != other.unsigned_tx {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "The PSBTs are of different unsigned transactions",
+            ));
+        }
+
+        let global = Self::combine_maps(&self.global, &other.global, "the global map")?;
+        let inputs = self
+            .inputs
+            .iter()
+            .zip(&other.inputs)
+            .enumerate()
+            .map(|(index, (map, other_map))| {
+                Self::combine_maps(map, other_map, &format!("input {}", index))
+            })
+            .collect::<io::Result<Vec<PsbtMap>>>()?;
+        let outputs = self
+            .outputs
+            .iter()
+            .zip(&other.outputs)
+            .enumerate()
+            .map(|(index, (map, other_map))| {
+                Self::combine_maps(map, other_map, &format!("output {}", index))
+            })
+            .collect::<io::Result<Vec<PsbtMap>>>()?;
+
+        Ok(Self::from_maps(
+            global,
+            inputs,
+            outputs,
+            self.unsigned_tx.clone(),
+        ))
+    }
+
+    /// Check the fields every PSBT has once `role` has processed it, so a
+    /// coordinator can reject a malformed PSBT handed back between signing rounds.
+    /// Whatever the role, each full previous transaction must be the one the
+    /// input spends and each partial signature must be a signature keyed by a
+    /// public key. On top of that
+    /// - after the creator the input and output maps are empty
+    /// - after the updater no input is signed or final
+    /// - after a signer every signed input has its previous output and none is final
+    /// - after the finalizer every input is final and only keeps its previous
+    ///   output, its final scriptSig and witness and unknown fields
+    pub fn validate(&self, role: PsbtRole) -> io::Result<()> {
+        self.inputs
+            .iter()
+            .enumerate()
+            .try_for_each(|(index, input)| {
+                self.validate_input(index, input)?;
+
+                let has = |key_types: &[u8]| {
+                    input.iter().any(|(key, _)| {
+                        key.first()
+                            .is_some_and(|key_type| key_types.contains(key_type))
+                    })
+                };
+                let is_signed = has(&[
+                    PSBT_IN_PARTIAL_SIG,
+                    PSBT_IN_TAP_KEY_SIG,
+                    PSBT_IN_TAP_SCRIPT_SIG,
+                ]);
+                let is_final = has(&[PSBT_IN_FINAL_SCRIPTSIG, PSBT_IN_FINAL_SCRIPTWITNESS]);
+
+                let violation = match role {
+                    PsbtRole::Creator => (!input.is_empty()).then_some("has fields"),
+                    PsbtRole::Updater if is_signed => Some("is signed"),
+                    PsbtRole::Updater | PsbtRole::Signer if is_final => Some("is final"),
+                    PsbtRole::Signer
+                        if is_signed && !has(&[PSBT_IN_NON_WITNESS_UTXO, PSBT_IN_WITNESS_UTXO]) =>
+                    {
+                        Some("is signed without its previous output")
+                    }
+                    PsbtRole::Finalizer if !is_final => Some("is not final"),
+                    PsbtRole::Finalizer if has(&PSBT_IN_CLEARED_BY_FINALIZER) => {
+                        Some("kept fields the finalizer clears")
+                    }
+                    _ => None,
+                };
+
+                match violation {
+                    Some(violation) => Err(Self::invalid(format!(
+                        "Input {} {} after the {:?}",
+                        index, violation, role
+                    ))),
+                    None => Ok(()),
+                }
+            })?;
+
+        if role == PsbtRole::Creator && self.outputs.iter().any(|output| !output.is_empty()) {
+            return Err(Self::invalid(
+                "An output has fields after the Creator".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Check that `updated` is this PSBT processed by `role`. It must be of the
+    /// same unsigned transaction, be valid after `role` and the role may only
+    /// add the fields it is responsible for. No field may change and only the
+    /// finalizer removes fields, the ones it clears. The creator makes a PSBT
+    /// from scratch so it may not add anything to an existing one
+    pub fn check_update(&self, updated: &Psbt, role: PsbtRole) -> io::Result<()> {
+        if self.unsigned_tx != updated.unsigned_tx {
+            return Err(Self::invalid(format!(
+                "The {:?} changed the unsigned transaction",
+                role
+            )));
+        }
+        updated.validate(role)?;
+
+        let may_add = |key_type: u8| match role {
+            PsbtRole::Creator => false,
+            PsbtRole::Updater => ![
+                PSBT_IN_PARTIAL_SIG,
+                PSBT_IN_TAP_KEY_SIG,
+                PSBT_IN_TAP_SCRIPT_SIG,
+                PSBT_IN_FINAL_SCRIPTSIG,
+                PSBT_IN_FINAL_SCRIPTWITNESS,
+            ]
+            .contains(&key_type),
+            PsbtRole::Signer => [
+                PSBT_IN_PARTIAL_SIG,
+                PSBT_IN_TAP_KEY_SIG,
+                PSBT_IN_TAP_SCRIPT_SIG,
+            ]
+            .contains(&key_type),
+            PsbtRole::Finalizer => {
+                [PSBT_IN_FINAL_SCRIPTSIG, PSBT_IN_FINAL_SCRIPTWITNESS].contains(&key_type)
+            }
+        };
+        let may_remove = |key_type: u8| {
+            role == PsbtRole::Finalizer && PSBT_IN_CLEARED_BY_FINALIZER.contains(&key_type)
+        };
+        // Only the updater adds to the global and output maps
+        let updater_adds = |_| role == PsbtRole::Updater;
+
+        Self::check_map(
+            role,
+            (&self.global, &updated.global),
+            updater_adds,
+            |_| false,
+            "the global map",
+        )?;
+        self.inputs
+            .iter()
+            .zip(&updated.inputs)
+            .enumerate()
+            .try_for_each(|(index, maps)| {
+                Self::check_map(role, maps, may_add, may_remove, &format!("input {}", index))
+            })?;
+        self.outputs
+            .iter()
+            .zip(&updated.outputs)
+            .enumerate()
+            .try_for_each(|(index, maps)| {
+                Self::check_map(
+                    role,
+                    maps,
+                    updater_adds,
+                    |_| false,
+                    &format!("output {}", index),
+                )
+            })
+    }
+
+    // Check that `role` only added and removed the keys it may in a map of `location`
+    fn check_map(
+        role: PsbtRole,
+        (before, after): (&PsbtMap, &PsbtMap),
+        may_add: impl Fn(u8) -> bool,
+        may_remove: impl Fn(u8) -> bool,
+        location: &str,
+    ) -> io::Result<()> {
+        let value_of = |map: &PsbtMap, key: &[u8]| {
+            map.iter()
+                .find(|(other_key, _)| other_key == key)
+                .map(|(_, value)| value.clone())
+        };
+
+        let changed = before
+            .iter()
+            .find(|(key, value)| match value_of(after, key) {
+                Some(after_value) => after_value != *value,
+                None => !may_remove(key[0]),
+            });
+        let added = after
+            .iter()
+            .find(|(key, _)| value_of(before, key).is_none() && !may_add(key[0]));
+
+        match changed.or(added) {
+            Some((key, _)) => Err(Self::invalid(format!(
+                "The {:?} may not change the key {} of {}",
+                role,
+                hex::encode(key),
+                location
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    // Set the value of `key` in the map of the input at `input_index`
+    fn with_input_field(
+        mut self,
+        input_index: usize,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    ) -> io::Result<Self> {
+        let input = self
+            .inputs
+            .get_mut(input_index)
+            .ok_or_else(Self::no_input)?;
+        Self::set_field(input, key, value);
+
+        Ok(Self::from_maps(
+            self.global,
+            self.inputs,
+            self.outputs,
+            self.unsigned_tx,
+        ))
+    }
+
+    fn set_field(map: &mut PsbtMap, key: Vec<u8>, value: Vec<u8>) {
+        match map.iter_mut().find(|(other_key, _)| *other_key == key) {
+            Some((_, old_value)) => *old_value = value,
+            None => map.push((key, value)),
+        }
+    }
+
+    // The value of `key` in the map of the input at `input_index`
+    fn input_field(&self, input_index: usize, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        Ok(self
+            .inputs
+            .get(input_index)
+            .ok_or_else(Self::no_input)?
+            .iter()
+            .find(|(other_key, _)| other_key == key)
+            .map(|(_, value)| value.clone()))
+    }
+
+    fn unsigned_input(&self, input_index: usize) -> io::Result<&TxInput> {
+        self.unsigned_tx
+            .inputs()
+            .get(input_index)
+            .ok_or_else(Self::no_input)
+    }
+
+    // The output spent by the input at `input_index` when it has been added,
+    // from the witness UTXO or else from the full previous transaction
+    fn spent_output(&self, input_index: usize) -> io::Result<Option<TxOutput>> {
+        if let Some(value) = self.input_field(input_index, &[PSBT_IN_WITNESS_UTXO])? {
+            let mut cursor = Cursor::new(value.as_slice());
+            let mut amount = [0u8; 8];
+            cursor.read_exact(&mut amount)?;
+            let locking_script = Self::read_bytes(&mut cursor)?;
+
+            return Ok(Some(TxOutput::new(
+                u64::from_le_bytes(amount),
+                locking_script,
+            )));
+        }
+
+        match self.input_field(input_index, &[PSBT_IN_NON_WITNESS_UTXO])? {
+            Some(value) => {
+                let vout = self.unsigned_input(input_index)?.previous_output_index() as usize;
+                Ok(BtcTx::from_hex_bytes(value)?.outputs().get(vout).cloned())
+            }
+            None => Ok(None),
+        }
+    }
+
+    // The value of a taproot key derivation, the leaf hashes followed by the key origin
+    fn tap_key_origin(leaf_hashes: &[Hash256], source: &KeySource) -> Vec<u8> {
+        let mut value = VarInt::encode(leaf_hashes.len() as u64);
+        leaf_hashes
+            .iter()
+            .for_each(|leaf_hash| value.extend_from_slice(leaf_hash.as_ref()));
+        value.extend(source.to_psbt_value());
+
+        value
+    }
+
+    fn no_input() -> io::Error {
+        io::Error::new(
+            ErrorKind::InvalidInput,
+            "The PSBT has no input at the index",
+        )
+    }
+
+    fn not_public_key(public_key: &[u8]) -> io::Error {
+        io::Error::new(
+            ErrorKind::InvalidInput,
+            format!("The {} bytes are not a public key", public_key.len()),
+        )
+    }
+
+    fn not_spent_script(script: &str, input_index: usize) -> io::Error {
+        io::Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "The {} does not hash to the output input {} spends",
+                script, input_index
+            ),
+        )
+    }
+
+    // The checks of the fields of an input which hold after any role
+    fn validate_input(&self, index: usize, input: &PsbtMap) -> io::Result<()> {
+        input.iter().try_for_each(|(key, value)| match key[0] {
+            PSBT_IN_NON_WITNESS_UTXO => {
+                let previous_tx = BtcTx::from_hex_bytes(value)?;
+                if previous_tx.txid() != self.unsigned_tx.inputs()[index].previous_tx_id() {
+                    return Err(Self::invalid(format!(
+                        "The previous transaction of input {} is not the one it spends",
+                        index
+                    )));
+                }
+
+                Ok(())
+            }
+            PSBT_IN_PARTIAL_SIG
+                if !SpendType::is_public_key(&key[1..]) || !SpendType::is_signature(value) =>
+            {
+                Err(Self::invalid(format!(
+                    "A partial signature of input {} is malformed",
+                    index
+                )))
+            }
+            _ => Ok(()),
+        })
+    }
+
+    // Merge two maps, failing on a key with different values in `location`
+    fn combine_maps(map: &PsbtMap, other: &PsbtMap, location: &str) -> io::Result<PsbtMap> {
+        let mut combined = map.clone();
+
+        other.iter().try_for_each(|(key, value)| {
+            match combined
+                .iter()
+                .find(|(combined_key, _)| combined_key == key)
+            {
+                Some((_, combined_value)) if combined_value != value => {
+                    Err(Self::invalid(format!(
+                        "The PSBTs have different values for the key {} of {}",
+                        hex::encode(key),
+                        location
+                    )))
+                }
+                Some(_) => Ok(()),
+                None => {
+                    combined.push((key.clone(), value.clone()));
+                    Ok(())
+                }
+            }
+        })?;
+
+        Ok(combined)
+    }
+
+    // Serialize the maps back into a binary PSBT
+    fn from_maps(
+        global: PsbtMap,
+        inputs: Vec<PsbtMap>,
+        outputs: Vec<PsbtMap>,
+        unsigned_tx: BtcTx,
+    ) -> Self {
+        let mut bytes = PSBT_MAGIC.to_vec();
+        std::iter::once(&global)
+            .chain(&inputs)
+            .chain(&outputs)
+            .for_each(|map| {
+                map.iter().for_each(|(key, value)| {
+                    bytes.extend(VarInt::encode(key.len() as u64));
+                    bytes.extend(key);
+                    bytes.extend(VarInt::encode(value.len() as u64));
+                    bytes.extend(value);
+                });
+                bytes.push(0x00);
+            });
+
+        Self {
+            bytes,
+            unsigned_tx,
+            global,
+            inputs,
+            outputs,
+        }
+    }
+
+    // Read the key-value pairs of a map up to its 0x00 separator
+    fn read_map(bytes: &mut Cursor<&[u8]>) -> io::Result<PsbtMap> {
+        let mut seen = BTreeSet::<Vec<u8>>::new();
+        let mut pairs = PsbtMap::new();
+
+        loop {
+            let key = Self::read_bytes(bytes)?;
+            if key.is_empty() {
+                return Ok(pairs);
+            }
+            if !seen.insert(key.clone()) {
+                return Err(Self::invalid(format!(
+                    "The key {} is repeated in a PSBT map",
+                    hex::encode(&key)
+                )));
+            }
+
+            let value = Self::read_bytes(bytes)?;
+            pairs.push((key, value));
+        }
+    }
+
+    fn read_varint(bytes: &mut Cursor<&[u8]>) -> io::Result<usize> {
+        let mut varint_len = [0u8];
+        bytes.read_exact(&mut varint_len)?;
+
+        VarInt::integer(VarInt::parse(varint_len[0]), bytes)
+    }
+
+    fn read_bytes(bytes: &mut Cursor<&[u8]>) -> io::Result<Vec<u8>> {
+        let len = Self::read_varint(bytes)?;
+
+        let remaining = bytes.get_ref().len() - bytes.position() as usize;
+        if len > remaining {
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "A PSBT key or value is longer than the bytes left",
+            ));
+        }
+
+        let mut buffer = vec![0u8; len];
+        bytes.read_exact(&mut buffer)?;
+
+        Ok(buffer)
+    }
+
+    fn base64_encode(bytes: &[u8]) -> String {
+        bytes
+            .chunks(3)
+            .flat_map(|chunk| {
+                let group = chunk.iter().enumerate().fold(0u32, |group, (index, byte)| {
+                    group | (*byte as u32) << (16 - index * 8)
+                });
+
+                (0..4).map(move |index| {
+                    if index > chunk.len() {
+                        '='
+                    } else {
+                        BASE64_ALPHABET[(group >> (18 - index * 6)) as usize & 0x3f] as char
+                    }
+                })
+            })
+            .collect()
+    }
+
+    fn base64_decode(encoded: &str) -> io::Result<Vec<u8>> {
+        let encoded = encoded.as_bytes();
+        if !encoded.len().is_multiple_of(4) {
+            return Err(Self::invalid(
+                "The base64 length is not a multiple of 4".to_string(),
+            ));
+        }
+
+        let padding = encoded
+            .iter()
+            .rev()
+            .take_while(|byte| **byte == b'=')
+            .count();
+        if padding > 2 {
+            return Err(Self::invalid("The base64 has too much padding".to_string()));
+        }
+
+        let mut bytes = Vec::<u8>::with_capacity(encoded.len() / 4 * 3);
+        for (group_index, group) in encoded.chunks(4).enumerate() {
+            let is_last = group_index == encoded.len() / 4 - 1;
+            let symbols = if is_last { 4 - padding } else { 4 };
+
+            let mut value = 0u32;
+            for (index, character) in group.iter().enumerate() {
+                let sextet = if index < symbols {
+                    BASE64_ALPHABET
+                        .iter()
+                        .position(|symbol| symbol == character)
+                        .ok_or_else(|| {
+                            Self::invalid(format!(
+                                "`{}` is not a base64 character",
+                                *character as char
+                            ))
+                        })?
+                } else {
+                    0
+                };
+                value = value << 6 | sextet as u32;
+            }
+
+            let decoded = value.to_be_bytes();
+            let len = symbols * 6 / 8;
+            // The bits after the last byte must be zero for one canonical encoding
+            if decoded[1 + len..].iter().any(|byte| *byte != 0) {
+                return Err(Self::invalid(
+                    "The base64 is not canonically encoded".to_string(),
+                ));
+            }
+            bytes.extend_from_slice(&decoded[1..1 + len]);
+        }
+
+        Ok(bytes)
+    }
+
+    fn invalid(message: String) -> io::Error {
+        io::Error::new(ErrorKind::InvalidData, message)
+    }
+}
+
+#[cfg(test)]
+mod psbt_sanity_checks {
+    use crate::{
+        fixtures::{tx, tx_to, txid},
+        BtcTx, Descriptor, Hash256, KeySource, Psbt, PsbtRole, Script, VarInt, PSBT_MAGIC,
+        TAPSCRIPT_LEAF_VERSION,
+    };
+    use hex_literal::hex;
+    use std::{io::ErrorKind, str::FromStr};
+
+    // A PSBT with an unsigned transaction of one input and one output and
+    // empty input and output maps
+    fn raw_psbt() -> Vec<u8> {
+        let unsigned_tx = [
+            &hex!("0200000001")[..],
+            &[0x11; 32],
+            &hex!("0000000000ffffffff01e8030000000000000151"),
+            &[0u8; 4],
+        ]
+        .concat();
+
+        [
+            &PSBT_MAGIC[..],
+            &[0x01, 0x00, unsigned_tx.len() as u8],
+            &unsigned_tx,
+            &[0x00, 0x00, 0x00],
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn base64_round_trip() {
+        let psbt = Psbt::from_bytes(raw_psbt()).unwrap();
+        let encoded = psbt.to_base64();
+        assert!(encoded.starts_with("cHNidP8B"));
+        assert_eq!(psbt, Psbt::from_base64(&encoded).unwrap());
+        assert_eq!(1, psbt.unsigned_tx().outputs().len());
+
+        assert_eq!("cHNidP8=", Psbt::base64_encode(&PSBT_MAGIC));
+        assert_eq!(
+            PSBT_MAGIC.to_vec(),
+            Psbt::base64_decode("cHNidP8=").unwrap()
+        );
+
+        // Whitespace, truncation and set trailing bits are rejected
+        assert!(Psbt::from_base64(&format!("{} ", encoded)).is_err());
+        assert!(Psbt::from_base64(&encoded[..encoded.len() - 1]).is_err());
+        assert!(Psbt::from_base64("cHNidP9=").is_err());
+
+        // A missing output map and an extra byte after the last map are rejected
+        let bytes = raw_psbt();
+        assert!(Psbt::from_bytes(bytes[..bytes.len() - 1].to_vec()).is_err());
+        assert!(Psbt::from_bytes([&bytes[..], &[0x00]].concat()).is_err());
+    }
+
+    #[test]
+    fn input_signers() {
+        let bytes = raw_psbt();
+        let key = KeySource::from_str("[d34db33f/48'/0'/0'/2'/0/3]").unwrap();
+        let tap_key = KeySource::from_str("[0badf00d/86'/0'/0'/0/1]").unwrap();
+        let origin = key.to_psbt_value();
+        // A taproot key used by one leaf
+        let tap_origin = [&[0x01][..], &[0x33; 32], &tap_key.to_psbt_value()].concat();
+
+        // The input map with a BIP32 derivation and a taproot derivation
+        let input_map = [
+            &[0x22, 0x06, 0x02][..],
+            &[0x44; 32],
+            &[origin.len() as u8],
+            &origin,
+            &[0x21, 0x16],
+            &[0x55; 32],
+            &[tap_origin.len() as u8],
+            &tap_origin,
+            &[0x00],
+        ]
+        .concat();
+        // The bytes before the empty input and output maps
+        let psbt =
+            Psbt::from_bytes([&bytes[..bytes.len() - 2], &input_map, &[0x00]].concat()).unwrap();
+        assert_eq!(
+            vec![key.clone(), tap_key.clone()],
+            psbt.input_key_sources(0)
+                .unwrap()
+                .into_iter()
+                .map(|(_, source)| source)
+                .collect::<Vec<KeySource>>()
+        );
+
+        let accounts = Descriptor::key_sources(
+            "wsh(sortedmulti(1,[d34db33f/48'/0'/0'/2']xpub1/0/*,[deadbeef/48'/0'/0'/2']xpub2/0/*))",
+        );
+        assert_eq!(2, accounts.len());
+        assert_eq!(vec![&accounts[0]], psbt.signers_of(0, &accounts).unwrap());
+        assert!(psbt.signers_of(1, &accounts).is_err());
+
+        // A count of leaf hashes which overflows the offset of the origin
+        let overflowing = [&hex!("ffffffffffffffffff")[..], &tap_key.to_psbt_value()].concat();
+        let input_map = [
+            &[0x21, 0x16][..],
+            &[0x55; 32],
+            &[overflowing.len() as u8],
+            &overflowing,
+            &[0x00],
+        ]
+        .concat();
+        let psbt =
+            Psbt::from_bytes([&bytes[..bytes.len() - 2], &input_map, &[0x00]].concat()).unwrap();
+        assert_eq!(
+            ErrorKind::InvalidData,
+            psbt.input_key_sources(0).unwrap_err().kind()
+        );
+    }
+
+    // The PSBT of `raw_psbt()` with the key-value pairs in its input map
+    fn with_input(pairs: &[(&[u8], &[u8])]) -> Psbt {
+        let bytes = raw_psbt();
+        let input_map = pairs
+            .iter()
+            .flat_map(|(key, value)| {
+                [
+                    VarInt::encode(key.len() as u64),
+                    key.to_vec(),
+                    VarInt::encode(value.len() as u64),
+                    value.to_vec(),
+                ]
+                .concat()
+            })
+            .chain([0x00])
+            .collect::<Vec<u8>>();
+
+        Psbt::from_bytes([&bytes[..bytes.len() - 2], &input_map, &[0x00]].concat()).unwrap()
+    }
+
+    // The key of a partial signature by a compressed public key
+    fn signature_key(byte: u8) -> Vec<u8> {
+        [&[0x02, 0x02][..], &[byte; 32]].concat()
+    }
+
+    const SIGNATURE: [u8; 9] = hex!("300602010102010101");
+    const WITNESS_UTXO: [u8; 10] = hex!("e8030000000000000151");
+
+    #[test]
+    fn combine() {
+        let first_key = signature_key(0x01);
+        let second_key = signature_key(0x02);
+        let first = with_input(&[(&[0x01], &WITNESS_UTXO), (&first_key, &SIGNATURE)]);
+        let second = with_input(&[(&[0x01], &WITNESS_UTXO), (&second_key, &SIGNATURE)]);
+
+        let combined = first.combine(&second).unwrap();
+        assert_eq!(
+            with_input(&[
+                (&[0x01], &WITNESS_UTXO),
+                (&first_key, &SIGNATURE),
+                (&second_key, &SIGNATURE)
+            ]),
+            combined
+        );
+        assert_eq!(
+            combined,
+            Psbt::from_bytes(combined.as_bytes().to_vec()).unwrap()
+        );
+        assert_eq!(combined, combined.combine(&first).unwrap());
+
+        // The same key signing twice with different signatures conflicts
+        let mut other_signature = SIGNATURE;
+        other_signature[8] = 0x02;
+        let conflicting = with_input(&[(&first_key, &other_signature)]);
+        assert_eq!(
+            ErrorKind::InvalidData,
+            first.combine(&conflicting).unwrap_err().kind()
+        );
+
+        // PSBTs of different transactions cannot be combined
+        let mut bytes = raw_psbt();
+        bytes[PSBT_MAGIC.len() + 3 + 4 + 1] = 0x22;
+        let other_tx = Psbt::from_bytes(bytes).unwrap();
+        assert_eq!(
+            ErrorKind::InvalidInput,
+            first.combine(&other_tx).unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn validate_roles() {
+        let key = signature_key(0x01);
+        let created = Psbt::from_bytes(raw_psbt()).unwrap();
+        let updated = with_input(&[(&[0x01], &WITNESS_UTXO), (&[0x03], &[1, 0, 0, 0])]);
+        let signed = with_input(&[
+            (&[0x01], &WITNESS_UTXO),
+            (&[0x03], &[1, 0, 0, 0]),
+            (&key, &SIGNATURE),
+        ]);
+        let finalized = with_input(&[(&[0x01], &WITNESS_UTXO), (&[0x08], &[0x00])]);
+
+        assert!(created.validate(PsbtRole::Creator).is_ok());
+        assert!(updated.validate(PsbtRole::Creator).is_err());
+        assert!(updated.validate(PsbtRole::Updater).is_ok());
+        assert!(signed.validate(PsbtRole::Updater).is_err());
+        assert!(signed.validate(PsbtRole::Signer).is_ok());
+        assert!(with_input(&[(&key, &SIGNATURE)])
+            .validate(PsbtRole::Signer)
+            .is_err());
+        assert!(signed.validate(PsbtRole::Finalizer).is_err());
+        assert!(finalized.validate(PsbtRole::Signer).is_err());
+        assert!(finalized.validate(PsbtRole::Finalizer).is_ok());
+
+        // A signature which is not DER and a previous transaction with another txid
+        assert!(with_input(&[(&key, &SIGNATURE[1..])])
+            .validate(PsbtRole::Updater)
+            .is_err());
+        let unsigned_tx = &raw_psbt()[PSBT_MAGIC.len() + 3..raw_psbt().len() - 3];
+        let error = with_input(&[(&[0x00], unsigned_tx)])
+            .validate(PsbtRole::Updater)
+            .unwrap_err();
+        assert_eq!(ErrorKind::InvalidData, error.kind());
+    }
+
+    #[test]
+    fn role_updates() {
+        let key = signature_key(0x01);
+        let created = Psbt::from_bytes(raw_psbt()).unwrap();
+        let updated = with_input(&[(&[0x01], &WITNESS_UTXO), (&[0x03], &[1, 0, 0, 0])]);
+        let signed = with_input(&[
+            (&[0x01], &WITNESS_UTXO),
+            (&[0x03], &[1, 0, 0, 0]),
+            (&key, &SIGNATURE),
+        ]);
+        let finalized = with_input(&[(&[0x01], &WITNESS_UTXO), (&[0x08], &[0x00])]);
+
+        assert!(created.check_update(&updated, PsbtRole::Updater).is_ok());
+        assert!(created.check_update(&updated, PsbtRole::Creator).is_err());
+        assert!(updated.check_update(&signed, PsbtRole::Signer).is_ok());
+        assert!(created.check_update(&signed, PsbtRole::Signer).is_err());
+        assert!(signed.check_update(&finalized, PsbtRole::Finalizer).is_ok());
+
+        // A signer may not change or drop the previous output it signs for
+        let mut other_utxo = WITNESS_UTXO;
+        other_utxo[0] = 0xe9;
+        let changed = with_input(&[
+            (&[0x01], &other_utxo),
+            (&[0x03], &[1, 0, 0, 0]),
+            (&key, &SIGNATURE),
+        ]);
+        assert!(updated.check_update(&changed, PsbtRole::Signer).is_err());
+        assert!(updated.check_update(&changed, PsbtRole::Updater).is_err());
+        let dropped = with_input(&[(&[0x01], &WITNESS_UTXO), (&key, &SIGNATURE)]);
+        assert!(updated.check_update(&dropped, PsbtRole::Signer).is_err());
+        assert!(dropped
+            .check_update(&finalized, PsbtRole::Finalizer)
+            .is_ok());
+    }
+
+    // A PSBT of `unsigned_tx` with empty input and output maps
+    fn psbt_of(unsigned_tx: &BtcTx) -> Psbt {
+        let maps = vec![0x00; unsigned_tx.inputs().len() + unsigned_tx.outputs().len()];
+        let unsigned_tx = unsigned_tx.to_bytes();
+
+        Psbt::from_bytes(
+            [
+                &PSBT_MAGIC[..],
+                &[0x01, 0x00],
+                &VarInt::encode(unsigned_tx.len() as u64),
+                &unsigned_tx,
+                &[0x00],
+                &maps,
+            ]
+            .concat(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn update_inputs() {
+        let public_key = [&[0x02][..], &[0x44; 32]].concat();
+        // A 1-of-1 multisig witness script nested in P2SH
+        let witness_script = Script::new([&[0x51, 0x21][..], &public_key, &[0x51, 0xae]].concat());
+        let redeem_script = witness_script.to_p2wsh();
+        let previous_tx = tx_to(
+            &[(txid(9), 0)],
+            vec![(50_000, redeem_script.to_p2sh().as_bytes().to_vec())],
+        );
+        let created = psbt_of(&tx(&[(previous_tx.txid(), 0)], &[40_000]));
+
+        let descriptor = "sh(wsh(multi(1,[d34db33f/48'/0'/0'/1']xpub1/0/*)))";
+        let updated = created
+            .clone()
+            .with_non_witness_utxo(0, &previous_tx)
+            .and_then(|psbt| psbt.with_witness_utxo(0, &previous_tx.outputs()[0]))
+            .and_then(|psbt| psbt.with_redeem_script(0, &redeem_script))
+            .and_then(|psbt| psbt.with_witness_script(0, &witness_script))
+            .and_then(|psbt| {
+                psbt.with_descriptor_derivations(
+                    0,
+                    descriptor,
+                    7,
+                    std::slice::from_ref(&public_key),
+                )
+            })
+            .and_then(|psbt| {
+                psbt.with_output_bip32_derivation(
+                    0,
+                    &public_key,
+                    &KeySource::from_str("[d34db33f/48'/0'/0'/1'/1/0]").unwrap(),
+                )
+            })
+            .unwrap();
+        assert_eq!(
+            vec![(
+                public_key.clone(),
+                KeySource::from_str("[d34db33f/48'/0'/0'/1'/0/7]").unwrap()
+            )],
+            updated.input_key_sources(0).unwrap()
+        );
+        assert!(created.check_update(&updated, PsbtRole::Updater).is_ok());
+        assert_eq!(
+            updated,
+            Psbt::from_bytes(updated.as_bytes().to_vec()).unwrap()
+        );
+
+        // Scripts and transactions which are not the ones the input spends
+        let error = updated
+            .clone()
+            .with_witness_script(0, &redeem_script)
+            .unwrap_err();
+        assert_eq!(ErrorKind::InvalidInput, error.kind());
+        assert!(updated
+            .clone()
+            .with_redeem_script(0, &witness_script)
+            .is_err());
+        assert!(created
+            .clone()
+            .with_non_witness_utxo(0, &tx(&[], &[50_000]))
+            .is_err());
+        assert!(created
+            .clone()
+            .with_witness_utxo(1, &previous_tx.outputs()[0])
+            .is_err());
+        assert!(created
+            .clone()
+            .with_descriptor_derivations(0, descriptor, 7, &[])
+            .is_err());
+        assert!(created
+            .clone()
+            .with_bip32_derivation(0, &[0x02; 20], &KeySource::default())
+            .is_err());
+    }
+
+    #[test]
+    fn update_taproot_inputs() {
+        let internal_key = [0x22; 32];
+        let leaf_script = Script::new([&[0x20][..], &[0x33; 32], &[0xac]].concat());
+        let control_block = [&[TAPSCRIPT_LEAF_VERSION][..], &internal_key].concat();
+        let source = KeySource::from_str("[0badf00d/86'/0'/0'/0/1]").unwrap();
+
+        let updated = Psbt::from_bytes(raw_psbt())
+            .and_then(|psbt| psbt.with_tap_internal_key(0, internal_key))
+            .and_then(|psbt| {
+                psbt.with_tap_merkle_root(0, leaf_script.tapleaf_hash(TAPSCRIPT_LEAF_VERSION))
+            })
+            .and_then(|psbt| {
+                psbt.with_tap_leaf_script(0, &control_block, &leaf_script, TAPSCRIPT_LEAF_VERSION)
+            })
+            .and_then(|psbt| {
+                psbt.with_tap_bip32_derivation(
+                    0,
+                    &[0x33; 32],
+                    &[leaf_script.tapleaf_hash(TAPSCRIPT_LEAF_VERSION)],
+                    &source,
+                )
+            })
+            .and_then(|psbt| psbt.with_bip32_derivation(0, &internal_key, &source))
+            .unwrap();
+        assert_eq!(
+            vec![
+                (vec![0x33; 32], source.clone()),
+                (internal_key.to_vec(), source)
+            ],
+            updated.input_key_sources(0).unwrap()
+        );
+        assert!(updated.validate(PsbtRole::Updater).is_ok());
+
+        assert!(updated
+            .clone()
+            .with_tap_leaf_script(0, &control_block[1..], &leaf_script, TAPSCRIPT_LEAF_VERSION)
+            .is_err());
+        assert!(updated
+            .with_tap_bip32_derivation(0, &[0x33; 33], &[Hash256::default()], &KeySource::default())
+            .is_err());
+    }
+}
@@ -0,0 +1,526 @@
+use crate::{
+    AsmFormat, BtcTx, LockTime, Network, RelativeLockTime, ScriptType, SpendType, StandardScripts,
+    TxOutput, SEQUENCE_FINAL, SEQUENCE_MAX_NON_RBF,
+};
+use std::io::{self, ErrorKind};
+
+/// Decode a raw transaction in hex into a report of everything this
+/// crate knows about it. Addresses are encoded for the main network,
+/// use `TxReport::new()` for other networks.
+pub fn decode_to_report(tx_hex: &str) -> io::Result<TxReport> {
+    let raw_tx = hex::decode(tx_hex.trim())
+        .map_err(|error| io::Error::new(ErrorKind::InvalidData, error))?;
+
+    TxReport::new(&raw_tx, Network::Mainnet)
+}
+
+/// A summary of a decoded transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxReport {
+    /// The transaction ID in the byte order explorers display it in
+    pub txid: String,
+    /// The version of the transaction
+    pub version: u32,
+    /// The size of the raw transaction in bytes
+    pub size: usize,
+    /// The weight in weight units
+    pub weight: usize,
+    /// The virtual size which is the weight divided by 4 rounded up
+    pub vsize: usize,
+    /// The raw locktime field
+    pub locktime: u32,
+    /// What the locktime field means
+    pub locktime_kind: LockTime,
+    /// Whether the locktime is enforced, it is ignored
+    /// when every input has a final sequence number
+    pub locktime_enforced: bool,
+    /// Whether the transaction signals BIP125 replaceability
+    pub signals_rbf: bool,
+    /// A summary of every input
+    pub inputs: Vec<InputReport>,
+    /// A summary of every output
+    pub outputs: Vec<OutputReport>,
+}
+
+/// A summary of an input of a decoded transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputReport {
+    /// The transaction ID of the output being spent in the
+    /// byte order explorers display it in
+    pub previous_tx_id: String,
+    /// The index of the output being spent
+    pub previous_output_index: u32,
+    /// The type of output being spent inferred from the scriptSig
+    pub spend_type: SpendType,
+    /// The address of the output being spent. Always `None`
+    /// since this requires the previous transaction
+    pub address: Option<String>,
+    /// The amount of the output being spent. Always `None`
+    /// since this requires the previous transaction
+    pub value: Option<u64>,
+    /// The raw sequence number
+    pub sequence_number: u32,
+    /// The BIP68 relative locktime encoded in the sequence number
+    pub relative_locktime: RelativeLockTime,
+}
+
+/// A summary of an output of a decoded transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputReport {
+    /// The amount in satoshis
+    pub amount: u64,
+    /// The standard script template of the locking script
+    pub script_type: ScriptType,
+    /// The address of the locking script if it has one
+    pub address: Option<String>,
+}
+
+impl TxReport {
+    /// Decode the raw transaction bytes into a report with
+    /// addresses encoded for `network`
+    pub fn new(raw_tx: &[u8], network: Network) -> io::Result<Self> {
+        let tx = BtcTx::from_hex_bytes(raw_tx)?;
+        let version = u32::from_le_bytes(tx.version().to_bytes());
+
+        let txid = tx.txid();
+        let size = raw_tx.len();
+        let weight = tx.weight();
+
+        let inputs = tx
+            .inputs()
+            .iter()
+            .map(|input| InputReport {
+                previous_tx_id: input.previous_tx_id().to_string(),
+                previous_output_index: input.previous_output_index(),
+                spend_type: input.inferred_spend_type(),
+                address: None,
+                value: None,
+                sequence_number: input.sequence_number(),
+                relative_locktime: RelativeLockTime::from_sequence(
+                    input.sequence_number(),
+                    version,
+                ),
+            })
+            .collect::<Vec<InputReport>>();
+
+        let outputs = tx
+            .outputs()
+            .iter()
+            .map(|output| OutputReport {
+                amount: output.amount(),
+                script_type: output.script_type(),
+                address: output.address(network),
+            })
+            .collect::<Vec<OutputReport>>();
+
+        Ok(Self {
+            txid: txid.to_string(),
+            version,
+            size,
+            weight,
+            vsize: weight.div_ceil(4),
+            locktime: tx.locktime(),
+            locktime_kind: LockTime::from_consensus(tx.locktime()),
+            locktime_enforced: inputs
+                .iter()
+                .any(|input| input.sequence_number != SEQUENCE_FINAL),
+            signals_rbf: inputs
+                .iter()
+                .any(|input| input.sequence_number < SEQUENCE_MAX_NON_RBF),
+            inputs,
+            outputs,
+        })
+    }
+}
+
+/// The decoding of a script mirroring the result of Bitcoin Core's `decodescript`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptReport {
+    /// The script in the `AsmFormat::CoreAsm` format
+    pub asm: String,
+    /// The inferred descriptor with its checksum
+    pub desc: String,
+    /// The name Bitcoin Core gives the script template like `pubkeyhash`
+    pub script_type: String,
+    /// The address of the script if it has one
+    pub address: Option<String>,
+    /// The P2SH address wrapping the script, if it can be wrapped
+    pub p2sh: Option<String>,
+    /// The segwit version of the script, if it can be wrapped
+    pub segwit: Option<SegwitReport>,
+}
+
+/// The segwit locking script wrapping a script. Scripts paying to a public key
+/// hash are wrapped as P2WPKH and other scripts as P2WSH
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegwitReport {
+    /// The segwit locking script in the `AsmFormat::CoreAsm` format
+    pub asm: String,
+    /// The segwit locking script in hex
+    pub hex: String,
+    /// The name Bitcoin Core gives the segwit script template
+    pub script_type: String,
+    /// The address of the segwit locking script
+    pub address: Option<String>,
+    /// The inferred descriptor of the segwit locking script
+    pub desc: String,
+    /// The P2SH address wrapping the segwit locking script
+    pub p2sh_segwit: String,
+}
+
+impl ScriptReport {
+    /// Serialize to JSON using the same keys as `decodescript`
+    pub fn to_json(&self) -> String {
+        let mut fields = vec![
+            ("asm", json_string(&self.asm)),
+            ("desc", json_string(&self.desc)),
+            ("type", json_string(&self.script_type)),
+        ];
+        if let Some(address) = &self.address {
+            fields.push(("address", json_string(address)));
+        }
+        if let Some(p2sh) = &self.p2sh {
+            fields.push(("p2sh", json_string(p2sh)));
+        }
+        if let Some(segwit) = &self.segwit {
+            let mut segwit_fields = vec![
+                ("asm", json_string(&segwit.asm)),
+                ("desc", json_string(&segwit.desc)),
+                ("hex", json_string(&segwit.hex)),
+            ];
+            if let Some(address) = &segwit.address {
+                segwit_fields.push(("address", json_string(address)));
+            }
+            segwit_fields.push(("type", json_string(&segwit.script_type)));
+            segwit_fields.push(("p2sh-segwit", json_string(&segwit.p2sh_segwit)));
+
+            fields.push(("segwit", json_object(&segwit_fields)));
+        }
+
+        json_object(&fields)
+    }
+}
+
+/// The name Bitcoin Core gives a script template in the `type` field of its RPCs
+pub fn core_script_type(script_type: ScriptType) -> &'static str {
+    match script_type {
+        ScriptType::P2PK => "pubkey",
+        ScriptType::P2PKH => "pubkeyhash",
+        ScriptType::P2SH => "scripthash",
+        ScriptType::P2WPKH => "witness_v0_keyhash",
+        ScriptType::P2WSH => "witness_v0_scripthash",
+        ScriptType::P2TR => "witness_v1_taproot",
+        ScriptType::P2MS => "multisig",
+        ScriptType::P2A => "anchor",
+        ScriptType::OpReturn => "nulldata",
+        ScriptType::WitnessUnknown(_) => "witness_unknown",
+        ScriptType::OpTrue | ScriptType::NonStandard => "nonstandard",
+    }
+}
+
+/// The name Esplora and mempool.space give a script template in the
+/// `scriptpubkey_type` field of their transaction JSON
+pub fn esplora_script_type(script_type: ScriptType) -> &'static str {
+    match script_type {
+        ScriptType::P2PK => "p2pk",
+        ScriptType::P2PKH => "p2pkh",
+        ScriptType::P2SH => "p2sh",
+        ScriptType::P2WPKH => "v0_p2wpkh",
+        ScriptType::P2WSH => "v0_p2wsh",
+        ScriptType::P2TR => "v1_p2tr",
+        ScriptType::P2MS => "multisig",
+        ScriptType::P2A => "anchor",
+        ScriptType::OpReturn => "op_return",
+        ScriptType::WitnessUnknown(_) | ScriptType::OpTrue | ScriptType::NonStandard => "unknown",
+    }
+}
+
+/// Serialize a raw transaction to the transaction JSON of the Esplora and
+/// mempool.space APIs. The outputs spent by the inputs are needed for the
+/// `prevout` objects and the `fee`, without them both are `null`. The `status`
+/// is always unconfirmed since the block is not known
+pub fn esplora_json(
+    raw_tx: &[u8],
+    prevouts: Option<&[TxOutput]>,
+    network: Network,
+) -> io::Result<String> {
+    let tx = BtcTx::from_hex_bytes(raw_tx)?;
+    if let Some(prevouts) = prevouts {
+        if prevouts.len() != tx.inputs().len() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "The transaction has {} inputs but {} outputs being spent were given",
+                    tx.inputs().len(),
+                    prevouts.len()
+                ),
+            ));
+        }
+    }
+
+    let txid = tx.txid();
+    let weight = tx.weight();
+
+    let vin = tx
+        .inputs()
+        .iter()
+        .enumerate()
+        .map(|(index, input)| {
+            let is_coinbase = input.inferred_spend_type() == SpendType::Coinbase;
+            let prevout = prevouts
+                .filter(|_| !is_coinbase)
+                .map(|prevouts| &prevouts[index]);
+
+            let mut fields = vec![
+                ("txid", json_string(&input.previous_tx_id().to_string())),
+                ("vout", input.previous_output_index().to_string()),
+                (
+                    "prevout",
+                    prevout.map_or("null".to_string(), |output| esplora_output(output, network)),
+                ),
+                (
+                    "scriptsig",
+                    json_string(&hex::encode(input.signature_script())),
+                ),
+                (
+                    "scriptsig_asm",
+                    json_string(&esplora_asm(input.signature_script())),
+                ),
+            ];
+            if !input.witness().is_empty() {
+                let witness = input
+                    .witness()
+                    .iter()
+                    .map(|element| json_string(&hex::encode(element)))
+                    .collect::<Vec<String>>();
+                fields.push(("witness", format!("[{}]", witness.join(","))));
+            }
+            fields.push(("is_coinbase", is_coinbase.to_string()));
+            fields.push(("sequence", input.sequence_number().to_string()));
+
+            // The scripts revealed by script hash spends
+            let prevout_type = prevout.map(|output| output.script_type());
+            let redeem_script = StandardScripts::read_pushes(input.signature_script())
+                .ok()
+                .and_then(|mut pushes| pushes.pop());
+            if let (Some(ScriptType::P2SH), Some(redeem_script)) = (prevout_type, &redeem_script) {
+                fields.push((
+                    "inner_redeemscript_asm",
+                    json_string(&esplora_asm(redeem_script)),
+                ));
+            }
+            let wraps_p2wsh = redeem_script
+                .as_deref()
+                .is_some_and(|script| ScriptType::from_script(script) == ScriptType::P2WSH);
+            if prevout_type == Some(ScriptType::P2WSH)
+                || (prevout_type == Some(ScriptType::P2SH) && wraps_p2wsh)
+            {
+                if let Some(witness_script) = input.witness().iter().last() {
+                    fields.push((
+                        "inner_witnessscript_asm",
+                        json_string(&esplora_asm(witness_script)),
+                    ));
+                }
+            }
+
+            json_object(&fields)
+        })
+        .collect::<Vec<String>>();
+
+    let vout = tx
+        .outputs()
+        .iter()
+        .map(|output| esplora_output(output, network))
+        .collect::<Vec<String>>();
+
+    let fee = prevouts.and_then(|prevouts| {
+        let spent = prevouts
+            .iter()
+            .zip(tx.inputs())
+            .filter(|(_, input)| input.inferred_spend_type() != SpendType::Coinbase)
+            .map(|(output, _)| output.amount())
+            .sum::<u64>();
+        let paid = tx
+            .outputs()
+            .iter()
+            .map(|output| output.amount())
+            .sum::<u64>();
+
+        spent.checked_sub(paid)
+    });
+
+    Ok(json_object(&[
+        ("txid", json_string(&txid.to_string())),
+        (
+            "version",
+            u32::from_le_bytes(tx.version().to_bytes()).to_string(),
+        ),
+        ("locktime", tx.locktime().to_string()),
+        ("vin", format!("[{}]", vin.join(","))),
+        ("vout", format!("[{}]", vout.join(","))),
+        ("size", raw_tx.len().to_string()),
+        ("weight", weight.to_string()),
+        ("fee", fee.map_or("null".to_string(), |fee| fee.to_string())),
+        ("status", json_object(&[("confirmed", "false".to_string())])),
+    ]))
+}
+
+fn esplora_output(output: &TxOutput, network: Network) -> String {
+    let mut fields = vec![
+        (
+            "scriptpubkey",
+            json_string(&hex::encode(output.locking_script())),
+        ),
+        (
+            "scriptpubkey_asm",
+            json_string(&esplora_asm(output.locking_script())),
+        ),
+        (
+            "scriptpubkey_type",
+            json_string(esplora_script_type(output.script_type())),
+        ),
+    ];
+    if let Some(address) = output.address(network) {
+        fields.push(("scriptpubkey_address", json_string(&address)));
+    }
+    fields.push(("value", output.amount().to_string()));
+
+    json_object(&fields)
+}
+
+// Esplora names every push opcode. Malformed scripts are valid in outputs
+// and witnesses so their asm is left empty instead of failing the transaction
+fn esplora_asm(script: &[u8]) -> String {
+    StandardScripts::to_asm(script, AsmFormat::Explicit).unwrap_or_default()
+}
+
+// The strings written are hex, addresses, descriptors and asm
+// so only quotes and backslashes need escaping
+pub(crate) fn json_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+pub(crate) fn json_object(fields: &[(&str, String)]) -> String {
+    let fields = fields
+        .iter()
+        .map(|(key, value)| format!("{}:{}", json_string(key), value))
+        .collect::<Vec<String>>();
+
+    format!("{{{}}}", fields.join(","))
+}
+
+#[cfg(test)]
+mod report_sanity_checks {
+    use crate::{
+        decode_to_report, esplora_json, LockTime, Network, RelativeLockTime, Script, ScriptType,
+        SpendType, TxOutput,
+    };
+    use hex_literal::hex;
+
+    #[test]
+    fn sample_transaction() {
+        let report = decode_to_report(include_str!(
+            "../fixtures/transactions/p2pkh_two_inputs.hex"
+        ))
+        .unwrap();
+
+        assert_eq!(64, report.txid.len());
+        assert_eq!(1, report.version);
+        assert_eq!(report.size * 4, report.weight);
+        assert_eq!(report.size, report.vsize);
+        assert_eq!(LockTime::Unlocked, report.locktime_kind);
+
+        assert_eq!(2, report.inputs.len());
+        report.inputs.iter().for_each(|input| {
+            assert_eq!(SpendType::P2PKH, input.spend_type);
+            assert_eq!(None, input.value);
+            assert_eq!(RelativeLockTime::Disabled, input.relative_locktime);
+        });
+
+        assert_eq!(1, report.outputs.len());
+        assert_eq!(95000, report.outputs[0].amount);
+        assert_eq!(ScriptType::P2PKH, report.outputs[0].script_type);
+        assert_eq!(
+            Some("12B7CgUyGLPVWKFFSCFVR7MHTM2ptxNnu4"),
+            report.outputs[0].address.as_deref()
+        );
+
+        assert!(decode_to_report("zz").is_err());
+    }
+
+    #[test]
+    fn esplora_transaction() {
+        let raw_tx =
+            hex::decode(include_str!("../fixtures/transactions/p2pkh_two_inputs.hex").trim())
+                .unwrap();
+        let spent = TxOutput::new(
+            50_000,
+            hex!("76a9140ce17649c1306c291ca9e587f8793b5b06563cea88ac").to_vec(),
+        );
+
+        let json: serde_json::Value = serde_json::from_str(
+            &esplora_json(&raw_tx, Some(&[spent.clone(), spent]), Network::Mainnet).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            json["txid"],
+            decode_to_report(&hex::encode(&raw_tx)).unwrap().txid
+        );
+        assert_eq!(json["fee"], 5_000);
+        assert_eq!(json["vin"][0]["prevout"]["scriptpubkey_type"], "p2pkh");
+        assert_eq!(json["vin"][0]["is_coinbase"], false);
+        assert!(json["vin"][0].get("witness").is_none());
+        assert_eq!(json["vout"][0]["value"], 95_000);
+        assert_eq!(
+            json["vout"][0]["scriptpubkey_address"],
+            "12B7CgUyGLPVWKFFSCFVR7MHTM2ptxNnu4"
+        );
+        assert!(json["vout"][0]["scriptpubkey_asm"]
+            .as_str()
+            .unwrap()
+            .starts_with("OP_DUP OP_HASH160 OP_PUSHBYTES_20"));
+        assert_eq!(json["status"]["confirmed"], false);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&esplora_json(&raw_tx, None, Network::Mainnet).unwrap()).unwrap();
+        assert!(json["fee"].is_null());
+        assert!(json["vin"][1]["prevout"].is_null());
+        assert!(esplora_json(&raw_tx, Some(&[]), Network::Mainnet).is_err());
+    }
+
+    #[test]
+    fn script_report() {
+        // A P2PKH script can be wrapped in P2SH and as P2WPKH
+        let script = Script::new(hex!("76a9140ce17649c1306c291ca9e587f8793b5b06563cea88ac"));
+        let report = script.decode_report(Network::Mainnet);
+
+        assert_eq!(
+            "OP_DUP OP_HASH160 0ce17649c1306c291ca9e587f8793b5b06563cea OP_EQUALVERIFY OP_CHECKSIG",
+            report.asm
+        );
+        assert_eq!("pubkeyhash", report.script_type);
+        assert!(report.desc.starts_with("addr(1"));
+        assert!(report.p2sh.as_deref().unwrap().starts_with('3'));
+
+        let segwit = report.segwit.as_ref().unwrap();
+        assert_eq!("00140ce17649c1306c291ca9e587f8793b5b06563cea", segwit.hex);
+        assert_eq!("witness_v0_keyhash", segwit.script_type);
+        assert!(segwit.p2sh_segwit.starts_with('3'));
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["type"], "pubkeyhash");
+        assert_eq!(json["segwit"]["hex"], segwit.hex.as_str());
+
+        // Data carrier and segwit scripts cannot be wrapped
+        let op_return =
+            Script::new(hex!("6a0b68656c6c6f20776f726c64")).decode_report(Network::Mainnet);
+        assert_eq!("nulldata", op_return.script_type);
+        assert_eq!(None, op_return.address);
+        assert_eq!(None, op_return.p2sh);
+        assert!(op_return.segwit.is_none());
+
+        let op_return = Script::new([&[0x6a, 0x4c, 0x50][..], &[0xab; 80]].concat())
+            .decode_report(Network::Mainnet);
+        assert_eq!(format!("OP_RETURN {}", "ab".repeat(80)), op_return.asm);
+    }
+}
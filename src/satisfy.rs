@@ -0,0 +1,247 @@
+use crate::{Script, ScriptType, Witness};
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
+use std::io::{self, ErrorKind};
+
+/// Signatures, public keys and scripts produced outside this crate, for example
+/// by a hardware signer, which are assembled into the scriptSig and witness of
+/// an input by `BtcTx::satisfy_input()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct InputSatisfaction {
+    /// The locking script of the output spent by the input
+    pub locking_script: Vec<u8>,
+    /// The signatures including their sighash byte in the order the script checks them
+    pub signatures: Vec<Vec<u8>>,
+    /// The public key of each signature for outputs paying to a key hash
+    pub public_keys: Vec<Vec<u8>>,
+    /// The redeem script of a P2SH output
+    pub redeem_script: Option<Vec<u8>>,
+    /// The witness script of a P2WSH output, also when it is wrapped in P2SH
+    pub witness_script: Option<Vec<u8>>,
+}
+
+impl InputSatisfaction {
+    /// Assemble the scriptSig and the witness for the type of the locking script.
+    /// P2PK, P2PKH, P2MS, P2WPKH, taproot key path spends and these scripts as
+    /// redeem or witness scripts of P2SH and P2WSH outputs are supported.
+    /// The redeem script, witness script and public keys must match the
+    /// hashes they are committed to
+    pub fn assemble(&self) -> io::Result<(Vec<u8>, Witness)> {
+        let mut signature_script = Vec::<u8>::new();
+        let mut witness = Witness::new();
+
+        match ScriptType::from_script(&self.locking_script) {
+            ScriptType::P2SH => {
+                let redeem_script = self.redeem_script()?;
+                if Script::new(redeem_script).to_p2sh().as_bytes() != self.locking_script {
+                    return Err(Self::mismatch("redeem script"));
+                }
+
+                match ScriptType::from_script(redeem_script) {
+                    // Wrapped segwit outputs only push the witness program
+                    ScriptType::P2WPKH | ScriptType::P2WSH => {
+                        witness = self.witness(redeem_script)?;
+                    }
+                    _ => self
+                        .stack(redeem_script)?
+                        .iter()
+                        .for_each(|element| push_data(&mut signature_script, element)),
+                }
+
+                push_data(&mut signature_script, redeem_script);
+            }
+            ScriptType::P2WPKH | ScriptType::P2WSH | ScriptType::P2TR => {
+                witness = self.witness(&self.locking_script)?;
+            }
+            _ => self
+                .stack(&self.locking_script)?
+                .iter()
+                .for_each(|element| push_data(&mut signature_script, element)),
+        }
+
+        Ok((signature_script, witness))
+    }
+
+    // The witness spending the witness program
+    fn witness(&self, witness_program: &[u8]) -> io::Result<Witness> {
+        let mut witness = Witness::new();
+
+        match ScriptType::from_script(witness_program) {
+            ScriptType::P2WPKH => {
+                self.check_key_hash(&witness_program[2..])?;
+                witness.push(self.signature(0)?).push(self.public_key()?);
+            }
+            ScriptType::P2WSH => {
+                let witness_script = self.witness_script.as_deref().ok_or_else(|| {
+                    io::Error::new(ErrorKind::InvalidInput, "The witness script is missing")
+                })?;
+                if Script::new(witness_script).to_p2wsh().as_bytes() != witness_program {
+                    return Err(Self::mismatch("witness script"));
+                }
+
+                self.stack(witness_script)?.iter().for_each(|element| {
+                    witness.push(element);
+                });
+                witness.push(witness_script);
+            }
+            // Only the key path which is a single signature
+            ScriptType::P2TR => {
+                witness.push(self.signature(0)?);
+            }
+            script_type => return Err(Self::unsupported(script_type)),
+        }
+
+        Ok(witness)
+    }
+
+    // The elements satisfying a script which is not a script hash or witness program
+    fn stack(&self, script: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+        match ScriptType::from_script(script) {
+            ScriptType::P2PK => Ok(vec![self.signature(0)?.to_vec()]),
+            ScriptType::P2PKH => {
+                self.check_key_hash(&script[3..23])?;
+
+                Ok(vec![
+                    self.signature(0)?.to_vec(),
+                    self.public_key()?.to_vec(),
+                ])
+            }
+            // `OP_CHECKMULTISIG` pops an extra element which must be empty
+            ScriptType::P2MS => {
+                let threshold = script[0] - 0x50;
+                if self.signatures.len() != threshold as usize {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidInput,
+                        format!(
+                            "The multisig script needs {} signatures but there are {}",
+                            threshold,
+                            self.signatures.len()
+                        ),
+                    ));
+                }
+
+                Ok([vec![Vec::new()], self.signatures.clone()].concat())
+            }
+            script_type => Err(Self::unsupported(script_type)),
+        }
+    }
+
+    fn redeem_script(&self) -> io::Result<&[u8]> {
+        self.redeem_script
+            .as_deref()
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "The redeem script is missing"))
+    }
+
+    fn signature(&self, index: usize) -> io::Result<&[u8]> {
+        self.signatures
+            .get(index)
+            .map(|signature| signature.as_slice())
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "A signature is missing"))
+    }
+
+    fn public_key(&self) -> io::Result<&[u8]> {
+        self.public_keys
+            .first()
+            .map(|public_key| public_key.as_slice())
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "The public key is missing"))
+    }
+
+    fn check_key_hash(&self, key_hash: &[u8]) -> io::Result<()> {
+        let hash: [u8; 20] = Ripemd160::digest(Sha256::digest(self.public_key()?)).into();
+        if hash != key_hash {
+            return Err(Self::mismatch("public key"));
+        }
+
+        Ok(())
+    }
+
+    fn mismatch(what: &str) -> io::Error {
+        io::Error::new(
+            ErrorKind::InvalidInput,
+            format!("The {} does not match the hash it is committed to", what),
+        )
+    }
+
+    fn unsupported(script_type: ScriptType) -> io::Error {
+        io::Error::new(
+            ErrorKind::Unsupported,
+            format!("Spending {} scripts is not supported", script_type),
+        )
+    }
+}
+
+// Push data with the shortest push opcode since scriptSigs must be push-only
+// and redeem scripts can be longer than 75 bytes
+fn push_data(script: &mut Vec<u8>, data: &[u8]) {
+    match data.len() {
+        0 => script.push(0x00),
+        length @ 1..=75 => script.push(length as u8),
+        length @ 76..=0xff => script.extend_from_slice(&[0x4c, length as u8]),
+        length => {
+            script.push(0x4d);
+            script.extend_from_slice(&(length as u16).to_le_bytes());
+        }
+    }
+    script.extend_from_slice(data);
+}
+
+#[cfg(test)]
+mod satisfy_sanity_checks {
+    use super::InputSatisfaction;
+    use crate::{BtcTx, Hash256, Script, TxInput, TxOutput, TxVersion, Txid};
+    use hex_literal::hex;
+
+    const PUBLIC_KEY: [u8; 33] =
+        hex!("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798");
+
+    #[test]
+    fn satisfy_inputs() {
+        let mut tx = BtcTx::new(
+            TxVersion::Two,
+            vec![
+                TxInput::new(Txid::new(Hash256::new([1u8; 32])), 0, Vec::new(), 0),
+                TxInput::new(Txid::new(Hash256::new([1u8; 32])), 1, Vec::new(), 0),
+            ],
+            vec![TxOutput::new(1_000, vec![0x51])],
+            0,
+        );
+
+        // The key hash of the public key of the private key 1
+        let p2wpkh = InputSatisfaction {
+            locking_script: hex!("0014751e76e8199196d454941c45d1b3a323f1433bd6").to_vec(),
+            signatures: vec![vec![0x30; 72]],
+            public_keys: vec![PUBLIC_KEY.to_vec()],
+            ..Default::default()
+        };
+        tx.satisfy_input(0, &p2wpkh).unwrap();
+        assert!(tx.inputs()[0].signature_script().is_empty());
+        assert_eq!(2, tx.inputs()[0].witness().len());
+
+        // A 1-of-2 multisig wrapped in P2SH
+        let multisig = [
+            &[0x51, 0x21][..],
+            &PUBLIC_KEY,
+            &[0x21],
+            &PUBLIC_KEY,
+            &[0x52, 0xae],
+        ]
+        .concat();
+        let mut p2sh = InputSatisfaction {
+            locking_script: Script::new(multisig.clone()).to_p2sh().as_bytes().to_vec(),
+            signatures: vec![vec![0x30; 71]],
+            redeem_script: Some(multisig.clone()),
+            ..Default::default()
+        };
+        tx.satisfy_input(1, &p2sh).unwrap();
+        assert_eq!(
+            [&[0x00, 71][..], &[0x30; 71], &[0x47], &multisig].concat(),
+            tx.inputs()[1].signature_script()
+        );
+        assert!(tx.inputs()[1].witness().is_empty());
+
+        // The redeem script must hash to the locking script
+        p2sh.redeem_script.as_mut().unwrap().push(0x51);
+        assert!(tx.satisfy_input(1, &p2sh).is_err());
+        assert!(tx.satisfy_input(2, &p2wpkh).is_err());
+    }
+}
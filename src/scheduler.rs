@@ -0,0 +1,248 @@
+use crate::{BtcTx, LockTime, OutPoint, RelativeLockTime, Txid, SEQUENCE_FINAL};
+use std::{
+    collections::BTreeMap,
+    io::{self, ErrorKind},
+};
+
+/// The height of a block and its median time past, the median of the
+/// timestamps of the block and the ten blocks before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct BlockTime {
+    /// The height of the block
+    pub height: u32,
+    /// The median time past of the block as a UNIX timestamp
+    pub median_time_past: u32,
+}
+
+/// A scheduled transaction whose timelocks are met by the chain tip
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ReadyTx {
+    /// The transaction ID
+    pub txid: Txid,
+    /// The raw transaction to broadcast
+    pub raw_tx: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ScheduledTx {
+    raw_tx: Vec<u8>,
+    tx: BtcTx,
+    // When the output spent by each input was confirmed, see
+    // `BroadcastScheduler::schedule()` for which block is meant
+    prevouts: Vec<Option<BlockTime>>,
+}
+
+/// Holds signed transactions until their absolute and relative timelocks allow
+/// them into the next block, like a watchtower holding justice or HTLC timeout
+/// transactions
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BroadcastScheduler {
+    pending: BTreeMap<Txid, ScheduledTx>,
+    tip: Option<BlockTime>,
+}
+
+impl BroadcastScheduler {
+    /// A scheduler without transactions which has not seen a chain tip
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hold a signed transaction until it can be broadcast. `prevouts` has an
+    /// entry for every input with the height of the block confirming the spent
+    /// output and the median time past of the block before it as BIP68 requires,
+    /// or `None` while the output is unconfirmed
+    pub fn schedule(
+        &mut self,
+        raw_tx: Vec<u8>,
+        prevouts: Vec<Option<BlockTime>>,
+    ) -> io::Result<Txid> {
+        let tx = BtcTx::from_hex_bytes(&raw_tx)?;
+        if prevouts.len() != tx.inputs().len() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "The transaction has {} inputs but {} confirmations of spent outputs were given",
+                    tx.inputs().len(),
+                    prevouts.len()
+                ),
+            ));
+        }
+
+        let txid = Txid::hash(&raw_tx);
+        self.pending.insert(
+            txid,
+            ScheduledTx {
+                raw_tx,
+                tx,
+                prevouts,
+            },
+        );
+
+        Ok(txid)
+    }
+
+    /// Record that the output spent by scheduled transactions was confirmed,
+    /// with the same meaning of `confirmation` as in `BroadcastScheduler::schedule()`
+    pub fn prevout_confirmed(&mut self, outpoint: OutPoint, confirmation: BlockTime) {
+        self.pending.values_mut().for_each(|scheduled| {
+            scheduled
+                .tx
+                .inputs()
+                .iter()
+                .zip(scheduled.prevouts.iter_mut())
+                .filter(|(input, _)| input.previous_output() == outpoint)
+                .for_each(|(_, prevout)| *prevout = Some(confirmation));
+        });
+    }
+
+    /// Move to a new chain tip and return the transactions that can be mined in
+    /// the next block. They are no longer held by the scheduler
+    pub fn update_tip(&mut self, tip: BlockTime) -> Vec<ReadyTx> {
+        self.tip = Some(tip);
+
+        let ready = self
+            .pending
+            .iter()
+            .filter(|(_, scheduled)| Self::is_broadcastable(scheduled, tip))
+            .map(|(txid, _)| *txid)
+            .collect::<Vec<Txid>>();
+
+        ready
+            .into_iter()
+            .filter_map(|txid| {
+                self.pending.remove(&txid).map(|scheduled| ReadyTx {
+                    txid,
+                    raw_tx: scheduled.raw_tx,
+                })
+            })
+            .collect()
+    }
+
+    /// The last chain tip passed to `BroadcastScheduler::update_tip()`
+    pub fn tip(&self) -> Option<BlockTime> {
+        self.tip
+    }
+
+    /// Stop holding a transaction
+    pub fn cancel(&mut self, txid: &Txid) -> bool {
+        self.pending.remove(txid).is_some()
+    }
+
+    /// The transaction IDs of the held transactions
+    pub fn pending(&self) -> impl Iterator<Item = &Txid> {
+        self.pending.keys()
+    }
+
+    /// The number of held transactions
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Whether no transaction is held
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    fn is_broadcastable(scheduled: &ScheduledTx, tip: BlockTime) -> bool {
+        earliest_tip(&scheduled.tx, &scheduled.prevouts).is_some_and(|earliest| {
+            tip.height >= earliest.height && tip.median_time_past >= earliest.median_time_past
+        })
+    }
+}
+
+// The lowest chain tip height and median time past on top of which `tx` can be
+// mined, following `IsFinalTx()` and `SequenceLocks()` of Bitcoin Core. `None`
+// while a relative timelock waits for an unconfirmed output of `prevouts`
+pub(crate) fn earliest_tip(tx: &BtcTx, prevouts: &[Option<BlockTime>]) -> Option<BlockTime> {
+    let mut earliest = BlockTime::default();
+
+    let is_final = tx
+        .inputs()
+        .iter()
+        .all(|input| input.sequence_number() == SEQUENCE_FINAL);
+    if !is_final {
+        match LockTime::from_consensus(tx.locktime()) {
+            LockTime::Unlocked => (),
+            LockTime::BlockHeight(height) => earliest.height = height,
+            LockTime::Timestamp(timestamp) => {
+                earliest.median_time_past = timestamp.saturating_add(1)
+            }
+        }
+    }
+
+    let version = u32::from_le_bytes(tx.version().to_bytes());
+    for (input, prevout) in tx.inputs().iter().zip(prevouts) {
+        match (
+            RelativeLockTime::from_sequence(input.sequence_number(), version),
+            prevout,
+        ) {
+            (RelativeLockTime::Disabled, _) => (),
+            (RelativeLockTime::Blocks(0), _) => (),
+            (RelativeLockTime::Seconds(0), _) => (),
+            // The output must be confirmed for the lock to start
+            (_, None) => return None,
+            (RelativeLockTime::Blocks(blocks), Some(confirmation)) => {
+                earliest.height = earliest
+                    .height
+                    .max(confirmation.height.saturating_add(blocks as u32 - 1))
+            }
+            (RelativeLockTime::Seconds(seconds), Some(confirmation)) => {
+                earliest.median_time_past = earliest
+                    .median_time_past
+                    .max(confirmation.median_time_past.saturating_add(seconds))
+            }
+        }
+    }
+
+    Some(earliest)
+}
+
+#[cfg(test)]
+mod scheduler_sanity_checks {
+    use crate::{BlockTime, BroadcastScheduler, BtcTx, Txid};
+    use hex_literal::hex;
+
+    // A version 2 transaction with one input and one output
+    fn raw_tx(sequence_number: u32, locktime: u32) -> Vec<u8> {
+        [
+            &hex!("0200000001")[..],
+            &[0x11; 32],
+            &hex!("0000000000"),
+            &sequence_number.to_le_bytes(),
+            &hex!("01e8030000000000000151"),
+            &locktime.to_le_bytes(),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn schedule_timelocks() {
+        let tip = |height| BlockTime {
+            height,
+            median_time_past: 1_700_000_000 + height * 600,
+        };
+        let mut scheduler = BroadcastScheduler::new();
+
+        // Mineable in block 800,001 once the tip is at 800,000
+        let absolute = scheduler
+            .schedule(raw_tx(0xfffffffe, 800_000), vec![None])
+            .unwrap();
+        // 144 blocks after an output which is not confirmed yet
+        let relative = scheduler.schedule(raw_tx(144, 0), vec![None]).unwrap();
+        assert!(scheduler.schedule(raw_tx(144, 0), vec![]).is_err());
+
+        assert!(scheduler.update_tip(tip(799_999)).is_empty());
+        let ready = scheduler.update_tip(tip(800_000));
+        assert_eq!(
+            vec![absolute],
+            ready.iter().map(|tx| tx.txid).collect::<Vec<Txid>>()
+        );
+
+        let outpoint =
+            BtcTx::from_hex_bytes(&ready[0].raw_tx).unwrap().inputs()[0].previous_output();
+        scheduler.prevout_confirmed(outpoint, tip(800_000));
+        assert!(scheduler.update_tip(tip(800_142)).is_empty());
+        assert_eq!(relative, scheduler.update_tip(tip(800_143))[0].txid);
+        assert!(scheduler.is_empty());
+    }
+}
@@ -0,0 +1,223 @@
+use crate::{
+    core_script_type, Address, AsmFormat, Descriptor, Hash160, Hash256, Network, ScriptReport,
+    ScriptType, SegwitReport, StandardScripts, VarInt,
+};
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
+
+/// The leaf version of tapscript as defined in BIP342
+pub const TAPSCRIPT_LEAF_VERSION: u8 = 0xc0;
+
+/// The raw bytes of a script like a redeem script, witness script or tapscript
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Default, Hash)]
+pub struct Script(Vec<u8>);
+
+impl Script {
+    /// Instantiate a script from its raw bytes
+    pub fn new(bytes: impl Into<Vec<u8>>) -> Self {
+        Self(bytes.into())
+    }
+
+    /// The raw bytes of the script
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// The number of bytes in the script
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether the script has no bytes
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Classify the script into one of the standard script templates
+    pub fn script_type(&self) -> ScriptType {
+        ScriptType::from_script(&self.0)
+    }
+
+    /// The HASH160 (RIPEMD160 of the SHA256) of the script used
+    /// when the script is the redeem script of a P2SH output
+    pub fn p2sh_hash(&self) -> Hash160 {
+        Hash160::new(Ripemd160::digest(Sha256::digest(&self.0)).into())
+    }
+
+    /// The SHA256 of the script used when the script is the
+    /// witness script of a P2WSH output
+    pub fn p2wsh_hash(&self) -> Hash256 {
+        Hash256::new(Sha256::digest(&self.0).into())
+    }
+
+    /// The BIP341 tagged hash of the script used as a leaf of a taproot
+    /// script tree. For tapscript the `leaf_version` is `TAPSCRIPT_LEAF_VERSION`
+    pub fn tapleaf_hash(&self, leaf_version: u8) -> Hash256 {
+        // The script is serialized with its VarInt length after the leaf version
+        let mut leaf = vec![leaf_version];
+        leaf.extend_from_slice(&VarInt::encode(self.0.len() as u64));
+        leaf.extend_from_slice(&self.0);
+
+        Hash256::new(tagged_hash("TapLeaf", &leaf))
+    }
+
+    /// The P2SH locking script `OP_HASH160 <hash> OP_EQUAL` wrapping this script
+    pub fn to_p2sh(&self) -> Script {
+        let mut locking_script = vec![0xa9, 0x14];
+        locking_script.extend_from_slice(self.p2sh_hash().as_ref());
+        locking_script.push(0x87);
+
+        Script(locking_script)
+    }
+
+    /// The P2WSH locking script `OP_0 <hash>` wrapping this script
+    pub fn to_p2wsh(&self) -> Script {
+        let mut locking_script = vec![0x00, 0x20];
+        locking_script.extend_from_slice(self.p2wsh_hash().as_ref());
+
+        Script(locking_script)
+    }
+
+    /// The P2SH address of this script as a redeem script
+    pub fn p2sh_address(&self, network: Network) -> String {
+        Address::p2sh(self.p2sh_hash().as_ref(), network)
+    }
+
+    /// The P2WSH address of this script as a witness script
+    pub fn p2wsh_address(&self, network: Network) -> String {
+        Address::segwit(0, self.p2wsh_hash().as_ref(), network)
+    }
+
+    /// Decode the script the same way as Bitcoin Core's `decodescript` including
+    /// the P2SH and segwit forms wrapping the script where they are allowed
+    pub fn decode_report(&self, network: Network) -> ScriptReport {
+        let script_type = self.script_type();
+
+        // P2SH, witness programs, anchors and data carriers cannot be wrapped in P2SH
+        let p2sh = match script_type {
+            ScriptType::P2SH
+            | ScriptType::P2WPKH
+            | ScriptType::P2WSH
+            | ScriptType::P2TR
+            | ScriptType::P2A
+            | ScriptType::WitnessUnknown(_)
+            | ScriptType::OpReturn => None,
+            _ => Some(self.p2sh_address(network)),
+        };
+
+        // A public key hash is wrapped as P2WPKH. Uncompressed public keys
+        // are not allowed in segwit so P2PK and P2MS using them are not wrapped
+        let segwit = match script_type {
+            ScriptType::P2PKH => {
+                let mut p2wpkh = vec![0x00, 0x14];
+                p2wpkh.extend_from_slice(&self.0[3..23]);
+                Some(Script(p2wpkh))
+            }
+            ScriptType::P2MS if !self.has_uncompressed_key() => Some(self.to_p2wsh()),
+            ScriptType::NonStandard | ScriptType::OpTrue => Some(self.to_p2wsh()),
+            _ => None,
+        }
+        .filter(|_| p2sh.is_some())
+        .map(|segwit| SegwitReport {
+            asm: segwit.asm(),
+            hex: hex::encode(segwit.as_bytes()),
+            script_type: core_script_type(segwit.script_type()).into(),
+            address: Address::from_script(segwit.as_bytes(), network),
+            desc: Descriptor::infer(segwit.as_bytes(), network),
+            p2sh_segwit: segwit.p2sh_address(network),
+        });
+
+        ScriptReport {
+            asm: self.asm(),
+            desc: Descriptor::infer(&self.0, network),
+            script_type: core_script_type(script_type).into(),
+            address: Address::from_script(&self.0, network),
+            p2sh,
+            segwit,
+        }
+    }
+
+    // Bitcoin Core writes `[error]` for scripts it cannot decode
+    fn asm(&self) -> String {
+        StandardScripts::to_asm(&self.0, AsmFormat::CoreAsm).unwrap_or_else(|_| "[error]".into())
+    }
+
+    // The public keys of a multisig script are the pushes after the threshold
+    fn has_uncompressed_key(&self) -> bool {
+        let mut position = 1usize;
+        while let Some(push_len @ 1..=75) = self.0.get(position).map(|byte| *byte as usize) {
+            if push_len == 65 {
+                return true;
+            }
+            position += 1 + push_len;
+        }
+
+        false
+    }
+}
+
+impl AsRef<[u8]> for Script {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for Script {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<&[u8]> for Script {
+    fn from(bytes: &[u8]) -> Self {
+        Self(bytes.to_vec())
+    }
+}
+
+// BIP340 tagged hashes prefix the data with the SHA256 of the tag twice
+// so that hashes for different purposes can never collide
+pub(crate) fn tagged_hash(tag: &str, data: &[u8]) -> [u8; 32] {
+    let tag_hash = Sha256::digest(tag.as_bytes());
+
+    Sha256::new()
+        .chain_update(tag_hash)
+        .chain_update(tag_hash)
+        .chain_update(data)
+        .finalize()
+        .into()
+}
+
+#[cfg(test)]
+mod script_sanity_checks {
+    use crate::{Network, Script, ScriptType, TAPSCRIPT_LEAF_VERSION};
+    use hex_literal::hex;
+
+    #[test]
+    fn script_hashes() {
+        // The anyone-can-spend `OP_TRUE` script
+        let script = Script::new([0x51]);
+
+        assert_eq!(
+            hex!("da1745e9b549bd0bfa1a569971c77eba30cd5a4b"),
+            script.p2sh_hash().to_byte_array()
+        );
+        assert_eq!(
+            hex!("4ae81572f06e1b88fd5ced7a1a000945432e83e1551e6f721ee9c00b8cc33260"),
+            script.p2wsh_hash().to_byte_array()
+        );
+        assert_eq!(
+            hex!("a85b2107f791b26a84e7586c28cec7cb61202ed3d01944d832500f363782d675"),
+            script.tapleaf_hash(TAPSCRIPT_LEAF_VERSION).to_byte_array()
+        );
+    }
+
+    #[test]
+    fn wrapped_forms() {
+        let script = Script::new([0x51]);
+
+        assert_eq!(ScriptType::P2SH, script.to_p2sh().script_type());
+        assert_eq!(ScriptType::P2WSH, script.to_p2wsh().script_type());
+        assert!(script.p2sh_address(Network::Mainnet).starts_with('3'));
+        assert!(script.p2wsh_address(Network::Regtest).starts_with("bcrt1q"));
+    }
+}
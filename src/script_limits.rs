@@ -0,0 +1,190 @@
+use crate::{Opcode, Script};
+use std::{
+    fmt,
+    io::{self, ErrorKind},
+    str::FromStr,
+};
+
+/// The largest script in bytes that legacy and segwit v0 scripts may run
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+
+/// The largest data push in bytes
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+
+/// The most opcodes which are not pushes in a legacy or segwit v0 script
+pub const MAX_OPS_PER_SCRIPT: usize = 201;
+
+/// The most items on the stack and the alternate stack together
+pub const MAX_STACK_SIZE: usize = 1_000;
+
+/// Why a script fails its resource limits, named like the `ScriptError` of
+/// Bitcoin Core without the `SCRIPT_ERR_` prefix as in its `script_tests.json`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ScriptErrorCode {
+    /// `SCRIPT_SIZE`, the script is larger than `MAX_SCRIPT_SIZE`
+    ScriptSize,
+    /// `PUSH_SIZE`, a push is larger than `MAX_SCRIPT_ELEMENT_SIZE`
+    PushSize,
+    /// `OP_COUNT`, more opcodes than `MAX_OPS_PER_SCRIPT`
+    OpCount,
+    /// `STACK_SIZE`, more stack items than `MAX_STACK_SIZE`
+    StackSize,
+    /// `BAD_OPCODE`, a push goes past the end of the script or `OP_VERIF` and
+    /// `OP_VERNOTIF` which fail even in a branch that is not executed
+    BadOpcode,
+    /// `DISABLED_OPCODE`, an opcode disabled since 2010 like `OP_CAT`
+    DisabledOpcode,
+}
+
+impl ScriptErrorCode {
+    /// The name used by Bitcoin Core test vectors
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::ScriptSize => "SCRIPT_SIZE",
+            Self::PushSize => "PUSH_SIZE",
+            Self::OpCount => "OP_COUNT",
+            Self::StackSize => "STACK_SIZE",
+            Self::BadOpcode => "BAD_OPCODE",
+            Self::DisabledOpcode => "DISABLED_OPCODE",
+        }
+    }
+}
+
+impl fmt::Display for ScriptErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for ScriptErrorCode {
+    type Err = io::Error;
+
+    /// Parse a name with or without the `SCRIPT_ERR_` prefix
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.strip_prefix("SCRIPT_ERR_").unwrap_or(name) {
+            "SCRIPT_SIZE" => Ok(Self::ScriptSize),
+            "PUSH_SIZE" => Ok(Self::PushSize),
+            "OP_COUNT" => Ok(Self::OpCount),
+            "STACK_SIZE" => Ok(Self::StackSize),
+            "BAD_OPCODE" => Ok(Self::BadOpcode),
+            "DISABLED_OPCODE" => Ok(Self::DisabledOpcode),
+            _ => Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("`{}` is not a script resource limit error", name),
+            )),
+        }
+    }
+}
+
+impl std::error::Error for ScriptErrorCode {}
+
+impl From<ScriptErrorCode> for io::Error {
+    fn from(error: ScriptErrorCode) -> Self {
+        io::Error::new(ErrorKind::InvalidData, error)
+    }
+}
+
+impl Script {
+    /// Check the limits of a legacy or segwit v0 script which do not depend on
+    /// running it, starting with `stack_items` items on the stack. The first
+    /// failure is returned in the order `EvalScript()` of Bitcoin Core finds
+    /// it. The opcode count does not include the keys `OP_CHECKMULTISIG` adds
+    /// when it runs and the stack size is only checked before the script runs
+    pub fn check_limits(&self, stack_items: usize) -> Result<(), ScriptErrorCode> {
+        let script = self.as_bytes();
+        if script.len() > MAX_SCRIPT_SIZE {
+            return Err(ScriptErrorCode::ScriptSize);
+        }
+        if stack_items > MAX_STACK_SIZE {
+            return Err(ScriptErrorCode::StackSize);
+        }
+
+        let mut position = 0usize;
+        let mut op_count = 0usize;
+        while position < script.len() {
+            let (opcode, push_len) =
+                Self::read_opcode(script, &mut position).map_err(|_| ScriptErrorCode::BadOpcode)?;
+
+            if push_len.is_some_and(|push_len| push_len > MAX_SCRIPT_ELEMENT_SIZE) {
+                return Err(ScriptErrorCode::PushSize);
+            }
+            let opcode = Opcode::from_byte(opcode);
+            // Opcodes above OP_16
+            if !matches!(
+                opcode,
+                Opcode::OP_0
+                    | Opcode::PushBytes(_)
+                    | Opcode::OP_PUSHDATA1
+                    | Opcode::OP_PUSHDATA2
+                    | Opcode::OP_PUSHDATA4
+                    | Opcode::OP_1NEGATE
+                    | Opcode::OP_RESERVED
+                    | Opcode::OP_1
+                    | Opcode::Num(_)
+            ) {
+                op_count += 1;
+                if op_count > MAX_OPS_PER_SCRIPT {
+                    return Err(ScriptErrorCode::OpCount);
+                }
+            }
+            if opcode.is_disabled() {
+                return Err(ScriptErrorCode::DisabledOpcode);
+            }
+            if matches!(opcode, Opcode::OP_VERIF | Opcode::OP_VERNOTIF) {
+                return Err(ScriptErrorCode::BadOpcode);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod script_limits_sanity_checks {
+    use crate::{Script, ScriptErrorCode, MAX_OPS_PER_SCRIPT};
+    use hex_literal::hex;
+
+    #[test]
+    fn resource_limits() {
+        let check = |bytes: Vec<u8>| Script::new(bytes).check_limits(0);
+
+        assert_eq!(
+            Ok(()),
+            check(hex!("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac").to_vec())
+        );
+        assert_eq!(Err(ScriptErrorCode::ScriptSize), check(vec![0x61; 10_001]));
+        // OP_PUSHDATA2 of 521 bytes
+        let push = [&hex!("4d0902")[..], &[0u8; 521]].concat();
+        assert_eq!(Err(ScriptErrorCode::PushSize), check(push));
+        assert_eq!(
+            Err(ScriptErrorCode::OpCount),
+            check(vec![0x61; MAX_OPS_PER_SCRIPT + 1])
+        );
+        // The op count is reached before the disabled OP_CAT
+        let mut script = vec![0x61; MAX_OPS_PER_SCRIPT];
+        script.push(0x7e);
+        assert_eq!(Err(ScriptErrorCode::OpCount), check(script));
+        assert_eq!(
+            Err(ScriptErrorCode::DisabledOpcode),
+            check(hex!("00637e68").to_vec())
+        );
+        assert_eq!(
+            Err(ScriptErrorCode::BadOpcode),
+            check(hex!("0063656851").to_vec())
+        );
+        assert_eq!(
+            Err(ScriptErrorCode::BadOpcode),
+            check(hex!("4c05aa").to_vec())
+        );
+        assert_eq!(
+            Err(ScriptErrorCode::StackSize),
+            Script::new(vec![0x51]).check_limits(1_001)
+        );
+
+        assert_eq!(
+            ScriptErrorCode::PushSize,
+            "SCRIPT_ERR_PUSH_SIZE".parse().unwrap()
+        );
+        assert_eq!("OP_COUNT", ScriptErrorCode::OpCount.to_string());
+    }
+}
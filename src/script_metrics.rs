@@ -0,0 +1,375 @@
+use crate::{Opcode, Script};
+use std::{
+    collections::BTreeMap,
+    io::{self, ErrorKind},
+};
+
+/// The group an opcode belongs to following the sections of the Bitcoin wiki
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum OpcodeCategory {
+    /// Data pushes, `OP_1NEGATE` and the small integers `OP_1` to `OP_16`
+    Push,
+    /// `OP_NOP`, `OP_IF`, `OP_NOTIF`, `OP_ELSE`, `OP_ENDIF`, `OP_VERIFY` and `OP_RETURN`
+    FlowControl,
+    /// Opcodes moving items on the main and alt stacks
+    Stack,
+    /// `OP_CAT`, `OP_SUBSTR`, `OP_LEFT`, `OP_RIGHT` and `OP_SIZE`
+    Splice,
+    /// Bitwise logic and `OP_EQUAL`
+    Bitwise,
+    /// Arithmetic on numbers of at most 4 bytes
+    Arithmetic,
+    /// Hashes and signature checks
+    Crypto,
+    /// `OP_CHECKLOCKTIMEVERIFY` and `OP_CHECKSEQUENCEVERIFY`
+    Locktime,
+    /// The `OP_NOP1` to `OP_NOP10` reserved for soft-forks
+    Nop,
+    /// `OP_RESERVED`, `OP_VER`, `OP_VERIF`, `OP_VERNOTIF`, `OP_RESERVED1`
+    /// and `OP_RESERVED2` which fail the script when executed
+    Reserved,
+    /// Bytes which are not assigned to any opcode
+    Invalid,
+}
+
+impl OpcodeCategory {
+    /// The category of an opcode byte
+    pub fn from_byte(byte: u8) -> Self {
+        Opcode::from_byte(byte).category()
+    }
+}
+
+impl Opcode {
+    /// The category of the opcode
+    pub fn category(&self) -> OpcodeCategory {
+        match self {
+            Self::OP_0
+            | Self::PushBytes(_)
+            | Self::OP_PUSHDATA1
+            | Self::OP_PUSHDATA2
+            | Self::OP_PUSHDATA4
+            | Self::OP_1NEGATE
+            | Self::OP_1
+            | Self::Num(_) => OpcodeCategory::Push,
+            Self::OP_RESERVED
+            | Self::OP_VER
+            | Self::OP_VERIF
+            | Self::OP_VERNOTIF
+            | Self::OP_RESERVED1
+            | Self::OP_RESERVED2 => OpcodeCategory::Reserved,
+            Self::OP_NOP
+            | Self::OP_IF
+            | Self::OP_NOTIF
+            | Self::OP_ELSE
+            | Self::OP_ENDIF
+            | Self::OP_VERIFY
+            | Self::OP_RETURN => OpcodeCategory::FlowControl,
+            Self::OP_TOALTSTACK
+            | Self::OP_FROMALTSTACK
+            | Self::OP_2DROP
+            | Self::OP_2DUP
+            | Self::OP_3DUP
+            | Self::OP_2OVER
+            | Self::OP_2ROT
+            | Self::OP_2SWAP
+            | Self::OP_IFDUP
+            | Self::OP_DEPTH
+            | Self::OP_DROP
+            | Self::OP_DUP
+            | Self::OP_NIP
+            | Self::OP_OVER
+            | Self::OP_PICK
+            | Self::OP_ROLL
+            | Self::OP_ROT
+            | Self::OP_SWAP
+            | Self::OP_TUCK => OpcodeCategory::Stack,
+            Self::OP_CAT | Self::OP_SUBSTR | Self::OP_LEFT | Self::OP_RIGHT | Self::OP_SIZE => {
+                OpcodeCategory::Splice
+            }
+            Self::OP_INVERT
+            | Self::OP_AND
+            | Self::OP_OR
+            | Self::OP_XOR
+            | Self::OP_EQUAL
+            | Self::OP_EQUALVERIFY => OpcodeCategory::Bitwise,
+            Self::OP_1ADD
+            | Self::OP_1SUB
+            | Self::OP_2MUL
+            | Self::OP_2DIV
+            | Self::OP_NEGATE
+            | Self::OP_ABS
+            | Self::OP_NOT
+            | Self::OP_0NOTEQUAL
+            | Self::OP_ADD
+            | Self::OP_SUB
+            | Self::OP_MUL
+            | Self::OP_DIV
+            | Self::OP_MOD
+            | Self::OP_LSHIFT
+            | Self::OP_RSHIFT
+            | Self::OP_BOOLAND
+            | Self::OP_BOOLOR
+            | Self::OP_NUMEQUAL
+            | Self::OP_NUMEQUALVERIFY
+            | Self::OP_NUMNOTEQUAL
+            | Self::OP_LESSTHAN
+            | Self::OP_GREATERTHAN
+            | Self::OP_LESSTHANOREQUAL
+            | Self::OP_GREATERTHANOREQUAL
+            | Self::OP_MIN
+            | Self::OP_MAX
+            | Self::OP_WITHIN => OpcodeCategory::Arithmetic,
+            Self::OP_RIPEMD160
+            | Self::OP_SHA1
+            | Self::OP_SHA256
+            | Self::OP_HASH160
+            | Self::OP_HASH256
+            | Self::OP_CODESEPARATOR
+            | Self::OP_CHECKSIG
+            | Self::OP_CHECKSIGVERIFY
+            | Self::OP_CHECKMULTISIG
+            | Self::OP_CHECKMULTISIGVERIFY
+            | Self::OP_CHECKSIGADD => OpcodeCategory::Crypto,
+            Self::OP_CHECKLOCKTIMEVERIFY | Self::OP_CHECKSEQUENCEVERIFY => OpcodeCategory::Locktime,
+            Self::Nop(_) => OpcodeCategory::Nop,
+            Self::Unknown(_) | Self::OP_INVALIDOPCODE => OpcodeCategory::Invalid,
+        }
+    }
+
+    // The number of items an opcode pops and pushes. Opcodes which depend on
+    // the value on top of the stack like `OP_PICK` only count that value
+    fn stack_effect(&self) -> (isize, isize) {
+        match self {
+            Self::OP_2DROP => (2, 0),
+            Self::OP_2DUP => (2, 4),
+            Self::OP_3DUP => (3, 6),
+            Self::OP_2OVER => (4, 6),
+            Self::OP_2ROT => (6, 6),
+            Self::OP_2SWAP => (4, 4),
+            Self::OP_NIP => (2, 1),
+            Self::OP_ROT => (3, 3),
+            Self::OP_SWAP => (2, 2),
+            Self::OP_CHECKSIGADD => (3, 1),
+            Self::OP_IF
+            | Self::OP_NOTIF
+            | Self::OP_VERIFY
+            | Self::OP_TOALTSTACK
+            | Self::OP_DROP
+            | Self::OP_ROLL => (1, 0),
+            Self::OP_FROMALTSTACK | Self::OP_DEPTH => (0, 1),
+            // These can add an item
+            Self::OP_IFDUP | Self::OP_DUP | Self::OP_SIZE => (1, 2),
+            Self::OP_OVER | Self::OP_TUCK => (2, 3),
+            Self::OP_SUBSTR | Self::OP_WITHIN => (3, 1),
+            Self::OP_EQUALVERIFY | Self::OP_NUMEQUALVERIFY | Self::OP_CHECKSIGVERIFY => (2, 0),
+            // OP_PICK, OP_INVERT, the unary arithmetic opcodes and the hashes
+            Self::OP_PICK
+            | Self::OP_INVERT
+            | Self::OP_1ADD
+            | Self::OP_1SUB
+            | Self::OP_2MUL
+            | Self::OP_2DIV
+            | Self::OP_NEGATE
+            | Self::OP_ABS
+            | Self::OP_NOT
+            | Self::OP_0NOTEQUAL
+            | Self::OP_RIPEMD160
+            | Self::OP_SHA1
+            | Self::OP_SHA256
+            | Self::OP_HASH160
+            | Self::OP_HASH256 => (1, 1),
+            // OP_CAT, OP_LEFT, OP_RIGHT, the binary logic and arithmetic
+            // opcodes and OP_CHECKSIG
+            Self::OP_CAT
+            | Self::OP_LEFT
+            | Self::OP_RIGHT
+            | Self::OP_AND
+            | Self::OP_OR
+            | Self::OP_XOR
+            | Self::OP_EQUAL
+            | Self::OP_ADD
+            | Self::OP_SUB
+            | Self::OP_MUL
+            | Self::OP_DIV
+            | Self::OP_MOD
+            | Self::OP_LSHIFT
+            | Self::OP_RSHIFT
+            | Self::OP_BOOLAND
+            | Self::OP_BOOLOR
+            | Self::OP_NUMEQUAL
+            | Self::OP_NUMNOTEQUAL
+            | Self::OP_LESSTHAN
+            | Self::OP_GREATERTHAN
+            | Self::OP_LESSTHANOREQUAL
+            | Self::OP_GREATERTHANOREQUAL
+            | Self::OP_MIN
+            | Self::OP_MAX
+            | Self::OP_CHECKSIG => (2, 1),
+            _ if self.category() == OpcodeCategory::Push => (0, 1),
+            _ => (0, 0),
+        }
+    }
+}
+
+/// Counts and estimates describing the size and complexity of a script
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ScriptMetrics {
+    /// The number of opcodes in each category. A data push counts as one opcode
+    pub opcode_counts: BTreeMap<OpcodeCategory, usize>,
+    /// The number of opcodes which are not pushes, limited to 201 by consensus
+    pub non_push_opcodes: usize,
+    /// The number of `OP_IF` and `OP_NOTIF` branches
+    pub branches: usize,
+    /// The deepest nesting of branches
+    pub max_branch_depth: usize,
+    /// The largest data push in bytes, limited to 520 by consensus
+    pub max_push_size: usize,
+    /// The signature operations counted the way legacy scripts are limited,
+    /// `OP_CHECKMULTISIG` counts as its number of keys when it is pushed
+    /// just before and as 20 otherwise
+    pub sigops: usize,
+    /// The least number of stack items the script needs before it runs
+    pub required_stack_items: usize,
+    /// The most items on the stack while the script runs, including the
+    /// required stack items
+    pub max_stack_depth: usize,
+}
+
+impl Script {
+    /// Walk the script and measure its complexity. The stack depths are
+    /// estimates which assume every branch is executed one after another and
+    /// that `OP_CHECKMULTISIG` consumes the whole stack. Returns an error if
+    /// a push goes past the end of the script
+    pub fn metrics(&self) -> io::Result<ScriptMetrics> {
+        let script = self.as_bytes();
+        let mut metrics = ScriptMetrics::default();
+        let mut position = 0usize;
+        let mut branch_depth = 0usize;
+        // The stack depth relative to the stack the script starts with
+        let mut stack_depth = 0isize;
+        let mut lowest_depth = 0isize;
+        let mut highest_depth = 0isize;
+        let mut previous = Option::<Opcode>::None;
+
+        while position < script.len() {
+            let (opcode, push_len) = Self::read_opcode(script, &mut position)?;
+            if let Some(push_len) = push_len {
+                metrics.max_push_size = metrics.max_push_size.max(push_len);
+            }
+            let opcode = Opcode::from_byte(opcode);
+
+            let category = opcode.category();
+            *metrics.opcode_counts.entry(category).or_default() += 1;
+            if category != OpcodeCategory::Push {
+                metrics.non_push_opcodes += 1;
+            }
+
+            match opcode {
+                Opcode::OP_IF | Opcode::OP_NOTIF => {
+                    metrics.branches += 1;
+                    branch_depth += 1;
+                    metrics.max_branch_depth = metrics.max_branch_depth.max(branch_depth);
+                }
+                Opcode::OP_ENDIF => branch_depth = branch_depth.saturating_sub(1),
+                Opcode::OP_CHECKSIG | Opcode::OP_CHECKSIGVERIFY => metrics.sigops += 1,
+                Opcode::OP_CHECKMULTISIG | Opcode::OP_CHECKMULTISIGVERIFY => {
+                    metrics.sigops += match previous {
+                        Some(Opcode::OP_1) => 1,
+                        Some(Opcode::Num(keys)) => keys as usize,
+                        _ => 20,
+                    }
+                }
+                _ => (),
+            }
+
+            let (pops, pushes) = match opcode {
+                Opcode::OP_CHECKMULTISIG => (stack_depth.max(1), 1),
+                Opcode::OP_CHECKMULTISIGVERIFY => (stack_depth.max(1), 0),
+                _ => opcode.stack_effect(),
+            };
+            stack_depth -= pops;
+            lowest_depth = lowest_depth.min(stack_depth);
+            stack_depth += pushes;
+            highest_depth = highest_depth.max(stack_depth);
+
+            previous = Some(opcode);
+        }
+
+        metrics.required_stack_items = -lowest_depth as usize;
+        metrics.max_stack_depth = (highest_depth - lowest_depth) as usize;
+
+        Ok(metrics)
+    }
+
+    // Read the opcode at `position` and skip the data it pushes, returning the
+    // opcode with the length of the data if it is a data push
+    pub(crate) fn read_opcode(
+        script: &[u8],
+        position: &mut usize,
+    ) -> io::Result<(u8, Option<usize>)> {
+        let opcode = script[*position];
+        *position += 1;
+
+        let push_len = match opcode {
+            0x01..=0x4b => Some(opcode as usize),
+            0x4c..=0x4e => {
+                let len_bytes = 1usize << (opcode - 0x4c);
+                let len = script
+                    .get(*position..*position + len_bytes)
+                    .ok_or_else(Self::truncated)?
+                    .iter()
+                    .rev()
+                    .fold(0usize, |len, byte| (len << 8) | *byte as usize);
+                *position += len_bytes;
+
+                Some(len)
+            }
+            _ => None,
+        };
+        if let Some(push_len) = push_len {
+            if *position + push_len > script.len() {
+                return Err(Self::truncated());
+            }
+            *position += push_len;
+        }
+
+        Ok((opcode, push_len))
+    }
+
+    fn truncated() -> io::Error {
+        io::Error::new(
+            ErrorKind::UnexpectedEof,
+            "A data push goes past the end of the script",
+        )
+    }
+}
+
+#[cfg(test)]
+mod script_metrics_sanity_checks {
+    use crate::{OpcodeCategory, Script};
+    use hex_literal::hex;
+
+    #[test]
+    fn script_metrics() {
+        // OP_DUP OP_HASH160 <20 bytes> OP_EQUALVERIFY OP_CHECKSIG
+        let p2pkh = Script::new(hex!("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac"))
+            .metrics()
+            .unwrap();
+        assert_eq!(Some(&1), p2pkh.opcode_counts.get(&OpcodeCategory::Push));
+        assert_eq!(Some(&2), p2pkh.opcode_counts.get(&OpcodeCategory::Crypto));
+        assert_eq!(4, p2pkh.non_push_opcodes);
+        assert_eq!(20, p2pkh.max_push_size);
+        assert_eq!(1, p2pkh.sigops);
+        // The signature and the public key
+        assert_eq!(2, p2pkh.required_stack_items);
+        assert_eq!(4, p2pkh.max_stack_depth);
+
+        // OP_IF OP_1 OP_ELSE OP_IF OP_2 OP_ENDIF OP_ENDIF then a 2-of-3 OP_CHECKMULTISIG
+        let branches = Script::new(hex!("635167635268685253ae")).metrics().unwrap();
+        assert_eq!(2, branches.branches);
+        assert_eq!(2, branches.max_branch_depth);
+        assert_eq!(3, branches.sigops);
+
+        assert!(Script::new(hex!("4c05aabb")).metrics().is_err());
+    }
+}
@@ -1,6 +1,12 @@
+use crate::{
+    instrument::traced, Instruction, MultisigError, Script, ScriptError, Witness, MAX_P2MS_KEYS,
+    MAX_STANDARD_P2MS_KEYS, TAPSCRIPT_LEAF_VERSION,
+};
 use std::{
-    io::{self, Cursor, Error, ErrorKind, Read},
+    fmt,
+    io::{self, Cursor, ErrorKind, Read},
     ops::Add,
+    str::FromStr,
 };
 
 /// Handles scriptSig parsing
@@ -10,55 +16,240 @@ pub struct StandardScripts;
 impl StandardScripts {
     /// Decides which scriptSig to parse
     pub fn parse(bytes: &mut Cursor<&[u8]>) -> io::Result<String> {
+        Self::parse_with_type(bytes).map(|(_, script)| script)
+    }
+
+    /// Same as `Self::parse()` but written out in the chosen `AsmFormat`
+    pub fn parse_as(bytes: &mut Cursor<&[u8]>, format: AsmFormat) -> io::Result<String> {
+        let start = bytes.position() as usize;
+        let asm = Self::parse(bytes)?;
+
+        match format {
+            AsmFormat::Explicit => Ok(asm),
+            AsmFormat::CoreAsm => {
+                Self::to_asm(&bytes.get_ref()[start..bytes.position() as usize], format)
+            }
+        }
+    }
+
+    /// Same as `Self::parse()` but also returns which
+    /// standard script template was matched
+    pub fn parse_with_type(bytes: &mut Cursor<&[u8]>) -> io::Result<(ScriptType, String)> {
+        let remaining = bytes
+            .get_ref()
+            .len()
+            .saturating_sub(bytes.position() as usize);
+
+        traced("classify_script", remaining, || Self::classify(bytes))
+    }
+
+    fn classify(bytes: &mut Cursor<&[u8]>) -> io::Result<(ScriptType, String)> {
         // Get the first OPCODE
-        let mut opcode_buffer = [0u8; 1];
-        bytes.read_exact(&mut opcode_buffer)?;
+        let first_byte = Self::next_byte(bytes, None, "an opcode")?;
         // Convert our byte into an `Opcode`
-        let first_opcode = Opcode::from_byte(opcode_buffer[0]);
+        let first_opcode = Opcode::from_byte(first_byte);
 
         match first_opcode {
             // If `OP_PUSHBYTES_65` then parse as P2PK
-            Opcode::PushBytes(65) => Self::parse_p2pk(bytes),
+            Opcode::PushBytes(65) => {
+                Self::parse_p2pk(bytes).map(|script| (ScriptType::P2PK, script))
+            }
             // If `OP_DUP` then parse as P2PKH
-            Opcode::OP_DUP => Self::parse_p2pkh(bytes),
+            Opcode::OP_DUP => Self::parse_p2pkh(bytes).map(|script| (ScriptType::P2PKH, script)),
             // If `OP_HASH160` then parse as P2PK
-            Opcode::OP_HASH160 => Self::parse_p2sh(bytes),
+            Opcode::OP_HASH160 => Self::parse_p2sh(bytes).map(|script| (ScriptType::P2SH, script)),
             // If `OP_RETURN` then parse as Data(OP_RETURN)
-            Opcode::OP_RETURN => Self::parse_data(bytes),
+            Opcode::OP_RETURN => {
+                Self::parse_data(bytes).map(|script| (ScriptType::OpReturn, script))
+            }
             // If `OP_0` as first OPCODE and OP_PUSHBYTES_20 is second OPCODE then parse as P2WPKH
             // Else if `OP_0` as first OPCODE and OP_PUSHBYTES_32 is second OPCODE then parse as P2WSH
             // Else return an an error if `OP_0` is first OPCODE
             Opcode::OP_0 => {
-                bytes.read_exact(&mut opcode_buffer)?;
-                let second_opcode = Opcode::from_byte(opcode_buffer[0]);
+                let expected = "OP_PUSHBYTES_20 or OP_PUSHBYTES_32";
+                let offset = bytes.position() as usize;
+                let second_byte = Self::next_byte(bytes, None, expected)?;
+                let second_opcode = Opcode::from_byte(second_byte);
                 if second_opcode.eq(&Opcode::PushBytes(20)) {
-                    Self::parse_p2wpkh(bytes)
+                    Self::parse_p2wpkh(bytes).map(|script| (ScriptType::P2WPKH, script))
                 } else if second_opcode.eq(&Opcode::PushBytes(32)) {
-                    Self::parse_p2wsh(bytes)
+                    Self::parse_p2wsh(bytes).map(|script| (ScriptType::P2WSH, script))
                 } else {
-                    return Self::to_io_error(
-                        "Invalid Script. Expected OP_PUSHBYTES_20 or OP_PUSHBYTES_32 after OP_0",
-                    );
+                    Err(ScriptError::unexpected_opcode(None, offset, expected, second_byte).into())
                 }
             }
             _ => {
                 // If `OP_1` as first OPCODE and OP_PUSHBYTES_32 is second OPCODE then parse as P2TR
+                // Else if `OP_1..16` is followed by a push of 2 to 40 bytes which ends the script
+                // then parse as a witness program of a version not yet defined by a soft-fork
                 // Else try parsing as P2MS
 
-                bytes.read_exact(&mut opcode_buffer)?;
-                let second_opcode = Opcode::from_byte(opcode_buffer[0]);
+                // A lone `OP_1` (`OP_TRUE`) is the bare anchor output anyone can spend
+                if first_opcode.eq(&Opcode::OP_1)
+                    && bytes.position() as usize == bytes.get_ref().len()
+                {
+                    return Self::parse_op_true().map(|script| (ScriptType::OpTrue, script));
+                }
+
+                let second_byte = Self::next_byte(bytes, None, "a second opcode")?;
+                let second_opcode = Opcode::from_byte(second_byte);
 
                 if first_opcode.eq(&Opcode::OP_1) && second_opcode.eq(&Opcode::PushBytes(32)) {
-                    Self::parse_p2tr(bytes)
+                    Self::parse_p2tr(bytes).map(|script| (ScriptType::P2TR, script))
+                } else if let Some(version) =
+                    Self::witness_version(first_opcode, second_opcode, bytes)
+                {
+                    let start = bytes.position() as usize - 2;
+                    let script = Self::parse_witness_unknown(first_opcode, second_opcode, bytes)?;
+
+                    // The witness version 1 program `0x4e73` is the pay-to-anchor (P2A) output
+                    let script_type =
+                        if bytes.get_ref()[start..bytes.position() as usize] == P2A_SCRIPT {
+                            ScriptType::P2A
+                        } else {
+                            ScriptType::WitnessUnknown(version)
+                        };
+
+                    Ok((script_type, script))
                 } else {
                     // Reset current position of cursor to the beginning
                     bytes.set_position(bytes.position() - 2);
-                    Self::parse_p2ms(bytes)
+                    Self::parse_p2ms(bytes).map(|script| (ScriptType::P2MS, script))
                 }
             }
         }
     }
 
+    /// Read every data push of a push only script like a scriptSig.
+    /// `OP_0`, `OP_PUSHBYTES_1` to `OP_PUSHBYTES_75` and `OP_PUSHDATA1/2/4`
+    /// are supported, any other opcode returns an error.
+    pub fn read_pushes(script: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+        let mut bytes = Cursor::new(script);
+        let mut pushes = Vec::<Vec<u8>>::new();
+        let mut opcode_buffer = [0u8; 1];
+
+        while (bytes.position() as usize) < script.len() {
+            bytes.read_exact(&mut opcode_buffer)?;
+
+            let push_len = match opcode_buffer[0] {
+                // OP_0 pushes an empty array
+                0 => 0usize,
+                // OP_PUSHBYTES_1 to OP_PUSHBYTES_75 push the number of bytes of the opcode
+                1..=75 => opcode_buffer[0] as usize,
+                // OP_PUSHDATA1 is followed by a 1 byte length
+                76 => {
+                    let mut len_bytes = [0u8; 1];
+                    bytes.read_exact(&mut len_bytes)?;
+                    len_bytes[0] as usize
+                }
+                // OP_PUSHDATA2 is followed by a 2 byte little-endian length
+                77 => {
+                    let mut len_bytes = [0u8; 2];
+                    bytes.read_exact(&mut len_bytes)?;
+                    u16::from_le_bytes(len_bytes) as usize
+                }
+                // OP_PUSHDATA4 is followed by a 4 byte little-endian length
+                78 => {
+                    let mut len_bytes = [0u8; 4];
+                    bytes.read_exact(&mut len_bytes)?;
+                    u32::from_le_bytes(len_bytes) as usize
+                }
+                _ => {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        "Invalid Script. Expected only push opcodes",
+                    ))
+                }
+            };
+
+            // Check the length before allocating so that a bogus
+            // OP_PUSHDATA4 length cannot allocate gigabytes
+            if push_len > script.len() - bytes.position() as usize {
+                return Err(io::Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "Invalid Script. The push length is greater than the remaining bytes",
+                ));
+            }
+
+            let mut data = vec![0u8; push_len];
+            bytes.read_exact(&mut data)?;
+            pushes.push(data);
+        }
+
+        Ok(pushes)
+    }
+
+    /// Write out any script in the chosen `AsmFormat` without checking that
+    /// it matches a standard template. Returns an `UnexpectedEof` error if a
+    /// push goes past the end of the script
+    pub fn to_asm(script: &[u8], format: AsmFormat) -> io::Result<String> {
+        Ok(Script::disassemble(script)?
+            .iter()
+            .map(|instruction| match (format, instruction.push_data()) {
+                (AsmFormat::Explicit, _) => instruction.to_string(),
+                (AsmFormat::CoreAsm, Some(data)) => ScriptBuilder::core_asm_data(data),
+                (AsmFormat::CoreAsm, None) => ScriptBuilder::core_asm_opcode(instruction.opcode()),
+            })
+            .collect::<Vec<String>>()
+            .join(" "))
+    }
+
+    // Read the next byte of the script. The `expected` description of what the
+    // `template` expects at this offset is reported if the script has ended
+    fn next_byte(
+        bytes: &mut Cursor<&[u8]>,
+        template: Option<ScriptType>,
+        expected: &str,
+    ) -> io::Result<u8> {
+        let offset = bytes.position() as usize;
+
+        let mut buffer = [0u8; 1];
+        bytes
+            .read_exact(&mut buffer)
+            .map_err(|_| io::Error::from(ScriptError::end_of_script(template, offset, expected)))?;
+
+        Ok(buffer[0])
+    }
+
+    // Read the next opcode and check that it is the `expected` opcode of the `template`
+    fn expect_opcode(
+        bytes: &mut Cursor<&[u8]>,
+        template: ScriptType,
+        expected: Opcode,
+    ) -> io::Result<()> {
+        let offset = bytes.position() as usize;
+        let expected_name = expected.to_string();
+
+        let byte = Self::next_byte(bytes, Some(template), &expected_name)?;
+        if Opcode::from_byte(byte).ne(&expected) {
+            return Err(ScriptError::unexpected_opcode(
+                Some(template),
+                offset,
+                expected_name,
+                byte,
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+
+    // Read exactly `N` bytes of data described by `expected`
+    fn read_array<const N: usize>(
+        bytes: &mut Cursor<&[u8]>,
+        template: ScriptType,
+        expected: &str,
+    ) -> io::Result<[u8; N]> {
+        let offset = bytes.position() as usize;
+
+        let mut buffer = [0u8; N];
+        bytes.read_exact(&mut buffer).map_err(|_| {
+            io::Error::from(ScriptError::end_of_script(Some(template), offset, expected))
+        })?;
+
+        Ok(buffer)
+    }
+
     // Error Handling returning an `io::Result<String>` to avoid
     // having to add `Err()` whenever we call this method.
     // Our message is unique so we add that as argument
@@ -71,19 +262,10 @@ impl StandardScripts {
         // Cursor is already at second byte to we parse
         // 65 bytes from that position to get the
         // Uncompressed Public Key
-        let mut public_key_bytes = [0u8; 65];
-        bytes.read_exact(&mut public_key_bytes)?;
+        let public_key_bytes: [u8; 65] =
+            Self::read_array(bytes, ScriptType::P2PK, "a 65 byte public key")?;
         // Next we parse OP_CHECKSIG
-        let mut op_checksig_byte = [0u8; 1];
-        bytes.read_exact(&mut op_checksig_byte)?;
-        let op_checksig = Opcode::from_byte(op_checksig_byte[0]);
-
-        if op_checksig.ne(&Opcode::OP_CHECKSIG) {
-            return Err(Error::new(
-                ErrorKind::InvalidData,
-                "Invalid Data. Expected OP_CHECKSIG as last byte of the script.",
-            ));
-        }
+        Self::expect_opcode(bytes, ScriptType::P2PK, Opcode::OP_CHECKSIG)?;
 
         // Lastly, we build our script
         let mut script_builder = ScriptBuilder::new();
@@ -97,51 +279,19 @@ impl StandardScripts {
 
     /// Parse P2PKH
     pub fn parse_p2pkh(bytes: &mut Cursor<&[u8]>) -> io::Result<String> {
-        let mut opcode_buffer = [0u8; 1];
-
-        bytes.read_exact(&mut opcode_buffer)?;
         // Parse second OPCODE as OP_HASH160
-        let should_be_ophash160 = Opcode::from_byte(opcode_buffer[0]);
-        if should_be_ophash160.ne(&Opcode::OP_HASH160) {
-            return Err(Error::new(
-                ErrorKind::InvalidData,
-                "Invalid Data. Expected OP_HASH160 as second byte of the script.",
-            ));
-        }
-
-        bytes.read_exact(&mut opcode_buffer)?;
+        Self::expect_opcode(bytes, ScriptType::P2PKH, Opcode::OP_HASH160)?;
         //  Parse third OPCODE as `OP_PUSHBYTES_20`
-        let should_be_op_pushbytes20 = Opcode::from_byte(opcode_buffer[0]);
-        if should_be_op_pushbytes20.ne(&Opcode::PushBytes(20)) {
-            return Err(Error::new(
-                ErrorKind::InvalidData,
-                "Invalid Data. Expected OP_PUSHBYTES_20 as third byte of the script.",
-            ));
-        }
+        Self::expect_opcode(bytes, ScriptType::P2PKH, Opcode::PushBytes(20))?;
 
         // Get the 20 bytes of the Hash160
-        let mut hash160_bytes = [0u8; 20];
-        bytes.read_exact(&mut hash160_bytes)?;
+        let hash160_bytes: [u8; 20] =
+            Self::read_array(bytes, ScriptType::P2PKH, "a 20 byte public key hash")?;
 
         // Parse the next byte as OP_EQUALVERIFY
-        bytes.read_exact(&mut opcode_buffer)?;
-        let should_be_opequalverify = Opcode::from_byte(opcode_buffer[0]);
-        if should_be_opequalverify.ne(&Opcode::OP_EQUALVERIFY) {
-            return Err(Error::new(
-                ErrorKind::InvalidData,
-                "Invalid Data. Expected OP_EQUALVERIFY after reading 20 bytes after third byte of the script.",
-            ));
-        }
-
+        Self::expect_opcode(bytes, ScriptType::P2PKH, Opcode::OP_EQUALVERIFY)?;
         // Parse the next byte as OP_CHECKSIG
-        bytes.read_exact(&mut opcode_buffer)?;
-        let should_be_opchecksing = Opcode::from_byte(opcode_buffer[0]);
-        if should_be_opchecksing.ne(&Opcode::OP_CHECKSIG) {
-            return Err(Error::new(
-                ErrorKind::InvalidData,
-                "Invalid Data. Expected OP_CHECKSIG after reading OP_EQUALVERIFY byte in the script.",
-            ));
-        }
+        Self::expect_opcode(bytes, ScriptType::P2PKH, Opcode::OP_CHECKSIG)?;
 
         // Build our script into a Sctring
         let mut script_builder = ScriptBuilder::new();
@@ -158,29 +308,15 @@ impl StandardScripts {
 
     /// Parse P2SH
     pub fn parse_p2sh(bytes: &mut Cursor<&[u8]>) -> io::Result<String> {
-        let mut script_buffer = [0u8; 1];
-
-        bytes.read_exact(&mut script_buffer)?;
-
         // Second OPCODE should be OP_PUSHBYTES_20
-        let second_opcode = Opcode::from_byte(script_buffer[0]);
-        if second_opcode.ne(&Opcode::PushBytes(20)) {
-            return Self::to_io_error(
-                "Invalid Data. Expected an OP_PUSHBYTES_20 opcode after OP_HASH160",
-            );
-        }
+        Self::expect_opcode(bytes, ScriptType::P2SH, Opcode::PushBytes(20))?;
 
         // Read the 20 bytes of HASH160
-        let mut bytes_20_buffer = [0u8; 20];
-        bytes.read_exact(&mut bytes_20_buffer)?;
-
-        bytes.read_exact(&mut script_buffer)?;
-        let last_opcode = Opcode::from_byte(script_buffer[0]);
-        if last_opcode.ne(&Opcode::OP_EQUAL) {
-            return Self::to_io_error(
-                "Invalid Data. Expected an OP_EQUAL opcode after reading 20 bytes",
-            );
-        }
+        let bytes_20_buffer: [u8; 20] =
+            Self::read_array(bytes, ScriptType::P2SH, "a 20 byte script hash")?;
+
+        // Last OPCODE should be OP_EQUAL
+        Self::expect_opcode(bytes, ScriptType::P2SH, Opcode::OP_EQUAL)?;
 
         // Build the script into a String
         let mut script_builder = ScriptBuilder::new();
@@ -193,30 +329,40 @@ impl StandardScripts {
         Ok(script_builder.build())
     }
 
-    // Parse OP_RETURN
+    /// Parse OP_RETURN. Like the null data outputs of Bitcoin Core every
+    /// opcode after `OP_RETURN` up to the end of the script must be a push,
+    /// so a bare `OP_RETURN` and any number of pushes are accepted
     pub fn parse_data(bytes: &mut Cursor<&[u8]>) -> io::Result<String> {
-        let mut script_buffer = [0u8; 1];
-
-        bytes.read_exact(&mut script_buffer)?;
-        // Get second OPCODE which is `OP_PUSHBYTES_*`
-        let second_opcode = Opcode::from_byte(script_buffer[0]);
-        // Read the number of bytes specified by second OPCODE
-        let data_bytes = second_opcode.read_bytes(bytes)?;
-
-        let mut script_builder = ScriptBuilder::new();
-        script_builder
-            .push_opcode(Opcode::OP_RETURN)?
-            .push_opcode(second_opcode)?
-            .push_bytes(&data_bytes)?;
+        let start = bytes.position() as usize;
+        let script = bytes.get_ref();
+        let instructions = Script::disassemble(&script[start..])?;
+
+        if let Some(instruction) = instructions
+            .iter()
+            .find(|instruction| !instruction.opcode().is_push())
+        {
+            let offset = start + instruction.offset();
+            return Err(ScriptError::unexpected_opcode(
+                Some(ScriptType::OpReturn),
+                offset,
+                "a data push",
+                script[offset],
+            )
+            .into());
+        }
+        bytes.set_position(script.len() as u64);
 
-        Ok(script_builder.build())
+        Ok(std::iter::once(Opcode::OP_RETURN.to_string())
+            .chain(instructions.iter().map(Instruction::to_string))
+            .collect::<Vec<String>>()
+            .join(" "))
     }
 
     /// Parse P2WPKH
     pub fn parse_p2wpkh(bytes: &mut Cursor<&[u8]>) -> io::Result<String> {
         // Read the next 20 bytes
-        let mut pubkey_hash_bytes = [0u8; 20];
-        bytes.read_exact(&mut pubkey_hash_bytes)?;
+        let pubkey_hash_bytes: [u8; 20] =
+            Self::read_array(bytes, ScriptType::P2WPKH, "a 20 byte public key hash")?;
 
         let mut scripts = ScriptBuilder::new();
         scripts
@@ -230,8 +376,8 @@ impl StandardScripts {
     /// Parse P2WSH
     pub fn parse_p2wsh(bytes: &mut Cursor<&[u8]>) -> io::Result<String> {
         // Parse next 32 bytes
-        let mut hash_bytes = [0u8; 32];
-        bytes.read_exact(&mut hash_bytes)?;
+        let hash_bytes: [u8; 32] =
+            Self::read_array(bytes, ScriptType::P2WSH, "a 32 byte script hash")?;
 
         let mut scripts = ScriptBuilder::new();
         scripts
@@ -245,8 +391,8 @@ impl StandardScripts {
     /// Parse P2TR
     pub fn parse_p2tr(bytes: &mut Cursor<&[u8]>) -> io::Result<String> {
         // Parse next 32 bytes
-        let mut hash_bytes = [0u8; 32];
-        bytes.read_exact(&mut hash_bytes)?;
+        let hash_bytes: [u8; 32] =
+            Self::read_array(bytes, ScriptType::P2TR, "a 32 byte taproot output key")?;
 
         let mut scripts = ScriptBuilder::new();
         scripts
@@ -257,13 +403,65 @@ impl StandardScripts {
         Ok(scripts.build())
     }
 
+    /// Parse the `OP_TRUE` anchor output. The cursor is already past the only byte
+    pub fn parse_op_true() -> io::Result<String> {
+        let mut scripts = ScriptBuilder::new();
+        scripts.push_opcode(Opcode::OP_1)?;
+
+        Ok(scripts.build())
+    }
+
+    /// Parse a witness program whose version or length has no meaning yet.
+    /// Such outputs are spendable by anyone today which is what allows a
+    /// future soft-fork to give them meaning, so we keep the program as is
+    /// instead of rejecting the script.
+    pub fn parse_witness_unknown(
+        version_opcode: Opcode,
+        push_opcode: Opcode,
+        bytes: &mut Cursor<&[u8]>,
+    ) -> io::Result<String> {
+        let program_bytes = push_opcode.read_bytes(bytes)?;
+
+        let mut scripts = ScriptBuilder::new();
+        scripts
+            .push_opcode(version_opcode)?
+            .push_opcode(push_opcode)?
+            .push_bytes(&program_bytes)?;
+
+        Ok(scripts.build())
+    }
+
+    // A witness program is a version opcode `OP_1..16` followed by a single
+    // push of 2 to 40 bytes which ends the script. Returns the witness version.
+    fn witness_version(
+        version_opcode: Opcode,
+        push_opcode: Opcode,
+        bytes: &Cursor<&[u8]>,
+    ) -> Option<u8> {
+        let version = match version_opcode {
+            Opcode::OP_1 => 1u8,
+            Opcode::Num(value) => value,
+            _ => return None,
+        };
+
+        match push_opcode {
+            Opcode::PushBytes(program_len @ 2..=40)
+                if bytes.position() as usize + program_len as usize == bytes.get_ref().len() =>
+            {
+                Some(version)
+            }
+            _ => None,
+        }
+    }
+
     /// Parse a P2MS.
     /// Also checks to see if the number of public keys parsed is equal to number of public keys requires
     /// or if the parsed public keys are less than the threshold
     pub fn parse_p2ms(bytes: &mut Cursor<&[u8]>) -> io::Result<String> {
-        let mut opcode_buffer = [0u8; 1];
-        bytes.read_exact(&mut opcode_buffer)?;
-        let threshold_opcode = Opcode::from_byte(opcode_buffer[0]);
+        let template = Some(ScriptType::P2MS);
+        let threshold_offset = bytes.position() as usize;
+        let threshold_byte = Self::next_byte(bytes, template, "OP_1..16")?;
+        let threshold_opcode = Opcode::from_byte(threshold_byte);
 
         match threshold_opcode {
             Opcode::Num(_) | Opcode::OP_1 => {
@@ -273,78 +471,371 @@ impl StandardScripts {
                 let mut pubkey_count = 0u8;
                 // The number of public keys specified in the scriptSig
                 let parsed_pubkey_count: u8;
-                let mut pushbytes_buffer = Vec::<u8>::new();
+
+                // The offset of the `OP_1..16` opcode with the number of public keys
+                let mut count_offset: usize;
 
                 loop {
-                    bytes.read_exact(&mut opcode_buffer)?;
-                    let current_opcode = Opcode::from_byte(opcode_buffer[0]);
+                    count_offset = bytes.position() as usize;
+                    let expected = "OP_PUSHBYTES_* or OP_1..16";
+                    let current_byte = Self::next_byte(bytes, template, expected)?;
+                    let current_opcode = Opcode::from_byte(current_byte);
 
                     match current_opcode {
-                        Opcode::Num(value) => {
-                            parsed_pubkey_count = value;
+                        Opcode::OP_1 | Opcode::Num(_) => {
+                            parsed_pubkey_count = Self::small_number(current_opcode);
                             script_builder.push_opcode(current_opcode)?;
                             //Break the loop if a `OP_1 to OP_16`  is encountered
                             break;
                         }
-                        Opcode::PushBytes(value) => {
-                            let new_position = bytes.position() as usize + value as usize;
-                            let read_bytes =
-                                &bytes.get_ref()[bytes.position() as usize..new_position];
-                            pushbytes_buffer.extend_from_slice(read_bytes);
+                        Opcode::PushBytes(_) => {
+                            let public_key = current_opcode.read_bytes_for(bytes, template)?;
+                            if !SpendType::is_public_key(&public_key) {
+                                return Err(MultisigError::InvalidPublicKey {
+                                    offset: count_offset,
+                                    len: public_key.len(),
+                                }
+                                .into());
+                            }
 
                             script_builder
                                 .push_opcode(current_opcode)?
-                                .push_bytes(&pushbytes_buffer)?;
+                                .push_bytes(&public_key)?;
 
-                            pushbytes_buffer.clear();
-                            bytes.set_position(new_position as u64);
                             pubkey_count = pubkey_count.add(1);
+                            if pubkey_count as usize > MAX_P2MS_KEYS {
+                                return Err(MultisigError::TooManyKeys {
+                                    keys: pubkey_count as usize,
+                                }
+                                .into());
+                            }
                         }
                         _ => {
-                            return Self::to_io_error(
-                                "Invalid Script. Expected a PUSH_BYTES_* or OP_1..16",
+                            return Err(ScriptError::unexpected_opcode(
+                                template,
+                                count_offset,
+                                expected,
+                                current_byte,
                             )
+                            .into())
                         }
                     }
                 }
 
+                // The number of public keys is less than or greater than the script requirements
                 if pubkey_count.ne(&parsed_pubkey_count) {
-                    return Self::to_io_error(
-                                "Invalid Script. The number of public keys for multisignature is less than or greater than the script requirements.",
-                            );
+                    return Err(ScriptError::new(
+                        template,
+                        count_offset,
+                        format!("OP_{} for {} public keys", pubkey_count, pubkey_count),
+                        Some(format!("OP_{}", parsed_pubkey_count)),
+                    )
+                    .into());
                 }
 
-                match threshold_opcode {
-                    Opcode::Num(threshold_inner) => {
-                        if parsed_pubkey_count.lt(&threshold_inner) {
-                            return Self::to_io_error(
-                                "Invalid Script. The number of public keys for multisignature is less the threshold.",
-                            );
-                        }
-                    }
-                    _ => (),
+                // The number of public keys is less than the threshold
+                let threshold = Self::small_number(threshold_opcode);
+                if parsed_pubkey_count.lt(&threshold) {
+                    return Err(ScriptError::new(
+                        template,
+                        threshold_offset,
+                        format!("a threshold of at most {}", parsed_pubkey_count),
+                        Some(format!("OP_{}", threshold)),
+                    )
+                    .into());
                 }
 
                 // Parse next byte and check if it is OP_CHECKMULTISIG opcode
-                bytes.read_exact(&mut opcode_buffer)?;
-                let opcheck_multisig = Opcode::from_byte(opcode_buffer[0]);
-
-                if opcheck_multisig.ne(&Opcode::OP_CHECKMULTISIG) {
-                    return Self::to_io_error(
-                        "Invalid Script. OP_CHECKMULTISIG opcode should be next",
-                    );
-                }
+                Self::expect_opcode(bytes, ScriptType::P2MS, Opcode::OP_CHECKMULTISIG)?;
                 script_builder.push_opcode(Opcode::OP_CHECKMULTISIG)?;
 
                 Ok(script_builder.build())
             }
-            _ => Self::to_io_error("Invalid Script."),
+            _ => Err(ScriptError::unexpected_opcode(
+                template,
+                threshold_offset,
+                "OP_1..16",
+                threshold_byte,
+            )
+            .into()),
+        }
+    }
+
+    // The number pushed by `OP_1` and `OP_2..16`, which are separate variants
+    fn small_number(opcode: Opcode) -> u8 {
+        match opcode {
+            Opcode::OP_1 => 1,
+            Opcode::Num(value) => value,
+            _ => 0,
+        }
+    }
+
+    /// Parse a P2MS like `StandardScripts::parse_p2ms()` and also apply the
+    /// standardness rule of Bitcoin Core limiting bare multisig outputs to
+    /// `MAX_STANDARD_P2MS_KEYS` keys
+    pub fn check_standard_p2ms(script: &[u8]) -> io::Result<String> {
+        let mut bytes = Cursor::new(script);
+        let asm = Self::parse_p2ms(&mut bytes)?;
+        if bytes.position() as usize != script.len() {
+            return Err(ScriptError::new(
+                Some(ScriptType::P2MS),
+                bytes.position() as usize,
+                "the end of script",
+                Some(format!(
+                    "{} more bytes",
+                    script.len() - bytes.position() as usize
+                )),
+            )
+            .into());
+        }
+
+        // `OP_n` is the byte before `OP_CHECKMULTISIG`
+        let keys = (script[script.len() - 2] - 0x50) as usize;
+        if keys > MAX_STANDARD_P2MS_KEYS {
+            return Err(MultisigError::NonStandardKeyCount { keys }.into());
+        }
+
+        Ok(asm)
+    }
+}
+
+/// The pay-to-anchor (P2A) locking script `OP_1 OP_PUSHBYTES_2 4e73`
+/// used for ephemeral anchors in version 3 packages and Lightning anchors
+pub const P2A_SCRIPT: [u8; 4] = [0x51, 0x02, 0x4e, 0x73];
+
+/// The `OP_TRUE` anchor locking script
+pub const OP_TRUE_SCRIPT: [u8; 1] = [0x51];
+
+/// The standard script templates recognised by `StandardScripts::parse()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum ScriptType {
+    /// Pay to Public Key
+    P2PK,
+    /// Pay to Public Key Hash
+    P2PKH,
+    /// Pay to Script Hash
+    P2SH,
+    /// Pay to Witness Public Key Hash
+    P2WPKH,
+    /// Pay to Witness Script Hash
+    P2WSH,
+    /// Pay to Taproot
+    P2TR,
+    /// Pay to Multisig
+    P2MS,
+    /// Pay to Anchor, the witness version 1 program `0x4e73`
+    P2A,
+    /// A bare `OP_TRUE` anchor output
+    OpTrue,
+    /// Data carrier output starting with `OP_RETURN`
+    OpReturn,
+    /// A witness program `OP_1..16 <2 to 40 bytes>` with a witness version
+    /// (or a version 1 program length) that no soft-fork has defined yet.
+    /// The version is kept so that the output can still be encoded as a
+    /// Bech32m address and re-serialized without loss.
+    WitnessUnknown(u8),
+    /// Any script that does not match one of the templates above
+    NonStandard,
+}
+
+impl fmt::Display for ScriptType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::P2PK => write!(f, "P2PK"),
+            Self::P2PKH => write!(f, "P2PKH"),
+            Self::P2SH => write!(f, "P2SH"),
+            Self::P2WPKH => write!(f, "P2WPKH"),
+            Self::P2WSH => write!(f, "P2WSH"),
+            Self::P2TR => write!(f, "P2TR"),
+            Self::P2MS => write!(f, "P2MS"),
+            Self::P2A => write!(f, "P2A"),
+            Self::OpTrue => write!(f, "OP_TRUE"),
+            Self::OpReturn => write!(f, "OP_RETURN"),
+            Self::WitnessUnknown(version) => write!(f, "Witness v{}", version),
+            Self::NonStandard => write!(f, "Non-standard"),
+        }
+    }
+}
+
+impl ScriptType {
+    /// Classify a whole locking script. A script is only treated as
+    /// one of the standard templates if parsing the template consumes
+    /// every byte of the script, otherwise it is `NonStandard`.
+    pub fn from_script(script: &[u8]) -> Self {
+        let mut bytes = Cursor::new(script);
+
+        match StandardScripts::parse_with_type(&mut bytes) {
+            Ok((script_type, _)) if bytes.position() as usize == script.len() => script_type,
+            _ => Self::NonStandard,
+        }
+    }
+}
+
+/// The type of output an input is spending, inferred from the shape of
+/// the scriptSig and the witness when the previous outputs are not available
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum SpendType {
+    /// The input of a coinbase transaction which does not spend any output
+    Coinbase,
+    /// A single signature push
+    P2PK,
+    /// A signature push followed by a public key push
+    P2PKH,
+    /// `OP_0` followed by signature pushes
+    P2MS,
+    /// Signature pushes followed by a push of a standard redeem script
+    P2SH,
+    /// A single push of a `OP_0 OP_PUSHBYTES_20 <20 bytes>` redeem script
+    NestedP2WPKH,
+    /// A single push of a `OP_0 OP_PUSHBYTES_32 <32 bytes>` redeem script
+    NestedP2WSH,
+    /// An empty scriptSig with a witness of a signature and a compressed public key
+    P2WPKH,
+    /// An empty scriptSig with a witness ending in a witness script
+    P2WSH,
+    /// An empty scriptSig with a witness of a single Schnorr signature
+    /// and an optional annex
+    P2TRKeyPath,
+    /// An empty scriptSig with a witness ending in a script and a control
+    /// block, followed by an optional annex
+    P2TRScriptPath,
+    /// An empty scriptSig whose witness does not match any of the shapes above
+    NativeSegwit,
+    /// The scriptSig does not match any of the shapes above
+    Unknown,
+}
+
+impl SpendType {
+    /// Infer the spend type from the scriptSig, or from the witness of a
+    /// spend with an empty scriptSig
+    pub fn from_input_scripts(signature_script: &[u8], witness: &Witness) -> Self {
+        if signature_script.is_empty() {
+            Self::from_witness(witness)
+        } else {
+            Self::from_signature_script(signature_script)
+        }
+    }
+
+    /// Infer the spend type from the data pushes of a scriptSig
+    pub fn from_signature_script(signature_script: &[u8]) -> Self {
+        if signature_script.is_empty() {
+            return Self::NativeSegwit;
+        }
+
+        let pushes = match StandardScripts::read_pushes(signature_script) {
+            Ok(pushes) => pushes,
+            Err(_) => return Self::Unknown,
+        };
+
+        match pushes.as_slice() {
+            [redeem_script] if redeem_script.len() == 22 && redeem_script[..2] == [0x00, 0x14] => {
+                Self::NestedP2WPKH
+            }
+            [redeem_script] if redeem_script.len() == 34 && redeem_script[..2] == [0x00, 0x20] => {
+                Self::NestedP2WSH
+            }
+            [signature] if Self::is_signature(signature) => Self::P2PK,
+            [signature, public_key]
+                if Self::is_signature(signature) && Self::is_public_key(public_key) =>
+            {
+                Self::P2PKH
+            }
+            [dummy, signatures @ ..]
+                if dummy.is_empty()
+                    && !signatures.is_empty()
+                    && signatures
+                        .iter()
+                        .all(|signature| Self::is_signature(signature)) =>
+            {
+                Self::P2MS
+            }
+            [.., redeem_script]
+                if ScriptType::from_script(redeem_script).ne(&ScriptType::NonStandard) =>
+            {
+                Self::P2SH
+            }
+            _ => Self::Unknown,
         }
     }
+
+    // Only taproot spends have an annex, so it is left out before
+    // matching the rest of the stack
+    fn from_witness(witness: &Witness) -> Self {
+        let annex = witness.annex();
+        let elements = witness
+            .iter()
+            .take(witness.len() - usize::from(annex.is_some()))
+            .collect::<Vec<&[u8]>>();
+
+        match elements.as_slice() {
+            // A Schnorr signature is 64 bytes or 65 bytes with a sighash byte
+            [signature] if matches!(signature.len(), 64 | 65) => Self::P2TRKeyPath,
+            [.., _, control_block] if Self::is_control_block(control_block) => Self::P2TRScriptPath,
+            _ if annex.is_some() => Self::NativeSegwit,
+            [signature, public_key]
+                if Self::is_signature(signature)
+                    && public_key.len() == 33
+                    && Self::is_public_key(public_key) =>
+            {
+                Self::P2WPKH
+            }
+            [.., witness_script]
+                if !witness_script.is_empty() && Script::disassemble(witness_script).is_ok() =>
+            {
+                Self::P2WSH
+            }
+            _ => Self::NativeSegwit,
+        }
+    }
+
+    // A tapscript control block is the leaf version with the parity bit,
+    // the 32 byte internal key and a merkle path of at most 128 hashes
+    pub(crate) fn is_control_block(bytes: &[u8]) -> bool {
+        (33..=33 + 32 * 128).contains(&bytes.len())
+            && (bytes.len() - 33).is_multiple_of(32)
+            && bytes[0] & 0xfe == TAPSCRIPT_LEAF_VERSION
+    }
+
+    // A DER encoded signature starts with `0x30`, is followed by the length
+    // of the DER sequence and the whole push ends with a sighash byte
+    pub(crate) fn is_signature(bytes: &[u8]) -> bool {
+        (9..=73).contains(&bytes.len()) && bytes[0] == 0x30 && bytes[1] as usize == bytes.len() - 3
+    }
+
+    // A compressed public key is 33 bytes starting with `0x02` or `0x03`
+    // while an uncompressed public key is 65 bytes starting with `0x04`
+    pub(crate) fn is_public_key(bytes: &[u8]) -> bool {
+        matches!(
+            (bytes.len(), bytes.first()),
+            (33, Some(0x02 | 0x03)) | (65, Some(0x04))
+        )
+    }
+}
+
+/// How the opcodes and data pushes of a script are written out as text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum AsmFormat {
+    /// Every opcode is named including the push opcodes,
+    /// for example `OP_0 OP_PUSHBYTES_20 <hex>`
+    #[default]
+    Explicit,
+    /// The format of the `asm` field of Bitcoin Core's `decodescript` and
+    /// `decoderawtransaction`. Push opcodes are left out, `OP_0` and `OP_1..16`
+    /// are written as numbers and pushes of up to 4 bytes are decoded as
+    /// script numbers, for example `0 <hex>`
+    CoreAsm,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AsmToken {
+    Opcode(Opcode),
+    Bytes(Vec<u8>),
 }
 
 #[derive(Debug, Default)]
-pub struct ScriptBuilder(Vec<String>);
+pub struct ScriptBuilder(Vec<AsmToken>);
 
 impl ScriptBuilder {
     pub fn new() -> Self {
@@ -352,94 +843,433 @@ impl ScriptBuilder {
     }
 
     pub fn push_opcode(&mut self, opcode: Opcode) -> io::Result<&mut Self> {
-        let opcode_string: String = opcode.try_into()?;
-        self.0.push(opcode_string);
+        // Check that the opcode has a byte before accepting it
+        if opcode.to_byte().is_none() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} is not an opcode", opcode),
+            ));
+        }
+        if matches!(
+            opcode,
+            Opcode::OP_PUSHDATA1 | Opcode::OP_PUSHDATA2 | Opcode::OP_PUSHDATA4
+        ) {
+            return Err(io::Error::new(
+                ErrorKind::Unsupported,
+                "Pushes of more than 75 bytes are not supported",
+            ));
+        }
+
+        if let Some(byte_len) = self.pending_push() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Expected the {} bytes of OP_PUSHBYTES_{}",
+                    byte_len, byte_len
+                ),
+            ));
+        }
+
+        self.0.push(AsmToken::Opcode(opcode));
 
         Ok(self)
     }
 
+    /// Push the data of the `OP_PUSHBYTES_*` opcode pushed before.
+    /// The length of the data must match the length declared by the opcode
     pub fn push_bytes(&mut self, bytes: &[u8]) -> io::Result<&mut Self> {
-        self.0.push(hex::encode(bytes));
+        match self.pending_push() {
+            Some(byte_len) if byte_len as usize == bytes.len() => (),
+            Some(byte_len) => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "OP_PUSHBYTES_{} expects {} bytes but got {} bytes",
+                        byte_len,
+                        byte_len,
+                        bytes.len()
+                    ),
+                ))
+            }
+            None => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "Data must follow an OP_PUSHBYTES_* opcode",
+                ))
+            }
+        }
+
+        self.0.push(AsmToken::Bytes(bytes.to_vec()));
 
         Ok(self)
     }
 
+    /// Push data together with the `OP_PUSHBYTES_*` opcode for its length.
+    /// Empty data is pushed as `OP_0`. Data longer than 75 bytes needs
+    /// `OP_PUSHDATA*` which is not supported
+    pub fn push_data(&mut self, bytes: &[u8]) -> io::Result<&mut Self> {
+        match bytes.len() {
+            0 => self.push_opcode(Opcode::OP_0),
+            byte_len @ 1..=75 => self
+                .push_opcode(Opcode::PushBytes(byte_len as u8))?
+                .push_bytes(bytes),
+            _ => Err(io::Error::new(
+                ErrorKind::Unsupported,
+                "Pushes of more than 75 bytes are not supported",
+            )),
+        }
+    }
+
+    /// Build the script in the `AsmFormat::Explicit` format
     pub fn build(self) -> String {
+        self.build_as(AsmFormat::Explicit)
+    }
+
+    /// Build the script in the chosen format
+    pub fn build_as(self, format: AsmFormat) -> String {
         self.0
             .into_iter()
-            .map(|mut part| {
-                part.push(' ');
-                part
+            .filter_map(|token| match (format, token) {
+                // The data already shows how many bytes are pushed
+                (AsmFormat::CoreAsm, AsmToken::Opcode(Opcode::PushBytes(_))) => None,
+                (AsmFormat::CoreAsm, AsmToken::Opcode(opcode)) => {
+                    Some(Self::core_asm_opcode(opcode))
+                }
+                (AsmFormat::CoreAsm, AsmToken::Bytes(bytes)) => Some(Self::core_asm_data(&bytes)),
+                (_, AsmToken::Opcode(opcode)) => Some(opcode.to_string()),
+                (_, AsmToken::Bytes(bytes)) => Some(hex::encode(bytes)),
             })
-            .collect::<String>()
-            .trim()
-            .into()
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    // The length of the last `OP_PUSHBYTES_*` opcode if its data was not pushed yet
+    fn pending_push(&self) -> Option<u8> {
+        match self.0.last() {
+            Some(AsmToken::Opcode(Opcode::PushBytes(byte_len))) => Some(*byte_len),
+            _ => None,
+        }
+    }
+
+    // Bitcoin Core writes the number pushed by `OP_0`, `OP_1NEGATE` and
+    // `OP_1..16` and a single name for every undefined opcode
+    fn core_asm_opcode(opcode: Opcode) -> String {
+        match opcode {
+            Opcode::OP_0 => "0".into(),
+            Opcode::OP_1NEGATE => "-1".into(),
+            Opcode::OP_1 => "1".into(),
+            Opcode::Num(value) => value.to_string(),
+            Opcode::Unknown(_) => "OP_UNKNOWN".into(),
+            opcode => opcode.to_string(),
+        }
+    }
+
+    // Pushes of up to 4 bytes are written as script numbers and longer ones as hex
+    fn core_asm_data(bytes: &[u8]) -> String {
+        if bytes.len() <= 4 {
+            Self::script_number(bytes).to_string()
+        } else {
+            hex::encode(bytes)
+        }
+    }
+
+    // Script numbers are little endian with the highest bit of the last byte as the sign
+    fn script_number(bytes: &[u8]) -> i64 {
+        let magnitude = bytes.iter().enumerate().fold(0i64, |value, (index, byte)| {
+            value | (*byte as i64) << (8 * index)
+        });
+
+        match bytes.last() {
+            Some(last) if last & 0x80 != 0 => -(magnitude & !(0x80i64 << (8 * (bytes.len() - 1)))),
+            _ => magnitude,
+        }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+/// An opcode of Bitcoin script, every byte is one of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[allow(non_camel_case_types)]
 pub enum Opcode {
-    OP_HASH160,
-    OP_CHECKSIG,
-    OP_EQUAL,
-    OP_EQUALVERIFY,
-    OP_CHECKMULTISIG,
-    OP_DUP,
-    OP_RETURN,
+    /// Pushes an empty array, also known as `OP_FALSE`
     OP_0,
+    /// `OP_PUSHBYTES_1` to `OP_PUSHBYTES_75` push the next 1 to 75 bytes
+    PushBytes(u8),
+    /// Pushes the number 1, also known as `OP_TRUE`
     OP_1,
+    /// `OP_2` to `OP_16` push the numbers 2 to 16, `Num(1)` is `OP_1`
     Num(u8),
-    PushBytes(u8),
-    UnsupportedOpcode,
+    /// `OP_NOP1` and `OP_NOP4` to `OP_NOP10` do nothing and are reserved for
+    /// soft-forks. `OP_NOP2` and `OP_NOP3` are `OP_CHECKLOCKTIMEVERIFY` and
+    /// `OP_CHECKSEQUENCEVERIFY`
+    Nop(u8),
+    /// The bytes `0xbb` to `0xfe` which are not defined, shown as
+    /// `OP_UNKNOWN_187` to `OP_UNKNOWN_254`. In tapscript they are `OP_SUCCESS*`
+    Unknown(u8),
+    OP_PUSHDATA1,
+    OP_PUSHDATA2,
+    OP_PUSHDATA4,
+    OP_1NEGATE,
+    OP_RESERVED,
+    OP_NOP,
+    OP_VER,
+    OP_IF,
+    OP_NOTIF,
+    OP_VERIF,
+    OP_VERNOTIF,
+    OP_ELSE,
+    OP_ENDIF,
+    OP_VERIFY,
+    OP_RETURN,
+    OP_TOALTSTACK,
+    OP_FROMALTSTACK,
+    OP_2DROP,
+    OP_2DUP,
+    OP_3DUP,
+    OP_2OVER,
+    OP_2ROT,
+    OP_2SWAP,
+    OP_IFDUP,
+    OP_DEPTH,
+    OP_DROP,
+    OP_DUP,
+    OP_NIP,
+    OP_OVER,
+    OP_PICK,
+    OP_ROLL,
+    OP_ROT,
+    OP_SWAP,
+    OP_TUCK,
+    OP_CAT,
+    OP_SUBSTR,
+    OP_LEFT,
+    OP_RIGHT,
+    OP_SIZE,
+    OP_INVERT,
+    OP_AND,
+    OP_OR,
+    OP_XOR,
+    OP_EQUAL,
+    OP_EQUALVERIFY,
+    OP_RESERVED1,
+    OP_RESERVED2,
+    OP_1ADD,
+    OP_1SUB,
+    OP_2MUL,
+    OP_2DIV,
+    OP_NEGATE,
+    OP_ABS,
+    OP_NOT,
+    OP_0NOTEQUAL,
+    OP_ADD,
+    OP_SUB,
+    OP_MUL,
+    OP_DIV,
+    OP_MOD,
+    OP_LSHIFT,
+    OP_RSHIFT,
+    OP_BOOLAND,
+    OP_BOOLOR,
+    OP_NUMEQUAL,
+    OP_NUMEQUALVERIFY,
+    OP_NUMNOTEQUAL,
+    OP_LESSTHAN,
+    OP_GREATERTHAN,
+    OP_LESSTHANOREQUAL,
+    OP_GREATERTHANOREQUAL,
+    OP_MIN,
+    OP_MAX,
+    OP_WITHIN,
+    OP_RIPEMD160,
+    OP_SHA1,
+    OP_SHA256,
+    OP_HASH160,
+    OP_HASH256,
+    OP_CODESEPARATOR,
+    OP_CHECKSIG,
+    OP_CHECKSIGVERIFY,
+    OP_CHECKMULTISIG,
+    OP_CHECKMULTISIGVERIFY,
+    OP_CHECKLOCKTIMEVERIFY,
+    OP_CHECKSEQUENCEVERIFY,
+    OP_CHECKSIGADD,
+    OP_INVALIDOPCODE,
 }
 
+// The opcodes without a number in their name with their byte and name
+const NAMED_OPCODES: [(Opcode, u8, &str); 88] = [
+    (Opcode::OP_PUSHDATA1, 0x4c, "OP_PUSHDATA1"),
+    (Opcode::OP_PUSHDATA2, 0x4d, "OP_PUSHDATA2"),
+    (Opcode::OP_PUSHDATA4, 0x4e, "OP_PUSHDATA4"),
+    (Opcode::OP_1NEGATE, 0x4f, "OP_1NEGATE"),
+    (Opcode::OP_RESERVED, 0x50, "OP_RESERVED"),
+    (Opcode::OP_NOP, 0x61, "OP_NOP"),
+    (Opcode::OP_VER, 0x62, "OP_VER"),
+    (Opcode::OP_IF, 0x63, "OP_IF"),
+    (Opcode::OP_NOTIF, 0x64, "OP_NOTIF"),
+    (Opcode::OP_VERIF, 0x65, "OP_VERIF"),
+    (Opcode::OP_VERNOTIF, 0x66, "OP_VERNOTIF"),
+    (Opcode::OP_ELSE, 0x67, "OP_ELSE"),
+    (Opcode::OP_ENDIF, 0x68, "OP_ENDIF"),
+    (Opcode::OP_VERIFY, 0x69, "OP_VERIFY"),
+    (Opcode::OP_RETURN, 0x6a, "OP_RETURN"),
+    (Opcode::OP_TOALTSTACK, 0x6b, "OP_TOALTSTACK"),
+    (Opcode::OP_FROMALTSTACK, 0x6c, "OP_FROMALTSTACK"),
+    (Opcode::OP_2DROP, 0x6d, "OP_2DROP"),
+    (Opcode::OP_2DUP, 0x6e, "OP_2DUP"),
+    (Opcode::OP_3DUP, 0x6f, "OP_3DUP"),
+    (Opcode::OP_2OVER, 0x70, "OP_2OVER"),
+    (Opcode::OP_2ROT, 0x71, "OP_2ROT"),
+    (Opcode::OP_2SWAP, 0x72, "OP_2SWAP"),
+    (Opcode::OP_IFDUP, 0x73, "OP_IFDUP"),
+    (Opcode::OP_DEPTH, 0x74, "OP_DEPTH"),
+    (Opcode::OP_DROP, 0x75, "OP_DROP"),
+    (Opcode::OP_DUP, 0x76, "OP_DUP"),
+    (Opcode::OP_NIP, 0x77, "OP_NIP"),
+    (Opcode::OP_OVER, 0x78, "OP_OVER"),
+    (Opcode::OP_PICK, 0x79, "OP_PICK"),
+    (Opcode::OP_ROLL, 0x7a, "OP_ROLL"),
+    (Opcode::OP_ROT, 0x7b, "OP_ROT"),
+    (Opcode::OP_SWAP, 0x7c, "OP_SWAP"),
+    (Opcode::OP_TUCK, 0x7d, "OP_TUCK"),
+    (Opcode::OP_CAT, 0x7e, "OP_CAT"),
+    (Opcode::OP_SUBSTR, 0x7f, "OP_SUBSTR"),
+    (Opcode::OP_LEFT, 0x80, "OP_LEFT"),
+    (Opcode::OP_RIGHT, 0x81, "OP_RIGHT"),
+    (Opcode::OP_SIZE, 0x82, "OP_SIZE"),
+    (Opcode::OP_INVERT, 0x83, "OP_INVERT"),
+    (Opcode::OP_AND, 0x84, "OP_AND"),
+    (Opcode::OP_OR, 0x85, "OP_OR"),
+    (Opcode::OP_XOR, 0x86, "OP_XOR"),
+    (Opcode::OP_EQUAL, 0x87, "OP_EQUAL"),
+    (Opcode::OP_EQUALVERIFY, 0x88, "OP_EQUALVERIFY"),
+    (Opcode::OP_RESERVED1, 0x89, "OP_RESERVED1"),
+    (Opcode::OP_RESERVED2, 0x8a, "OP_RESERVED2"),
+    (Opcode::OP_1ADD, 0x8b, "OP_1ADD"),
+    (Opcode::OP_1SUB, 0x8c, "OP_1SUB"),
+    (Opcode::OP_2MUL, 0x8d, "OP_2MUL"),
+    (Opcode::OP_2DIV, 0x8e, "OP_2DIV"),
+    (Opcode::OP_NEGATE, 0x8f, "OP_NEGATE"),
+    (Opcode::OP_ABS, 0x90, "OP_ABS"),
+    (Opcode::OP_NOT, 0x91, "OP_NOT"),
+    (Opcode::OP_0NOTEQUAL, 0x92, "OP_0NOTEQUAL"),
+    (Opcode::OP_ADD, 0x93, "OP_ADD"),
+    (Opcode::OP_SUB, 0x94, "OP_SUB"),
+    (Opcode::OP_MUL, 0x95, "OP_MUL"),
+    (Opcode::OP_DIV, 0x96, "OP_DIV"),
+    (Opcode::OP_MOD, 0x97, "OP_MOD"),
+    (Opcode::OP_LSHIFT, 0x98, "OP_LSHIFT"),
+    (Opcode::OP_RSHIFT, 0x99, "OP_RSHIFT"),
+    (Opcode::OP_BOOLAND, 0x9a, "OP_BOOLAND"),
+    (Opcode::OP_BOOLOR, 0x9b, "OP_BOOLOR"),
+    (Opcode::OP_NUMEQUAL, 0x9c, "OP_NUMEQUAL"),
+    (Opcode::OP_NUMEQUALVERIFY, 0x9d, "OP_NUMEQUALVERIFY"),
+    (Opcode::OP_NUMNOTEQUAL, 0x9e, "OP_NUMNOTEQUAL"),
+    (Opcode::OP_LESSTHAN, 0x9f, "OP_LESSTHAN"),
+    (Opcode::OP_GREATERTHAN, 0xa0, "OP_GREATERTHAN"),
+    (Opcode::OP_LESSTHANOREQUAL, 0xa1, "OP_LESSTHANOREQUAL"),
+    (Opcode::OP_GREATERTHANOREQUAL, 0xa2, "OP_GREATERTHANOREQUAL"),
+    (Opcode::OP_MIN, 0xa3, "OP_MIN"),
+    (Opcode::OP_MAX, 0xa4, "OP_MAX"),
+    (Opcode::OP_WITHIN, 0xa5, "OP_WITHIN"),
+    (Opcode::OP_RIPEMD160, 0xa6, "OP_RIPEMD160"),
+    (Opcode::OP_SHA1, 0xa7, "OP_SHA1"),
+    (Opcode::OP_SHA256, 0xa8, "OP_SHA256"),
+    (Opcode::OP_HASH160, 0xa9, "OP_HASH160"),
+    (Opcode::OP_HASH256, 0xaa, "OP_HASH256"),
+    (Opcode::OP_CODESEPARATOR, 0xab, "OP_CODESEPARATOR"),
+    (Opcode::OP_CHECKSIG, 0xac, "OP_CHECKSIG"),
+    (Opcode::OP_CHECKSIGVERIFY, 0xad, "OP_CHECKSIGVERIFY"),
+    (Opcode::OP_CHECKMULTISIG, 0xae, "OP_CHECKMULTISIG"),
+    (
+        Opcode::OP_CHECKMULTISIGVERIFY,
+        0xaf,
+        "OP_CHECKMULTISIGVERIFY",
+    ),
+    (
+        Opcode::OP_CHECKLOCKTIMEVERIFY,
+        0xb1,
+        "OP_CHECKLOCKTIMEVERIFY",
+    ),
+    (
+        Opcode::OP_CHECKSEQUENCEVERIFY,
+        0xb2,
+        "OP_CHECKSEQUENCEVERIFY",
+    ),
+    (Opcode::OP_CHECKSIGADD, 0xba, "OP_CHECKSIGADD"),
+    (Opcode::OP_INVALIDOPCODE, 0xff, "OP_INVALIDOPCODE"),
+];
+
 impl Opcode {
     pub fn from_byte(byte: u8) -> Self {
         match byte {
-            169 => Self::OP_HASH160,
-            1..=75 => Self::PushBytes(byte),
-            172 => Self::OP_CHECKSIG,
-            135 => Self::OP_EQUAL,
-            136 => Self::OP_EQUALVERIFY,
-            174 => Self::OP_CHECKMULTISIG,
-            118 => Self::OP_DUP,
-            106 => Self::OP_RETURN,
             0 => Self::OP_0,
+            1..=75 => Self::PushBytes(byte),
             81 => Self::OP_1,
-            82..=96 => {
-                let to_num = match byte {
-                    82 => 2u8,
-                    83 => 3,
-                    84 => 4,
-                    85 => 5,
-                    86 => 6,
-                    87 => 7,
-                    88 => 8,
-                    89 => 9,
-                    90 => 10,
-                    91 => 11,
-                    92 => 12,
-                    93 => 13,
-                    94 => 14,
-                    95 => 15,
-                    96 => 16,
-                    _ => return Self::UnsupportedOpcode,
-                };
-                Self::Num(to_num)
-            }
-            _ => Self::UnsupportedOpcode,
+            82..=96 => Self::Num(byte - 80),
+            0xb0 => Self::Nop(1),
+            0xb3..=0xb9 => Self::Nop(byte - 0xb3 + 4),
+            0xbb..=0xfe => Self::Unknown(byte),
+            _ => NAMED_OPCODES
+                .iter()
+                .find(|(_, named_byte, _)| *named_byte == byte)
+                .map(|(opcode, _, _)| *opcode)
+                .unwrap_or(Self::Unknown(byte)),
         }
     }
 
+    /// The byte of the opcode. Returns `None` for the numbered variants
+    /// holding a number without an opcode like `Num(17)`
+    pub fn to_byte(&self) -> Option<u8> {
+        let byte = match self {
+            Self::OP_0 => 0,
+            Self::PushBytes(byte_len @ 1..=75) => *byte_len,
+            Self::OP_1 => 81,
+            Self::Num(value @ 1..=16) => value + 80,
+            Self::Nop(1) => 0xb0,
+            Self::Nop(value @ 4..=10) => value - 4 + 0xb3,
+            Self::Unknown(byte @ 0xbb..=0xfe) => *byte,
+            Self::PushBytes(_) | Self::Num(_) | Self::Nop(_) | Self::Unknown(_) => return None,
+            named => {
+                return NAMED_OPCODES
+                    .iter()
+                    .find(|(opcode, _, _)| opcode == named)
+                    .map(|(_, byte, _)| *byte)
+            }
+        };
+
+        Some(byte)
+    }
+
+    /// Whether the opcode counts as a push in Bitcoin Core's push only check,
+    /// which is every opcode up to `OP_16` including `OP_RESERVED`
+    pub fn is_push(&self) -> bool {
+        self.to_byte().is_some_and(|byte| byte <= 0x60)
+    }
+
+    /// Read the data pushed by an `OP_PUSHBYTES_*` opcode. A script that ends
+    /// before all the bytes of the push returns an `UnexpectedEof` error
     pub fn read_bytes(&self, bytes: &mut Cursor<&[u8]>) -> io::Result<Vec<u8>> {
-        let mut buffer = Vec::<u8>::new();
+        self.read_bytes_for(bytes, None)
+    }
 
+    // Same as `read_bytes()` reporting the `template` being parsed in the error
+    fn read_bytes_for(
+        &self,
+        bytes: &mut Cursor<&[u8]>,
+        template: Option<ScriptType>,
+    ) -> io::Result<Vec<u8>> {
         match self {
             Self::PushBytes(byte_len) => {
-                let new_position = (bytes.position() as usize).add(*byte_len as usize);
-                buffer.extend_from_slice(&bytes.get_ref()[bytes.position() as usize..new_position]);
-                bytes.set_position(new_position as u64);
+                let offset = bytes.position() as usize;
+
+                let mut buffer = vec![0u8; *byte_len as usize];
+                bytes.read_exact(&mut buffer).map_err(|_| {
+                    io::Error::from(ScriptError::end_of_script(
+                        template,
+                        offset,
+                        format!("{} bytes of data", byte_len),
+                    ))
+                })?;
 
                 Ok(buffer)
             }
@@ -451,33 +1281,386 @@ impl Opcode {
     }
 }
 
-impl TryFrom<Opcode> for String {
-    // All errors are converted to std::io::Error
-    type Error = io::Error;
-
-    fn try_from(value: Opcode) -> Result<Self, Self::Error> {
-        let opcode = match value {
-            Opcode::OP_HASH160 => "OP_HASH160",
-            Opcode::PushBytes(bytes_len) => {
-                return Ok(String::from("OP_PUSHBYTES_").add(bytes_len.to_string().as_str()))
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OP_0 => write!(f, "OP_0"),
+            Self::PushBytes(byte_len) => write!(f, "OP_PUSHBYTES_{}", byte_len),
+            Self::OP_1 => write!(f, "OP_1"),
+            Self::Num(value) => write!(f, "OP_{}", value),
+            Self::Nop(value) => write!(f, "OP_NOP{}", value),
+            Self::Unknown(byte) => write!(f, "OP_UNKNOWN_{}", byte),
+            named => {
+                let name = NAMED_OPCODES
+                    .iter()
+                    .find(|(opcode, _, _)| opcode == named)
+                    .map(|(_, _, name)| *name)
+                    .unwrap_or_default();
+
+                write!(f, "{}", name)
             }
-            Opcode::OP_CHECKSIG => "OP_CHECKSIG",
-            Opcode::OP_EQUAL => "OP_EQUAL",
-            Opcode::OP_EQUALVERIFY => "OP_EQUALVERIFY",
-            Opcode::OP_CHECKMULTISIG => "OP_CHECKMULTISIG",
-            Opcode::OP_DUP => "OP_DUP",
-            Opcode::OP_RETURN => "OP_RETURN",
-            Opcode::OP_0 => "OP_0",
-            Opcode::OP_1 => "OP_1",
-            Opcode::Num(value) => return Ok(String::from("OP_").add(value.to_string().as_str())),
-            Opcode::UnsupportedOpcode => {
-                return Err(io::Error::new(
-                    ErrorKind::InvalidData,
-                    "Unsupported Opcode. Opcode not part of Bitcoin Core standard scripts",
-                ))
+        }
+    }
+}
+
+impl FromStr for Opcode {
+    type Err = io::Error;
+
+    /// Parse the name of an opcode as written by `Display`. The aliases
+    /// `OP_FALSE`, `OP_TRUE`, `OP_NOP2` and `OP_NOP3` are accepted too
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let unknown = || {
+            io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("`{}` is not the name of an opcode", name),
+            )
+        };
+        // The number after a prefix, which must not have leading zeros
+        let number = |prefix: &str| {
+            name.strip_prefix(prefix)
+                .filter(|digits| !digits.starts_with('0') || *digits == "0")
+                .and_then(|digits| digits.parse::<u8>().ok())
+        };
+
+        if let Some(named) = NAMED_OPCODES
+            .iter()
+            .find(|(_, _, opcode_name)| *opcode_name == name)
+        {
+            return Ok(named.0);
+        }
+
+        let opcode = match name {
+            "OP_0" | "OP_FALSE" => Self::OP_0,
+            "OP_1" | "OP_TRUE" => Self::OP_1,
+            "OP_NOP2" => Self::OP_CHECKLOCKTIMEVERIFY,
+            "OP_NOP3" => Self::OP_CHECKSEQUENCEVERIFY,
+            _ => {
+                if let Some(byte_len) = number("OP_PUSHBYTES_") {
+                    Self::PushBytes(byte_len)
+                } else if let Some(value) = number("OP_NOP") {
+                    Self::Nop(value)
+                } else if let Some(byte) = number("OP_UNKNOWN_") {
+                    Self::Unknown(byte)
+                } else if let Some(value) = number("OP_") {
+                    Self::Num(value)
+                } else {
+                    return Err(unknown());
+                }
             }
         };
 
-        Ok(opcode.into())
+        // Only numbers which have an opcode are accepted
+        opcode.to_byte().map(Self::from_byte).ok_or_else(unknown)
+    }
+}
+
+#[cfg(test)]
+mod scripts_sanity_checks {
+    use crate::{
+        AsmFormat, MultisigError, Opcode, ScriptBuilder, ScriptError, ScriptType, StandardScripts,
+    };
+    use hex_literal::hex;
+    use std::io::{Cursor, ErrorKind};
+
+    #[test]
+    fn core_asm() {
+        let parse = |script: &[u8]| {
+            StandardScripts::parse_as(&mut Cursor::new(script), AsmFormat::CoreAsm).unwrap()
+        };
+
+        assert_eq!(
+            "0 751e76e8199196d454941c45d1b3a323f1433bd6",
+            parse(&hex!("0014751e76e8199196d454941c45d1b3a323f1433bd6"))
+        );
+        // Pushes of up to 4 bytes are shown as script numbers
+        assert_eq!("OP_RETURN 1234", parse(&hex!("6a02d204")));
+        assert_eq!("OP_RETURN -32767", parse(&hex!("6a02ffff")));
+        assert_eq!("OP_RETURN 128", parse(&hex!("6a028000")));
+
+        // `OP_PUSHDATA*` opcodes are left out like the other push opcodes
+        let op_return = [&hex!("6a4c50")[..], &[0xab; 80]].concat();
+        assert_eq!(
+            format!("OP_RETURN {}", "ab".repeat(80)),
+            StandardScripts::to_asm(&op_return, AsmFormat::CoreAsm).unwrap()
+        );
+        assert_eq!(
+            format!("OP_RETURN OP_PUSHDATA1 {}", "ab".repeat(80)),
+            StandardScripts::to_asm(&op_return, AsmFormat::Explicit).unwrap()
+        );
+        assert_eq!(
+            "-42 0 OP_CHECKSIG",
+            StandardScripts::to_asm(&hex!("4c01aa4e00000000ac"), AsmFormat::CoreAsm).unwrap()
+        );
+        // OP_1NEGATE, an undefined opcode, OP_1 and OP_16
+        assert_eq!(
+            "-1 OP_UNKNOWN 1 16",
+            StandardScripts::to_asm(&hex!("4fbb5160"), AsmFormat::CoreAsm).unwrap()
+        );
+    }
+
+    #[test]
+    fn template_errors() {
+        // OP_EQUAL in place of OP_EQUALVERIFY
+        let p2pkh = hex!("76a914000000000000000000000000000000000000000087ac");
+        let error = StandardScripts::parse(&mut Cursor::new(p2pkh.as_ref())).unwrap_err();
+        assert_eq!(ErrorKind::InvalidData, error.kind());
+        assert_eq!(
+            "P2PKH: expected OP_EQUALVERIFY at offset 23, found OP_EQUAL",
+            error.to_string()
+        );
+
+        let script_error = error
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<ScriptError>())
+            .unwrap();
+        assert_eq!(Some(ScriptType::P2PKH), script_error.template());
+        assert_eq!(23, script_error.offset());
+
+        // A truncated P2SH hash
+        let p2sh = hex!("a9147482");
+        let error = StandardScripts::parse(&mut Cursor::new(p2sh.as_ref())).unwrap_err();
+        assert_eq!(ErrorKind::UnexpectedEof, error.kind());
+        assert_eq!(
+            "P2SH: expected a 20 byte script hash at offset 2, found end of script",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn opcode_table() {
+        (0..=u8::MAX).for_each(|byte| {
+            let opcode = Opcode::from_byte(byte);
+            assert_eq!(Some(byte), opcode.to_byte());
+            assert_eq!(opcode, opcode.to_string().parse::<Opcode>().unwrap());
+        });
+
+        assert_eq!(Opcode::OP_IF, Opcode::from_byte(0x63));
+        assert_eq!("OP_CHECKSIGADD", Opcode::from_byte(0xba).to_string());
+        assert_eq!("OP_NOP10", Opcode::from_byte(0xb9).to_string());
+        assert_eq!("OP_UNKNOWN_187", Opcode::from_byte(0xbb).to_string());
+        assert_eq!(
+            Opcode::OP_CHECKLOCKTIMEVERIFY,
+            "OP_NOP2".parse::<Opcode>().unwrap()
+        );
+        assert_eq!(Opcode::OP_0, "OP_FALSE".parse::<Opcode>().unwrap());
+        assert!("OP_17".parse::<Opcode>().is_err());
+        assert!("OP_PUSHBYTES_076".parse::<Opcode>().is_err());
+        assert_eq!(None, Opcode::Num(17).to_byte());
+    }
+
+    #[test]
+    fn builder_push_lengths() {
+        let mut builder = ScriptBuilder::new();
+        builder.push_opcode(Opcode::PushBytes(2)).unwrap();
+        assert!(builder.push_bytes(&[1, 2, 3]).is_err());
+        assert!(builder.push_opcode(Opcode::OP_CHECKSIG).is_err());
+        builder.push_bytes(&[1, 2]).unwrap();
+        assert!(builder.push_bytes(&[1, 2]).is_err());
+
+        builder
+            .push_data(&[])
+            .unwrap()
+            .push_data(&[0xab; 20])
+            .unwrap();
+        assert!(builder.push_data(&[0u8; 76]).is_err());
+        assert_eq!(
+            format!(
+                "OP_PUSHBYTES_2 0102 OP_0 OP_PUSHBYTES_20 {}",
+                "ab".repeat(20)
+            ),
+            builder.build()
+        );
+    }
+
+    #[test]
+    fn multisig_public_keys() {
+        let key = |prefix: u8, len: usize| {
+            let mut push = vec![len as u8, prefix];
+            push.resize(len + 1, 0x01);
+            push
+        };
+        let multisig = |keys: &[Vec<u8>]| {
+            [
+                &[0x51][..],
+                &keys.concat(),
+                &[0x50 + keys.len() as u8, 0xae],
+            ]
+            .concat()
+        };
+        let multisig_error = |script: &[u8]| {
+            let error = StandardScripts::parse(&mut Cursor::new(script)).unwrap_err();
+            *error
+                .get_ref()
+                .and_then(|inner| inner.downcast_ref::<MultisigError>())
+                .unwrap()
+        };
+
+        // OP_1 is a separate variant from OP_2..16 for both counts
+        let one_of_one = multisig(&[key(0x02, 33)]);
+        assert_eq!(ScriptType::P2MS, ScriptType::from_script(&one_of_one));
+        assert_eq!(
+            format!(
+                "OP_1 OP_PUSHBYTES_33 02{} OP_1 OP_CHECKMULTISIG",
+                "01".repeat(32)
+            ),
+            StandardScripts::check_standard_p2ms(&one_of_one).unwrap()
+        );
+        let two_of_one = [&[0x52][..], &key(0x02, 33), &[0x51, 0xae]].concat();
+        assert!(StandardScripts::parse(&mut Cursor::new(two_of_one.as_slice())).is_err());
+
+        let valid = multisig(&[key(0x02, 33), key(0x04, 65)]);
+        assert_eq!(ScriptType::P2MS, ScriptType::from_script(&valid));
+        assert!(StandardScripts::check_standard_p2ms(&valid).is_ok());
+
+        // A 32 byte push and a 33 byte push with an uncompressed prefix
+        assert_eq!(
+            MultisigError::InvalidPublicKey {
+                offset: 35,
+                len: 32
+            },
+            multisig_error(&multisig(&[key(0x02, 33), key(0x02, 32)]))
+        );
+        assert_eq!(
+            MultisigError::InvalidPublicKey { offset: 1, len: 33 },
+            multisig_error(&multisig(&[key(0x04, 33)]))
+        );
+
+        let four_keys = multisig(&vec![key(0x03, 33); 4]);
+        assert_eq!(ScriptType::P2MS, ScriptType::from_script(&four_keys));
+        let error = StandardScripts::check_standard_p2ms(&four_keys).unwrap_err();
+        assert_eq!(
+            Some(&MultisigError::NonStandardKeyCount { keys: 4 }),
+            error
+                .get_ref()
+                .and_then(|inner| inner.downcast_ref::<MultisigError>())
+        );
+
+        let too_many = [
+            &[0x51][..],
+            &vec![key(0x02, 33); 21].concat(),
+            &[0x60, 0xae],
+        ]
+        .concat();
+        assert_eq!(
+            MultisigError::TooManyKeys { keys: 21 },
+            multisig_error(&too_many)
+        );
+    }
+
+    #[test]
+    fn truncated_pushes() {
+        // A 1-of-2 multisig cut off in the middle of the second public key
+        let p2ms =
+            hex!("512102000000000000000000000000000000000000000000000000000000000000000121030000");
+        let error = StandardScripts::parse(&mut Cursor::new(p2ms.as_ref())).unwrap_err();
+        assert_eq!(ErrorKind::UnexpectedEof, error.kind());
+        assert_eq!(
+            "P2MS: expected 33 bytes of data at offset 36, found end of script",
+            error.to_string()
+        );
+
+        let error = StandardScripts::to_asm(&hex!("6a0b6865"), AsmFormat::CoreAsm).unwrap_err();
+        assert_eq!(ErrorKind::UnexpectedEof, error.kind());
+
+        // Every truncation and random corruption of valid scripts must return
+        // an error instead of panicking
+        let scripts = [
+            hex!("76a914000000000000000000000000000000000000000088ac").to_vec(),
+            hex!("a914748284390f9e263a4b766a75d0633c50426eb87587").to_vec(),
+            hex!("6a0b68656c6c6f20776f726c64").to_vec(),
+            hex!("5210751e76e8199196d454941c45d1b3a323").to_vec(),
+            p2ms.to_vec(),
+        ];
+        let mut state = 0x2545f4914f6cdd1du64;
+        let mut random = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for script in scripts {
+            for end in 0..=script.len() {
+                let mut fuzzed = script[..end].to_vec();
+                if !fuzzed.is_empty() {
+                    let index = random() as usize % fuzzed.len();
+                    fuzzed[index] = random() as u8;
+                }
+
+                for bytes in [&script[..end], fuzzed.as_slice()] {
+                    let _ = StandardScripts::parse(&mut Cursor::new(bytes));
+                    let _ = StandardScripts::to_asm(bytes, AsmFormat::CoreAsm);
+                    let _ = StandardScripts::read_pushes(bytes);
+                    let _ = ScriptType::from_script(bytes);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn script_type_classification() {
+        assert_eq!(
+            ScriptType::P2PKH,
+            ScriptType::from_script(&hex!("76a914000000000000000000000000000000000000000088ac"))
+        );
+        assert_eq!(
+            ScriptType::P2SH,
+            ScriptType::from_script(&hex!("a914748284390f9e263a4b766a75d0633c50426eb87587"))
+        );
+        assert_eq!(
+            ScriptType::P2WPKH,
+            ScriptType::from_script(&hex!("00140000000000000000000000000000000000000000"))
+        );
+        assert_eq!(
+            ScriptType::OpReturn,
+            ScriptType::from_script(&hex!("6a0b68656c6c6f20776f726c64"))
+        );
+        // Witness version 16 and a witness version 2 program are not defined yet
+        assert_eq!(
+            ScriptType::WitnessUnknown(16),
+            ScriptType::from_script(&hex!("6002751e"))
+        );
+        assert_eq!(
+            ScriptType::WitnessUnknown(2),
+            ScriptType::from_script(&hex!("5210751e76e8199196d454941c45d1b3a323"))
+        );
+        // Null data is any number of pushes after OP_RETURN, including none
+        // and OP_PUSHDATA1 pushes
+        let op_return = [&hex!("6a4c50")[..], &[0xab; 80]].concat();
+        assert_eq!(ScriptType::OpReturn, ScriptType::from_script(&op_return));
+        assert_eq!(ScriptType::OpReturn, ScriptType::from_script(&hex!("6a")));
+        assert_eq!(
+            ScriptType::OpReturn,
+            ScriptType::from_script(&hex!("6a01aa01bb"))
+        );
+        assert_eq!(
+            "OP_RETURN OP_PUSHBYTES_1 aa OP_PUSHBYTES_1 bb",
+            StandardScripts::parse(&mut Cursor::new(hex!("6a01aa01bb").as_ref())).unwrap()
+        );
+        assert_eq!(
+            ScriptType::NonStandard,
+            ScriptType::from_script(&hex!("6a01aa76"))
+        );
+        assert_eq!(
+            ScriptType::NonStandard,
+            ScriptType::from_script(&hex!("6a02aa"))
+        );
+
+        // Anchor outputs are not treated as undefined witness programs
+        assert_eq!(ScriptType::P2A, ScriptType::from_script(&hex!("51024e73")));
+        // The anchor is recognized inside a longer buffer too
+        let buffer = hex!("0051024e73");
+        let mut bytes = Cursor::new(buffer.as_ref());
+        bytes.set_position(1);
+        assert_eq!(
+            ScriptType::P2A,
+            StandardScripts::parse_with_type(&mut bytes).unwrap().0
+        );
+        assert_eq!(ScriptType::OpTrue, ScriptType::from_script(&hex!("51")));
+        // Trailing bytes after a template make the script non-standard
+        assert_eq!(
+            ScriptType::NonStandard,
+            ScriptType::from_script(&hex!("a914748284390f9e263a4b766a75d0633c50426eb8758700"))
+        );
     }
 }
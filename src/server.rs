@@ -0,0 +1,266 @@
+use crate::{
+    esplora_json,
+    report::{json_object, json_string},
+    Address, BtcTx, Network, Script,
+};
+use std::{
+    io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+// Requests with larger bodies are rejected, the largest standard transaction is 400 kB
+const MAX_BODY_LEN: usize = 1_000_000;
+
+// Connections beyond this many are answered with 503 without spawning a thread
+const MAX_CONNECTIONS: usize = 64;
+
+// A client that stalls reading or writing for longer is disconnected
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A blocking HTTP server decoding transactions and scripts sent as hex in the
+/// body of `POST` requests. Every response is JSON.
+///
+/// - `/decode` returns the transaction in the Esplora schema
+/// - `/script` returns the script like Bitcoin Core's `decodescript`
+/// - `/address` returns the address of a locking script
+/// - `/fee?rate=<sat/vB>` returns the size of a transaction and its fee at the rate
+#[derive(Debug)]
+pub struct DecodeServer {
+    listener: TcpListener,
+    network: Network,
+}
+
+impl DecodeServer {
+    /// Listen on `address` and encode addresses for `network`
+    pub fn bind(address: impl ToSocketAddrs, network: Network) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(address)?,
+            network,
+        })
+    }
+
+    /// The address the server listens on
+    pub fn local_addr(&self) -> io::Result<std::net::SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Answer requests forever, each connection on its own thread up to 64
+    /// at a time. Failing to accept a connection is logged to stderr
+    pub fn serve(self) -> io::Result<()> {
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        for stream in self.listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(error) => {
+                    eprintln!("Failed to accept a connection: {}", error);
+                    continue;
+                }
+            };
+            if stream.set_read_timeout(Some(IO_TIMEOUT)).is_err()
+                || stream.set_write_timeout(Some(IO_TIMEOUT)).is_err()
+            {
+                continue;
+            }
+
+            let Some(slot) = ConnectionSlot::acquire(&connections) else {
+                let (status, json) = Self::error(503, "Too many connections");
+                let _ = Self::write_response(&stream, status, &json);
+                continue;
+            };
+            let network = self.network;
+
+            thread::spawn(move || {
+                // A client going away is not an error of the server
+                let _ = Self::respond(stream, network);
+                drop(slot);
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Answer a request and return the HTTP status code and the JSON body
+    pub fn handle(method: &str, target: &str, body: &str, network: Network) -> (u16, String) {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+        if method != "POST" {
+            return Self::error(405, "Only POST requests are supported");
+        }
+        let bytes = match hex::decode(body.trim()) {
+            Ok(bytes) => bytes,
+            Err(error) => return Self::error(400, &error.to_string()),
+        };
+
+        match path {
+            "/decode" => match esplora_json(&bytes, None, network) {
+                Ok(json) => (200, json),
+                Err(error) => Self::error(400, &error.to_string()),
+            },
+            "/script" => (200, Script::new(bytes).decode_report(network).to_json()),
+            "/address" => match Address::from_script(&bytes, network) {
+                Some(address) => (200, json_object(&[("address", json_string(&address))])),
+                None => Self::error(404, "The script has no address"),
+            },
+            "/fee" => {
+                let Some(fee_rate) = query
+                    .split('&')
+                    .find_map(|pair| pair.strip_prefix("rate="))
+                    .and_then(|rate| rate.parse::<u64>().ok())
+                else {
+                    return Self::error(400, "The fee rate in sat/vB is missing");
+                };
+                let weight = match BtcTx::from_hex_bytes(&bytes) {
+                    Ok(tx) => tx.weight() as u64,
+                    Err(error) => return Self::error(400, &error.to_string()),
+                };
+
+                let vsize = weight.div_ceil(4);
+                let Some(fee) = vsize.checked_mul(fee_rate) else {
+                    return Self::error(400, "The fee overflows at the fee rate");
+                };
+                (
+                    200,
+                    json_object(&[
+                        ("size", bytes.len().to_string()),
+                        ("weight", weight.to_string()),
+                        ("vsize", vsize.to_string()),
+                        ("fee_rate", fee_rate.to_string()),
+                        ("fee", fee.to_string()),
+                    ]),
+                )
+            }
+            _ => Self::error(404, "The endpoint does not exist"),
+        }
+    }
+
+    fn respond(stream: TcpStream, network: Network) -> io::Result<()> {
+        let mut reader = BufReader::new(&stream);
+
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        let mut parts = request_line.split_whitespace();
+        let (method, target) = match (parts.next(), parts.next()) {
+            (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+            _ => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "The HTTP request line is malformed",
+                ))
+            }
+        };
+
+        let mut content_len = 0usize;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.trim().eq_ignore_ascii_case("content-length") {
+                    content_len = value.trim().parse().unwrap_or_default();
+                }
+            }
+        }
+
+        let (status, json) = if content_len > MAX_BODY_LEN {
+            Self::error(413, "The body is too large")
+        } else {
+            let mut body = vec![0u8; content_len];
+            reader.read_exact(&mut body)?;
+
+            Self::handle(&method, &target, &String::from_utf8_lossy(&body), network)
+        };
+
+        Self::write_response(&stream, status, &json)
+    }
+
+    fn write_response(mut stream: &TcpStream, status: u16, json: &str) -> io::Result<()> {
+        let reason = match status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            503 => "Service Unavailable",
+            _ => "Payload Too Large",
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            reason,
+            json.len(),
+            json
+        )
+    }
+
+    fn error(status: u16, message: &str) -> (u16, String) {
+        (status, json_object(&[("error", json_string(message))]))
+    }
+}
+
+// Counts a connection being answered until it is dropped
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl ConnectionSlot {
+    fn acquire(connections: &Arc<AtomicUsize>) -> Option<Self> {
+        connections
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+                (count < MAX_CONNECTIONS).then_some(count + 1)
+            })
+            .ok()
+            .map(|_| Self(connections.clone()))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod server_sanity_checks {
+    use crate::{DecodeServer, Network};
+
+    #[test]
+    fn endpoints() {
+        let raw_tx = include_str!("../fixtures/transactions/p2pkh_two_inputs.hex");
+        let script = "76a9140ce17649c1306c291ca9e587f8793b5b06563cea88ac";
+        let handle = |method, target, body| {
+            let (status, json) = DecodeServer::handle(method, target, body, Network::Mainnet);
+            (
+                status,
+                serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+            )
+        };
+
+        let (status, json) = handle("POST", "/decode", raw_tx);
+        assert_eq!(200, status);
+        assert_eq!(json["vout"][0]["value"], 95_000);
+
+        let (status, json) = handle("POST", "/script", script);
+        assert_eq!(200, status);
+        assert_eq!(json["type"], "pubkeyhash");
+
+        assert_eq!(200, handle("POST", "/address", script).0);
+        assert_eq!(404, handle("POST", "/address", "6a").0);
+
+        let (status, json) = handle("POST", "/fee?rate=2", raw_tx);
+        assert_eq!(200, status);
+        assert_eq!(json["fee"], json["vsize"].as_u64().unwrap() * 2);
+        assert_eq!(400, handle("POST", "/fee", raw_tx).0);
+        let overflow = format!("/fee?rate={}", u64::MAX);
+        assert_eq!(400, handle("POST", &overflow, raw_tx).0);
+
+        assert_eq!(400, handle("POST", "/decode", "zz").0);
+        assert_eq!(405, handle("GET", "/decode", raw_tx).0);
+        assert_eq!(404, handle("POST", "/unknown", raw_tx).0);
+    }
+}
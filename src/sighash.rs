@@ -0,0 +1,841 @@
+use crate::{
+    tagged_hash, BtcTx, Checksum, Hash256, Script, ScriptType, StandardScripts, TxInput, TxOutput,
+    VarInt,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    cell::OnceCell,
+    fmt,
+    io::{self, ErrorKind},
+};
+
+/// The sighash flag appended to every signature which decides
+/// which parts of the transaction the signature commits to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SigHashType {
+    /// `SIGHASH_ALL` commits to all inputs and outputs
+    All,
+    /// `SIGHASH_NONE` commits to all inputs and no outputs
+    None,
+    /// `SIGHASH_SINGLE` commits to all inputs and the output
+    /// with the same index as the input
+    Single,
+    /// `SIGHASH_ALL | SIGHASH_ANYONECANPAY` commits to only this input
+    AllAnyoneCanPay,
+    /// `SIGHASH_NONE | SIGHASH_ANYONECANPAY` commits to only this input
+    NoneAnyoneCanPay,
+    /// `SIGHASH_SINGLE | SIGHASH_ANYONECANPAY` commits to only this input
+    /// and the output with the same index
+    SingleAnyoneCanPay,
+    /// Any other byte which is valid by consensus but not standard
+    NonStandard(u8),
+}
+
+impl SigHashType {
+    /// The `SIGHASH_ANYONECANPAY` bit
+    pub const ANYONECANPAY: u8 = 0x80;
+
+    /// The taproot `SIGHASH_DEFAULT` which commits to all inputs and outputs
+    /// like `SIGHASH_ALL` but is left out of the signature
+    pub const TAPROOT_DEFAULT: Self = Self::NonStandard(0x00);
+
+    /// Convert the last byte of a signature into a `SigHashType`
+    pub const fn from_byte(byte: u8) -> Self {
+        match byte {
+            0x01 => Self::All,
+            0x02 => Self::None,
+            0x03 => Self::Single,
+            0x81 => Self::AllAnyoneCanPay,
+            0x82 => Self::NoneAnyoneCanPay,
+            0x83 => Self::SingleAnyoneCanPay,
+            _ => Self::NonStandard(byte),
+        }
+    }
+
+    /// Convert to the byte appended to a signature
+    pub const fn to_byte(&self) -> u8 {
+        match self {
+            Self::All => 0x01,
+            Self::None => 0x02,
+            Self::Single => 0x03,
+            Self::AllAnyoneCanPay => 0x81,
+            Self::NoneAnyoneCanPay => 0x82,
+            Self::SingleAnyoneCanPay => 0x83,
+            Self::NonStandard(byte) => *byte,
+        }
+    }
+
+    /// Whether the signature only commits to its own input
+    pub const fn is_anyone_can_pay(&self) -> bool {
+        self.to_byte() & Self::ANYONECANPAY == Self::ANYONECANPAY
+    }
+
+    /// Whether the signature commits to the output with the same index as the input
+    pub const fn is_single(&self) -> bool {
+        matches!(self, Self::Single | Self::SingleAnyoneCanPay)
+    }
+}
+
+impl fmt::Display for SigHashType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::All => write!(f, "ALL"),
+            Self::None => write!(f, "NONE"),
+            Self::Single => write!(f, "SINGLE"),
+            Self::AllAnyoneCanPay => write!(f, "ALL|ANYONECANPAY"),
+            Self::NoneAnyoneCanPay => write!(f, "NONE|ANYONECANPAY"),
+            Self::SingleAnyoneCanPay => write!(f, "SINGLE|ANYONECANPAY"),
+            Self::NonStandard(byte) => write!(f, "0x{:02x}", byte),
+        }
+    }
+}
+
+/// The exact message a signature commits to and the digest which is signed.
+/// External signers can recompute the digest from the preimage to check
+/// what they are asked to sign
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SigHashPreimage {
+    /// The serialized message
+    pub preimage: Vec<u8>,
+    /// The digest passed to the signing algorithm. It is the SHA256d of the
+    /// preimage for legacy and segwit v0 inputs and the `TapSighash` tagged
+    /// hash of the preimage for taproot inputs. It is not reversed
+    pub digest: Hash256,
+}
+
+// The base type in the low bits of the sighash type
+const SIGHASH_NONE: u8 = 0x02;
+const SIGHASH_SINGLE: u8 = 0x03;
+
+impl BtcTx {
+    /// The preimage signed by a legacy input. The `script_code` is the locking
+    /// script being spent, or the redeem script for P2SH, and is used as is so
+    /// any `OP_CODESEPARATOR` must already be removed
+    pub fn legacy_sighash_preimage(
+        &self,
+        input_index: usize,
+        script_code: &[u8],
+        sighash_type: SigHashType,
+    ) -> io::Result<SigHashPreimage> {
+        self.check_input_index(input_index)?;
+        let base_type = sighash_type.to_byte() & 0x1f;
+        if base_type == SIGHASH_SINGLE {
+            self.check_single_output(input_index)?;
+        }
+
+        let mut preimage = self.version().to_bytes().to_vec();
+
+        let inputs = self
+            .inputs()
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !sighash_type.is_anyone_can_pay() || *index == input_index)
+            .collect::<Vec<(usize, &TxInput)>>();
+        preimage.extend_from_slice(&VarInt::encode(inputs.len() as u64));
+        inputs.into_iter().for_each(|(index, input)| {
+            preimage.extend_from_slice(&outpoint_bytes(input));
+
+            // Only the input being signed has a script, the others are empty
+            let script = if index == input_index {
+                script_code
+            } else {
+                &[]
+            };
+            preimage.extend_from_slice(&VarInt::encode(script.len() as u64));
+            preimage.extend_from_slice(script);
+
+            // The other inputs can be replaced when the outputs are not all signed
+            let sequence_number = if index != input_index
+                && (base_type == SIGHASH_NONE || base_type == SIGHASH_SINGLE)
+            {
+                0
+            } else {
+                input.sequence_number()
+            };
+            preimage.extend_from_slice(&sequence_number.to_le_bytes());
+        });
+
+        match base_type {
+            SIGHASH_NONE => preimage.extend_from_slice(&VarInt::encode(0)),
+            SIGHASH_SINGLE => {
+                // The outputs before the signed one are blanked with an amount of -1
+                preimage.extend_from_slice(&VarInt::encode(input_index as u64 + 1));
+                (0..input_index).for_each(|_| {
+                    preimage.extend_from_slice(&u64::MAX.to_le_bytes());
+                    preimage.extend_from_slice(&VarInt::encode(0));
+                });
+                preimage.extend_from_slice(&output_bytes(&self.outputs()[input_index]));
+            }
+            _ => {
+                preimage.extend_from_slice(&VarInt::encode(self.outputs().len() as u64));
+                self.outputs()
+                    .iter()
+                    .for_each(|output| preimage.extend_from_slice(&output_bytes(output)));
+            }
+        }
+
+        preimage.extend_from_slice(&self.locktime().to_le_bytes());
+        preimage.extend_from_slice(&(sighash_type.to_byte() as u32).to_le_bytes());
+
+        let digest = Hash256::new(Checksum::sha256d(&preimage));
+
+        Ok(SigHashPreimage { preimage, digest })
+    }
+
+    /// The BIP143 preimage signed by a segwit v0 input spending `amount` satoshis.
+    /// The `script_code` is `OP_DUP OP_HASH160 <key hash> OP_EQUALVERIFY OP_CHECKSIG`
+    /// for P2WPKH and the witness script for P2WSH, without its length prefix
+    pub fn segwit_v0_sighash_preimage(
+        &self,
+        input_index: usize,
+        script_code: &[u8],
+        amount: u64,
+        sighash_type: SigHashType,
+    ) -> io::Result<SigHashPreimage> {
+        SigHashCache::new(self).segwit_v0_sighash_preimage(
+            input_index,
+            script_code,
+            amount,
+            sighash_type,
+        )
+    }
+
+    /// The BIP341 preimage signed by a taproot input, starting with the epoch byte.
+    /// `prevouts` are the outputs spent by every input in order. A `leaf_hash`
+    /// makes it a script path spend of that leaf, a key path spend otherwise.
+    /// The annex is read from the witness of the input. Spends after an
+    /// `OP_CODESEPARATOR` are not supported
+    pub fn taproot_sighash_preimage(
+        &self,
+        input_index: usize,
+        prevouts: &[TxOutput],
+        leaf_hash: Option<Hash256>,
+        sighash_type: SigHashType,
+    ) -> io::Result<SigHashPreimage> {
+        SigHashCache::new(self)
+            .with_prevouts(prevouts)
+            .taproot_sighash_preimage(input_index, leaf_hash, sighash_type)
+    }
+
+    fn check_input_index(&self, input_index: usize) -> io::Result<()> {
+        if input_index >= self.inputs().len() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "The transaction has no input at the index being signed",
+            ));
+        }
+
+        Ok(())
+    }
+
+    // Legacy inputs would sign the constant one instead of a message and
+    // taproot makes it invalid so neither has a preimage
+    fn check_single_output(&self, input_index: usize) -> io::Result<()> {
+        if input_index >= self.outputs().len() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "SIGHASH_SINGLE needs an output with the same index as the input",
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn sequence_bytes(&self) -> Vec<u8> {
+        self.inputs()
+            .iter()
+            .flat_map(|input| input.sequence_number().to_le_bytes())
+            .collect()
+    }
+
+    fn outputs_bytes(&self) -> Vec<u8> {
+        self.outputs().iter().flat_map(output_bytes).collect()
+    }
+}
+
+/// The hashes of the inputs and outputs shared by the signature messages of
+/// every input of a transaction. They are computed the first time an input
+/// needs them so signing many inputs hashes the transaction once
+#[derive(Debug, Clone)]
+pub struct SigHashCache<'a> {
+    tx: &'a BtcTx,
+    prevouts: &'a [TxOutput],
+    // The double SHA256 of BIP143
+    hash_prevouts: OnceCell<[u8; 32]>,
+    hash_sequence: OnceCell<[u8; 32]>,
+    hash_outputs: OnceCell<[u8; 32]>,
+    // The single SHA256 of BIP341
+    sha_prevouts: OnceCell<[u8; 32]>,
+    sha_amounts: OnceCell<[u8; 32]>,
+    sha_locking_scripts: OnceCell<[u8; 32]>,
+    sha_sequences: OnceCell<[u8; 32]>,
+    sha_outputs: OnceCell<[u8; 32]>,
+}
+
+impl<'a> SigHashCache<'a> {
+    /// An empty cache for the signatures of `tx`
+    pub fn new(tx: &'a BtcTx) -> Self {
+        Self {
+            tx,
+            prevouts: &[],
+            hash_prevouts: OnceCell::new(),
+            hash_sequence: OnceCell::new(),
+            hash_outputs: OnceCell::new(),
+            sha_prevouts: OnceCell::new(),
+            sha_amounts: OnceCell::new(),
+            sha_locking_scripts: OnceCell::new(),
+            sha_sequences: OnceCell::new(),
+            sha_outputs: OnceCell::new(),
+        }
+    }
+
+    /// Add the outputs spent by every input in order, which taproot
+    /// signatures commit to
+    pub fn with_prevouts(mut self, prevouts: &'a [TxOutput]) -> Self {
+        self.prevouts = prevouts;
+
+        self
+    }
+
+    /// The transaction being signed
+    pub fn tx(&self) -> &BtcTx {
+        self.tx
+    }
+
+    /// The outputs spent by every input, empty unless they were added
+    pub fn prevouts(&self) -> &[TxOutput] {
+        self.prevouts
+    }
+
+    /// Same as `BtcTx::legacy_sighash_preimage()`, legacy messages share no hashes
+    pub fn legacy_sighash_preimage(
+        &self,
+        input_index: usize,
+        script_code: &[u8],
+        sighash_type: SigHashType,
+    ) -> io::Result<SigHashPreimage> {
+        self.tx
+            .legacy_sighash_preimage(input_index, script_code, sighash_type)
+    }
+
+    /// Same as `BtcTx::segwit_v0_sighash_preimage()` reusing the hashes
+    pub fn segwit_v0_sighash_preimage(
+        &self,
+        input_index: usize,
+        script_code: &[u8],
+        amount: u64,
+        sighash_type: SigHashType,
+    ) -> io::Result<SigHashPreimage> {
+        let tx = self.tx;
+        tx.check_input_index(input_index)?;
+        let base_type = sighash_type.to_byte() & 0x1f;
+        let input = &tx.inputs()[input_index];
+
+        let hash_prevouts = if sighash_type.is_anyone_can_pay() {
+            [0u8; 32]
+        } else {
+            *self.hash_prevouts.get_or_init(|| {
+                Checksum::sha256d(
+                    &tx.inputs()
+                        .iter()
+                        .flat_map(outpoint_bytes)
+                        .collect::<Vec<u8>>(),
+                )
+            })
+        };
+
+        let hash_sequence = if sighash_type.is_anyone_can_pay()
+            || base_type == SIGHASH_NONE
+            || base_type == SIGHASH_SINGLE
+        {
+            [0u8; 32]
+        } else {
+            *self
+                .hash_sequence
+                .get_or_init(|| Checksum::sha256d(&tx.sequence_bytes()))
+        };
+
+        let hash_outputs = match base_type {
+            SIGHASH_SINGLE if input_index < tx.outputs().len() => {
+                Checksum::sha256d(&output_bytes(&tx.outputs()[input_index]))
+            }
+            SIGHASH_NONE | SIGHASH_SINGLE => [0u8; 32],
+            _ => *self
+                .hash_outputs
+                .get_or_init(|| Checksum::sha256d(&tx.outputs_bytes())),
+        };
+
+        let mut preimage = tx.version().to_bytes().to_vec();
+        preimage.extend_from_slice(&hash_prevouts);
+        preimage.extend_from_slice(&hash_sequence);
+        preimage.extend_from_slice(&outpoint_bytes(input));
+        preimage.extend_from_slice(&VarInt::encode(script_code.len() as u64));
+        preimage.extend_from_slice(script_code);
+        preimage.extend_from_slice(&amount.to_le_bytes());
+        preimage.extend_from_slice(&input.sequence_number().to_le_bytes());
+        preimage.extend_from_slice(&hash_outputs);
+        preimage.extend_from_slice(&tx.locktime().to_le_bytes());
+        preimage.extend_from_slice(&(sighash_type.to_byte() as u32).to_le_bytes());
+
+        let digest = Hash256::new(Checksum::sha256d(&preimage));
+
+        Ok(SigHashPreimage { preimage, digest })
+    }
+
+    /// Same as `BtcTx::taproot_sighash_preimage()` with the prevouts of the
+    /// cache, reusing the hashes
+    pub fn taproot_sighash_preimage(
+        &self,
+        input_index: usize,
+        leaf_hash: Option<Hash256>,
+        sighash_type: SigHashType,
+    ) -> io::Result<SigHashPreimage> {
+        let tx = self.tx;
+        let prevouts = self.prevouts;
+        tx.check_input_index(input_index)?;
+        if let SigHashType::NonStandard(byte @ 0x01..) = sighash_type {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("The sighash type 0x{:02x} is invalid for taproot", byte),
+            ));
+        }
+        if prevouts.len() != tx.inputs().len() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "Taproot signatures commit to the previous output of every input",
+            ));
+        }
+        let base_type = sighash_type.to_byte() & 0x03;
+        if base_type == SIGHASH_SINGLE {
+            tx.check_single_output(input_index)?;
+        }
+
+        let sha256 = |bytes: &[u8]| -> [u8; 32] { Sha256::digest(bytes).into() };
+
+        // The epoch of the signature message
+        let mut preimage = vec![0x00, sighash_type.to_byte()];
+        preimage.extend_from_slice(&tx.version().to_bytes());
+        preimage.extend_from_slice(&tx.locktime().to_le_bytes());
+
+        if !sighash_type.is_anyone_can_pay() {
+            let sha_prevouts = self.sha_prevouts.get_or_init(|| {
+                sha256(
+                    &tx.inputs()
+                        .iter()
+                        .flat_map(outpoint_bytes)
+                        .collect::<Vec<u8>>(),
+                )
+            });
+            let sha_amounts = self.sha_amounts.get_or_init(|| {
+                sha256(
+                    &prevouts
+                        .iter()
+                        .flat_map(|prevout| prevout.amount().to_le_bytes())
+                        .collect::<Vec<u8>>(),
+                )
+            });
+            let sha_locking_scripts = self.sha_locking_scripts.get_or_init(|| {
+                sha256(
+                    &prevouts
+                        .iter()
+                        .flat_map(|prevout| output_bytes(prevout).split_off(8))
+                        .collect::<Vec<u8>>(),
+                )
+            });
+            let sha_sequences = self
+                .sha_sequences
+                .get_or_init(|| sha256(&tx.sequence_bytes()));
+
+            preimage.extend_from_slice(sha_prevouts);
+            preimage.extend_from_slice(sha_amounts);
+            preimage.extend_from_slice(sha_locking_scripts);
+            preimage.extend_from_slice(sha_sequences);
+        }
+
+        if base_type != SIGHASH_NONE && base_type != SIGHASH_SINGLE {
+            preimage
+                .extend_from_slice(self.sha_outputs.get_or_init(|| sha256(&tx.outputs_bytes())));
+        }
+
+        // The spend type is the extension flag doubled plus one with an annex
+        let annex = tx.inputs()[input_index].witness().annex();
+        preimage.push(u8::from(leaf_hash.is_some()) * 2 + u8::from(annex.is_some()));
+
+        if sighash_type.is_anyone_can_pay() {
+            let input = &tx.inputs()[input_index];
+            preimage.extend_from_slice(&outpoint_bytes(input));
+            preimage.extend_from_slice(&output_bytes(&prevouts[input_index]));
+            preimage.extend_from_slice(&input.sequence_number().to_le_bytes());
+        } else {
+            preimage.extend_from_slice(&(input_index as u32).to_le_bytes());
+        }
+
+        if let Some(annex) = annex {
+            preimage.extend_from_slice(&sha256(
+                &[&VarInt::encode(annex.len() as u64), annex].concat(),
+            ));
+        }
+
+        if base_type == SIGHASH_SINGLE {
+            preimage.extend_from_slice(&sha256(&output_bytes(&tx.outputs()[input_index])));
+        }
+
+        if let Some(leaf_hash) = leaf_hash {
+            preimage.extend_from_slice(leaf_hash.as_ref());
+            // The key version followed by the position of the last executed
+            // `OP_CODESEPARATOR` which is none
+            preimage.push(0x00);
+            preimage.extend_from_slice(&u32::MAX.to_le_bytes());
+        }
+
+        let digest = Hash256::new(tagged_hash("TapSighash", &preimage));
+
+        Ok(SigHashPreimage { preimage, digest })
+    }
+}
+
+impl TxInput {
+    /// The scriptCode this input signs when it spends `prevout`. It is the
+    /// locking script or the P2SH redeem script without `OP_CODESEPARATOR`
+    /// for legacy spends, `OP_DUP OP_HASH160 <key hash> OP_EQUALVERIFY
+    /// OP_CHECKSIG` for P2WPKH, the witness script for P2WSH and the leaf
+    /// script for a tapscript spend, each also when nested in P2SH. The
+    /// scripts revealed by the input must hash to the ones committed to
+    pub fn script_code(&self, prevout: &TxOutput) -> io::Result<Vec<u8>> {
+        let mut script = prevout.locking_script().to_vec();
+
+        if prevout.script_type() == ScriptType::P2SH {
+            let redeem_script = StandardScripts::read_pushes(self.signature_script())?
+                .pop()
+                .ok_or_else(|| Self::script_code_error("The P2SH input has no redeem script"))?;
+            if Script::new(redeem_script.as_slice()).p2sh_hash().as_ref() != &script[2..22] {
+                return Err(Self::script_code_error(
+                    "The redeem script does not hash to the P2SH output",
+                ));
+            }
+            script = redeem_script;
+        }
+
+        match ScriptType::from_script(&script) {
+            ScriptType::P2WPKH => {
+                Ok([&[0x76, 0xa9, 0x14][..], &script[2..22], &[0x88, 0xac]].concat())
+            }
+            ScriptType::P2WSH => {
+                let witness_script = self.witness().last().ok_or_else(|| {
+                    Self::script_code_error("The P2WSH input has no witness script")
+                })?;
+                if Script::new(witness_script).p2wsh_hash().as_ref() != &script[2..34] {
+                    return Err(Self::script_code_error(
+                        "The witness script does not hash to the P2WSH output",
+                    ));
+                }
+
+                Ok(witness_script.to_vec())
+            }
+            ScriptType::P2TR => {
+                let mut elements = self.witness().iter().collect::<Vec<&[u8]>>();
+                if self.witness().annex().is_some() {
+                    elements.pop();
+                }
+                if elements.len() < 2 {
+                    return Err(Self::script_code_error(
+                        "A taproot key path spend signs no script",
+                    ));
+                }
+
+                // The control block is last and the leaf script before it
+                Ok(elements[elements.len() - 2].to_vec())
+            }
+            _ => {
+                let mut script_code = Vec::<u8>::with_capacity(script.len());
+                let mut position = 0usize;
+                while position < script.len() {
+                    let start = position;
+                    let (opcode, _) = Script::read_opcode(&script, &mut position)?;
+                    // OP_CODESEPARATOR
+                    if opcode != 0xab {
+                        script_code.extend_from_slice(&script[start..position]);
+                    }
+                }
+
+                Ok(script_code)
+            }
+        }
+    }
+
+    fn script_code_error(message: &str) -> io::Error {
+        io::Error::new(ErrorKind::InvalidInput, message.to_string())
+    }
+}
+
+// The outpoint of the input in the wire format
+fn outpoint_bytes(input: &TxInput) -> Vec<u8> {
+    let mut bytes = input
+        .previous_tx_id()
+        .to_hash()
+        .reversed()
+        .to_byte_array()
+        .to_vec();
+    bytes.extend_from_slice(&input.previous_output_index().to_le_bytes());
+
+    bytes
+}
+
+// The output in the wire format
+fn output_bytes(output: &TxOutput) -> Vec<u8> {
+    let mut bytes = output.amount().to_le_bytes().to_vec();
+    bytes.extend_from_slice(&VarInt::encode(output.locking_script().len() as u64));
+    bytes.extend_from_slice(output.locking_script());
+
+    bytes
+}
+
+#[cfg(test)]
+mod sighash_sanity_checks {
+    use crate::{
+        BtcTx, Hash256, Script, SigHashCache, SigHashType, TxInput, TxOutput, Txid, Witness,
+    };
+    use hex_literal::hex;
+
+    #[test]
+    fn sighash_type() {
+        (0u8..=255).for_each(|byte| {
+            assert_eq!(byte, SigHashType::from_byte(byte).to_byte());
+        });
+
+        assert!(SigHashType::SingleAnyoneCanPay.is_anyone_can_pay());
+        assert!(SigHashType::SingleAnyoneCanPay.is_single());
+        assert!(!SigHashType::All.is_anyone_can_pay());
+        assert_eq!(SigHashType::NonStandard(0x04), SigHashType::from_byte(0x04));
+    }
+
+    #[test]
+    fn sighash_preimages() {
+        // The native P2WPKH example of BIP143
+        let tx = BtcTx::from_hex_bytes(hex!("0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000")).unwrap();
+        let script_code = hex!("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac");
+
+        let segwit = tx
+            .segwit_v0_sighash_preimage(1, &script_code, 600_000_000, SigHashType::All)
+            .unwrap();
+        assert_eq!(
+            "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670",
+            segwit.digest.to_string()
+        );
+        assert_eq!(&hex!("0100000096b827c8"), &segwit.preimage[..8]);
+
+        let legacy = tx
+            .legacy_sighash_preimage(0, &script_code, SigHashType::NoneAnyoneCanPay)
+            .unwrap();
+        // One input with the script code, no outputs, the locktime and the sighash type
+        assert_eq!(4 + 1 + 36 + 26 + 4 + 1 + 4 + 4, legacy.preimage.len());
+        assert!(tx
+            .legacy_sighash_preimage(2, &script_code, SigHashType::All)
+            .is_err());
+
+        // The signature message of BIP341 has a fixed size for key path spends
+        let prevouts = vec![TxOutput::new(1, vec![0x51]), TxOutput::new(2, vec![0x51])];
+        let taproot = tx
+            .taproot_sighash_preimage(0, &prevouts, None, SigHashType::TAPROOT_DEFAULT)
+            .unwrap();
+        assert_eq!(1 + 174, taproot.preimage.len());
+        let script_path = tx
+            .taproot_sighash_preimage(
+                0,
+                &prevouts,
+                Some(Hash256::all_zeros()),
+                SigHashType::SingleAnyoneCanPay,
+            )
+            .unwrap();
+        // The outpoint, amount, script and sequence replace the input index
+        // and the output being signed and the leaf are added
+        assert_eq!(
+            1 + 174 - 32 * 4 - 4 + 36 + 8 + 2 + 4 + 37,
+            script_path.preimage.len()
+        );
+        assert!(tx
+            .taproot_sighash_preimage(0, &prevouts[..1], None, SigHashType::All)
+            .is_err());
+    }
+
+    #[test]
+    fn published_vectors() {
+        // The keyPathSpending vector of BIP341
+        let tx = BtcTx::from_hex_bytes(hex!("02000000097de20cbff686da83a54981d2b9bab3586f4ca7e48f57f5b55963115f3b334e9c010000000000000000d7b7cab57b1393ace2d064f4d4a2cb8af6def61273e127517d44759b6dafdd990000000000fffffffff8e1f583384333689228c5d28eac13366be082dc57441760d957275419a418420000000000fffffffff0689180aa63b30cb162a73c6d2a38b7eeda2a83ece74310fda0843ad604853b0100000000feffffffaa5202bdf6d8ccd2ee0f0202afbbb7461d9264a25e5bfd3c5a52ee1239e0ba6c0000000000feffffff956149bdc66faa968eb2be2d2faa29718acbfe3941215893a2a3446d32acd050000000000000000000e664b9773b88c09c32cb70a2a3e4da0ced63b7ba3b22f848531bbb1d5d5f4c94010000000000000000e9aa6b8e6c9de67619e6a3924ae25696bb7b694bb677a632a74ef7eadfd4eabf0000000000ffffffffa778eb6a263dc090464cd125c466b5a99667720b1c110468831d058aa1b82af10100000000ffffffff0200ca9a3b000000001976a91406afd46bcdfd22ef94ac122aa11f241244a37ecc88ac807840cb0000000020ac9a87f5594be208f8532db38cff670c450ed2fea8fcdefcc9a663f78bab962b0065cd1d")).unwrap();
+        let prevouts = [
+            (
+                420_000_000,
+                &hex!("512053a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343")[..],
+            ),
+            (
+                462_000_000,
+                &hex!("5120147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3"),
+            ),
+            (
+                294_000_000,
+                &hex!("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac"),
+            ),
+            (
+                504_000_000,
+                &hex!("5120e4d810fd50586274face62b8a807eb9719cef49c04177cc6b76a9a4251d5450e"),
+            ),
+            (
+                630_000_000,
+                &hex!("512091b64d5324723a985170e4dc5a0f84c041804f2cd12660fa5dec09fc21783605"),
+            ),
+            (
+                378_000_000,
+                &hex!("00147dd65592d0ab2fe0d0257d571abf032cd9db93dc"),
+            ),
+            (
+                672_000_000,
+                &hex!("512075169f4001aa68f15bbed28b218df1d0a62cbbcf1188c6665110c293c907b831"),
+            ),
+            (
+                546_000_000,
+                &hex!("5120712447206d7a5238acc7ff53fbe94a3b64539ad291c7cdbc490b7577e4b17df5"),
+            ),
+            (
+                588_000_000,
+                &hex!("512077e30a5522dd9f894c3f8b8bd4c4b2cf82ca7da8a3ea6a239655c39c050ab220"),
+            ),
+        ]
+        .into_iter()
+        .map(|(amount, script)| TxOutput::new(amount, script.to_vec()))
+        .collect::<Vec<TxOutput>>();
+
+        let cache = SigHashCache::new(&tx).with_prevouts(&prevouts);
+        [
+            (
+                0,
+                0x03,
+                "2514a6272f85cfa0f45eb907fcb0d121b808ed37c6ea160a5a9046ed5526d555",
+            ),
+            (
+                1,
+                0x83,
+                "325a644af47e8a5a2591cda0ab0723978537318f10e6a63d4eed783b96a71a4d",
+            ),
+            (
+                3,
+                0x01,
+                "bf013ea93474aa67815b1b6cc441d23b64fa310911d991e713cd34c7f5d46669",
+            ),
+            (
+                4,
+                0x00,
+                "4f900a0bae3f1446fd48490c2958b5a023228f01661cda3496a11da502a7f7ef",
+            ),
+            (
+                6,
+                0x02,
+                "15f25c298eb5cdc7eb1d638dd2d45c97c4c59dcaec6679cfc16ad84f30876b85",
+            ),
+            (
+                7,
+                0x82,
+                "cd292de50313804dabe4685e83f923d2969577191a3e1d2882220dca88cbeb10",
+            ),
+            (
+                8,
+                0x81,
+                "cccb739eca6c13a8a89e6e5cd317ffe55669bbda23f2fd37b0f18755e008edd2",
+            ),
+        ]
+        .into_iter()
+        .for_each(|(input_index, sighash_type, digest)| {
+            let sighash = cache
+                .taproot_sighash_preimage(input_index, None, SigHashType::from_byte(sighash_type))
+                .unwrap();
+            assert_eq!(digest, hex::encode(sighash.digest.as_ref()));
+        });
+
+        // A key path spend with an annex from the test framework of Bitcoin Core
+        let tx = BtcTx::from_hex_bytes(hex!("0200000001df8123752e8f37d132c4e9f1ff7e4f9b986ade9211267e9ebd5fd22a5e718dec6d01000000ce4023b903cb7b23000000000017a914a18b36ea7a094db2f4940fc09edf154e86de7bd787580200000000000017a914afd0d512a2c5c2b40e25669e9cc460303c325b8b87580200000000000017a914a18b36ea7a094db2f4940fc09edf154e86de7bd787f6020000")).unwrap();
+        let annex = hex!("507b979802e62d397acb29f56743a791894b99372872fc5af06a4f6e8d242d0615cda53062bb20e6ec79756fe39183f0c128adfe85559a8fa042b042c018aa8010143799e44f0893c40e1e");
+        let inputs = tx
+            .inputs()
+            .iter()
+            .map(|input| {
+                input
+                    .clone()
+                    .with_witness(Witness::from_slice(&[&[0x30; 65], &annex]))
+            })
+            .collect();
+        let tx = BtcTx::new(*tx.version(), inputs, tx.outputs().to_vec(), tx.locktime());
+        let prevouts = [TxOutput::new(
+            2_509_290,
+            hex!("5120ab5e9800806bf18cb246edcf5fe63441208fe955a4b5a35bbff65f5db622a010").to_vec(),
+        )];
+        let sighash = tx
+            .taproot_sighash_preimage(0, &prevouts, None, SigHashType::SingleAnyoneCanPay)
+            .unwrap();
+        assert_eq!(
+            "3b003000add359a364a156e73e02846782a59d0d95ca8c4638aaad99f2ef915c",
+            hex::encode(sighash.digest.as_ref())
+        );
+
+        // The P2PK spend of block 170, the signature in it verifies against this digest
+        let tx = BtcTx::from_hex_bytes(hex!("0100000001c997a5e56e104102fa209c6a852dd90660a20b2d9c352423edce25857fcd3704000000004847304402204e45e16932b8af514961a1d3a1a25fdf3f4f7732e9d624c6c61548ab5fb8cd410220181522ec8eca07de4860a4acdd12909d831cc56cbbac4622082221a8768d1d0901ffffffff0200ca9a3b00000000434104ae1a62fe09c5f51b13905f07f06b99a2f7159b2225f374cd378d71302fa28414e7aab37397f554a7df5f142c21c1b7303b8a0626f1baded5c72a704f7e6cd84cac00286bee0000000043410411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3ac00000000")).unwrap();
+        let script_code = hex!("410411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3ac");
+        let sighash = tx
+            .legacy_sighash_preimage(0, &script_code, SigHashType::All)
+            .unwrap();
+        assert_eq!(
+            "7a05c6145f10101e9d6325494245adf1297d80f8f38d4d576d57cdba220bcb19",
+            hex::encode(sighash.digest.as_ref())
+        );
+    }
+
+    #[test]
+    fn script_codes() {
+        let key_hash = hex!("1d0f172a0ecb48aee1be1f2687d2963ae33f71a1");
+        let input = |signature_script: Vec<u8>, witness: &[&[u8]]| {
+            TxInput::new(Txid::new(Hash256::new([1u8; 32])), 0, signature_script, 0)
+                .with_witness(Witness::from_slice(witness))
+        };
+
+        // P2WPKH signs the implied P2PKH script
+        let p2wpkh = TxOutput::new(1_000, [&[0x00, 0x14][..], &key_hash].concat());
+        assert_eq!(
+            hex!("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac").to_vec(),
+            input(Vec::new(), &[&[0x30; 71], &[0x02; 33]])
+                .script_code(&p2wpkh)
+                .unwrap()
+        );
+
+        // P2SH-P2WSH signs the witness script which must match the program
+        let witness_script = Script::new(hex!("51ab52"));
+        let p2wsh = witness_script.to_p2wsh();
+        let nested = TxOutput::new(1_000, p2wsh.to_p2sh().as_bytes().to_vec());
+        let signature_script = [&[0x22][..], p2wsh.as_bytes()].concat();
+        assert_eq!(
+            witness_script.as_bytes(),
+            input(signature_script.clone(), &[witness_script.as_bytes()])
+                .script_code(&nested)
+                .unwrap()
+        );
+        assert!(input(signature_script, &[&[0x51]])
+            .script_code(&nested)
+            .is_err());
+
+        // Legacy spends drop OP_CODESEPARATOR
+        let bare = TxOutput::new(1_000, witness_script.as_bytes().to_vec());
+        assert_eq!(
+            vec![0x51, 0x52],
+            input(Vec::new(), &[]).script_code(&bare).unwrap()
+        );
+
+        // Tapscript spends sign the leaf script before the control block and annex
+        let p2tr = TxOutput::new(1_000, [&[0x51, 0x20][..], &[0x11; 32]].concat());
+        assert_eq!(
+            vec![0x51],
+            input(Vec::new(), &[&[0x01], &[0x51], &[0xc0; 33], &[0x50, 0x00]])
+                .script_code(&p2tr)
+                .unwrap()
+        );
+        assert!(input(Vec::new(), &[&[0x30; 64]])
+            .script_code(&p2tr)
+            .is_err());
+    }
+}
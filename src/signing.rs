@@ -0,0 +1,596 @@
+use crate::{
+    BtcTx, InputSatisfaction, ScriptType, SigHashCache, SigHashPreimage, SigHashType,
+    StandardScripts, TxOutput,
+};
+#[cfg(feature = "rust-bitcoin-compat")]
+use bitcoin::secp256k1::{Message, Secp256k1, SecretKey, SignOnly};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, ErrorKind},
+};
+
+/// The keys that can sign an input and how many of them must sign
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InputSigners {
+    // The scripts needed to assemble the scriptSig and witness once signed
+    satisfaction: InputSatisfaction,
+    // The keys in the order the script checks their signatures
+    public_keys: Vec<Vec<u8>>,
+    threshold: usize,
+}
+
+impl InputSigners {
+    /// Find the signers of an input spending `locking_script` through the redeem
+    /// and witness scripts of the input if it has them. Multisig, P2PK and
+    /// taproot key path scripts name their keys, scripts paying to the hash of
+    /// a key need that `public_key`
+    pub fn new(
+        locking_script: Vec<u8>,
+        redeem_script: Option<Vec<u8>>,
+        witness_script: Option<Vec<u8>>,
+        public_key: Option<Vec<u8>>,
+    ) -> io::Result<Self> {
+        // The script whose template decides who signs
+        let script = match (&witness_script, &redeem_script) {
+            (Some(witness_script), _) => witness_script.clone(),
+            (None, Some(redeem_script)) => redeem_script.clone(),
+            (None, None) => locking_script.clone(),
+        };
+
+        let (public_keys, threshold) = match ScriptType::from_script(&script) {
+            ScriptType::P2PK => (vec![script[1..script.len() - 1].to_vec()], 1),
+            ScriptType::P2PKH | ScriptType::P2WPKH => {
+                let public_key = public_key.clone().ok_or_else(|| {
+                    io::Error::new(
+                        ErrorKind::InvalidInput,
+                        "The public key of a key hash script is needed",
+                    )
+                })?;
+
+                (vec![public_key], 1)
+            }
+            // The output key is the only key of a key path spend
+            ScriptType::P2TR => (vec![script[2..34].to_vec()], 1),
+            ScriptType::P2MS => {
+                // `OP_m <keys> OP_n OP_CHECKMULTISIG`
+                let threshold = (script[0] - 0x50) as usize;
+                let public_keys = StandardScripts::read_pushes(&script[1..script.len() - 2])?;
+
+                (public_keys, threshold)
+            }
+            script_type => {
+                return Err(io::Error::new(
+                    ErrorKind::Unsupported,
+                    format!("Signing {} scripts is not supported", script_type),
+                ))
+            }
+        };
+
+        Ok(Self {
+            satisfaction: InputSatisfaction {
+                locking_script,
+                public_keys: public_key.into_iter().collect(),
+                redeem_script,
+                witness_script,
+                ..Default::default()
+            },
+            public_keys,
+            threshold,
+        })
+    }
+
+    /// The keys that can sign the input
+    pub fn public_keys(&self) -> &[Vec<u8>] {
+        &self.public_keys
+    }
+
+    /// The number of signatures the input needs
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+}
+
+/// The outcome of `SigningSession::sign_all()` for every input it signed, by
+/// input index, with the number of signatures added
+pub type SigningResults = Vec<(usize, io::Result<usize>)>;
+
+/// Gathers the signatures of a transaction from several signers, for example
+/// the devices of a multisig wallet, and tracks what is still missing. Signers
+/// are identified by their public keys
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SigningSession {
+    tx: BtcTx,
+    signers: Vec<InputSigners>,
+    // The signatures of every input by public key
+    signatures: Vec<BTreeMap<Vec<u8>, Vec<u8>>>,
+}
+
+impl SigningSession {
+    /// Start a session for `tx` with the signers of every input
+    pub fn new(tx: BtcTx, signers: Vec<InputSigners>) -> io::Result<Self> {
+        if signers.len() != tx.inputs().len() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "The transaction has {} inputs but signers of {} inputs were given",
+                    tx.inputs().len(),
+                    signers.len()
+                ),
+            ));
+        }
+
+        Ok(Self {
+            signatures: vec![BTreeMap::new(); signers.len()],
+            tx,
+            signers,
+        })
+    }
+
+    /// Add the signature by `public_key` of the input at `input_index`,
+    /// replacing an earlier signature by the same key
+    pub fn add_signature(
+        &mut self,
+        input_index: usize,
+        public_key: &[u8],
+        signature: Vec<u8>,
+    ) -> io::Result<()> {
+        let signers = self.signers.get(input_index).ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidInput,
+                "The transaction has no input at the index being signed",
+            )
+        })?;
+        if !signers.public_keys.iter().any(|key| key == public_key) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "{} cannot sign input {}",
+                    hex::encode(public_key),
+                    input_index
+                ),
+            ));
+        }
+
+        self.signatures[input_index].insert(public_key.to_vec(), signature);
+
+        Ok(())
+    }
+
+    /// Whether the input at `input_index` has enough signatures
+    pub fn is_input_complete(&self, input_index: usize) -> bool {
+        self.signers
+            .get(input_index)
+            .is_some_and(|signers| self.signatures[input_index].len() >= signers.threshold)
+    }
+
+    /// Whether every input has enough signatures
+    pub fn is_complete(&self) -> bool {
+        (0..self.signers.len()).all(|input_index| self.is_input_complete(input_index))
+    }
+
+    /// The share of the needed signatures that were gathered, from 0 to 1.
+    /// Signatures beyond the threshold of an input are not counted
+    pub fn completion(&self) -> f64 {
+        let needed = self
+            .signers
+            .iter()
+            .map(|signers| signers.threshold)
+            .sum::<usize>();
+        if needed == 0 {
+            return 1.0;
+        }
+
+        let gathered = self
+            .signers
+            .iter()
+            .zip(&self.signatures)
+            .map(|(signers, signatures)| signatures.len().min(signers.threshold))
+            .sum::<usize>();
+
+        gathered as f64 / needed as f64
+    }
+
+    /// The keys which have not signed the input at `input_index` while it
+    /// still needs signatures
+    pub fn missing_keys(&self, input_index: usize) -> Vec<&[u8]> {
+        if self.is_input_complete(input_index) {
+            return Vec::new();
+        }
+
+        self.signers
+            .get(input_index)
+            .map(|signers| {
+                signers
+                    .public_keys
+                    .iter()
+                    .filter(|key| !self.signatures[input_index].contains_key(*key))
+                    .map(|key| key.as_slice())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The keys that could still sign one of the inputs needing signatures
+    pub fn missing_signers(&self) -> BTreeSet<&[u8]> {
+        (0..self.signers.len())
+            .flat_map(|input_index| self.missing_keys(input_index))
+            .collect()
+    }
+
+    /// Sign the inputs for which `inputs_filter` is true and which still need
+    /// signatures, with `prevouts` the outputs spent by every input in order.
+    /// `sign` is called with every missing key and the message of the input
+    /// and returns the signature with its sighash byte, or `None` if it does
+    /// not hold the key. The hashes shared by the messages are computed once.
+    /// A failing input does not stop the others from being signed
+    pub fn sign_all(
+        &mut self,
+        prevouts: &[TxOutput],
+        sighash_type: SigHashType,
+        inputs_filter: impl Fn(usize) -> bool,
+        mut sign: impl FnMut(&[u8], &SigHashPreimage) -> io::Result<Option<Vec<u8>>>,
+    ) -> io::Result<SigningResults> {
+        if prevouts.len() != self.tx.inputs().len() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "The transaction has {} inputs but {} spent outputs were given",
+                    self.tx.inputs().len(),
+                    prevouts.len()
+                ),
+            ));
+        }
+
+        let tx = self.tx.clone();
+        let cache = SigHashCache::new(&tx).with_prevouts(prevouts);
+        let mut results = SigningResults::new();
+        for input_index in (0..self.signers.len()).filter(|index| inputs_filter(*index)) {
+            let missing_keys = self
+                .missing_keys(input_index)
+                .into_iter()
+                .map(|key| key.to_vec())
+                .collect::<Vec<Vec<u8>>>();
+            if missing_keys.is_empty() {
+                results.push((input_index, Ok(0)));
+                continue;
+            }
+
+            let mut sign_input = || -> io::Result<usize> {
+                let preimage = self.sighash_preimage(&cache, input_index, sighash_type)?;
+
+                let mut signed = 0usize;
+                for public_key in &missing_keys {
+                    if let Some(signature) = sign(public_key, &preimage)? {
+                        self.add_signature(input_index, public_key, signature)?;
+                        signed += 1;
+                    }
+                }
+
+                Ok(signed)
+            };
+            results.push((input_index, sign_input()));
+        }
+
+        Ok(results)
+    }
+
+    /// Assemble the scriptSig and witness of every input from the signatures.
+    /// Multisig signatures are put in the order of the keys in the script
+    pub fn finalize(&self) -> io::Result<BtcTx> {
+        if !self.is_complete() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} signers are missing", self.missing_signers().len()),
+            ));
+        }
+
+        let mut tx = self.tx.clone();
+        for (input_index, (signers, signatures)) in
+            self.signers.iter().zip(&self.signatures).enumerate()
+        {
+            let mut satisfaction = signers.satisfaction.clone();
+            satisfaction.signatures = signers
+                .public_keys
+                .iter()
+                .filter_map(|key| signatures.get(key).cloned())
+                .take(signers.threshold)
+                .collect();
+
+            tx.satisfy_input(input_index, &satisfaction)?;
+        }
+
+        Ok(tx)
+    }
+
+    // The message signed by the input, decided by the script its signers are
+    // taken from like in `InputSigners::new()`
+    fn sighash_preimage(
+        &self,
+        cache: &SigHashCache,
+        input_index: usize,
+        sighash_type: SigHashType,
+    ) -> io::Result<SigHashPreimage> {
+        let satisfaction = &self.signers[input_index].satisfaction;
+        let amount = cache.prevouts()[input_index].amount();
+        let script = satisfaction
+            .redeem_script
+            .as_ref()
+            .unwrap_or(&satisfaction.locking_script);
+
+        match (
+            &satisfaction.witness_script,
+            ScriptType::from_script(script),
+        ) {
+            (Some(witness_script), _) => {
+                cache.segwit_v0_sighash_preimage(input_index, witness_script, amount, sighash_type)
+            }
+            (None, ScriptType::P2WPKH) => {
+                let script_code = [&[0x76, 0xa9, 0x14][..], &script[2..22], &[0x88, 0xac]].concat();
+                cache.segwit_v0_sighash_preimage(input_index, &script_code, amount, sighash_type)
+            }
+            (None, ScriptType::P2TR) => {
+                cache.taproot_sighash_preimage(input_index, None, sighash_type)
+            }
+            (None, _) => cache.legacy_sighash_preimage(input_index, script, sighash_type),
+        }
+    }
+}
+
+/// Signs the legacy and segwit v0 inputs of a `SigningSession` with an ECDSA
+/// secret key using RFC6979 nonces
+#[cfg(feature = "rust-bitcoin-compat")]
+#[derive(Debug, Clone)]
+pub struct EcdsaSigner {
+    secp: Secp256k1<SignOnly>,
+    secret_key: SecretKey,
+    public_key: [u8; 33],
+    low_r: bool,
+}
+
+#[cfg(feature = "rust-bitcoin-compat")]
+impl EcdsaSigner {
+    /// A signer of the 32 byte `secret_key` and its compressed public key
+    pub fn new(secret_key: [u8; 32]) -> io::Result<Self> {
+        let secp = Secp256k1::signing_only();
+        let secret_key = SecretKey::from_slice(&secret_key).map_err(|error| {
+            io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("The secret key is invalid: {}", error),
+            )
+        })?;
+        let public_key = secret_key.public_key(&secp).serialize();
+
+        Ok(Self {
+            secp,
+            secret_key,
+            public_key,
+            low_r: false,
+        })
+    }
+
+    /// Grind the nonce until R is below 2^255 like Bitcoin Core so every
+    /// signature with its sighash byte is at most 71 bytes, the size
+    /// `low_r_input_weight()` predicts
+    pub fn with_low_r(mut self) -> Self {
+        self.low_r = true;
+
+        self
+    }
+
+    /// The compressed public key
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    /// The DER signature of the `preimage` followed by the `sighash_type` byte.
+    /// Returns `None` if `public_key` is not the key of the signer, so it can
+    /// be called from the closure passed to `SigningSession::sign_all()`
+    pub fn sign(
+        &self,
+        public_key: &[u8],
+        preimage: &SigHashPreimage,
+        sighash_type: SigHashType,
+    ) -> io::Result<Option<Vec<u8>>> {
+        if public_key != self.public_key {
+            return Ok(None);
+        }
+
+        let message = Message::from_digest(preimage.digest.to_byte_array());
+        let signature = if self.low_r {
+            self.secp.sign_ecdsa_low_r(&message, &self.secret_key)
+        } else {
+            self.secp.sign_ecdsa(&message, &self.secret_key)
+        };
+
+        let mut signature = signature.serialize_der().to_vec();
+        signature.push(sighash_type.to_byte());
+
+        Ok(Some(signature))
+    }
+}
+
+#[cfg(test)]
+mod signing_sanity_checks {
+    use crate::{
+        BtcTx, Hash256, InputSigners, Script, SigHashType, SigningSession, TxInput, TxOutput,
+        TxVersion, Txid,
+    };
+    use std::io;
+
+    #[test]
+    fn multisig_session() {
+        let keys = [
+            [0x02; 33].to_vec(),
+            [0x03; 33].to_vec(),
+            [&[0x02][..], &[0x11; 32]].concat(),
+        ];
+        // A 2-of-3 multisig wrapped in P2WSH
+        let multisig = [
+            &[0x52, 0x21][..],
+            &keys[0],
+            &[0x21],
+            &keys[1],
+            &[0x21],
+            &keys[2],
+            &[0x53, 0xae],
+        ]
+        .concat();
+        let signers = InputSigners::new(
+            Script::new(multisig.clone()).to_p2wsh().as_bytes().to_vec(),
+            None,
+            Some(multisig.clone()),
+            None,
+        )
+        .unwrap();
+        assert_eq!(2, signers.threshold());
+
+        let tx = BtcTx::new(
+            TxVersion::Two,
+            vec![TxInput::new(
+                Txid::new(Hash256::new([1u8; 32])),
+                0,
+                Vec::new(),
+                0,
+            )],
+            vec![TxOutput::new(1_000, vec![0x51])],
+            0,
+        );
+        let mut session = SigningSession::new(tx, vec![signers]).unwrap();
+
+        session.add_signature(0, &keys[2], vec![0x32; 71]).unwrap();
+        assert_eq!(0.5, session.completion());
+        assert_eq!(2, session.missing_signers().len());
+        assert!(session.finalize().is_err());
+        assert!(session
+            .add_signature(0, &[0x04; 33], vec![0x30; 71])
+            .is_err());
+
+        session.add_signature(0, &keys[0], vec![0x30; 71]).unwrap();
+        assert!(session.is_complete());
+        assert!(session.missing_keys(0).is_empty());
+
+        // The signatures follow the order of the keys and the witness script is last
+        let signed = session.finalize().unwrap();
+        let witness = signed.inputs()[0].witness();
+        assert_eq!(Some([0x30; 71].as_slice()), witness.get(1));
+        assert_eq!(Some([0x32; 71].as_slice()), witness.get(2));
+        assert_eq!(Some(multisig.as_slice()), witness.last());
+    }
+
+    #[test]
+    fn sign_all_inputs() {
+        let key = |byte: u8| [&[0x02][..], &[byte; 32]].concat();
+        let p2wpkh = |byte: u8| [&[0x00, 0x14][..], &[byte; 20]].concat();
+        let signers = (1..=3)
+            .map(|byte| InputSigners::new(p2wpkh(byte), None, None, Some(key(byte))).unwrap())
+            .collect();
+        let prevouts = (1..=3)
+            .map(|byte| TxOutput::new(10_000, p2wpkh(byte)))
+            .collect::<Vec<TxOutput>>();
+        let tx = BtcTx::new(
+            TxVersion::Two,
+            (0..3)
+                .map(|vout| TxInput::new(Txid::new(Hash256::new([1u8; 32])), vout, Vec::new(), 0))
+                .collect(),
+            vec![TxOutput::new(29_000, vec![0x51])],
+            0,
+        );
+        let expected = tx
+            .segwit_v0_sighash_preimage(
+                0,
+                &[&[0x76, 0xa9, 0x14][..], &[1u8; 20], &[0x88, 0xac]].concat(),
+                10_000,
+                SigHashType::All,
+            )
+            .unwrap();
+        let mut session = SigningSession::new(tx, signers).unwrap();
+
+        // The signer holds the first key, fails on the second and the third
+        // input is left out
+        let results = session
+            .sign_all(
+                &prevouts,
+                SigHashType::All,
+                |input_index| input_index < 2,
+                |public_key, preimage| match public_key[1] {
+                    1 => {
+                        assert_eq!(expected, *preimage);
+                        Ok(Some(vec![0x30; 72]))
+                    }
+                    _ => Err(io::Error::other("The device is locked")),
+                },
+            )
+            .unwrap();
+        assert_eq!(2, results.len());
+        assert_eq!(1, *results[0].1.as_ref().unwrap());
+        assert!(results[1].1.is_err());
+        assert!(session.is_input_complete(0));
+        assert!(!session.is_input_complete(1));
+
+        assert!(session
+            .sign_all(&prevouts[..1], SigHashType::All, |_| true, |_, _| Ok(None))
+            .is_err());
+    }
+
+    #[cfg(feature = "rust-bitcoin-compat")]
+    #[test]
+    fn low_r_signatures() {
+        use crate::EcdsaSigner;
+        use bitcoin::secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1};
+
+        let signer = EcdsaSigner::new([0x11; 32]).unwrap();
+        let low_r = signer.clone().with_low_r();
+        let public_key = signer.public_key().to_vec();
+        let p2pk = Script::new([&[0x21][..], &public_key, &[0xac]].concat());
+        let tx = |vout| {
+            BtcTx::new(
+                TxVersion::Two,
+                vec![TxInput::new(
+                    Txid::new(Hash256::new([1u8; 32])),
+                    vout,
+                    Vec::new(),
+                    0,
+                )],
+                vec![TxOutput::new(1_000, vec![0x51])],
+                0,
+            )
+        };
+
+        // Without grinding about half of the signatures have a 33 byte R
+        let secp = Secp256k1::verification_only();
+        let key = PublicKey::from_slice(&public_key).unwrap();
+        let lengths = (0..16)
+            .map(|vout| {
+                let preimage = tx(vout)
+                    .legacy_sighash_preimage(0, p2pk.as_bytes(), SigHashType::All)
+                    .unwrap();
+                let message = Message::from_digest(preimage.digest.to_byte_array());
+
+                [&signer, &low_r].map(|signer| {
+                    let signature = signer
+                        .sign(&public_key, &preimage, SigHashType::All)
+                        .unwrap()
+                        .unwrap();
+                    assert_eq!(Some(&0x01), signature.last());
+                    let der = Signature::from_der(&signature[..signature.len() - 1]).unwrap();
+                    assert!(secp.verify_ecdsa(&message, &der, &key).is_ok());
+
+                    signature.len()
+                })
+            })
+            .collect::<Vec<[usize; 2]>>();
+        assert!(lengths.iter().any(|[length, _]| *length == 72));
+        assert!(lengths.iter().all(|[_, length]| *length <= 71));
+
+        let preimage = tx(0)
+            .legacy_sighash_preimage(0, p2pk.as_bytes(), SigHashType::All)
+            .unwrap();
+        assert_eq!(
+            None,
+            signer
+                .sign(&[0x02; 33], &preimage, SigHashType::All)
+                .unwrap()
+        );
+        assert!(EcdsaSigner::new([0u8; 32]).is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use crate::{BtcTx, Network, ScriptType};
+use std::fmt;
+
+/// The soft-fork which gave a script form the spending rules it has today
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SoftFork {
+    /// The rules of the first release like P2PK, P2PKH and bare multisig
+    Genesis,
+    /// BIP16 pay to script hash
+    Bip16,
+    /// BIP141 segregated witness with the version 0 witness programs. Other
+    /// witness programs have been valid but unencumbered since then
+    Segwit,
+    /// BIP341 taproot, the version 1 witness program of 32 bytes
+    Taproot,
+}
+
+impl SoftFork {
+    /// The first block enforcing the soft-fork, `None` if the height is not
+    /// known for the network. Signet and regtest enforce every soft-fork
+    /// from the start
+    pub const fn activation_height(&self, network: Network) -> Option<u32> {
+        match (self, network) {
+            (Self::Genesis, _) => Some(0),
+            (_, Network::Signet | Network::Regtest) => Some(0),
+            (Self::Bip16, Network::Mainnet) => Some(173_805),
+            (Self::Segwit, Network::Mainnet) => Some(481_824),
+            (Self::Taproot, Network::Mainnet) => Some(709_632),
+            (Self::Segwit, Network::Testnet) => Some(834_624),
+            (_, Network::Testnet) => None,
+        }
+    }
+
+    /// Whether the soft-fork is enforced in the block at `height`
+    pub fn is_active(&self, height: u32, network: Network) -> Option<bool> {
+        self.activation_height(network)
+            .map(|activation| height >= activation)
+    }
+}
+
+impl fmt::Display for SoftFork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Genesis => write!(f, "genesis"),
+            Self::Bip16 => write!(f, "BIP16"),
+            Self::Segwit => write!(f, "segwit"),
+            Self::Taproot => write!(f, "taproot"),
+        }
+    }
+}
+
+impl ScriptType {
+    /// The soft-fork which defined how outputs of this type are spent
+    pub const fn soft_fork(&self) -> SoftFork {
+        match self {
+            Self::P2SH => SoftFork::Bip16,
+            Self::P2WPKH | Self::P2WSH | Self::P2A | Self::WitnessUnknown(_) => SoftFork::Segwit,
+            Self::P2TR => SoftFork::Taproot,
+            Self::P2PK
+            | Self::P2PKH
+            | Self::P2MS
+            | Self::OpTrue
+            | Self::OpReturn
+            | Self::NonStandard => SoftFork::Genesis,
+        }
+    }
+}
+
+/// A part of a transaction which uses rules that were not enforced yet at
+/// the height of its block. Such outputs could be spent by anyone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Anachronism {
+    /// An output of a type whose soft-fork activated later
+    Output {
+        output_index: usize,
+        script_type: ScriptType,
+        soft_fork: SoftFork,
+    },
+    /// An input with a witness before segwit activated
+    Witness { input_index: usize },
+}
+
+impl BtcTx {
+    /// The outputs and witnesses of the transaction which are anachronistic
+    /// in a block at `height`. Soft-forks without a known activation height on
+    /// the network are not checked
+    pub fn anachronisms(&self, height: u32, network: Network) -> Vec<Anachronism> {
+        let mut anachronisms = self
+            .outputs()
+            .iter()
+            .enumerate()
+            .filter_map(|(output_index, output)| {
+                let script_type = output.script_type();
+                let soft_fork = script_type.soft_fork();
+
+                (soft_fork.is_active(height, network) == Some(false)).then_some(
+                    Anachronism::Output {
+                        output_index,
+                        script_type,
+                        soft_fork,
+                    },
+                )
+            })
+            .collect::<Vec<Anachronism>>();
+
+        if SoftFork::Segwit.is_active(height, network) == Some(false) {
+            anachronisms.extend(
+                self.inputs()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, input)| !input.witness().is_empty())
+                    .map(|(input_index, _)| Anachronism::Witness { input_index }),
+            );
+        }
+
+        anachronisms
+    }
+}
+
+#[cfg(test)]
+mod softfork_sanity_checks {
+    use crate::{
+        Anachronism, BtcTx, Hash256, Network, ScriptType, SoftFork, TxInput, TxOutput, TxVersion,
+        Txid, Witness,
+    };
+
+    #[test]
+    fn anachronistic_outputs() {
+        let tx = BtcTx::new(
+            TxVersion::Two,
+            vec![
+                TxInput::new(Txid::new(Hash256::new([1u8; 32])), 0, Vec::new(), 0)
+                    .with_witness(Witness::from_slice(&[&[0x30; 64]])),
+            ],
+            vec![
+                TxOutput::new(1_000, [&[0xa9, 0x14][..], &[0x11; 20], &[0x87]].concat()),
+                TxOutput::new(1_000, [&[0x51, 0x20][..], &[0x22; 32]].concat()),
+            ],
+            0,
+        );
+
+        assert_eq!(
+            vec![
+                Anachronism::Output {
+                    output_index: 1,
+                    script_type: ScriptType::P2TR,
+                    soft_fork: SoftFork::Taproot,
+                },
+                Anachronism::Witness { input_index: 0 },
+            ],
+            tx.anachronisms(400_000, Network::Mainnet)
+        );
+        assert_eq!(3, tx.anachronisms(100_000, Network::Mainnet).len());
+        assert!(tx.anachronisms(709_632, Network::Mainnet).is_empty());
+        assert!(tx.anachronisms(1, Network::Regtest).is_empty());
+
+        // Taproot has no activation height known for testnet
+        assert_eq!(
+            Some(true),
+            SoftFork::Segwit.is_active(900_000, Network::Testnet)
+        );
+        assert_eq!(None, SoftFork::Taproot.is_active(900_000, Network::Testnet));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::{BtcTx, ScriptType, Txid};
+use std::{collections::BTreeMap, io};
+
+/// A raw transaction with the script types of its outputs as kept by a `TxStore`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredTx {
+    // The transaction ID computed once the raw transaction is parsed
+    txid: Txid,
+    raw_tx: Vec<u8>,
+    // The type of the locking script of every output
+    output_types: Vec<ScriptType>,
+}
+
+impl StoredTx {
+    /// Parse the raw transaction and classify the locking scripts of its outputs
+    pub fn new(raw_tx: Vec<u8>) -> io::Result<Self> {
+        let tx = BtcTx::from_hex_bytes(&raw_tx)?;
+        let output_types = tx
+            .outputs()
+            .iter()
+            .map(|output| output.script_type())
+            .collect();
+
+        Ok(Self {
+            txid: tx.txid(),
+            raw_tx,
+            output_types,
+        })
+    }
+
+    /// The transaction ID the transaction is stored under
+    pub fn txid(&self) -> Txid {
+        self.txid
+    }
+
+    /// The raw transaction
+    pub fn raw_tx(&self) -> &[u8] {
+        &self.raw_tx
+    }
+
+    /// Parse the raw transaction
+    pub fn tx(&self) -> io::Result<BtcTx> {
+        BtcTx::from_hex_bytes(&self.raw_tx)
+    }
+
+    /// The type of the locking script of every output
+    pub fn output_types(&self) -> &[ScriptType] {
+        &self.output_types
+    }
+}
+
+/// Persistence of transactions keyed by their transaction ID. Indexers write
+/// the transactions of a block at once with `TxStore::put_batch()` which
+/// backends should apply atomically
+pub trait TxStore {
+    /// Store every transaction, replacing any stored under the same transaction ID
+    fn put_batch(&mut self, batch: Vec<StoredTx>) -> io::Result<()>;
+
+    /// The transaction stored under `txid`
+    fn get(&self, txid: &Txid) -> io::Result<Option<StoredTx>>;
+
+    /// Remove the transaction stored under `txid` and return it
+    fn remove(&mut self, txid: &Txid) -> io::Result<Option<StoredTx>>;
+
+    /// Store a single transaction
+    fn put(&mut self, tx: StoredTx) -> io::Result<()> {
+        self.put_batch(vec![tx])
+    }
+
+    /// Whether a transaction is stored under `txid`
+    fn contains(&self, txid: &Txid) -> io::Result<bool> {
+        Ok(self.get(txid)?.is_some())
+    }
+}
+
+/// A `TxStore` keeping the transactions in memory, for tests and
+/// short-lived indexes
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MemoryTxStore(BTreeMap<Txid, StoredTx>);
+
+impl MemoryTxStore {
+    /// An empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of stored transactions
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether no transaction is stored
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl TxStore for MemoryTxStore {
+    fn put_batch(&mut self, batch: Vec<StoredTx>) -> io::Result<()> {
+        self.0
+            .extend(batch.into_iter().map(|stored| (stored.txid(), stored)));
+
+        Ok(())
+    }
+
+    fn get(&self, txid: &Txid) -> io::Result<Option<StoredTx>> {
+        Ok(self.0.get(txid).cloned())
+    }
+
+    fn remove(&mut self, txid: &Txid) -> io::Result<Option<StoredTx>> {
+        Ok(self.0.remove(txid))
+    }
+}
+
+#[cfg(test)]
+mod storage_sanity_checks {
+    use crate::{MemoryTxStore, ScriptType, StoredTx, TxStore, Txid};
+
+    #[test]
+    fn memory_store() {
+        let raw_tx =
+            hex::decode(include_str!("../fixtures/transactions/p2pkh_two_inputs.hex").trim())
+                .unwrap();
+        let stored = StoredTx::new(raw_tx.clone()).unwrap();
+        assert_eq!(&[ScriptType::P2PKH], stored.output_types());
+
+        let mut store = MemoryTxStore::new();
+        let txid = stored.txid();
+        store
+            .put_batch(vec![stored.clone(), stored.clone()])
+            .unwrap();
+        assert_eq!(1, store.len());
+        assert_eq!(Some(stored), store.get(&txid).unwrap());
+        assert!(!store.contains(&Txid::hash(&[0u8])).unwrap());
+
+        assert!(store.remove(&txid).unwrap().is_some());
+        assert!(store.is_empty());
+        assert!(StoredTx::new(raw_tx[..10].to_vec()).is_err());
+    }
+}
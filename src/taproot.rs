@@ -0,0 +1,273 @@
+use crate::{tagged_hash, Hash256, Script, Witness, TAPSCRIPT_LEAF_VERSION};
+use bitcoin::{
+    hashes::Hash,
+    key::{Secp256k1, TapTweak, XOnlyPublicKey},
+    taproot::TapNodeHash,
+};
+use std::io::{self, ErrorKind};
+
+/// The deepest a leaf can be in a taproot script tree, which limits the
+/// merkle path of a control block to 128 hashes
+pub const TAPROOT_CONTROL_MAX_NODE_COUNT: usize = 128;
+
+/// A taproot script tree of BIP341. The hashes of the two children of a
+/// branch are sorted before hashing so their order does not matter
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TapTree {
+    /// A script committed to with its leaf version
+    Leaf {
+        /// The leaf version, `TAPSCRIPT_LEAF_VERSION` for tapscript
+        leaf_version: u8,
+        /// The script spent through this leaf
+        script: Script,
+    },
+    /// A node committing to two subtrees
+    Branch(Box<TapTree>, Box<TapTree>),
+}
+
+impl TapTree {
+    /// A tapscript leaf
+    pub fn leaf(script: Script) -> Self {
+        Self::Leaf {
+            leaf_version: TAPSCRIPT_LEAF_VERSION,
+            script,
+        }
+    }
+
+    /// A node with the subtrees `left` and `right`
+    pub fn branch(left: TapTree, right: TapTree) -> Self {
+        Self::Branch(Box::new(left), Box::new(right))
+    }
+
+    /// The `TapLeaf` hash of a leaf or the `TapBranch` hash of a node. The
+    /// hash of the whole tree is the merkle root the internal key is tweaked with
+    pub fn node_hash(&self) -> Hash256 {
+        match self {
+            Self::Leaf {
+                leaf_version,
+                script,
+            } => script.tapleaf_hash(*leaf_version),
+            Self::Branch(left, right) => {
+                let (left, right) = (left.node_hash(), right.node_hash());
+                let (first, second) = if left <= right {
+                    (left, right)
+                } else {
+                    (right, left)
+                };
+
+                Hash256::new(tagged_hash(
+                    "TapBranch",
+                    &[first.as_ref(), second.as_ref()].concat(),
+                ))
+            }
+        }
+    }
+
+    /// The leaf version of the first leaf with `script` and the hashes of the
+    /// siblings on the way from that leaf to the root, starting with the leaf's
+    pub fn merkle_path(&self, script: &Script) -> Option<(u8, Vec<Hash256>)> {
+        match self {
+            Self::Leaf {
+                leaf_version,
+                script: leaf_script,
+            } => (leaf_script == script).then(|| (*leaf_version, Vec::new())),
+            Self::Branch(left, right) => left
+                .merkle_path(script)
+                .map(|(leaf_version, path)| (leaf_version, path, right))
+                .or_else(|| {
+                    right
+                        .merkle_path(script)
+                        .map(|(leaf_version, path)| (leaf_version, path, left))
+                })
+                .map(|(leaf_version, mut path, sibling)| {
+                    path.push(sibling.node_hash());
+
+                    (leaf_version, path)
+                }),
+        }
+    }
+}
+
+/// Builds the witness spending a P2TR output through one of the leaves of
+/// its script tree
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ScriptPathSpend {
+    internal_key: [u8; 32],
+    tree: TapTree,
+}
+
+impl ScriptPathSpend {
+    /// Spend the output of the x-only `internal_key` tweaked with the merkle
+    /// root of `tree`
+    pub fn new(internal_key: [u8; 32], tree: TapTree) -> Self {
+        Self { internal_key, tree }
+    }
+
+    /// The x-only internal key
+    pub fn internal_key(&self) -> [u8; 32] {
+        self.internal_key
+    }
+
+    /// The script tree
+    pub fn tree(&self) -> &TapTree {
+        &self.tree
+    }
+
+    /// The x-only output key, the internal key tweaked with the merkle root
+    pub fn output_key(&self) -> io::Result<[u8; 32]> {
+        self.tweak().map(|(output_key, _)| output_key)
+    }
+
+    /// The P2TR locking script `OP_1 <output key>`
+    pub fn locking_script(&self) -> io::Result<Script> {
+        let mut locking_script = vec![0x51, 0x20];
+        locking_script.extend_from_slice(&self.output_key()?);
+
+        Ok(Script::new(locking_script))
+    }
+
+    /// The control block of the leaf with `script`. It is the leaf version
+    /// with the parity of the output key in the lowest bit, the internal key
+    /// and the merkle path of the leaf
+    pub fn control_block(&self, script: &Script) -> io::Result<Vec<u8>> {
+        let (leaf_version, path) = self.tree.merkle_path(script).ok_or_else(|| {
+            io::Error::new(ErrorKind::NotFound, "The script is not a leaf of the tree")
+        })?;
+        if path.len() > TAPROOT_CONTROL_MAX_NODE_COUNT {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "The leaf is {} levels deep but at most {} are allowed",
+                    path.len(),
+                    TAPROOT_CONTROL_MAX_NODE_COUNT
+                ),
+            ));
+        }
+        let (_, parity) = self.tweak()?;
+
+        let mut control_block = vec![leaf_version | parity];
+        control_block.extend_from_slice(&self.internal_key);
+        path.iter()
+            .for_each(|hash| control_block.extend_from_slice(hash.as_ref()));
+
+        Ok(control_block)
+    }
+
+    /// The witness spending the leaf with `script`. The `satisfaction` is the
+    /// stack the script runs with in stack order, like its signatures and
+    /// preimages, and is followed by the script and its control block
+    pub fn witness(&self, script: &Script, satisfaction: &[&[u8]]) -> io::Result<Witness> {
+        let control_block = self.control_block(script)?;
+
+        let mut witness = Witness::from_slice(satisfaction);
+        witness.push(script.as_bytes()).push(control_block);
+
+        Ok(witness)
+    }
+
+    // The x-only output key and its parity
+    fn tweak(&self) -> io::Result<([u8; 32], u8)> {
+        let internal_key = XOnlyPublicKey::from_slice(&self.internal_key).map_err(|error| {
+            io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("The internal key is invalid: {}", error),
+            )
+        })?;
+        let merkle_root = TapNodeHash::from_byte_array(self.tree.node_hash().to_byte_array());
+
+        let (output_key, parity) =
+            internal_key.tap_tweak(&Secp256k1::verification_only(), Some(merkle_root));
+
+        Ok((
+            output_key.to_x_only_public_key().serialize(),
+            parity.to_u8(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod taproot_sanity_checks {
+    use crate::{Script, ScriptPathSpend, TapTree};
+    use bitcoin::{
+        key::{Secp256k1, XOnlyPublicKey},
+        taproot::{ControlBlock, LeafVersion, TaprootBuilder},
+        ScriptBuf,
+    };
+    use hex_literal::hex;
+
+    #[test]
+    fn script_path_spends() {
+        // The single leaf tree of the scriptPubKey vectors of BIP341
+        let internal_key = hex!("187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27");
+        let leaf = Script::new(hex!(
+            "20d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8ac"
+        ));
+        let spend = ScriptPathSpend::new(internal_key, TapTree::leaf(leaf.clone()));
+        assert_eq!(
+            hex!("147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3"),
+            spend.output_key().unwrap()
+        );
+        assert_eq!(
+            [&[0xc1][..], &internal_key].concat(),
+            spend.control_block(&leaf).unwrap()
+        );
+
+        // The control blocks of a deeper tree match the `bitcoin` crate and
+        // commit to the output key
+        let scripts = [
+            Script::new([0x51]),
+            Script::new([0x52]),
+            Script::new([0x53]),
+        ];
+        let tree = TapTree::branch(
+            TapTree::leaf(scripts[0].clone()),
+            TapTree::branch(
+                TapTree::leaf(scripts[1].clone()),
+                TapTree::leaf(scripts[2].clone()),
+            ),
+        );
+        let spend = ScriptPathSpend::new(internal_key, tree);
+
+        let secp = Secp256k1::verification_only();
+        let key = XOnlyPublicKey::from_slice(&internal_key).unwrap();
+        let spend_info = TaprootBuilder::new()
+            .add_leaf(1, ScriptBuf::from_bytes(scripts[0].as_bytes().to_vec()))
+            .unwrap()
+            .add_leaf(2, ScriptBuf::from_bytes(scripts[1].as_bytes().to_vec()))
+            .unwrap()
+            .add_leaf(2, ScriptBuf::from_bytes(scripts[2].as_bytes().to_vec()))
+            .unwrap()
+            .finalize(&secp, key)
+            .unwrap();
+        assert_eq!(
+            spend_info.output_key().to_x_only_public_key().serialize(),
+            spend.output_key().unwrap()
+        );
+
+        scripts.iter().for_each(|script| {
+            let script_buf = ScriptBuf::from_bytes(script.as_bytes().to_vec());
+            let expected = spend_info
+                .control_block(&(script_buf.clone(), LeafVersion::TapScript))
+                .unwrap();
+            let control_block = spend.control_block(script).unwrap();
+            assert_eq!(expected.serialize(), control_block);
+            assert!(ControlBlock::decode(&control_block)
+                .unwrap()
+                .verify_taproot_commitment(
+                    &secp,
+                    spend_info.output_key().to_x_only_public_key(),
+                    &script_buf
+                ));
+        });
+
+        let witness = spend.witness(&scripts[1], &[&[0x01]]).unwrap();
+        assert_eq!(3, witness.len());
+        assert_eq!(Some(&[0x52][..]), witness.get(1));
+        assert_eq!(1 + 32 + 32 * 2, witness.last().unwrap().len());
+
+        assert!(spend.control_block(&Script::new([0x54])).is_err());
+        assert!(ScriptPathSpend::new([0u8; 32], TapTree::leaf(leaf))
+            .output_key()
+            .is_err());
+    }
+}
@@ -0,0 +1,375 @@
+use crate::{BlockHeader, BtcTx, Hash256, Network, OutPoint, TxOutput, Txid};
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    io::{self, ErrorKind},
+};
+
+/// The number of recent blocks an `AddressTracker` can disconnect by default
+pub const DEFAULT_UNDO_DEPTH: usize = 100;
+
+/// A change of the best chain reported by a node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainEvent {
+    /// A block extending the best chain with its raw transactions in block order
+    Connected {
+        header: BlockHeader,
+        height: u32,
+        raw_txs: Vec<Vec<u8>>,
+    },
+    /// The block at the tip of the best chain with this hash was disconnected
+    Disconnected(Hash256),
+}
+
+/// Something that happened to the outputs watched by an `AddressTracker`.
+/// Every event carries the parsed transaction it is about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrackerEvent {
+    /// A transaction paid a watched script or address
+    Funded {
+        outpoint: OutPoint,
+        output: TxOutput,
+        tx: BtcTx,
+    },
+    /// A transaction spent a watched output
+    Spent {
+        outpoint: OutPoint,
+        input_index: usize,
+        tx: BtcTx,
+    },
+    /// A transaction funding or spending watched outputs was confirmed
+    Confirmed { txid: Txid, height: u32, tx: BtcTx },
+    /// The block confirming a transaction was disconnected. The transaction
+    /// is unconfirmed again and may be confirmed by another block
+    ReorgedOut { txid: Txid, height: u32, tx: BtcTx },
+}
+
+/// Watches scripts and addresses across the transactions of the mempool and of
+/// connected and disconnected blocks. Transactions are fed in by the caller
+/// from whatever source it listens to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressTracker {
+    network: Network,
+    scripts: BTreeSet<Vec<u8>>,
+    addresses: BTreeSet<String>,
+    // The outputs paying a watched script or address
+    outputs: BTreeMap<OutPoint, TxOutput>,
+    // The transactions funding or spending watched outputs and the
+    // height of the block confirming them
+    txs: BTreeMap<Txid, (BtcTx, Option<u32>)>,
+    // The hash and height of the most recent blocks from the oldest
+    recent_blocks: VecDeque<(Hash256, u32)>,
+    undo_depth: usize,
+}
+
+impl AddressTracker {
+    /// A tracker matching addresses encoded for `network`
+    pub fn new(network: Network) -> Self {
+        Self {
+            network,
+            scripts: BTreeSet::new(),
+            addresses: BTreeSet::new(),
+            outputs: BTreeMap::new(),
+            txs: BTreeMap::new(),
+            recent_blocks: VecDeque::new(),
+            undo_depth: DEFAULT_UNDO_DEPTH,
+        }
+    }
+
+    /// Keep the `undo_depth` most recent blocks connected by
+    /// `AddressTracker::apply()` so they can be disconnected
+    pub fn with_undo_depth(mut self, undo_depth: usize) -> Self {
+        self.undo_depth = undo_depth;
+
+        self
+    }
+
+    /// The hash and height of the tip of the chain as seen through
+    /// `AddressTracker::apply()`
+    pub fn tip(&self) -> Option<(Hash256, u32)> {
+        self.recent_blocks.back().copied()
+    }
+
+    /// Follow the best chain. A connected block must extend the tip and only
+    /// the tip can be disconnected so the state rolls back block by block
+    /// during a reorg. Disconnecting more than the undo depth returns an error
+    pub fn apply(&mut self, event: ChainEvent) -> io::Result<Vec<TrackerEvent>> {
+        match event {
+            ChainEvent::Connected {
+                header,
+                height,
+                raw_txs,
+            } => {
+                if let Some((tip_hash, tip_height)) = self.tip() {
+                    if header.previous_block_hash() != tip_hash || height != tip_height + 1 {
+                        return Err(io::Error::new(
+                            ErrorKind::InvalidInput,
+                            format!(
+                                "Block {} at height {} does not extend the tip {}",
+                                header.block_hash(),
+                                height,
+                                tip_hash
+                            ),
+                        ));
+                    }
+                }
+
+                let raw_txs = raw_txs
+                    .iter()
+                    .map(|raw_tx| raw_tx.as_slice())
+                    .collect::<Vec<&[u8]>>();
+                let events = self.block_connected(height, &raw_txs)?;
+
+                self.recent_blocks.push_back((header.block_hash(), height));
+                while self.recent_blocks.len() > self.undo_depth {
+                    self.recent_blocks.pop_front();
+                }
+
+                Ok(events)
+            }
+            ChainEvent::Disconnected(block_hash) => match self.tip() {
+                Some((tip_hash, height)) if tip_hash == block_hash => {
+                    self.recent_blocks.pop_back();
+
+                    Ok(self.block_disconnected(height))
+                }
+                _ => Err(io::Error::new(
+                    ErrorKind::NotFound,
+                    format!(
+                        "Block {} is not the tip or is deeper than the undo depth of {} blocks",
+                        block_hash, self.undo_depth
+                    ),
+                )),
+            },
+        }
+    }
+
+    /// Watch the outputs paying `locking_script`
+    pub fn watch_script(&mut self, locking_script: Vec<u8>) -> &mut Self {
+        self.scripts.insert(locking_script);
+
+        self
+    }
+
+    /// Watch the outputs paying `address`
+    pub fn watch_address(&mut self, address: impl Into<String>) -> &mut Self {
+        self.addresses.insert(address.into());
+
+        self
+    }
+
+    /// The watched outputs which have been funded, spent or not
+    pub fn outputs(&self) -> &BTreeMap<OutPoint, TxOutput> {
+        &self.outputs
+    }
+
+    /// The height of the block confirming a tracked transaction, `None` if it is
+    /// unconfirmed and also if the transaction is not tracked
+    pub fn confirmation_height(&self, txid: &Txid) -> Option<u32> {
+        self.txs.get(txid).and_then(|(_, height)| *height)
+    }
+
+    /// Process a raw transaction seen in the mempool. Transactions already
+    /// seen return no events
+    pub fn process_tx(&mut self, raw_tx: &[u8]) -> io::Result<Vec<TrackerEvent>> {
+        let tx = BtcTx::from_hex_bytes(raw_tx)?;
+        let txid = tx.txid();
+
+        Ok(self.track(txid, tx))
+    }
+
+    /// Process the raw transactions of the block connected at `height`. The
+    /// funding and spending events of transactions not seen before are
+    /// followed by a `TrackerEvent::Confirmed` for every relevant transaction
+    pub fn block_connected(
+        &mut self,
+        height: u32,
+        raw_txs: &[&[u8]],
+    ) -> io::Result<Vec<TrackerEvent>> {
+        let mut events = Vec::<TrackerEvent>::new();
+
+        for raw_tx in raw_txs {
+            let tx = BtcTx::from_hex_bytes(raw_tx)?;
+            let txid = tx.txid();
+            events.extend(self.track(txid, tx));
+
+            if let Some((tx, confirmation)) = self.txs.get_mut(&txid) {
+                *confirmation = Some(height);
+                events.push(TrackerEvent::Confirmed {
+                    txid,
+                    height,
+                    tx: tx.clone(),
+                });
+            }
+        }
+
+        Ok(events)
+    }
+
+    /// Process the disconnection of the block at `height`. Every tracked
+    /// transaction it confirmed is unconfirmed again
+    pub fn block_disconnected(&mut self, height: u32) -> Vec<TrackerEvent> {
+        self.txs
+            .iter_mut()
+            .filter(|(_, (_, confirmation))| *confirmation == Some(height))
+            .map(|(txid, (tx, confirmation))| {
+                *confirmation = None;
+
+                TrackerEvent::ReorgedOut {
+                    txid: *txid,
+                    height,
+                    tx: tx.clone(),
+                }
+            })
+            .collect()
+    }
+
+    fn track(&mut self, txid: Txid, tx: BtcTx) -> Vec<TrackerEvent> {
+        if self.txs.contains_key(&txid) {
+            return Vec::new();
+        }
+
+        let mut events = tx
+            .inputs()
+            .iter()
+            .enumerate()
+            .filter(|(_, input)| self.outputs.contains_key(&input.previous_output()))
+            .map(|(input_index, input)| TrackerEvent::Spent {
+                outpoint: input.previous_output(),
+                input_index,
+                tx: tx.clone(),
+            })
+            .collect::<Vec<TrackerEvent>>();
+
+        let funded = tx
+            .outputs()
+            .iter()
+            .enumerate()
+            .filter(|(_, output)| self.is_watched(output))
+            .map(|(vout, output)| (OutPoint::new(txid, vout as u32), output.clone()))
+            .collect::<Vec<(OutPoint, TxOutput)>>();
+        funded.into_iter().for_each(|(outpoint, output)| {
+            self.outputs.insert(outpoint, output.clone());
+            events.push(TrackerEvent::Funded {
+                outpoint,
+                output,
+                tx: tx.clone(),
+            });
+        });
+
+        if !events.is_empty() {
+            self.txs.insert(txid, (tx, None));
+        }
+
+        events
+    }
+
+    fn is_watched(&self, output: &TxOutput) -> bool {
+        self.scripts.contains(output.locking_script())
+            || output
+                .address(self.network)
+                .is_some_and(|address| self.addresses.contains(&address))
+    }
+}
+
+#[cfg(test)]
+mod tracker_sanity_checks {
+    use crate::{AddressTracker, BlockHeader, ChainEvent, Hash256, Network, TrackerEvent, Txid};
+    use hex_literal::hex;
+
+    const GENESIS_HEADER: [u8; 80] = hex!("0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c");
+
+    #[test]
+    fn track_address() {
+        let funding =
+            hex::decode(include_str!("../fixtures/transactions/p2pkh_two_inputs.hex").trim())
+                .unwrap();
+        let funding_txid = Txid::hash(&funding);
+        // A transaction spending the first output of the funding transaction
+        let spending = [
+            &hex!("0100000001")[..],
+            &funding_txid
+                .to_byte_array()
+                .iter()
+                .rev()
+                .copied()
+                .collect::<Vec<u8>>(),
+            &hex!("0000000000ffffffff01e8030000000000000151"),
+            &[0u8; 4],
+        ]
+        .concat();
+
+        let mut tracker = AddressTracker::new(Network::Mainnet);
+        tracker.watch_address("12B7CgUyGLPVWKFFSCFVR7MHTM2ptxNnu4");
+
+        let events = tracker.process_tx(&funding).unwrap();
+        assert!(matches!(events[..], [TrackerEvent::Funded { .. }]));
+        assert!(tracker.process_tx(&funding).unwrap().is_empty());
+
+        let events = tracker
+            .block_connected(800_000, &[&funding, &spending])
+            .unwrap();
+        assert!(matches!(
+            events[..],
+            [
+                TrackerEvent::Confirmed {
+                    height: 800_000,
+                    ..
+                },
+                TrackerEvent::Spent { input_index: 0, .. },
+                TrackerEvent::Confirmed { .. },
+            ]
+        ));
+        assert_eq!(Some(800_000), tracker.confirmation_height(&funding_txid));
+
+        assert_eq!(2, tracker.block_disconnected(800_000).len());
+        assert_eq!(None, tracker.confirmation_height(&funding_txid));
+    }
+
+    #[test]
+    fn chain_events() {
+        let funding =
+            hex::decode(include_str!("../fixtures/transactions/p2pkh_two_inputs.hex").trim())
+                .unwrap();
+        let funding_txid = Txid::hash(&funding);
+        let genesis = BlockHeader::from_bytes(GENESIS_HEADER).unwrap();
+        let block = BlockHeader::new(
+            1,
+            genesis.block_hash(),
+            Hash256::new([2u8; 32]),
+            genesis.time() + 600,
+            genesis.bits(),
+            0,
+        );
+
+        let mut tracker = AddressTracker::new(Network::Mainnet).with_undo_depth(1);
+        tracker.watch_address("12B7CgUyGLPVWKFFSCFVR7MHTM2ptxNnu4");
+        tracker
+            .apply(ChainEvent::Connected {
+                header: genesis,
+                height: 0,
+                raw_txs: Vec::new(),
+            })
+            .unwrap();
+        let connect = ChainEvent::Connected {
+            header: block,
+            height: 1,
+            raw_txs: vec![funding],
+        };
+        assert_eq!(2, tracker.apply(connect.clone()).unwrap().len());
+        assert_eq!(Some(1), tracker.confirmation_height(&funding_txid));
+        // The block no longer extends the tip
+        assert!(tracker.apply(connect).is_err());
+
+        let events = tracker
+            .apply(ChainEvent::Disconnected(block.block_hash()))
+            .unwrap();
+        assert!(matches!(
+            events[..],
+            [TrackerEvent::ReorgedOut { height: 1, .. }]
+        ));
+        // The genesis block is deeper than the undo depth
+        assert!(tracker
+            .apply(ChainEvent::Disconnected(genesis.block_hash()))
+            .is_err());
+    }
+}
@@ -1,8 +1,14 @@
-use crate::{TxVersion, VarInt};
-use std::io::{self, Cursor, Read};
+use crate::{
+    instrument::traced, Address, Hash256, InputSatisfaction, Network, Ntxid, ScriptType, SpendType,
+    TxVersion, Txid, VarInt, Witness, Wtxid, OP_TRUE_SCRIPT, P2A_SCRIPT,
+};
+use std::{
+    fmt,
+    io::{self, Cursor, ErrorKind, Read},
+};
 
 /// The structure of the Bitcoin transaction
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Default)]
 pub struct BtcTx {
     // The version of the Bitcoin transaction
     version: TxVersion,
@@ -16,15 +22,41 @@ pub struct BtcTx {
 }
 
 impl BtcTx {
+    /// Instantiate a new transaction from its parts
+    pub fn new(
+        version: TxVersion,
+        inputs: Vec<TxInput>,
+        outputs: Vec<TxOutput>,
+        locktime: u32,
+    ) -> Self {
+        Self {
+            version,
+            inputs,
+            outputs,
+            locktime,
+        }
+    }
+
     /// Convert hex bytes into a Transaction struct. This calls all other
     /// methods to parse the version, inputs, outputs and locktime.
     pub fn from_hex_bytes(bytes: impl AsRef<[u8]>) -> io::Result<Self> {
+        let bytes = bytes.as_ref();
+
+        traced("parse_tx", bytes.len(), || Self::parse(bytes))
+    }
+
+    fn parse(bytes: &[u8]) -> io::Result<Self> {
         // Instantiate a new cursor to hold the bytes.
         // The cursor's position advances whenever we read
         // bytes allowing us to simplify the logic
         // instead of using a counter to keep track of bytes read
-        let mut bytes = Cursor::new(bytes.as_ref());
+        BtcTx::read_from(&mut Cursor::new(bytes))
+    }
 
+    // Parse a transaction from the position of the cursor and leave the cursor
+    // after its locktime so transactions following each other like the ones
+    // of a block can be parsed in turn
+    pub(crate) fn read_from(bytes: &mut Cursor<&[u8]>) -> io::Result<Self> {
         // The version number is always a 4 byte array
         let mut version_bytes = [0u8; 4];
         // Read exactly 4 bytes and advance the cursor to the 4th byte
@@ -32,12 +64,18 @@ impl BtcTx {
         // Get the transaction version from the bytes
         let version = TxVersion::from_bytes(version_bytes);
 
+        // BIP144 transactions have a marker and a flag before the inputs
+        let segwit = BtcTx::read_segwit_marker(bytes)?;
         // Get a vector of inputs by calling the `Self::get_inputs()` method
-        let inputs = BtcTx::get_inputs(&mut bytes)?;
+        let mut inputs = BtcTx::get_inputs(bytes)?;
         // Get a vector of outputs by calling the `Self::get_outputs()` method
-        let outputs = BtcTx::get_outputs(&mut bytes)?;
-        // Get a vector of inputs by calling the `Self::locktime()` method
-        let locktime = BtcTx::locktime(&mut bytes)?;
+        let outputs = BtcTx::get_outputs(bytes)?;
+        // The witnesses of every input follow the outputs
+        if segwit {
+            BtcTx::read_witnesses(bytes, &mut inputs)?;
+        }
+        // Get the locktime by calling the `Self::get_locktime()` method
+        let locktime = BtcTx::get_locktime(bytes)?;
 
         Ok(BtcTx {
             version,
@@ -47,6 +85,116 @@ impl BtcTx {
         })
     }
 
+    /// Parse a transaction like `Self::from_hex_bytes()` but keep what was parsed
+    /// when it fails. The `PartialTx` has the version and the complete inputs and
+    /// outputs read before the error and the offset of the item that failed
+    pub fn parse_partial(bytes: impl AsRef<[u8]>) -> Result<Self, PartialTx> {
+        let mut bytes = Cursor::new(bytes.as_ref());
+        let mut version = Option::<TxVersion>::None;
+        let mut inputs = Vec::<TxInput>::new();
+        let mut outputs = Vec::<TxOutput>::new();
+        // The offset of the item being parsed
+        let mut offset = 0u64;
+
+        let outcome = (|| {
+            let mut version_bytes = [0u8; 4];
+            bytes.read_exact(&mut version_bytes)?;
+            version = Some(TxVersion::from_bytes(version_bytes));
+
+            offset = bytes.position();
+            let segwit = BtcTx::read_segwit_marker(&mut bytes)?;
+
+            offset = bytes.position();
+            for _ in 0..BtcTx::read_count(&mut bytes)? {
+                offset = bytes.position();
+                inputs.push(BtcTx::input_decoder(&mut bytes)?);
+            }
+
+            offset = bytes.position();
+            for _ in 0..BtcTx::read_count(&mut bytes)? {
+                offset = bytes.position();
+                outputs.push(BtcTx::output_decoder(&mut bytes)?);
+            }
+
+            offset = bytes.position();
+            if segwit {
+                BtcTx::read_witnesses(&mut bytes, &mut inputs)?;
+            }
+
+            offset = bytes.position();
+            BtcTx::get_locktime(&mut bytes)
+        })();
+
+        match outcome {
+            Ok(locktime) => Ok(BtcTx {
+                // The version is always parsed when the locktime is
+                version: version.unwrap_or_default(),
+                inputs,
+                outputs,
+                locktime,
+            }),
+            Err(error) => Err(PartialTx {
+                version,
+                inputs,
+                outputs,
+                error,
+                offset,
+            }),
+        }
+    }
+
+    // Whether the transaction is serialized with witnesses, starting with the
+    // 0x00 marker and the 0x01 flag. Otherwise the cursor is left on the
+    // count of inputs, which is never zero for a valid legacy transaction
+    fn read_segwit_marker(bytes: &mut Cursor<&[u8]>) -> io::Result<bool> {
+        let start = bytes.position();
+        let mut marker = [0u8; 2];
+        if bytes.read_exact(&mut marker).is_err() || marker[0] != 0x00 {
+            bytes.set_position(start);
+            return Ok(false);
+        }
+
+        if marker[1] != 0x01 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("The segwit flag {:#04x} is not supported", marker[1]),
+            ));
+        }
+
+        Ok(true)
+    }
+
+    // Read the witness of every input. BIP144 forbids the witness serialization
+    // when every witness is empty
+    fn read_witnesses(bytes: &mut Cursor<&[u8]>, inputs: &mut [TxInput]) -> io::Result<()> {
+        for input in inputs.iter_mut() {
+            let mut witness = Witness::new();
+            for _ in 0..BtcTx::read_count(bytes)? {
+                let element_len = BtcTx::read_count(bytes)?;
+                witness.push(BtcTx::read_script(bytes, element_len)?);
+            }
+
+            input.witness = witness;
+        }
+
+        if inputs.iter().all(|input| input.witness.is_empty()) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "A transaction serialized with witnesses has no witness",
+            ));
+        }
+
+        Ok(())
+    }
+
+    // Read a VarInt count of inputs or outputs
+    fn read_count(bytes: &mut Cursor<&[u8]>) -> io::Result<usize> {
+        let mut varint_len = [0u8];
+        bytes.read_exact(&mut varint_len)?;
+
+        VarInt::integer(VarInt::parse(varint_len[0]), bytes)
+    }
+
     /// Get all inputs from the current position of the `Cursor`.
     /// This method decodes the number of inputs by first decoding the
     /// `varint` and then looping number of inputs calling
@@ -60,9 +208,9 @@ impl BtcTx {
 
         let mut inputs = Vec::<TxInput>::new();
 
-        (0..no_of_inputs).for_each(|_| {
-            inputs.push(BtcTx::input_decoder(bytes).unwrap());
-        });
+        for _ in 0..no_of_inputs {
+            inputs.push(BtcTx::input_decoder(bytes)?);
+        }
 
         Ok(inputs)
     }
@@ -75,7 +223,7 @@ impl BtcTx {
         bytes.read_exact(&mut previous_tx_id)?;
         // The transaction ID in hex format is in network byte order so we reverse
         // it to little endian
-        previous_tx_id.reverse();
+        let previous_tx_id = Txid::new(Hash256::new(previous_tx_id).reversed());
 
         //Previous transaction index is 4 bytes long which is a Rust u32
         let mut previous_tx_index_bytes = [0u8; 4];
@@ -91,16 +239,8 @@ impl BtcTx {
         // Get the length by converting VarInt into an integer by calling `integer`
         let integer_from_varint = VarInt::integer(varint_byte_len, bytes)?;
 
-        // Buffer to hold the signature script
-        let mut signature_script = Vec::<u8>::new();
-        let mut sig_buf = [0u8; 1];
-        // Since we are using a cursor, we iterate in order to advance
-        // the cursor in each iteration
-        (0..integer_from_varint).for_each(|_| {
-            bytes.read_exact(&mut sig_buf).unwrap();
-
-            signature_script.extend_from_slice(&sig_buf);
-        });
+        // Read the whole signature script at once
+        let signature_script = BtcTx::read_script(bytes, integer_from_varint)?;
 
         // The sequence number is a u32 (4 bytes long)
         let mut sequence_num_bytes = [0u8; 4];
@@ -113,6 +253,7 @@ impl BtcTx {
             previous_output_index,
             signature_script,
             sequence_number,
+            witness: Witness::new(),
         })
     }
 
@@ -128,43 +269,255 @@ impl BtcTx {
         let mut outputs = Vec::<TxOutput>::new();
 
         // Iterate over number of outputs
-        (0..num_of_outputs).for_each(|_| {
-            // The first value of the output is the amount in satoshis
-            // which is 8 bytes long (Rust u64)
-            let mut satoshis_as_bytes = [0u8; 8];
-            bytes.read_exact(&mut satoshis_as_bytes).unwrap();
-            // Get the number of satoshis in decimal
-            let satoshis = u64::from_le_bytes(satoshis_as_bytes);
-
-            // Get the exact size of the locking script
-            let mut locking_script_len = [0u8; 1];
-            bytes.read_exact(&mut locking_script_len).unwrap();
-            // Parse the length into a varint
-            let script_byte_len = VarInt::parse(locking_script_len[0]);
-            // Convert our VarInt to an integer
-            let script_len = VarInt::integer(script_byte_len, bytes).unwrap();
-            let mut script = Vec::<u8>::new();
-
-            // For the length of the script, read each byte and advance the cursor in each iteration
-            (0..script_len).for_each(|_| {
-                let mut current_byte = [0u8; 1];
-
-                bytes.read_exact(&mut current_byte).unwrap();
-                script.extend_from_slice(&current_byte);
-            });
-
-            // Construct our Transaction Output struct and then push it to the outputs vec
-            outputs.push(TxOutput {
-                amount: satoshis,
-                locking_script: script,
-            });
-        });
+        for _ in 0..num_of_outputs {
+            outputs.push(BtcTx::output_decoder(bytes)?);
+        }
 
         Ok(outputs)
     }
 
+    // Decodes an output from current `Cursor` position.
+    fn output_decoder(bytes: &mut Cursor<&[u8]>) -> io::Result<TxOutput> {
+        // The first value of the output is the amount in satoshis
+        // which is 8 bytes long (Rust u64)
+        let mut satoshis_as_bytes = [0u8; 8];
+        bytes.read_exact(&mut satoshis_as_bytes)?;
+        // Get the number of satoshis in decimal
+        let satoshis = u64::from_le_bytes(satoshis_as_bytes);
+
+        // Get the exact size of the locking script
+        let mut locking_script_len = [0u8; 1];
+        bytes.read_exact(&mut locking_script_len)?;
+        // Parse the length into a varint
+        let script_byte_len = VarInt::parse(locking_script_len[0]);
+        // Convert our VarInt to an integer
+        let script_len = VarInt::integer(script_byte_len, bytes)?;
+        // Read the whole locking script at once
+        let script = BtcTx::read_script(bytes, script_len)?;
+
+        // Construct our Transaction Output struct
+        Ok(TxOutput {
+            amount: satoshis,
+            locking_script: script,
+        })
+    }
+
+    // Read a script of `len` bytes into a buffer of that size. The length is
+    // checked against the bytes left first so that a corrupt VarInt cannot
+    // allocate more memory than the transaction has
+    fn read_script(bytes: &mut Cursor<&[u8]>, len: usize) -> io::Result<Vec<u8>> {
+        let remaining = (bytes.get_ref().len() as u64).saturating_sub(bytes.position());
+        if len as u64 > remaining {
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "The script is longer than the bytes left in the transaction",
+            ));
+        }
+
+        let mut script = vec![0u8; len];
+        bytes.read_exact(&mut script)?;
+
+        Ok(script)
+    }
+
+    /// The version of the transaction
+    pub fn version(&self) -> &TxVersion {
+        &self.version
+    }
+
+    /// The inputs of the transaction
+    pub fn inputs(&self) -> &[TxInput] {
+        &self.inputs
+    }
+
+    /// The outputs of the transaction
+    pub fn outputs(&self) -> &[TxOutput] {
+        &self.outputs
+    }
+
+    /// The outputs whose locking script is of `script_type`
+    pub fn outputs_of_type(&self, script_type: ScriptType) -> impl Iterator<Item = &TxOutput> {
+        self.outputs
+            .iter()
+            .filter(move |output| output.script_type() == script_type)
+    }
+
+    /// The outputs paying more than `amount` satoshis
+    pub fn outputs_above(&self, amount: u64) -> impl Iterator<Item = &TxOutput> {
+        self.outputs
+            .iter()
+            .filter(move |output| output.amount() > amount)
+    }
+
+    /// The inputs spending one of `outpoints`
+    pub fn inputs_spending<'a>(
+        &'a self,
+        outpoints: &'a [OutPoint],
+    ) -> impl Iterator<Item = &'a TxInput> {
+        self.inputs
+            .iter()
+            .filter(|input| outpoints.contains(&input.previous_output()))
+    }
+
+    /// The locktime of the transaction
+    pub fn locktime(&self) -> u32 {
+        self.locktime
+    }
+
+    /// Set the scriptSig and witness of the input at `index` from signatures produced
+    /// elsewhere. See `InputSatisfaction::assemble()` for the supported scripts
+    pub fn satisfy_input(
+        &mut self,
+        index: usize,
+        satisfaction: &InputSatisfaction,
+    ) -> io::Result<()> {
+        let input = self.inputs.get_mut(index).ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidInput,
+                "The transaction has no input at the index being satisfied",
+            )
+        })?;
+
+        let (signature_script, witness) =
+            traced("satisfy_input", satisfaction.locking_script.len(), || {
+                satisfaction.assemble()
+            })?;
+        input.signature_script = signature_script;
+        input.witness = witness;
+
+        Ok(())
+    }
+
+    /// Set the locktime. Like every setter below it invalidates the signatures
+    /// committing to the transaction, which have to be made again
+    pub fn set_locktime(&mut self, locktime: u32) {
+        self.locktime = locktime;
+    }
+
+    /// Set the sequence number of the input at `index`
+    pub fn set_sequence(&mut self, index: usize, sequence_number: u32) -> io::Result<()> {
+        let input = self.inputs.get_mut(index).ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidInput,
+                "The transaction has no input at the index of the sequence number",
+            )
+        })?;
+        input.sequence_number = sequence_number;
+
+        Ok(())
+    }
+
+    /// Append an output
+    pub fn push_output(&mut self, output: TxOutput) {
+        self.outputs.push(output);
+    }
+
+    /// Remove and return the output at `index`, the outputs after it move down one index
+    pub fn remove_output(&mut self, index: usize) -> io::Result<TxOutput> {
+        if index >= self.outputs.len() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "The transaction has no output at the index being removed",
+            ));
+        }
+
+        Ok(self.outputs.remove(index))
+    }
+
+    /// The transaction ID, the SHA256d of the serialization without witnesses
+    pub fn txid(&self) -> Txid {
+        Txid::hash(&self.serialize(true))
+    }
+
+    /// The witness transaction ID, the SHA256d of the serialization with
+    /// witnesses. It is the `Txid` for transactions without a witness
+    pub fn wtxid(&self) -> Wtxid {
+        Wtxid::hash(&self.to_bytes())
+    }
+
+    /// The weight of the transaction in weight units. Bytes of the
+    /// serialization without witnesses count 4 units and the marker, flag
+    /// and witnesses count 1 unit per byte
+    pub fn weight(&self) -> usize {
+        self.serialize(true).len() * 3 + self.to_bytes().len()
+    }
+
+    /// The normalized transaction ID which is the SHA256d of the transaction with
+    /// every scriptSig left empty. Malleating a scriptSig changes the `Txid` but
+    /// not the `Ntxid`, see `Ntxid` for what it can and cannot be used for
+    pub fn ntxid(&self) -> Ntxid {
+        Ntxid::hash(&self.serialize(false))
+    }
+
+    /// Serialize the transaction in the wire format. Transactions with a witness
+    /// use the BIP144 serialization with the marker, the flag and the witness
+    /// of every input. Parsing and serializing gives back the same bytes
+    /// unless the original used VarInts that are not minimally encoded
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let legacy = self.serialize(true);
+        if !self.has_witness() {
+            return legacy;
+        }
+
+        let locktime_offset = legacy.len() - 4;
+        let mut bytes = legacy[..4].to_vec();
+        bytes.extend_from_slice(&[0x00, 0x01]);
+        bytes.extend_from_slice(&legacy[4..locktime_offset]);
+        self.inputs
+            .iter()
+            .for_each(|input| bytes.extend_from_slice(&input.witness.to_bytes()));
+        bytes.extend_from_slice(&legacy[locktime_offset..]);
+
+        bytes
+    }
+
+    /// Serialize the transaction like `BtcTx::to_bytes()` as hex
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.to_bytes())
+    }
+
+    /// Whether any input has a witness so the transaction is serialized with witnesses
+    pub fn has_witness(&self) -> bool {
+        self.inputs.iter().any(|input| !input.witness.is_empty())
+    }
+
+    // Serialize the transaction without witnesses in the wire format,
+    // optionally leaving every scriptSig empty
+    fn serialize(&self, with_signature_scripts: bool) -> Vec<u8> {
+        let mut bytes = self.version.to_bytes().to_vec();
+
+        bytes.extend_from_slice(&VarInt::encode(self.inputs.len() as u64));
+        self.inputs.iter().for_each(|input| {
+            // The transaction ID is stored in the byte order explorers display
+            // it in so it is reversed back to the wire byte order
+            bytes.extend_from_slice(input.previous_tx_id.to_hash().reversed().as_ref());
+            bytes.extend_from_slice(&input.previous_output_index.to_le_bytes());
+
+            let signature_script = if with_signature_scripts {
+                input.signature_script.as_slice()
+            } else {
+                &[]
+            };
+            bytes.extend_from_slice(&VarInt::encode(signature_script.len() as u64));
+            bytes.extend_from_slice(signature_script);
+
+            bytes.extend_from_slice(&input.sequence_number.to_le_bytes());
+        });
+
+        bytes.extend_from_slice(&VarInt::encode(self.outputs.len() as u64));
+        self.outputs.iter().for_each(|output| {
+            bytes.extend_from_slice(&output.amount.to_le_bytes());
+            bytes.extend_from_slice(&VarInt::encode(output.locking_script.len() as u64));
+            bytes.extend_from_slice(&output.locking_script);
+        });
+
+        bytes.extend_from_slice(&self.locktime.to_le_bytes());
+
+        bytes
+    }
+
     // Lastly, after parsing our version, inputs and outputs we parse the locktime
-    fn locktime(bytes: &mut Cursor<&[u8]>) -> io::Result<u32> {
+    fn get_locktime(bytes: &mut Cursor<&[u8]>) -> io::Result<u32> {
         // The locktime is 4 bytes long
         let mut locktime_bytes = [0u8; 4];
         bytes.read_exact(&mut locktime_bytes)?;
@@ -174,18 +527,138 @@ impl BtcTx {
     }
 }
 
+/// What was parsed from a transaction before an error was found
+#[derive(Debug)]
+pub struct PartialTx {
+    /// The version if the first four bytes could be read
+    pub version: Option<TxVersion>,
+    /// The inputs read completely before the error
+    pub inputs: Vec<TxInput>,
+    /// The outputs read completely before the error
+    pub outputs: Vec<TxOutput>,
+    /// The error which stopped the parsing
+    pub error: io::Error,
+    /// The offset in bytes of the input, output, count or locktime which failed
+    pub offset: u64,
+}
+
 /// Our transaction inputs
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct TxInput {
     // The SHA256 bytes of the previous transaction ID
     // of the unspent UTXO
-    previous_tx_id: [u8; 32],
+    previous_tx_id: Txid,
     // Previous index of the previous transaction output
     previous_output_index: u32,
     // The scriptSig
     signature_script: Vec<u8>,
     // The sequence number
     sequence_number: u32,
+    // The witness stack which is empty for inputs spending non-segwit outputs
+    witness: Witness,
+}
+
+impl TxInput {
+    /// Instantiate a new input spending output `previous_output_index`
+    /// of the transaction `previous_tx_id`
+    pub fn new(
+        previous_tx_id: Txid,
+        previous_output_index: u32,
+        signature_script: Vec<u8>,
+        sequence_number: u32,
+    ) -> Self {
+        Self {
+            previous_tx_id,
+            previous_output_index,
+            signature_script,
+            sequence_number,
+            witness: Witness::new(),
+        }
+    }
+
+    /// Set the witness stack of the input
+    pub fn with_witness(mut self, witness: Witness) -> Self {
+        self.witness = witness;
+
+        self
+    }
+
+    /// The transaction ID of the output being spent
+    pub fn previous_tx_id(&self) -> Txid {
+        self.previous_tx_id
+    }
+
+    /// The index of the output being spent
+    pub fn previous_output_index(&self) -> u32 {
+        self.previous_output_index
+    }
+
+    /// The raw bytes of the scriptSig
+    pub fn signature_script(&self) -> &[u8] {
+        &self.signature_script
+    }
+
+    /// The output being spent
+    pub fn previous_output(&self) -> OutPoint {
+        OutPoint::new(self.previous_tx_id, self.previous_output_index)
+    }
+
+    /// The sequence number
+    pub fn sequence_number(&self) -> u32 {
+        self.sequence_number
+    }
+
+    /// The witness stack, empty for inputs spending non-segwit outputs
+    pub fn witness(&self) -> &Witness {
+        &self.witness
+    }
+
+    /// Infer the type of output this input spends from the shape of the
+    /// scriptSig and the witness. This is a heuristic useful when the previous outputs are
+    /// not available, for an exact answer classify the previous output.
+    pub fn inferred_spend_type(&self) -> SpendType {
+        // A coinbase input does not spend a previous output so the previous
+        // transaction ID is all zeros and the index is `0xffffffff`
+        if self.previous_tx_id == Txid::new(Hash256::all_zeros())
+            && self.previous_output_index == u32::MAX
+        {
+            return SpendType::Coinbase;
+        }
+
+        SpendType::from_input_scripts(&self.signature_script, &self.witness)
+    }
+}
+
+/// A reference to an output of a transaction
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub struct OutPoint {
+    // The transaction ID in the byte order explorers display it in
+    txid: Txid,
+    // The index of the output in the transaction
+    vout: u32,
+}
+
+impl OutPoint {
+    /// Instantiate a reference to output `vout` of the transaction `txid`
+    pub fn new(txid: Txid, vout: u32) -> Self {
+        Self { txid, vout }
+    }
+
+    /// The transaction ID in the byte order explorers display it in
+    pub fn txid(&self) -> Txid {
+        self.txid
+    }
+
+    /// The index of the output in the transaction
+    pub fn vout(&self) -> u32 {
+        self.vout
+    }
+}
+
+impl fmt::Display for OutPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.txid, self.vout)
+    }
 }
 
 /// Transaction outputs
@@ -196,3 +669,333 @@ pub struct TxOutput {
     // The locking script which gives conditions for spending the bitcoins
     locking_script: Vec<u8>,
 }
+
+impl TxOutput {
+    /// Create an output paying `amount` satoshis to the `locking_script`
+    pub fn new(amount: u64, locking_script: Vec<u8>) -> Self {
+        Self {
+            amount,
+            locking_script,
+        }
+    }
+
+    /// Create a pay-to-anchor (P2A) output. Ephemeral anchors in
+    /// version 3 packages carry an `amount` of zero while keyless
+    /// anchors that must be relayable on their own use the dust
+    /// limit of 240 satoshis.
+    pub fn pay_to_anchor(amount: u64) -> Self {
+        Self::new(amount, P2A_SCRIPT.to_vec())
+    }
+
+    /// Create a bare `OP_TRUE` anchor output
+    pub fn op_true_anchor(amount: u64) -> Self {
+        Self::new(amount, OP_TRUE_SCRIPT.to_vec())
+    }
+
+    /// Amount in satoshis
+    pub fn amount(&self) -> u64 {
+        self.amount
+    }
+
+    /// The raw bytes of the locking script (scriptPubKey)
+    pub fn locking_script(&self) -> &[u8] {
+        &self.locking_script
+    }
+
+    /// Classify the locking script into one of the standard script templates
+    pub fn script_type(&self) -> ScriptType {
+        ScriptType::from_script(&self.locking_script)
+    }
+
+    /// The address of the locking script for the given network.
+    /// Returns `None` if the locking script has no address form.
+    pub fn address(&self, network: Network) -> Option<String> {
+        Address::from_script(&self.locking_script, network)
+    }
+}
+
+#[cfg(test)]
+mod btc_tx_sanity_checks {
+    use crate::{
+        BtcTx, Hash256, OutPoint, ScriptType, SpendType, TxInput, TxOutput, TxVersion, Txid,
+        Witness, Wtxid,
+    };
+    use hex_literal::hex;
+    use std::io::ErrorKind;
+
+    // The spend type of an input with an empty scriptSig and the witness `elements`
+    fn witness_spend_type(elements: &[&[u8]]) -> SpendType {
+        input(&[])
+            .with_witness(Witness::from_slice(elements))
+            .inferred_spend_type()
+    }
+
+    // A 71 byte DER signature with its sighash byte
+    fn signature() -> Vec<u8> {
+        [&[0x30, 0x44][..], &[0x01; 68], &[0x01]].concat()
+    }
+
+    fn input(signature_script: &[u8]) -> TxInput {
+        TxInput {
+            previous_tx_id: Txid::new(Hash256::new([1u8; 32])),
+            previous_output_index: 0,
+            signature_script: signature_script.to_vec(),
+            sequence_number: u32::MAX,
+            witness: Witness::new(),
+        }
+    }
+
+    #[test]
+    fn ntxid() {
+        let raw_tx = hex!("0100000001c997a5e56e104102fa209c6a852dd90660a20b2d9c352423edce25857fcd3704000000004847304402204e45e16932b8af514961a1d3a1a25fdf3f4f7732e9d624c6c61548ab5fb8cd410220181522ec8eca07de4860a4acdd12909d831cc56cbbac4622082221a8768d1d0901ffffffff0200ca9a3b00000000434104ae1a62fe09c5f51b13905f07f06b99a2f7159b2225f374cd378d71302fa28414e7aab37397f554a7df5f142c21c1b7303b8a0626f1baded5c72a704f7e6cd84cac00286bee0000000043410411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3ac00000000");
+        let tx = BtcTx::from_hex_bytes(raw_tx).unwrap();
+        assert!(!tx.has_witness());
+        assert_eq!(tx.txid().to_hash(), tx.wtxid().to_hash());
+        assert_eq!(raw_tx.len() * 4, tx.weight());
+        assert_eq!(hex::encode(raw_tx), tx.to_hex());
+        assert_eq!(
+            "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16",
+            Txid::hash(&tx.serialize(true)).to_string()
+        );
+
+        // Malleating the scriptSig does not change the normalized transaction ID
+        let mut malleated = tx.clone();
+        malleated.inputs[0].signature_script.push(0x00);
+        assert_ne!(
+            Txid::hash(&tx.serialize(true)),
+            Txid::hash(&malleated.serialize(true))
+        );
+        assert_eq!(tx.ntxid(), malleated.ntxid());
+    }
+
+    #[test]
+    fn parse_partial() {
+        let raw_tx = hex!("0100000001c997a5e56e104102fa209c6a852dd90660a20b2d9c352423edce25857fcd3704000000004847304402204e45e16932b8af514961a1d3a1a25fdf3f4f7732e9d624c6c61548ab5fb8cd410220181522ec8eca07de4860a4acdd12909d831cc56cbbac4622082221a8768d1d0901ffffffff0200ca9a3b00000000434104ae1a62fe09c5f51b13905f07f06b99a2f7159b2225f374cd378d71302fa28414e7aab37397f554a7df5f142c21c1b7303b8a0626f1baded5c72a704f7e6cd84cac00286bee0000000043410411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3ac00000000");
+        assert_eq!(
+            BtcTx::from_hex_bytes(raw_tx).unwrap(),
+            BtcTx::parse_partial(raw_tx).unwrap()
+        );
+
+        // Cut in the middle of the second output which starts after the
+        // version, one input of 113 bytes, the output count and one output
+        let partial = BtcTx::parse_partial(&raw_tx[..4 + 1 + 113 + 1 + 76 + 10]).unwrap_err();
+        assert_eq!(1, partial.inputs.len());
+        assert_eq!(1, partial.outputs.len());
+        assert_eq!(4 + 1 + 113 + 1 + 76, partial.offset);
+        assert_eq!(ErrorKind::UnexpectedEof, partial.error.kind());
+        assert!(BtcTx::from_hex_bytes(&raw_tx[..200]).is_err());
+    }
+
+    #[test]
+    fn segwit_transactions() {
+        // The native P2WPKH example of BIP143 spending a P2PK and a P2WPKH output
+        let raw_tx = hex!("01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f00000000494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeeea35711000000");
+        let tx = BtcTx::from_hex_bytes(raw_tx).unwrap();
+        assert_eq!(2, tx.inputs().len());
+        assert_eq!(2, tx.outputs().len());
+        assert_eq!(17, tx.locktime());
+        assert!(tx.inputs()[0].witness().is_empty());
+        assert_eq!(2, tx.inputs()[1].witness().len());
+        assert_eq!(
+            Some(&hex!("025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeeea357")[..]),
+            tx.inputs()[1].witness().last()
+        );
+        assert_eq!(tx, BtcTx::parse_partial(raw_tx).unwrap());
+        assert_eq!(raw_tx.to_vec(), tx.to_bytes());
+        assert_eq!(
+            "e8151a2af31c368a35053ddd4bdb285a8595c769a3ad83e0fa02314a602d4609",
+            tx.txid().to_string()
+        );
+        assert_ne!(tx.txid().to_hash(), tx.wtxid().to_hash());
+        assert_eq!(Wtxid::hash(&raw_tx), tx.wtxid());
+
+        // Only the flag 0x01 is defined
+        let mut flag = raw_tx.to_vec();
+        flag[5] = 0x02;
+        assert_eq!(
+            ErrorKind::InvalidData,
+            BtcTx::from_hex_bytes(&flag).unwrap_err().kind()
+        );
+
+        // The witness serialization with only empty witnesses is invalid
+        let empty_witnesses = [
+            &hex!("020000000001")[..],
+            &hex!("01"),
+            &[0x11; 32],
+            &hex!("0000000000ffffffff01e8030000000000000151"),
+            &hex!("00"),
+            &[0u8; 4],
+        ]
+        .concat();
+        assert!(BtcTx::from_hex_bytes(&empty_witnesses).is_err());
+    }
+
+    #[test]
+    fn large_scripts() {
+        // A transaction whose output has a script of 100KB
+        let script = vec![0x61; 100_000];
+        let raw_tx = [
+            &hex!("0200000001")[..],
+            &[0x11; 32],
+            &hex!("0000000000ffffffff01e803000000000000fea0860100"),
+            &script,
+            &[0u8; 4],
+        ]
+        .concat();
+        let tx = BtcTx::from_hex_bytes(&raw_tx).unwrap();
+        assert_eq!(script.as_slice(), tx.outputs()[0].locking_script());
+
+        // A script length beyond the end of the transaction fails before allocating
+        let mut corrupt = raw_tx[..raw_tx.len() - 100_004].to_vec();
+        corrupt.extend_from_slice(&[0u8; 8]);
+        let last = corrupt.len() - 8;
+        corrupt[last - 5..last].copy_from_slice(&hex!("feffffff7f"));
+        assert_eq!(
+            ErrorKind::UnexpectedEof,
+            BtcTx::from_hex_bytes(&corrupt).unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn filtered_iterators() {
+        let tx = BtcTx::new(
+            TxVersion::Two,
+            vec![input(&[]), input(&[]).with_witness(Witness::new())],
+            vec![
+                TxOutput::pay_to_anchor(0),
+                TxOutput::new(1_000, vec![0x51]),
+                TxOutput::pay_to_anchor(240),
+            ],
+            0,
+        );
+
+        assert_eq!(2, tx.outputs_of_type(ScriptType::P2A).count());
+        assert_eq!(
+            vec![1_000, 240],
+            tx.outputs_above(0)
+                .map(|output| output.amount())
+                .collect::<Vec<u64>>()
+        );
+        assert_eq!(0, tx.outputs_above(1_000).count());
+
+        let outpoint = tx.inputs()[0].previous_output();
+        assert_eq!(2, tx.inputs_spending(&[outpoint]).count());
+        assert_eq!(
+            0,
+            tx.inputs_spending(&[OutPoint::new(outpoint.txid(), 1)])
+                .count()
+        );
+    }
+
+    #[test]
+    fn setters() {
+        let mut tx = BtcTx::new(
+            TxVersion::Two,
+            vec![input(&[]), input(&[])],
+            vec![TxOutput::new(1_000, vec![0x51])],
+            0,
+        );
+        let txid = tx.txid();
+
+        tx.set_locktime(800_000);
+        tx.set_sequence(1, 0xfffffffd).unwrap();
+        tx.push_output(TxOutput::pay_to_anchor(0));
+        assert_eq!(800_000, tx.locktime());
+        assert_eq!(0xfffffffd, tx.inputs()[1].sequence_number());
+        assert_eq!(u32::MAX, tx.inputs()[0].sequence_number());
+        assert_ne!(txid, tx.txid());
+
+        assert_eq!(1_000, tx.remove_output(0).unwrap().amount());
+        assert_eq!(vec![TxOutput::pay_to_anchor(0)], tx.outputs());
+
+        assert!(tx.set_sequence(2, 0).is_err());
+        assert!(tx.remove_output(1).is_err());
+    }
+
+    #[test]
+    fn anchor_outputs() {
+        let p2a = TxOutput::pay_to_anchor(0);
+        assert_eq!(ScriptType::P2A, p2a.script_type());
+        assert_eq!(0, p2a.amount());
+
+        assert_eq!(
+            ScriptType::OpTrue,
+            TxOutput::op_true_anchor(0).script_type()
+        );
+    }
+
+    #[test]
+    fn inferred_spend_type() {
+        let p2pkh = input(&hex!("4730440220199a6aa56306cebcdacd1eba26b55eaf6f92eb46eb90d1b7e7724bacbe1d19140220101c0d46e033361c60536b6989efdd6fa692265fcda164676e2f49885871038a0121039ac8bac8f6d916b8a85b458e087e0cd07e6a76a6bfdde9bb766b17086d9a5c8a"));
+        assert_eq!(SpendType::P2PKH, p2pkh.inferred_spend_type());
+
+        let nested_p2wpkh = input(&hex!("160014751e76e8199196d454941c45d1b3a323f1433bd6"));
+        assert_eq!(SpendType::NestedP2WPKH, nested_p2wpkh.inferred_spend_type());
+
+        assert_eq!(SpendType::NativeSegwit, input(&[]).inferred_spend_type());
+
+        let mut coinbase = input(&hex!("03a0bb0d"));
+        coinbase.previous_tx_id = Txid::new(Hash256::all_zeros());
+        coinbase.previous_output_index = u32::MAX;
+        assert_eq!(SpendType::Coinbase, coinbase.inferred_spend_type());
+    }
+
+    #[test]
+    fn inferred_p2wpkh() {
+        assert_eq!(
+            SpendType::P2WPKH,
+            witness_spend_type(&[&signature(), &[0x02; 33]])
+        );
+        // An uncompressed key is not standard in segwit
+        assert_ne!(
+            SpendType::P2WPKH,
+            witness_spend_type(&[&signature(), &[0x04; 65]])
+        );
+    }
+
+    #[test]
+    fn inferred_p2wsh() {
+        // A 2-of-2 multisig witness script
+        let witness_script = [
+            &[0x52, 0x21][..],
+            &[0x02; 33],
+            &[0x21],
+            &[0x03; 33],
+            &[0x52, 0xae],
+        ]
+        .concat();
+        assert_eq!(
+            SpendType::P2WSH,
+            witness_spend_type(&[&[], &signature(), &signature(), &witness_script])
+        );
+        // A witness script whose push goes past its end
+        assert_eq!(
+            SpendType::NativeSegwit,
+            witness_spend_type(&[&signature(), &[0x4c, 0x05]])
+        );
+    }
+
+    #[test]
+    fn inferred_p2tr_key_path() {
+        assert_eq!(SpendType::P2TRKeyPath, witness_spend_type(&[&[0x01; 64]]));
+        assert_eq!(
+            SpendType::P2TRKeyPath,
+            witness_spend_type(&[&[0x01; 65], &[0x50, 0xaa]])
+        );
+    }
+
+    #[test]
+    fn inferred_p2tr_script_path() {
+        // A control block with the parity bit set and a merkle path of one hash
+        let control_block = [&[0xc1][..], &[0x02; 32], &[0x03; 32]].concat();
+        assert_eq!(
+            SpendType::P2TRScriptPath,
+            witness_spend_type(&[&[0x01; 64], &[0x51], &control_block])
+        );
+        // Followed by an annex
+        assert_eq!(
+            SpendType::P2TRScriptPath,
+            witness_spend_type(&[&[0x51], &control_block[..33], &[0x50]])
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use crate::{Checksum, Hash256};
+use std::{fmt, io, str::FromStr};
+
+// The identifiers all wrap a SHA256d but are distinct types so a map keyed
+// by one kind of identifier cannot be queried with another by mistake
+macro_rules! transaction_identifier {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Default)]
+        pub struct $name(Hash256);
+
+        impl $name {
+            /// Instantiate the identifier from a hash in the byte order explorers display it in
+            pub const fn new(hash: Hash256) -> Self {
+                Self(hash)
+            }
+
+            /// Compute the identifier from the serialization it commits to. The SHA256d
+            /// is reversed into the byte order explorers display it in
+            pub fn hash(serialization: &[u8]) -> Self {
+                Self(Hash256::new(Checksum::sha256d(serialization)).reversed())
+            }
+
+            /// The hash in the byte order explorers display it in
+            pub const fn to_hash(&self) -> Hash256 {
+                self.0
+            }
+
+            /// The bytes in the byte order explorers display them in
+            pub const fn to_byte_array(&self) -> [u8; 32] {
+                self.0.to_byte_array()
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = io::Error;
+
+            fn from_str(hex_str: &str) -> Result<Self, Self::Err> {
+                hex_str.parse().map(Self)
+            }
+        }
+
+        impl AsRef<[u8]> for $name {
+            fn as_ref(&self) -> &[u8] {
+                self.0.as_ref()
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                self.0.serialize(serializer)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                Hash256::deserialize(deserializer).map(Self)
+            }
+        }
+    };
+}
+
+transaction_identifier!(
+    /// The transaction ID, the SHA256d of the transaction without its witnesses.
+    /// This is what inputs use to refer to the outputs they spend
+    Txid
+);
+
+transaction_identifier!(
+    /// The witness transaction ID, the SHA256d of the transaction including its
+    /// witnesses. It is used by the witness commitment of a block and by
+    /// transaction relay and equals the `Txid` for transactions without a witness
+    Wtxid
+);
+
+transaction_identifier!(
+    /// The normalized transaction ID, the SHA256d of the transaction without its
+    /// witnesses and with every scriptSig left empty. It does not change when the
+    /// scriptSigs are malleated so it can follow a transaction before it confirms,
+    /// but it is not used by the protocol and cannot look up a transaction
+    Ntxid
+);
+
+#[cfg(test)]
+mod txid_sanity_checks {
+    use crate::{Ntxid, Txid};
+
+    #[test]
+    fn identifiers() {
+        let txid: Txid = "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16",
+            txid.to_string()
+        );
+        assert_eq!(0x16, txid.to_byte_array()[31]);
+
+        // The same hash is a different identifier depending on the kind
+        assert_eq!(Ntxid::new(txid.to_hash()).to_hash(), txid.to_hash());
+        assert!("f4184f".parse::<Txid>().is_err());
+    }
+}
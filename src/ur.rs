@@ -0,0 +1,699 @@
+use crate::{BtcTx, Psbt};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, ErrorKind},
+};
+
+/// The least number of bytes of a fragment, shorter messages are sent in one part
+pub const MIN_UR_FRAGMENT_LEN: usize = 10;
+
+// The bytewords of BCR-2020-012 encoding each byte value, the minimal
+// encoding keeps the first and the last letter of each word
+const BYTEWORDS: &str = "\
+    able acid also apex aqua arch atom aunt away axis back bald barn belt beta bias \
+    blue body brag brew bulb buzz calm cash cats chef city claw code cola cook cost \
+    crux curl cusp cyan dark data days deli dice diet door down draw drop drum \
+    dull duty each easy echo edge epic even exam exit eyes fact fair fern figs film \
+    fish fizz flap flew flux foxy free frog fuel fund gala game gear gems gift girl \
+    glow good gray grim guru gush gyro half hang hard hawk heat help high hill holy \
+    hope horn huts iced idea idle inch inky into iris iron item jade jazz join jolt \
+    jowl judo jugs jump junk jury keep keno kept keys kick kiln king kite kiwi knob \
+    lamb lava lazy leaf legs liar limp lion list logo loud love luau luck lung main \
+    many math maze memo menu meow mild mint miss monk nail navy need news next noon \
+    note numb obey oboe omit onyx open oval owls paid part peck play plus poem pool \
+    pose puff puma purr quad quiz race ramp real redo rich road rock roof ruby ruin \
+    runs rust safe saga scar sets silk skew slot soap solo song stub surf swan taco \
+    task taxi tent tied time tiny toil tomb toys trip tuna twin ugly undo unit urge \
+    user vast very veto vial vibe view visa void vows wall wand warm wasp wave waxy \
+    webs what when whiz wolf work yank yawn yell yoga yurt zaps zero zest zinc zone zoom";
+
+// The CBOR major types of unsigned integers, byte strings and arrays
+const CBOR_UNSIGNED: u8 = 0x00;
+const CBOR_BYTES: u8 = 0x40;
+const CBOR_ARRAY: u8 = 0x80;
+
+/// The payload of a Uniform Resource of BCR-2020-005 exchanged with air-gapped
+/// signers over animated QR codes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UrPayload {
+    /// A PSBT of the `crypto-psbt` type, or `psbt` in the newer registry
+    Psbt(Psbt),
+    /// A serialized transaction of the generic `bytes` type
+    Tx(BtcTx),
+}
+
+impl UrPayload {
+    /// The registered type the payload is encoded as
+    pub fn ur_type(&self) -> &'static str {
+        match self {
+            Self::Psbt(_) => "crypto-psbt",
+            Self::Tx(_) => "bytes",
+        }
+    }
+
+    // Both types are a CBOR byte string of the serialization
+    fn to_cbor(&self) -> Vec<u8> {
+        let bytes = match self {
+            Self::Psbt(psbt) => psbt.as_bytes().to_vec(),
+            Self::Tx(tx) => tx.to_bytes(),
+        };
+
+        [cbor_header(CBOR_BYTES, bytes.len() as u64), bytes].concat()
+    }
+
+    fn from_cbor(ur_type: &str, message: &[u8]) -> io::Result<Self> {
+        let mut position = 0;
+        let bytes = cbor_bytes(message, &mut position)?;
+        if position != message.len() {
+            return Err(invalid(
+                "The UR message has bytes after its CBOR byte string",
+            ));
+        }
+
+        match ur_type {
+            "crypto-psbt" | "psbt" => Ok(Self::Psbt(Psbt::from_bytes(bytes.to_vec())?)),
+            "bytes" => Ok(Self::Tx(BtcTx::from_hex_bytes(bytes)?)),
+            _ => Err(io::Error::new(
+                ErrorKind::Unsupported,
+                format!("The UR type `{}` is not supported", ur_type),
+            )),
+        }
+    }
+}
+
+/// Split a payload into the parts of a UR, shown one after the other as QR codes.
+/// A payload fitting in one fragment is a single part `ur:<type>/<bytewords>`.
+/// Larger payloads are split into fragments sent as `ur:<type>/<seq>-<count>/<bytewords>`
+/// parts, the first `count` carry each fragment in order and the following ones are
+/// fountain code mixes of fragments, so a scanner missing some frames can still
+/// finish the message without waiting for the whole loop again.
+///
+/// ```
+/// # use btc_tx_hex::{BtcTx, TxInput, TxOutput, TxVersion, Txid};
+/// use btc_tx_hex::{UrDecoder, UrEncoder, UrPayload};
+///
+/// # let tx = BtcTx::new(TxVersion::Two, vec![TxInput::new(Txid::default(), 0, Vec::new(), u32::MAX)], vec![TxOutput::new(1_000, vec![0x51]); 4], 0);
+/// let payload = UrPayload::Tx(tx);
+/// let mut encoder = UrEncoder::new(&payload, 30)?;
+/// let mut decoder = UrDecoder::new();
+/// let decoded = loop {
+///     if let Some(decoded) = decoder.receive(&encoder.next_part())? {
+///         break decoded;
+///     }
+/// };
+/// assert_eq!(payload, decoded);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct UrEncoder {
+    ur_type: &'static str,
+    message: Vec<u8>,
+    fragments: Vec<Vec<u8>>,
+    checksum: u32,
+    // The sequence number of the last part returned
+    sequence: u32,
+}
+
+impl UrEncoder {
+    /// Split the payload into fragments of at most `max_fragment_len` bytes,
+    /// which must be at least `MIN_UR_FRAGMENT_LEN`
+    pub fn new(payload: &UrPayload, max_fragment_len: usize) -> io::Result<Self> {
+        if max_fragment_len < MIN_UR_FRAGMENT_LEN {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "The fragments must be at least {} bytes",
+                    MIN_UR_FRAGMENT_LEN
+                ),
+            ));
+        }
+
+        let message = payload.to_cbor();
+        let fragment_len = nominal_fragment_len(message.len(), max_fragment_len);
+        let fragments = message
+            .chunks(fragment_len)
+            .map(|chunk| {
+                let mut fragment = chunk.to_vec();
+                // The last fragment is padded with zeros
+                fragment.resize(fragment_len, 0);
+
+                fragment
+            })
+            .collect();
+
+        Ok(Self {
+            ur_type: payload.ur_type(),
+            checksum: crc32(&message),
+            message,
+            fragments,
+            sequence: 0,
+        })
+    }
+
+    /// The number of fragments the payload is split into
+    pub fn fragment_count(&self) -> usize {
+        self.fragments.len()
+    }
+
+    /// Whether the payload fits in one part which is returned again and again
+    pub fn is_single_part(&self) -> bool {
+        self.fragments.len() == 1
+    }
+
+    /// The next part to show, it never runs out
+    pub fn next_part(&mut self) -> String {
+        if self.is_single_part() {
+            return format!("ur:{}/{}", self.ur_type, bytewords_encode(&self.message));
+        }
+
+        self.sequence = self.sequence.wrapping_add(1).max(1);
+        let count = self.fragments.len() as u32;
+        let mut fragment = vec![0u8; self.fragments[0].len()];
+        choose_fragments(self.sequence, count, self.checksum)
+            .iter()
+            .for_each(|index| xor_into(&mut fragment, &self.fragments[*index]));
+
+        let part = [
+            cbor_header(CBOR_ARRAY, 5),
+            cbor_header(CBOR_UNSIGNED, self.sequence as u64),
+            cbor_header(CBOR_UNSIGNED, count as u64),
+            cbor_header(CBOR_UNSIGNED, self.message.len() as u64),
+            cbor_header(CBOR_UNSIGNED, self.checksum as u64),
+            cbor_header(CBOR_BYTES, fragment.len() as u64),
+            fragment,
+        ]
+        .concat();
+
+        format!(
+            "ur:{}/{}-{}/{}",
+            self.ur_type,
+            self.sequence,
+            count,
+            bytewords_encode(&part)
+        )
+    }
+}
+
+// The fixed description of the message every part of a multi-part UR repeats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct UrMessage {
+    count: u32,
+    len: usize,
+    checksum: u32,
+    fragment_len: usize,
+}
+
+/// Reassemble a payload from the parts of a UR scanned in any order. Parts
+/// can be missed or repeated, the fountain code mixes fill in the gaps
+#[derive(Debug, Clone, Default)]
+pub struct UrDecoder {
+    ur_type: Option<String>,
+    message: Option<UrMessage>,
+    // The fragments recovered by their index
+    fragments: BTreeMap<usize, Vec<u8>>,
+    // The mixes of fragments which could not be reduced to one fragment yet
+    mixed: Vec<(BTreeSet<usize>, Vec<u8>)>,
+}
+
+impl UrDecoder {
+    /// A decoder which has not received any part
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The share of the fragments recovered so far, from 0 to 1
+    pub fn progress(&self) -> f64 {
+        self.message.map_or(0.0, |message| {
+            self.fragments.len() as f64 / message.count as f64
+        })
+    }
+
+    /// Receive a scanned part in either case. The payload is returned once
+    /// every fragment is recovered and its checksum matches
+    pub fn receive(&mut self, part: &str) -> io::Result<Option<UrPayload>> {
+        let part = part.trim().to_lowercase();
+        let components = part
+            .strip_prefix("ur:")
+            .ok_or_else(|| invalid("A UR starts with `ur:`"))?
+            .split('/')
+            .collect::<Vec<&str>>();
+
+        let (ur_type, words) = match components[..] {
+            [ur_type, words] => (ur_type, words),
+            [ur_type, _, words] => (ur_type, words),
+            _ => {
+                return Err(invalid(
+                    "A UR is `ur:<type>/<bytewords>` or `ur:<type>/<seq>-<count>/<bytewords>`",
+                ))
+            }
+        };
+        if self
+            .ur_type
+            .as_deref()
+            .is_some_and(|expected| expected != ur_type)
+        {
+            return Err(invalid("The part belongs to a UR of another type"));
+        }
+        let bytes = bytewords_decode(words)?;
+
+        if components.len() == 2 {
+            return UrPayload::from_cbor(ur_type, &bytes).map(Some);
+        }
+
+        let (sequence, message, fragment) = Self::parse_part(&bytes)?;
+        if self.message.is_some_and(|expected| expected != message) {
+            return Err(invalid("The part belongs to another UR message"));
+        }
+        self.ur_type = Some(ur_type.to_owned());
+        self.message = Some(message);
+
+        self.add(
+            choose_fragments(sequence, message.count, message.checksum),
+            fragment,
+        );
+        if self.fragments.len() < message.count as usize {
+            return Ok(None);
+        }
+
+        let mut joined = self
+            .fragments
+            .values()
+            .flatten()
+            .copied()
+            .collect::<Vec<u8>>();
+        joined.truncate(message.len);
+        if crc32(&joined) != message.checksum {
+            return Err(invalid(
+                "The checksum of the reassembled UR message does not match",
+            ));
+        }
+
+        UrPayload::from_cbor(ur_type, &joined).map(Some)
+    }
+
+    // The sequence number, the message description and the fragment of a part
+    fn parse_part(bytes: &[u8]) -> io::Result<(u32, UrMessage, Vec<u8>)> {
+        let mut position = 0;
+        if cbor_value(bytes, &mut position, CBOR_ARRAY)? != 5 {
+            return Err(invalid("A UR part is a CBOR array of five items"));
+        }
+        let mut unsigned = || -> io::Result<u32> {
+            u32::try_from(cbor_value(bytes, &mut position, CBOR_UNSIGNED)?)
+                .map_err(|_| invalid("A UR part number does not fit in 32 bits"))
+        };
+        let sequence = unsigned()?;
+        let count = unsigned()?;
+        let len = unsigned()? as usize;
+        let checksum = unsigned()?;
+        let fragment = cbor_bytes(bytes, &mut position)?.to_vec();
+
+        if sequence == 0 || count == 0 || fragment.len() * (count as usize) < len {
+            return Err(invalid("The UR part does not describe a valid message"));
+        }
+
+        Ok((
+            sequence,
+            UrMessage {
+                count,
+                len,
+                checksum,
+                fragment_len: fragment.len(),
+            },
+            fragment,
+        ))
+    }
+
+    // Reduce the mix by the fragments already known, then use a fragment found
+    // alone to reduce the mixes received before it
+    fn add(&mut self, indexes: BTreeSet<usize>, fragment: Vec<u8>) {
+        let mut pending = vec![(indexes, fragment)];
+        while let Some((mut indexes, mut fragment)) = pending.pop() {
+            for (index, known) in &self.fragments {
+                if indexes.remove(index) {
+                    xor_into(&mut fragment, known);
+                }
+            }
+
+            match (indexes.len(), indexes.first().copied()) {
+                (0, _) => {}
+                (1, Some(index)) => {
+                    self.fragments.insert(index, fragment);
+                    // Every mix holding the new fragment is tried again
+                    let (reducible, rest) = std::mem::take(&mut self.mixed)
+                        .into_iter()
+                        .partition(|(mixed, _)| mixed.contains(&index));
+                    self.mixed = rest;
+                    pending.extend(reducible);
+                }
+                _ => {
+                    if !self.mixed.iter().any(|(mixed, _)| *mixed == indexes) {
+                        self.mixed.push((indexes, fragment));
+                    }
+                }
+            }
+        }
+    }
+}
+
+// The length of the fragments splitting `message_len` bytes into the fewest
+// fragments of at most `max_fragment_len` bytes, like the reference implementation
+fn nominal_fragment_len(message_len: usize, max_fragment_len: usize) -> usize {
+    let max_count = (message_len / MIN_UR_FRAGMENT_LEN).max(1);
+
+    (1..=max_count)
+        .map(|count| message_len.div_ceil(count))
+        .find(|fragment_len| *fragment_len <= max_fragment_len)
+        .unwrap_or(MIN_UR_FRAGMENT_LEN)
+}
+
+// The fragments mixed into part `sequence`. The first `count` parts carry one
+// fragment each, the degree and the fragments of the following ones are drawn
+// from a generator seeded by the sequence number and the checksum
+fn choose_fragments(sequence: u32, count: u32, checksum: u32) -> BTreeSet<usize> {
+    if sequence <= count {
+        return BTreeSet::from([sequence as usize - 1]);
+    }
+
+    let mut rng = Xoshiro256::new(&[sequence.to_be_bytes(), checksum.to_be_bytes()].concat());
+    let degree = choose_degree(count as usize, &mut rng);
+
+    let mut remaining = (0..count as usize).collect::<Vec<usize>>();
+    let mut shuffled = Vec::<usize>::new();
+    while !remaining.is_empty() {
+        let index = rng.next_int(0, remaining.len() as u64 - 1) as usize;
+        shuffled.push(remaining.remove(index));
+    }
+
+    shuffled.into_iter().take(degree).collect()
+}
+
+// A degree from 1 to `count` drawn with probabilities proportional to `1 / degree`
+// with the alias method of the reference implementation
+fn choose_degree(count: usize, rng: &mut Xoshiro256) -> usize {
+    let mut scaled = (1..=count)
+        .map(|degree| 1.0 / degree as f64)
+        .collect::<Vec<f64>>();
+    let sum = scaled.iter().sum::<f64>();
+    scaled
+        .iter_mut()
+        .for_each(|probability| *probability *= count as f64 / sum);
+
+    let mut small = Vec::<usize>::new();
+    let mut large = Vec::<usize>::new();
+    (0..count).rev().for_each(|index| {
+        if scaled[index] < 1.0 {
+            small.push(index);
+        } else {
+            large.push(index);
+        }
+    });
+
+    let mut probabilities = vec![0.0; count];
+    let mut aliases = vec![0; count];
+    while let (Some(less), Some(more)) = (small.last().copied(), large.last().copied()) {
+        small.pop();
+        large.pop();
+        probabilities[less] = scaled[less];
+        aliases[less] = more;
+        scaled[more] += scaled[less] - 1.0;
+        if scaled[more] < 1.0 {
+            small.push(more);
+        } else {
+            large.push(more);
+        }
+    }
+    large
+        .into_iter()
+        .chain(small)
+        .for_each(|index| probabilities[index] = 1.0);
+
+    let column = (count as f64 * rng.next_double()) as usize;
+    let degree = if rng.next_double() < probabilities[column] {
+        column
+    } else {
+        aliases[column]
+    };
+
+    degree + 1
+}
+
+// The xoshiro256** generator seeded from the SHA256 of the seed bytes
+struct Xoshiro256 {
+    state: [u64; 4],
+}
+
+impl Xoshiro256 {
+    fn new(seed: &[u8]) -> Self {
+        let digest: [u8; 32] = Sha256::digest(seed).into();
+        let mut state = [0u64; 4];
+        digest
+            .chunks(8)
+            .zip(state.iter_mut())
+            .for_each(|(chunk, word)| {
+                *word = chunk
+                    .iter()
+                    .fold(0, |word, byte| (word << 8) | *byte as u64)
+            });
+
+        Self { state }
+    }
+
+    fn next(&mut self) -> u64 {
+        let result = self.state[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let shifted = self.state[1] << 17;
+
+        self.state[2] ^= self.state[0];
+        self.state[3] ^= self.state[1];
+        self.state[1] ^= self.state[2];
+        self.state[0] ^= self.state[3];
+        self.state[2] ^= shifted;
+        self.state[3] = self.state[3].rotate_left(45);
+
+        result
+    }
+
+    fn next_double(&mut self) -> f64 {
+        self.next() as f64 / (u64::MAX as f64 + 1.0)
+    }
+
+    fn next_int(&mut self, low: u64, high: u64) -> u64 {
+        (self.next_double() * (high - low + 1) as f64) as u64 + low
+    }
+}
+
+fn xor_into(fragment: &mut [u8], other: &[u8]) {
+    fragment
+        .iter_mut()
+        .zip(other)
+        .for_each(|(byte, other)| *byte ^= other);
+}
+
+// The CRC-32 of ISO-HDLC used by zlib and UR, computed bit by bit
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(u32::MAX, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |crc, _| {
+            (crc >> 1) ^ (0xedb88320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
+
+// The minimal bytewords of the bytes followed by their CRC-32 in big endian
+fn bytewords_encode(bytes: &[u8]) -> String {
+    let words = BYTEWORDS.split_whitespace().collect::<Vec<&str>>();
+
+    bytes
+        .iter()
+        .chain(crc32(bytes).to_be_bytes().iter())
+        .flat_map(|byte| {
+            let word = words[*byte as usize].as_bytes();
+            [word[0] as char, word[3] as char]
+        })
+        .collect()
+}
+
+fn bytewords_decode(encoded: &str) -> io::Result<Vec<u8>> {
+    let words = BYTEWORDS.split_whitespace().collect::<Vec<&str>>();
+    if !encoded.len().is_multiple_of(2) || !encoded.is_ascii() {
+        return Err(invalid("Minimal bytewords are pairs of letters"));
+    }
+
+    let mut bytes = encoded
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            words
+                .iter()
+                .position(|word| word.as_bytes()[0] == pair[0] && word.as_bytes()[3] == pair[1])
+                .map(|byte| byte as u8)
+                .ok_or_else(|| invalid("The UR has a letter pair which is not a byteword"))
+        })
+        .collect::<io::Result<Vec<u8>>>()?;
+
+    if bytes.len() < 4 {
+        return Err(invalid("The bytewords are shorter than their checksum"));
+    }
+    let checksum = bytes.split_off(bytes.len() - 4);
+    if crc32(&bytes).to_be_bytes() != checksum[..] {
+        return Err(invalid("The checksum of the bytewords does not match"));
+    }
+
+    Ok(bytes)
+}
+
+// The head of a CBOR item of major type `major` with the argument `value`
+fn cbor_header(major: u8, value: u64) -> Vec<u8> {
+    match value {
+        0..=23 => vec![major | value as u8],
+        24..=0xff => vec![major | 24, value as u8],
+        0x100..=0xffff => [&[major | 25][..], &(value as u16).to_be_bytes()].concat(),
+        0x1_0000..=0xffff_ffff => [&[major | 26][..], &(value as u32).to_be_bytes()].concat(),
+        _ => [&[major | 27][..], &value.to_be_bytes()].concat(),
+    }
+}
+
+// The argument of the CBOR item of major type `major` at `position`
+fn cbor_value(bytes: &[u8], position: &mut usize, major: u8) -> io::Result<u64> {
+    let head = *bytes
+        .get(*position)
+        .ok_or_else(|| invalid("The CBOR item is truncated"))?;
+    if head & 0xe0 != major {
+        return Err(invalid("The CBOR item is not of the expected type"));
+    }
+
+    let len = match head & 0x1f {
+        0..=23 => 0,
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        _ => {
+            return Err(invalid(
+                "Indefinite and reserved CBOR lengths are not supported",
+            ))
+        }
+    };
+    let argument = bytes
+        .get(*position + 1..*position + 1 + len)
+        .ok_or_else(|| invalid("The CBOR item is truncated"))?;
+    *position += 1 + len;
+
+    Ok(match len {
+        0 => (head & 0x1f) as u64,
+        _ => argument
+            .iter()
+            .fold(0, |value, byte| (value << 8) | *byte as u64),
+    })
+}
+
+fn cbor_bytes<'a>(bytes: &'a [u8], position: &mut usize) -> io::Result<&'a [u8]> {
+    let len = cbor_value(bytes, position, CBOR_BYTES)? as usize;
+    let value = bytes
+        .get(*position..position.saturating_add(len))
+        .ok_or_else(|| invalid("The CBOR byte string is truncated"))?;
+    *position += len;
+
+    Ok(value)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod ur_sanity_checks {
+    use super::{bytewords_decode, bytewords_encode, crc32, Xoshiro256, BYTEWORDS};
+    use crate::{
+        fixtures::{tx, txid},
+        Psbt, UrDecoder, UrEncoder, UrPayload, PSBT_MAGIC,
+    };
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn primitives() {
+        let words = BYTEWORDS.split_whitespace().collect::<Vec<&str>>();
+        assert_eq!(256, words.len());
+        // The first and last letters are enough to tell every word apart
+        let minimal = words
+            .iter()
+            .map(|word| (&word[..1], &word[3..]))
+            .collect::<BTreeSet<_>>();
+        assert_eq!(256, minimal.len());
+
+        assert_eq!(0xebe6c6e6, crc32(b"Hello, world!"));
+        assert_eq!(0x598c84dc, crc32(b"Wolf"));
+
+        assert_eq!("aeadaolazmjendeoti", bytewords_encode(&[0, 1, 2, 128, 255]));
+        assert_eq!(
+            vec![0, 1, 2, 128, 255],
+            bytewords_decode("aeadaolazmjendeoti").unwrap()
+        );
+        assert!(bytewords_decode("aeadaolazmjendeota").is_err());
+
+        let mut rng = Xoshiro256::new(b"Wolf");
+        assert_eq!(
+            vec![42, 81, 85, 8, 82, 84, 76, 73, 70, 88],
+            (0..10).map(|_| rng.next() % 100).collect::<Vec<u64>>()
+        );
+    }
+
+    #[test]
+    fn single_part() {
+        let payload = UrPayload::Tx(tx(&[(txid(9), 0)], &[1_000]));
+        let mut encoder = UrEncoder::new(&payload, 1_000).unwrap();
+        assert!(encoder.is_single_part());
+
+        let part = encoder.next_part();
+        assert!(part.starts_with("ur:bytes/"));
+        assert_eq!(part, encoder.next_part());
+        assert_eq!(
+            Some(payload),
+            UrDecoder::new().receive(&part.to_uppercase()).unwrap()
+        );
+        assert!(UrEncoder::new(&UrPayload::Tx(tx(&[], &[])), 9).is_err());
+    }
+
+    #[test]
+    fn multi_part() {
+        let unsigned_tx = tx(&[(txid(9), 0)], &[1_000; 10]).to_bytes();
+        let raw_psbt = [
+            &PSBT_MAGIC[..],
+            &[0x01, 0x00, unsigned_tx.len() as u8],
+            &unsigned_tx,
+            &[0x00; 12],
+        ]
+        .concat();
+        let payload = UrPayload::Psbt(Psbt::from_bytes(raw_psbt).unwrap());
+
+        let mut encoder = UrEncoder::new(&payload, 40).unwrap();
+        assert_eq!(5, encoder.fragment_count());
+        let parts = (0..40)
+            .map(|_| encoder.next_part())
+            .collect::<Vec<String>>();
+        assert!(parts[0].starts_with("ur:crypto-psbt/1-5/"));
+        assert!(parts[9].starts_with("ur:crypto-psbt/10-5/"));
+
+        // Every part in order
+        let mut decoder = UrDecoder::new();
+        let decoded = parts.iter().find_map(|part| decoder.receive(part).unwrap());
+        assert_eq!(Some(&payload), decoded.as_ref());
+
+        // Missing every other pure part, the mixes fill in the gaps
+        let mut decoder = UrDecoder::new();
+        let decoded = parts
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index >= 5 || index % 2 == 0)
+            .find_map(|(_, part)| decoder.receive(part).unwrap());
+        assert_eq!(Some(&payload), decoded.as_ref());
+
+        // A part of another message is rejected
+        let mut decoder = UrDecoder::new();
+        decoder.receive(&parts[0]).unwrap();
+        assert_eq!(0.2, decoder.progress());
+        let other = UrPayload::Tx(tx(&[(txid(9), 0)], &[2_000; 10]));
+        let other_part = UrEncoder::new(&other, 40).unwrap().next_part();
+        assert!(decoder.receive(&other_part).is_err());
+        assert!(decoder.receive("ur:crypto-psbt/1-5/aeadao").is_err());
+    }
+}
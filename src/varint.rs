@@ -74,6 +74,27 @@ impl VarInt {
 
         Ok(outcome)
     }
+
+    /// The number of bytes needed to encode `value` as a VarInt
+    pub const fn encoded_len(value: u64) -> usize {
+        match value {
+            0..=252 => 1,
+            253..=0xffff => 3,
+            0x10000..=0xffffffff => 5,
+            _ => 9,
+        }
+    }
+
+    /// Encode `value` as a VarInt. Values above 252 are prefixed with
+    /// 253, 254 or 255 followed by the value as a u16, u32 or u64
+    pub fn encode(value: u64) -> Vec<u8> {
+        match Self::encoded_len(value) {
+            1 => vec![value as u8],
+            3 => [&[253u8][..], &(value as u16).to_le_bytes()].concat(),
+            5 => [&[254u8][..], &(value as u32).to_le_bytes()].concat(),
+            _ => [&[255u8][..], &value.to_le_bytes()].concat(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -150,4 +171,23 @@ mod varint_sanity_checks {
         assert!(varint_len.is_ok());
         assert_eq!(72340172838076673usize, varint_len.unwrap());
     }
+
+    #[test]
+    fn encode() {
+        [0u64, 252, 253, 0xffff, 0x10000, 0xffffffff, 0x100000000]
+            .iter()
+            .for_each(|value| {
+                let encoded = VarInt::encode(*value);
+                assert_eq!(VarInt::encoded_len(*value), encoded.len());
+
+                let mut bytes = Cursor::new(encoded.as_slice());
+                let mut varint_byte = [0u8; 1];
+                bytes.read_exact(&mut varint_byte).unwrap();
+                let varint_byte_len = VarInt::parse(varint_byte[0]);
+                assert_eq!(
+                    *value as usize,
+                    VarInt::integer(varint_byte_len, &mut bytes).unwrap()
+                );
+            });
+    }
 }
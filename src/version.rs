@@ -2,7 +2,7 @@
 /// by Bitcoin core. A node must pre-configure a transaction
 /// version higher than version 2 and this transaction is
 /// not guaranteed to be propagated by all Bitcoin core.
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default)]
 pub enum TxVersion {
     /// This will be treated as the default version
     /// when calling TxVersion::default()
@@ -14,7 +14,10 @@ pub enum TxVersion {
     Two,
     /// Custom transaction version which is considered non-standard,
     /// must be set by the Bitcoin node operator and is not guaranteed
-    /// to be accepted by other nodes running Bitcoin core software
+    /// to be accepted by other nodes running Bitcoin core software.
+    /// Version 3 (TRUC) transactions and versions using the high bits
+    /// are parsed into this variant and the exact `u32` is kept so
+    /// that `Self::to_bytes()` returns the original bytes.
     Custom(u32),
 }
 
@@ -0,0 +1,217 @@
+use crate::{
+    input_weight, BtcTx, InputSigners, KeySource, OutPoint, SigningSession, TxInput, TxOutput,
+    TxVersion, WalletUtxo,
+};
+use std::io::{self, ErrorKind};
+
+/// The weight of an input with an empty scriptSig and no witness, the
+/// outpoint, the VarInt length of the scriptSig and the sequence number
+pub const EMPTY_INPUT_WEIGHT: u64 = (36 + 1 + 4) * 4;
+
+/// An unspent output with everything needed to select, spend and sign it, so
+/// coin selection, building and signing pass one value around
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct WeightedUtxo {
+    /// The output
+    pub outpoint: OutPoint,
+    /// The amount and locking script of the output
+    pub txout: TxOutput,
+    /// The weight of the scriptSig and witness which will spend the output
+    pub satisfaction_weight: u64,
+    /// The public key of an output paying to a key hash
+    pub public_key: Option<Vec<u8>>,
+    /// The redeem script of a P2SH output
+    pub redeem_script: Option<Vec<u8>>,
+    /// The witness script of a P2WSH output
+    pub witness_script: Option<Vec<u8>>,
+    /// The origin of the keys which can sign, by public key
+    pub key_sources: Vec<(Vec<u8>, KeySource)>,
+}
+
+impl WeightedUtxo {
+    /// An output spent with a scriptSig and witness of `satisfaction_weight`
+    pub fn new(outpoint: OutPoint, txout: TxOutput, satisfaction_weight: u64) -> Self {
+        Self {
+            outpoint,
+            txout,
+            satisfaction_weight,
+            public_key: None,
+            redeem_script: None,
+            witness_script: None,
+            key_sources: Vec::new(),
+        }
+    }
+
+    /// An output whose satisfaction weight is estimated from the type of its
+    /// locking script like `input_weight()`. Returns `None` if the weight
+    /// depends on a script that is not known
+    pub fn estimated(outpoint: OutPoint, txout: TxOutput) -> Option<Self> {
+        let satisfaction_weight = input_weight(txout.script_type())? - EMPTY_INPUT_WEIGHT;
+
+        Some(Self::new(outpoint, txout, satisfaction_weight))
+    }
+
+    /// Add the public key of an output paying to a key hash
+    pub fn with_public_key(mut self, public_key: Vec<u8>) -> Self {
+        self.public_key = Some(public_key);
+
+        self
+    }
+
+    /// Add the redeem script of a P2SH output
+    pub fn with_redeem_script(mut self, redeem_script: Vec<u8>) -> Self {
+        self.redeem_script = Some(redeem_script);
+
+        self
+    }
+
+    /// Add the witness script of a P2WSH output
+    pub fn with_witness_script(mut self, witness_script: Vec<u8>) -> Self {
+        self.witness_script = Some(witness_script);
+
+        self
+    }
+
+    /// Add the origin of a key which can sign
+    pub fn with_key_source(mut self, public_key: Vec<u8>, key_source: KeySource) -> Self {
+        self.key_sources.push((public_key, key_source));
+
+        self
+    }
+
+    /// The weight of the input spending the output once it is signed
+    pub fn input_weight(&self) -> u64 {
+        EMPTY_INPUT_WEIGHT + self.satisfaction_weight
+    }
+
+    /// The fee in satoshis of spending the output at `fee_rate` sat/vB.
+    /// Returns `None` if the fee does not fit in 64 bits
+    pub fn spending_fee(&self, fee_rate: u64) -> Option<u64> {
+        EMPTY_INPUT_WEIGHT
+            .checked_add(self.satisfaction_weight)?
+            .div_ceil(4)
+            .checked_mul(fee_rate)
+    }
+
+    /// The output as the `WalletUtxo` planned by a `DustPlanner`
+    pub fn to_wallet_utxo(&self) -> WalletUtxo {
+        WalletUtxo {
+            outpoint: self.outpoint,
+            amount: self.txout.amount(),
+            script_type: self.txout.script_type(),
+        }
+    }
+
+    /// The unsigned input spending the output
+    pub fn to_input(&self, sequence_number: u32) -> TxInput {
+        TxInput::new(
+            self.outpoint.txid(),
+            self.outpoint.vout(),
+            Vec::new(),
+            sequence_number,
+        )
+    }
+
+    /// The keys which can sign the input spending the output
+    pub fn signers(&self) -> io::Result<InputSigners> {
+        InputSigners::new(
+            self.txout.locking_script().to_vec(),
+            self.redeem_script.clone(),
+            self.witness_script.clone(),
+            self.public_key.clone(),
+        )
+    }
+}
+
+impl BtcTx {
+    /// Build an unsigned transaction spending `utxos` in order to `outputs`
+    pub fn spending(
+        utxos: &[WeightedUtxo],
+        outputs: Vec<TxOutput>,
+        sequence_number: u32,
+        locktime: u32,
+    ) -> Self {
+        BtcTx::new(
+            TxVersion::Two,
+            utxos
+                .iter()
+                .map(|utxo| utxo.to_input(sequence_number))
+                .collect(),
+            outputs,
+            locktime,
+        )
+    }
+}
+
+impl SigningSession {
+    /// Start a session for `tx` with the signers of `utxos`, the outputs spent
+    /// by the inputs of `tx` in order
+    pub fn from_utxos(tx: BtcTx, utxos: &[WeightedUtxo]) -> io::Result<Self> {
+        if tx
+            .inputs()
+            .iter()
+            .zip(utxos)
+            .any(|(input, utxo)| input.previous_output() != utxo.outpoint)
+        {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "The outputs are not spent by the inputs in the same order",
+            ));
+        }
+
+        let signers = utxos
+            .iter()
+            .map(WeightedUtxo::signers)
+            .collect::<io::Result<Vec<InputSigners>>>()?;
+
+        Self::new(tx, signers)
+    }
+}
+
+#[cfg(test)]
+mod weighted_utxo_sanity_checks {
+    use crate::{
+        BtcTx, Hash256, KeySource, OutPoint, SigningSession, TxOutput, Txid, WeightedUtxo,
+    };
+    use hex_literal::hex;
+    use std::str::FromStr;
+
+    #[test]
+    fn select_build_sign() {
+        let public_key = [&[0x02][..], &[0x11; 32]].concat();
+        let utxo = WeightedUtxo::estimated(
+            OutPoint::new(Txid::new(Hash256::new([1u8; 32])), 0),
+            TxOutput::new(
+                50_000,
+                hex!("0014751e76e8199196d454941c45d1b3a323f1433bd6").to_vec(),
+            ),
+        )
+        .unwrap()
+        .with_public_key(public_key.clone())
+        .with_key_source(
+            public_key.clone(),
+            KeySource::from_str("[d34db33f/84'/0'/0'/0/0]").unwrap(),
+        );
+
+        // A witness with a signature and a public key
+        assert_eq!(1 + 73 + 34, utxo.satisfaction_weight);
+        assert_eq!(Some(68 * 2), utxo.spending_fee(2));
+        assert_eq!(None, utxo.spending_fee(u64::MAX));
+        assert_eq!(50_000, utxo.to_wallet_utxo().amount);
+
+        let tx = BtcTx::spending(
+            std::slice::from_ref(&utxo),
+            vec![TxOutput::new(49_000, vec![0x51])],
+            0xfffffffd,
+            0,
+        );
+        assert_eq!(utxo.outpoint, tx.inputs()[0].previous_output());
+
+        let session = SigningSession::from_utxos(tx.clone(), std::slice::from_ref(&utxo)).unwrap();
+        assert_eq!(vec![public_key.as_slice()], session.missing_keys(0));
+
+        let mut other = utxo.clone();
+        other.outpoint = OutPoint::new(Txid::new(Hash256::new([2u8; 32])), 0);
+        assert!(SigningSession::from_utxos(tx, &[other]).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+use crate::VarInt;
+use std::slice;
+
+/// The witness of a segwit input which is a stack of byte arrays.
+/// It is serialized as a VarInt count of the elements followed by
+/// each element prefixed with its VarInt length
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Default, Hash)]
+pub struct Witness(Vec<Vec<u8>>);
+
+impl Witness {
+    /// Instantiate an empty witness as used by inputs spending non-segwit outputs
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Instantiate a witness from its elements in stack order
+    pub fn from_slice(elements: &[&[u8]]) -> Self {
+        Self(elements.iter().map(|element| element.to_vec()).collect())
+    }
+
+    /// Push an element to the top of the witness stack
+    pub fn push(&mut self, element: impl AsRef<[u8]>) -> &mut Self {
+        self.0.push(element.as_ref().to_vec());
+
+        self
+    }
+
+    /// The number of elements in the witness stack
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether the witness stack has no elements
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The element at `index` where index 0 is the bottom of the stack
+    pub fn get(&self, index: usize) -> Option<&[u8]> {
+        self.0.get(index).map(|element| element.as_slice())
+    }
+
+    /// The last element which is the witness script for P2WSH spends
+    /// and the control block for taproot script path spends
+    pub fn last(&self) -> Option<&[u8]> {
+        self.0.last().map(|element| element.as_slice())
+    }
+
+    /// The annex of a taproot spend, the last element of two or more
+    /// starting with `0x50`
+    pub fn annex(&self) -> Option<&[u8]> {
+        self.last()
+            .filter(|annex| self.0.len() >= 2 && annex.first() == Some(&0x50))
+    }
+
+    /// Iterate over the elements from the bottom of the stack
+    pub fn iter(&self) -> WitnessIter<'_> {
+        WitnessIter(self.0.iter())
+    }
+
+    /// The number of bytes the witness takes when serialized including
+    /// the VarInt count of elements and the VarInt length of each element.
+    /// Every witness byte counts as one weight unit.
+    pub fn serialized_size(&self) -> usize {
+        self.0
+            .iter()
+            .fold(VarInt::encoded_len(self.0.len() as u64), |size, element| {
+                size + VarInt::encoded_len(element.len() as u64) + element.len()
+            })
+    }
+
+    /// Serialize the witness as it appears in a segwit transaction
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::<u8>::with_capacity(self.serialized_size());
+        bytes.extend_from_slice(&VarInt::encode(self.0.len() as u64));
+
+        self.0.iter().for_each(|element| {
+            bytes.extend_from_slice(&VarInt::encode(element.len() as u64));
+            bytes.extend_from_slice(element);
+        });
+
+        bytes
+    }
+}
+
+/// An iterator over the elements of a `Witness`
+#[derive(Debug, Clone)]
+pub struct WitnessIter<'a>(slice::Iter<'a, Vec<u8>>);
+
+impl<'a> Iterator for WitnessIter<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|element| element.as_slice())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl ExactSizeIterator for WitnessIter<'_> {}
+
+impl<'a> IntoIterator for &'a Witness {
+    type Item = &'a [u8];
+    type IntoIter = WitnessIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod witness_sanity_checks {
+    use crate::Witness;
+
+    #[test]
+    fn witness() {
+        let empty = Witness::new();
+        assert!(empty.is_empty());
+        assert_eq!(vec![0u8], empty.to_bytes());
+        assert_eq!(1, empty.serialized_size());
+
+        // A P2WPKH witness has a 72 byte signature and a 33 byte public key
+        let mut p2wpkh = Witness::from_slice(&[&[0x30; 72]]);
+        p2wpkh.push([0x02; 33]);
+        assert_eq!(2, p2wpkh.len());
+        assert_eq!(Some([0x02; 33].as_slice()), p2wpkh.last());
+        assert_eq!(1 + 1 + 72 + 1 + 33, p2wpkh.serialized_size());
+        assert_eq!(p2wpkh.serialized_size(), p2wpkh.to_bytes().len());
+
+        // Elements of 253 bytes or more need a three byte length prefix
+        let large = Witness::from_slice(&[&[], &[1u8; 300]]);
+        assert_eq!(1 + 1 + 3 + 300, large.serialized_size());
+        assert_eq!(
+            vec![0usize, 300],
+            large
+                .iter()
+                .map(|element| element.len())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(&[0xfd, 0x2c, 0x01], &large.to_bytes()[2..5]);
+    }
+}
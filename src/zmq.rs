@@ -0,0 +1,392 @@
+use crate::{Block, BtcTx};
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::mpsc,
+    thread,
+};
+
+// The greeting of ZMTP 3.0 with the NULL security mechanism and the client role
+const GREETING_LEN: usize = 64;
+
+// Frames with larger bodies are rejected, a block is at most 4 MB
+const MAX_FRAME_LEN: u64 = 8_000_000;
+
+// The bits of the flags byte starting every frame
+const FLAG_MORE: u8 = 0x01;
+const FLAG_LONG: u8 = 0x02;
+const FLAG_COMMAND: u8 = 0x04;
+
+/// The topics bitcoind publishes with `-zmqpubrawtx` and `-zmqpubrawblock`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ZmqTopic {
+    /// Every transaction entering the mempool or confirmed in a block
+    RawTx,
+    /// Every block connected to the chain
+    RawBlock,
+}
+
+impl ZmqTopic {
+    /// The name bitcoind publishes the topic under
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::RawTx => "rawtx",
+            Self::RawBlock => "rawblock",
+        }
+    }
+}
+
+/// A transaction or block published by bitcoind. The sequence number counts the
+/// messages of the topic so a gap means messages were dropped by the publisher
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ZmqMessage {
+    /// A transaction of the `rawtx` topic
+    Tx {
+        /// The parsed transaction
+        tx: BtcTx,
+        /// The sequence number of the message
+        sequence: u32,
+    },
+    /// A block of the `rawblock` topic
+    Block {
+        /// The parsed block
+        block: Block,
+        /// The sequence number of the message
+        sequence: u32,
+    },
+}
+
+/// A blocking subscriber to the ZMQ publisher of bitcoind. It speaks ZMTP 3.0
+/// over TCP with the NULL security mechanism bitcoind uses, so it does not
+/// need `libzmq`. Messages of topics other than `rawtx` and `rawblock` are skipped.
+///
+/// ```no_run
+/// use btc_tx_hex::{ZmqMessage, ZmqSubscriber, ZmqTopic};
+///
+/// let subscriber =
+///     ZmqSubscriber::connect("127.0.0.1:28332", &[ZmqTopic::RawTx, ZmqTopic::RawBlock])?;
+/// for message in subscriber.spawn() {
+///     match message? {
+///         ZmqMessage::Tx { tx, .. } => println!("tx {}", tx.txid()),
+///         ZmqMessage::Block { block, .. } => println!("block {}", block.block_hash()),
+///     }
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct ZmqSubscriber {
+    stream: TcpStream,
+}
+
+impl ZmqSubscriber {
+    /// Connect to the publisher at `address`, the value of `-zmqpubrawtx`
+    /// without the `tcp://` prefix, and subscribe to `topics`
+    pub fn connect(address: impl ToSocketAddrs, topics: &[ZmqTopic]) -> io::Result<Self> {
+        let mut stream = TcpStream::connect(address)?;
+        Self::handshake(&mut stream)?;
+
+        for topic in topics {
+            // A subscription is a message starting with 0x01 followed by the topic prefix
+            let subscription = [&[0x01], topic.name().as_bytes()].concat();
+            Self::write_frame(&mut stream, 0, &subscription)?;
+        }
+        stream.flush()?;
+
+        Ok(Self { stream })
+    }
+
+    /// Block until the next transaction or block is published
+    pub fn receive(&mut self) -> io::Result<ZmqMessage> {
+        loop {
+            let parts = Self::read_message(&mut self.stream)?;
+            // bitcoind publishes the topic, the body and a little-endian sequence number
+            let [topic, body, sequence] = parts.as_slice() else {
+                continue;
+            };
+            let sequence = u32::from_le_bytes(sequence.as_slice().try_into().map_err(|_| {
+                io::Error::new(
+                    ErrorKind::InvalidData,
+                    "The sequence number of a ZMQ message must be 4 bytes",
+                )
+            })?);
+
+            match topic.as_slice() {
+                b"rawtx" => {
+                    return Ok(ZmqMessage::Tx {
+                        tx: BtcTx::from_hex_bytes(body)?,
+                        sequence,
+                    })
+                }
+                b"rawblock" => {
+                    return Ok(ZmqMessage::Block {
+                        block: Block::from_bytes(body)?,
+                        sequence,
+                    })
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    /// Receive on a thread forwarding every message to the returned channel.
+    /// The thread stops after sending the first error or once the receiver is dropped
+    pub fn spawn(mut self) -> mpsc::Receiver<io::Result<ZmqMessage>> {
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || loop {
+            let message = self.receive();
+            let failed = message.is_err();
+            if sender.send(message).is_err() || failed {
+                break;
+            }
+        });
+
+        receiver
+    }
+
+    // Exchange greetings and READY commands with the publisher
+    fn handshake(stream: &mut (impl Read + Write)) -> io::Result<()> {
+        let mut greeting = [0u8; GREETING_LEN];
+        // The signature, version 3.0 and the NULL mechanism padded with zeros
+        greeting[0] = 0xff;
+        greeting[9] = 0x7f;
+        greeting[10] = 3;
+        greeting[12..16].copy_from_slice(b"NULL");
+        stream.write_all(&greeting)?;
+
+        let mut peer = [0u8; GREETING_LEN];
+        stream.read_exact(&mut peer)?;
+        if peer[0] != 0xff || peer[9] != 0x7f {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "The peer is not a ZMQ socket",
+            ));
+        }
+        if peer[10] < 3 || &peer[12..16] != b"NULL" || peer[16..32].iter().any(|byte| *byte != 0) {
+            return Err(io::Error::new(
+                ErrorKind::Unsupported,
+                "The peer does not speak ZMTP 3 with the NULL security mechanism",
+            ));
+        }
+
+        let mut ready = command_name(b"READY");
+        ready.extend_from_slice(&property(b"Socket-Type", b"SUB"));
+        Self::write_frame(stream, FLAG_COMMAND, &ready)?;
+        stream.flush()?;
+
+        match Self::read_frame(stream)? {
+            (flags, body)
+                if flags & FLAG_COMMAND != 0 && body.starts_with(&command_name(b"READY")) =>
+            {
+                Ok(())
+            }
+            (flags, body)
+                if flags & FLAG_COMMAND != 0 && body.starts_with(&command_name(b"ERROR")) =>
+            {
+                Err(io::Error::new(
+                    ErrorKind::ConnectionRefused,
+                    "The ZMQ publisher refused the connection",
+                ))
+            }
+            _ => Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "Expected the READY command of the ZMQ publisher",
+            )),
+        }
+    }
+
+    // Read the frames of the next multipart message, skipping commands
+    fn read_message(stream: &mut impl Read) -> io::Result<Vec<Vec<u8>>> {
+        let mut parts = Vec::<Vec<u8>>::new();
+
+        loop {
+            let (flags, body) = Self::read_frame(stream)?;
+            if flags & FLAG_COMMAND != 0 {
+                continue;
+            }
+
+            parts.push(body);
+            if flags & FLAG_MORE == 0 {
+                return Ok(parts);
+            }
+        }
+    }
+
+    // Read the flags and body of a frame. Long frames have an 8 byte
+    // big-endian size and short frames a single byte
+    fn read_frame(stream: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+        let mut flags = [0u8];
+        stream.read_exact(&mut flags)?;
+
+        let size = if flags[0] & FLAG_LONG != 0 {
+            let mut size = [0u8; 8];
+            stream.read_exact(&mut size)?;
+            u64::from_be_bytes(size)
+        } else {
+            let mut size = [0u8];
+            stream.read_exact(&mut size)?;
+            size[0] as u64
+        };
+        if size > MAX_FRAME_LEN {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "A ZMQ frame is larger than the largest block",
+            ));
+        }
+
+        let mut body = vec![0u8; size as usize];
+        stream.read_exact(&mut body)?;
+
+        Ok((flags[0], body))
+    }
+
+    fn write_frame(stream: &mut impl Write, flags: u8, body: &[u8]) -> io::Result<()> {
+        match u8::try_from(body.len()) {
+            Ok(size) => stream.write_all(&[flags, size])?,
+            Err(_) => {
+                stream.write_all(&[flags | FLAG_LONG])?;
+                stream.write_all(&(body.len() as u64).to_be_bytes())?;
+            }
+        }
+
+        stream.write_all(body)
+    }
+}
+
+impl Iterator for ZmqSubscriber {
+    type Item = io::Result<ZmqMessage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.receive())
+    }
+}
+
+// The name of a command prefixed with its length
+fn command_name(name: &[u8]) -> Vec<u8> {
+    [&[name.len() as u8], name].concat()
+}
+
+// A metadata property of the READY command, the value length is 4 bytes big-endian
+fn property(name: &[u8], value: &[u8]) -> Vec<u8> {
+    [
+        &[name.len() as u8],
+        name,
+        &(value.len() as u32).to_be_bytes(),
+        value,
+    ]
+    .concat()
+}
+
+#[cfg(test)]
+mod zmq_sanity_checks {
+    use super::{command_name, property, ZmqSubscriber, FLAG_COMMAND, FLAG_MORE};
+    use crate::{Block, ZmqMessage, ZmqTopic};
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    // The publisher side of the handshake, returning the subscriptions
+    fn accept(listener: &TcpListener) -> (TcpStream, Vec<Vec<u8>>) {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut greeting = [0u8; 64];
+        stream.read_exact(&mut greeting).unwrap();
+        assert_eq!(b"NULL", &greeting[12..16]);
+        let mut reply = greeting;
+        // The publisher is the server
+        reply[32] = 1;
+        stream.write_all(&reply).unwrap();
+
+        let (flags, ready) = ZmqSubscriber::read_frame(&mut stream).unwrap();
+        assert_eq!(FLAG_COMMAND, flags);
+        assert!(ready.ends_with(&property(b"Socket-Type", b"SUB")));
+        let mut ready = command_name(b"READY");
+        ready.extend_from_slice(&property(b"Socket-Type", b"PUB"));
+        ZmqSubscriber::write_frame(&mut stream, FLAG_COMMAND, &ready).unwrap();
+
+        let subscriptions = (0..2)
+            .map(|_| ZmqSubscriber::read_frame(&mut stream).unwrap().1)
+            .collect();
+
+        (stream, subscriptions)
+    }
+
+    fn publish(stream: &mut TcpStream, topic: &[u8], body: &[u8], sequence: u32) {
+        ZmqSubscriber::write_frame(stream, FLAG_MORE, topic).unwrap();
+        ZmqSubscriber::write_frame(stream, FLAG_MORE, body).unwrap();
+        ZmqSubscriber::write_frame(stream, 0, &sequence.to_le_bytes()).unwrap();
+    }
+
+    #[test]
+    fn subscribe() {
+        let raw_tx = include_str!("../fixtures/transactions/p2pkh_two_inputs.hex");
+        let tx = hex::decode(raw_tx.trim()).unwrap();
+        let header = hex_literal::hex!("0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c");
+        // The header of the genesis block with the transaction above
+        let block = [&header[..], &[1], &tx].concat();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let publisher = {
+            let (tx, block) = (tx.clone(), block.clone());
+            thread::spawn(move || {
+                let (mut stream, subscriptions) = accept(&listener);
+                assert_eq!(
+                    vec![b"\x01rawtx".to_vec(), b"\x01rawblock".to_vec()],
+                    subscriptions
+                );
+
+                publish(&mut stream, b"rawtx", &tx, 7);
+                // Other topics are skipped and long frames are read
+                publish(&mut stream, b"hashtx", &[0u8; 300], 0);
+                publish(&mut stream, b"rawblock", &block, 1);
+                publish(&mut stream, b"rawtx", &tx[..10], 8);
+            })
+        };
+
+        let subscriber =
+            ZmqSubscriber::connect(address, &[ZmqTopic::RawTx, ZmqTopic::RawBlock]).unwrap();
+        let messages = subscriber.spawn();
+
+        match messages.recv().unwrap().unwrap() {
+            ZmqMessage::Tx {
+                tx: parsed,
+                sequence,
+            } => {
+                assert_eq!(7, sequence);
+                assert_eq!(tx, parsed.to_bytes());
+            }
+            message => panic!("Expected a transaction but got {:?}", message),
+        }
+        assert_eq!(
+            ZmqMessage::Block {
+                block: Block::from_bytes(&block).unwrap(),
+                sequence: 1,
+            },
+            messages.recv().unwrap().unwrap()
+        );
+        // A transaction that does not parse is an error ending the channel
+        assert!(messages.recv().unwrap().is_err());
+        publisher.join().unwrap();
+        assert!(messages.recv().is_err());
+    }
+
+    #[test]
+    fn not_a_publisher() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut greeting = [0u8; 64];
+            stream.read_exact(&mut greeting).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n")
+                .unwrap();
+            stream.write_all(&[0u8; 64]).unwrap();
+        });
+
+        assert!(ZmqSubscriber::connect(address, &[ZmqTopic::RawTx]).is_err());
+        server.join().unwrap();
+    }
+}